@@ -0,0 +1,62 @@
+use parts::arrange;
+use crate::{Lyric, Translation};
+
+fn pick<T: Clone>(items: &[T], order: &[usize]) -> Vec<T> {
+    order.iter().map(|index| items[*index].clone()).collect()
+}
+
+impl Lyric {
+    /// The lyric with the parts in the order they are sung, a repeated part as often as it is sung
+    pub fn arranged(&self) -> Lyric {
+        if self.arrangement.is_empty() {
+            return self.clone();
+        }
+        let order = arrange(&self.labels, &self.arrangement);
+        Lyric {
+            id: self.id,
+            title: self.title.clone(),
+            parts: pick(&self.parts, &order),
+            chords:
+                if self.chords.is_empty() {
+                    vec![]
+                }
+                else {
+                    order.iter().map(|index| self.chords.get(*index).cloned().unwrap_or_default()).collect()
+                },
+            labels: pick(&self.labels, &order),
+            arrangement: vec![],
+            translations:
+                self.translations
+                .iter()
+                .map(|(language, translation)| (
+                    language.clone(),
+                    Translation {
+                        title: translation.title.clone(),
+                        parts: order.iter().map(|index| translation.parts.get(*index).cloned().unwrap_or_default()).collect(),
+                    },
+                ))
+                .collect(),
+            tags: self.tags.clone(),
+            metadata: self.metadata.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{Lyric, LyricPost};
+
+    #[test]
+    fn arranged() {
+        let lyric: Lyric = LyricPost::from(("Roodkapje", "Zeg roodkapje\n\nRefrein:\nNaar grootmoeder\n\nWat heb je in je mandje\n\nRefrein")).into();
+        assert_eq!(lyric.arrangement, vec!["1", "Refrein", "3", "Refrein"]);
+
+        let arranged = lyric.arranged();
+        assert_eq!(
+            arranged.parts,
+            vec![vec!["Zeg roodkapje"], vec!["Naar grootmoeder"], vec!["Wat heb je in je mandje"], vec!["Naar grootmoeder"]],
+        );
+        assert!(arranged.arrangement.is_empty());
+        assert_eq!(arranged.labels[3].as_deref(), Some("Refrein"));
+    }
+}
@@ -0,0 +1,25 @@
+use serde::{Deserialize, Serialize};
+use crate::{Lyric, Playlist, Uuid};
+
+/// A single mutation. A list of transactions is applied all-or-nothing by LiplRepo::apply_batch
+/// and every successful mutation of the file repo is logged as a transaction.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[allow(clippy::large_enum_variant)]
+pub enum Transaction {
+    LyricDelete(Uuid),
+    LyricUpsert(Lyric),
+    PlaylistDelete(Uuid),
+    PlaylistUpsert(Playlist),
+}
+
+impl Transaction {
+    /// Id of the lyric or playlist affected
+    pub fn id(&self) -> Uuid {
+        match self {
+            Transaction::LyricDelete(id) => *id,
+            Transaction::LyricUpsert(lyric) => lyric.id,
+            Transaction::PlaylistDelete(id) => *id,
+            Transaction::PlaylistUpsert(playlist) => playlist.id,
+        }
+    }
+}
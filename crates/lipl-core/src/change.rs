@@ -0,0 +1,196 @@
+use std::pin::Pin;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use futures_core::Stream;
+use serde::{Deserialize, Serialize};
+use crate::Transaction;
+
+/// Stream of changes returned by LiplRepo::watch
+pub type ChangeStream = Pin<Box<dyn Stream<Item = Change> + Send>>;
+
+/// A committed mutation with a sequence number that increases with every change of the repo
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Change {
+    pub sequence: u64,
+    /// Milliseconds since the unix epoch
+    pub timestamp: u64,
+    pub transaction: Transaction,
+}
+
+impl Change {
+    pub fn new(sequence: u64, transaction: Transaction) -> Self {
+        Self {
+            sequence,
+            timestamp: now(),
+            transaction,
+        }
+    }
+}
+
+/// Milliseconds since the unix epoch
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+#[cfg(feature = "watch")]
+pub use broadcast::Broadcaster;
+
+#[cfg(feature = "watch")]
+mod broadcast {
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use tokio::sync::broadcast::{self, error::RecvError};
+    use super::{Change, ChangeStream};
+    use crate::Transaction;
+
+    const CAPACITY: usize = 256;
+
+    /// Numbers the changes of an in-process repo and sends them to all watchers.
+    /// Watchers that lag more than the capacity of the channel miss the oldest changes.
+    pub struct Broadcaster {
+        sender: broadcast::Sender<Change>,
+        sequence: AtomicU64,
+    }
+
+    impl Default for Broadcaster {
+        fn default() -> Self {
+            Self {
+                sender: broadcast::channel(CAPACITY).0,
+                sequence: AtomicU64::new(0),
+            }
+        }
+    }
+
+    impl Broadcaster {
+        pub fn send(&self, transaction: Transaction) {
+            let sequence = self.sequence.fetch_add(1, Ordering::SeqCst) + 1;
+            // Sending only fails if nobody is watching
+            let _ = self.sender.send(Change::new(sequence, transaction));
+        }
+
+        pub fn subscribe(&self) -> ChangeStream {
+            Box::pin(
+                futures::stream::unfold(self.sender.subscribe(), |mut receiver| async move {
+                    loop {
+                        match receiver.recv().await {
+                            Ok(change) => return Some((change, receiver)),
+                            Err(RecvError::Lagged(_)) => continue,
+                            Err(RecvError::Closed) => return None,
+                        }
+                    }
+                })
+            )
+        }
+    }
+}
+
+#[cfg(feature = "postgres")]
+pub use notify::listen_changes;
+
+#[cfg(feature = "postgres")]
+mod notify {
+    use std::str::FromStr;
+    use bb8_postgres::tokio_postgres::{self, AsyncMessage, NoTls};
+    use futures::{stream, Stream, StreamExt};
+    use tokio::sync::mpsc;
+    use super::{Change, ChangeStream};
+    use crate::{error::PostgresRepoError, LiplRepo, Transaction, Uuid};
+
+    const LISTEN: &str = "LISTEN lipl_change";
+
+    /// Payload of the notifications sent by fn_notify_change: sequence, timestamp, table, operation and id
+    struct Notification {
+        sequence: u64,
+        timestamp: u64,
+        table: String,
+        operation: String,
+        id: Uuid,
+    }
+
+    impl FromStr for Notification {
+        type Err = crate::Error;
+
+        fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+            let fields = s.split(' ').collect::<Vec<_>>();
+            match fields[..] {
+                [sequence, timestamp, table, operation, id] => Ok(
+                    Self {
+                        sequence: sequence.parse().map_err(|_| crate::Error::Argument("sequence"))?,
+                        timestamp: timestamp.parse().map_err(|_| crate::Error::Argument("timestamp"))?,
+                        table: table.to_owned(),
+                        operation: operation.to_owned(),
+                        id: uuid::Uuid::parse_str(id).map(Uuid::from)?,
+                    }
+                ),
+                _ => Err(crate::Error::Argument("notification")),
+            }
+        }
+    }
+
+    impl Notification {
+        /// Reads the inserted or updated lyric or playlist. Returns None if it has been deleted in the mean time.
+        async fn into_change<R: LiplRepo>(self, repo: &R) -> Option<Change> {
+            let transaction = match (self.table.as_str(), self.operation.as_str()) {
+                ("lyric", "DELETE") => Transaction::LyricDelete(self.id),
+                ("lyric", _) => Transaction::LyricUpsert(repo.get_lyric(self.id).await.ok()?),
+                ("playlist", "DELETE") => Transaction::PlaylistDelete(self.id),
+                ("playlist", _) => Transaction::PlaylistUpsert(repo.get_playlist(self.id).await.ok()?),
+                _ => return None,
+            };
+            Some(
+                Change {
+                    sequence: self.sequence,
+                    timestamp: self.timestamp,
+                    transaction,
+                }
+            )
+        }
+    }
+
+    /// Listens for notifications on a dedicated connection, that is closed when the stream is dropped
+    async fn listen(connection_string: &str) -> Result<impl Stream<Item = Notification>, PostgresRepoError> {
+        let (client, mut connection) = tokio_postgres::connect(connection_string, NoTls).await?;
+        let (tx, rx) = mpsc::unbounded_channel::<String>();
+        tokio::spawn(async move {
+            let mut messages = stream::poll_fn(move |cx| connection.poll_message(cx));
+            while let Some(Ok(message)) = messages.next().await {
+                if let AsyncMessage::Notification(notification) = message {
+                    if tx.send(notification.payload().to_owned()).is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+        client.batch_execute(LISTEN).await?;
+
+        Ok(
+            stream::unfold((rx, client), |(mut rx, client)| async move {
+                rx.recv().await.map(|payload| (payload, (rx, client)))
+            })
+            .filter_map(|payload| async move {
+                payload
+                    .parse::<Notification>()
+                    .map_err(|error| tracing::error!("Invalid notification {payload}: {error}"))
+                    .ok()
+            })
+        )
+    }
+
+    /// Changes of a postgres repo, from the notifications sent by the triggers of its database.
+    /// The upserted items are read back from the repo.
+    pub async fn listen_changes<R>(repo: R, connection_string: &str) -> crate::Result<ChangeStream>
+    where R: LiplRepo + Clone + 'static
+    {
+        let notifications = listen(connection_string).await?;
+        Ok(
+            notifications
+            .filter_map(move |notification| {
+                let repo = repo.clone();
+                async move { notification.into_change(&repo).await }
+            })
+            .boxed()
+        )
+    }
+}
@@ -0,0 +1,114 @@
+use parts::{parse_chordpro, split_songs, without_chords, ChordPro};
+use crate::{Lyric, LyricMetadata, LyricPost};
+
+pub const CONTENT_TYPE: &str = "text/x-chordpro";
+const NEW_SONG: &str = "{new_song}\n";
+
+fn directive(name: &str, value: &Option<String>) -> Option<(String, String)> {
+    value.as_ref().map(|value| (name.to_owned(), value.clone()))
+}
+
+fn meta(name: &str, value: &Option<String>) -> Option<(String, String)> {
+    value.as_ref().map(|value| ("meta".to_owned(), format!("{name} {value}")))
+}
+
+impl From<&ChordPro> for LyricPost {
+    fn from(chordpro: &ChordPro) -> Self {
+        let value = |name: &str| chordpro.meta(name).map(str::to_owned);
+        let (parts, chords) = without_chords(chordpro.parts.clone());
+        LyricPost {
+            title: value("title").unwrap_or_default(),
+            parts,
+            chords,
+            labels: chordpro.labels.clone(),
+            arrangement: chordpro.arrangement.clone(),
+            translations: Default::default(),
+            tags: Default::default(),
+            metadata: LyricMetadata {
+                sub_title: value("subtitle"),
+                lyricist: value("lyricist").or_else(|| value("artist")),
+                composer: value("composer"),
+                language: value("language"),
+                year: value("year").and_then(|year| year.parse().ok()),
+                copyright: value("copyright"),
+                source: value("source"),
+            },
+        }
+    }
+}
+
+impl From<&Lyric> for ChordPro {
+    fn from(lyric: &Lyric) -> Self {
+        let metadata = &lyric.metadata;
+        ChordPro {
+            directives:
+                [
+                    Some(("title".to_owned(), lyric.title.clone())),
+                    directive("subtitle", &metadata.sub_title),
+                    directive("lyricist", &metadata.lyricist),
+                    directive("composer", &metadata.composer),
+                    directive("year", &metadata.year.map(|year| year.to_string())),
+                    directive("copyright", &metadata.copyright),
+                    meta("language", &metadata.language),
+                    meta("source", &metadata.source),
+                ]
+                .into_iter()
+                .flatten()
+                .collect(),
+            parts: lyric.chord_lines(),
+            labels: lyric.labels.clone(),
+            arrangement: lyric.arrangement.clone(),
+        }
+    }
+}
+
+impl Lyric {
+    pub fn to_chordpro(&self) -> String {
+        ChordPro::from(self).to_string()
+    }
+}
+
+/// The lyrics in a ChordPro file, which can have more than one song separated by `{new_song}`
+pub fn lyrics_from_chordpro(s: &str) -> Vec<LyricPost> {
+    split_songs(s)
+        .iter()
+        .map(|song| LyricPost::from(&parse_chordpro(song)))
+        .collect()
+}
+
+/// The lyrics as one ChordPro file, separated by `{new_song}`
+pub fn lyrics_to_chordpro(lyrics: &[Lyric]) -> String {
+    lyrics
+        .iter()
+        .map(Lyric::to_chordpro)
+        .collect::<Vec<_>>()
+        .join(NEW_SONG)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{Lyric, LyricMetadata, LyricPost};
+    use super::{lyrics_from_chordpro, lyrics_to_chordpro};
+
+    #[test]
+    fn round_trip() {
+        let lyric: Lyric = LyricPost {
+            title: "Roodkapje".to_owned(),
+            parts: vec![vec!["Zeg roodkapje".to_owned(), "waar ga je heen".to_owned()], vec!["Zo alleen".to_owned()]],
+            chords: vec![],
+            labels: vec![],
+            arrangement: vec![],
+            translations: Default::default(),
+            tags: Default::default(),
+            metadata: LyricMetadata { language: Some("nl".to_owned()), year: Some(1900), ..Default::default() },
+        }.into();
+        let other: Lyric = LyricPost::from(("Daar bij die molen", "Daar bij die molen")).into();
+
+        let lyric_posts = lyrics_from_chordpro(&lyrics_to_chordpro(&[lyric.clone(), other.clone()]));
+        assert_eq!(lyric_posts.len(), 2);
+        assert_eq!(lyric_posts[0].title, lyric.title);
+        assert_eq!(lyric_posts[0].parts, lyric.parts);
+        assert_eq!(lyric_posts[0].metadata, lyric.metadata);
+        assert_eq!(lyric_posts[1].title, other.title);
+    }
+}
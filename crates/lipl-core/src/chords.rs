@@ -0,0 +1,53 @@
+use parts::{from_chord_text, to_chord_text, with_chords, without_chords, Accidental, Chord, ChordLine};
+use crate::Lyric;
+
+/// Chords per line, in the same shape as the parts of a lyric. Empty if the lyric has no chords.
+pub type Chords = Vec<Vec<Vec<Chord>>>;
+
+impl Lyric {
+    /// The lines of the parts together with their chords
+    pub fn chord_lines(&self) -> Vec<Vec<ChordLine>> {
+        with_chords(&self.parts, &self.chords)
+    }
+
+    /// The lyric with every chord transposed by a number of semitones, up if positive
+    pub fn transpose(mut self, semitones: i32, accidental: Accidental) -> Self {
+        let lines = self.chord_lines().iter().map(|part| part.iter().map(|line| line.transpose(semitones, accidental)).collect()).collect();
+        (_, self.chords) = without_chords(lines);
+        self
+    }
+
+    /// The parts as text with the chords inline, for backends that store the parts as text
+    pub fn text(&self) -> String {
+        to_chord_text(&self.chord_lines())
+    }
+}
+
+/// Parts and chords of a text with the chords inline, as written by `Lyric::text`
+pub fn from_text(text: String) -> (Vec<Vec<String>>, Chords) {
+    without_chords(from_chord_text(text))
+}
+
+#[cfg(test)]
+mod test {
+    use parts::{Accidental, Chord};
+    use crate::{Lyric, LyricPost};
+    use super::from_text;
+
+    #[test]
+    fn text_and_transpose() {
+        let lyric: Lyric = LyricPost {
+            title: "Roodkapje".to_owned(),
+            parts: vec![vec!["Zeg roodkapje".to_owned(), "Zo alleen".to_owned()]],
+            chords: vec![vec![vec![Chord { position: 0, name: "A".to_owned() }], vec![]]],
+            ..Default::default()
+        }.into();
+        assert_eq!(lyric.text(), "[A]Zeg roodkapje\nZo alleen");
+        assert_eq!(from_text(lyric.text()), (lyric.parts.clone(), lyric.chords.clone()));
+        assert_eq!(from_text("Zeg roodkapje".to_owned()).1, Vec::<Vec<Vec<Chord>>>::new());
+
+        let transposed = lyric.clone().transpose(1, Accidental::Flat);
+        assert_eq!(transposed.chords[0][0][0].name, "Bb");
+        assert_eq!(transposed.parts, lyric.parts);
+    }
+}
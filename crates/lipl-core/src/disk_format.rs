@@ -1,15 +1,24 @@
 use core::str::{FromStr};
 use core::fmt::{Display, Formatter};
 use core::str::Lines;
-use core::iter::once;
 
 use lipl_util::VecExt;
-use crate::{Etag, Lyric, LyricMeta, LyricPost, PlaylistPost, Playlist};
+use parts::{without_chords, ChordLine};
+use serde::{Deserialize, Serialize};
+use serde_yaml::Value;
+use crate::{Lyric, LyricMeta, LyricPost, PlaylistPost, Playlist, Translation, Trashed};
 use crate::error::{Error};
 
 const YAML_PREFIX: &str = "---";
 
-fn lines_to_lyric_post(acc: LyricPost, mut lines: Lines) -> Result<LyricPost, serde_yaml::Error>
+/// Title of a translation, in the frontmatter of its section
+#[derive(Deserialize, Serialize)]
+struct TranslationMeta {
+    title: String,
+}
+
+/// Reads the parts of the lyric, and after a `--- <language>` frontmatter those of the translation in that language
+fn lines_to_lyric_post(mut acc: LyricPost, language: Option<String>, mut lines: Lines) -> Result<LyricPost, serde_yaml::Error>
 {
     let next = 
         lines
@@ -24,9 +33,22 @@ fn lines_to_lyric_post(acc: LyricPost, mut lines: Lines) -> Result<LyricPost, se
         .map(String::from)
         .collect::<Vec<_>>();
 
+    let translation_language =
+        next
+        .first()
+        .and_then(|s| s.strip_prefix(YAML_PREFIX))
+        .map(str::trim)
+        .filter(|language| !language.is_empty())
+        .map(str::to_owned);
+
     if next.is_empty() {
         Ok(acc)
     }
+    else if let Some(language) = translation_language {
+        let meta: TranslationMeta = serde_yaml::from_str(&next[1..].to_vec().without(&YAML_PREFIX.to_owned()).join("\n"))?;
+        acc.translations.insert(language.clone(), Translation { title: meta.title, parts: vec![] });
+        lines_to_lyric_post(acc, Some(language), lines)
+    }
     else if next.first().map(|s| s.trim()) == Some(YAML_PREFIX) {
         let new = next.without(&YAML_PREFIX.to_owned());
         let meta: LyricMeta = serde_yaml::from_str(&new.join("\n"))?;
@@ -34,38 +56,70 @@ fn lines_to_lyric_post(acc: LyricPost, mut lines: Lines) -> Result<LyricPost, se
             LyricPost {
                 title: meta.title,
                 parts: acc.parts,
+                chords: acc.chords,
+                labels: meta.labels,
+                arrangement: meta.arrangement,
+                translations: acc.translations,
+                tags: meta.tags,
+                metadata: meta.metadata,
             },
+            language,
             lines
         )
     }
     else {
-        lines_to_lyric_post(
-            LyricPost {
-                title: acc.title,
-                parts: acc.parts.into_iter().chain(once(next)).collect::<Vec<_>>(),
-            },
-            lines
-        )
+        match language.as_ref().and_then(|language| acc.translations.get_mut(language)) {
+            Some(translation) => translation.parts.push(next),
+            None => acc.parts.push(next),
+        }
+        lines_to_lyric_post(acc, language, lines)
     }
 }
 
 impl FromStr for LyricPost {
     type Err = Error;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let lyric_post = lines_to_lyric_post(Default::default(), s.lines())?;
+        let mut lyric_post = lines_to_lyric_post(Default::default(), None, s.lines())?;
+        (lyric_post.parts, lyric_post.chords) = without_chords(
+            lyric_post.parts
+            .iter()
+            .map(|part| part.iter().map(|line| ChordLine::from(line.as_str())).collect())
+            .collect()
+        );
         Ok(lyric_post)
     }
 }
 
+fn without_nulls(value: Value) -> Value {
+    match value {
+        Value::Mapping(mapping) => Value::Mapping(
+            mapping
+            .into_iter()
+            .filter(|(_, v)| !v.is_null())
+            .collect()
+        ),
+        _ => value,
+    }
+}
+
+fn parts_to_string(parts: impl Iterator<Item = Vec<String>>) -> String {
+    parts
+    .map(|p| p.join("  \n"))
+    .collect::<Vec<_>>()
+    .join("\n\n")
+}
+
 impl Display for Lyric {
     fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
-        let lyric_meta = LyricMeta {
-            title: self.title.clone(),
-            hash: self.etag(),
-        };
+        let lyric_meta = serde_yaml::to_value(LyricMeta::from(self)).map(without_nulls).unwrap();
         let yaml = serde_yaml::to_string(&lyric_meta).unwrap();
-        let parts_string: String = self.parts.iter().map(|p| p.join("  \n")).collect::<Vec<_>>().join("\n\n");
-        write!(f, "{YAML_PREFIX}\n{yaml}{YAML_PREFIX}\n\n{parts_string}")
+        let parts_string = parts_to_string(self.chord_lines().iter().map(|p| p.iter().map(ChordLine::to_string).collect()));
+        write!(f, "{YAML_PREFIX}\n{yaml}{YAML_PREFIX}\n\n{parts_string}")?;
+        self.translations.iter().try_for_each(|(language, translation)| {
+            let yaml = serde_yaml::to_string(&TranslationMeta { title: translation.title.clone() }).unwrap();
+            let parts_string = parts_to_string(translation.parts.iter().cloned());
+            write!(f, "\n\n{YAML_PREFIX} {language}\n{yaml}{YAML_PREFIX}\n\n{parts_string}")
+        })
     }
 }
 
@@ -85,6 +139,21 @@ impl Display for Playlist {
     }
 }
 
+impl FromStr for Trashed {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_yaml::from_str::<Trashed>(s)
+        .map_err(Into::into)
+    }
+}
+
+impl Display for Trashed {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let yaml = serde_yaml::to_string(self).unwrap_or_default();
+        write!(f, "{}", yaml)
+    }
+}
+
 fn empty_line(s: &&str) -> bool {
     s.trim().is_empty()
 }
@@ -114,7 +183,8 @@ impl FromStr for LyricMeta {
 mod tests {
 
     use std::vec;
-    use super::{Lyric, LyricMeta, LyricPost, PlaylistPost};
+    use super::{Lyric, LyricMeta, LyricPost, Playlist, PlaylistPost};
+    use crate::{LyricMetadata, PlaylistEntry, Translation};
     use crate::{Uuid};
 
 
@@ -204,7 +274,13 @@ mod tests {
                     "En op Sint Jan geklommen".to_owned(),
                     "Daar staat hij dag en nacht".to_owned(),
                 ]
-            ]
+            ],
+            chords: vec![],
+            labels: vec![],
+            arrangement: vec![],
+            translations: Default::default(),
+            tags: Default::default(),
+            metadata: LyricMetadata::default(),
         }
     }
 
@@ -226,6 +302,19 @@ mod tests {
         assert_eq!(playlist_post.members[2].to_string(), PLAYLIST_MEMBER3.to_owned());
     }
 
+    #[test]
+    fn playlist_entries_roundtrip() {
+        let member = PLAYLIST_MEMBER1.parse::<Uuid>().unwrap();
+        let entries = vec![
+            PlaylistEntry::divider("Preek"),
+            PlaylistEntry { note: Some("Alleen het refrein".to_owned()), parts: vec![2], key: Some("D".to_owned()), ..PlaylistEntry::lyric(member) },
+        ];
+        let playlist = Playlist::from(PlaylistPost { title: PLAYLIST_TITLE.to_owned(), members: vec![], entries: entries.clone(), event: None });
+        let playlist_post: PlaylistPost = playlist.to_string().parse().unwrap();
+        assert_eq!(playlist_post.members, vec![member]);
+        assert_eq!(playlist_post.entries, entries);
+    }
+
     #[test]
     fn lyric_post_parse() {
         let hertog_jan = hertog_jan_lyric().to_string();
@@ -249,4 +338,71 @@ mod tests {
         assert_eq!(lyric_meta.title, HERTOG_JAN_TITLE.to_owned());
         assert_eq!(lyric_meta.hash, Some("\"2530-189459479300553739784561073837696755448\"".to_owned()));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn lyric_metadata_roundtrip() {
+        let mut lyric = hertog_jan_lyric();
+        lyric.metadata = LyricMetadata {
+            sub_title: Some("Brabants volkslied".to_owned()),
+            composer: Some("Traditioneel".to_owned()),
+            language: Some("nl".to_owned()),
+            year: Some(1917),
+            ..Default::default()
+        };
+        let text = lyric.to_string();
+        assert!(text.contains("year: 1917"));
+        assert!(!text.contains("lyricist"));
+
+        let lyric_post: LyricPost = text.parse().unwrap();
+        assert_eq!(lyric_post.metadata, lyric.metadata);
+        assert_eq!(lyric_post.parts.len(), 9);
+
+        let lyric_meta: LyricMeta = text.parse().unwrap();
+        assert_eq!(lyric_meta.metadata.sub_title, Some("Brabants volkslied".to_owned()));
+    }
+
+    #[test]
+    fn lyric_labels_roundtrip() {
+        let mut lyric = hertog_jan_lyric();
+        lyric.labels = (1..=9).map(|number| Some(format!("Couplet {number}"))).collect();
+        lyric.arrangement = vec!["Couplet 1".to_owned(), "Couplet 2".to_owned(), "Couplet 1".to_owned()];
+        let text = lyric.to_string();
+        assert!(text.contains("arrangement:"));
+
+        let lyric_post: LyricPost = text.parse().unwrap();
+        assert_eq!(lyric_post.labels, lyric.labels);
+        assert_eq!(lyric_post.arrangement, lyric.arrangement);
+        assert_eq!(lyric_post.parts.len(), 9);
+    }
+
+    #[test]
+    fn lyric_tags_roundtrip() {
+        let mut lyric = hertog_jan_lyric();
+        lyric.tags = ["volksliedjes".to_owned(), "brabant".to_owned()].into();
+        let text = lyric.to_string();
+        assert!(text.contains("tags:\n- brabant\n- volksliedjes\n"));
+
+        let lyric_post: LyricPost = text.parse().unwrap();
+        assert_eq!(lyric_post.tags, lyric.tags);
+        let lyric_meta: LyricMeta = text.parse().unwrap();
+        assert_eq!(lyric_meta.tags, lyric.tags);
+    }
+
+    #[test]
+    fn lyric_translations_roundtrip() {
+        let mut lyric = hertog_jan_lyric();
+        let english = Translation { title: "Duke John".to_owned(), parts: vec![vec!["When duke John came sailing".to_owned(), "On his horse".to_owned()]] };
+        let frisian = Translation { title: "Hartoch Jan".to_owned(), parts: vec![vec!["Doe't hartoch Jan kaam farren".to_owned()]] };
+        lyric.translations.insert("en".to_owned(), english);
+        lyric.translations.insert("fy".to_owned(), frisian);
+        let text = lyric.to_string();
+        assert!(text.contains("\n--- en\ntitle: Duke John\n---\n"));
+
+        let lyric_post: LyricPost = text.parse().unwrap();
+        assert_eq!(lyric_post.parts, lyric.parts);
+        assert_eq!(lyric_post.translations, lyric.translations);
+
+        let lyric_meta: LyricMeta = text.parse().unwrap();
+        assert_eq!(lyric_meta.title, HERTOG_JAN_TITLE.to_owned());
+    }
+}
@@ -0,0 +1,181 @@
+use std::collections::HashSet;
+
+use parts::to_words;
+use serde::{Deserialize, Serialize};
+
+use crate::{by_title, HasSummary, Lyric, Playlist, Summary, Transaction, Uuid};
+
+/// Similarity from which two lyrics are reported as duplicates
+pub const DEFAULT_THRESHOLD: f32 = 0.8;
+/// Share of the title in the similarity of lyrics that have text
+const TITLE_WEIGHT: f32 = 0.3;
+
+/// Lyrics that are probably the same song, ordered by title.
+/// The similarity is that of the least similar pair of lyrics that put them in the cluster.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct DuplicateCluster {
+    pub lyrics: Vec<Summary>,
+    pub similarity: f32,
+}
+
+/// The lyric to keep and the lyrics to merge into it
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct Merge {
+    pub keep: Uuid,
+    pub duplicates: Vec<Uuid>,
+}
+
+/// Normalized words of the title and pairs of consecutive normalized words of the text
+struct Fingerprint {
+    title: HashSet<String>,
+    text: HashSet<String>,
+}
+
+impl From<&Lyric> for Fingerprint {
+    fn from(lyric: &Lyric) -> Self {
+        let words = lyric.parts.iter().flatten().flat_map(|line| to_words(line)).collect::<Vec<_>>();
+        let text = match words.len() {
+            1 => words.into_iter().collect(),
+            _ => words.windows(2).map(|pair| pair.join(" ")).collect(),
+        };
+        Self {
+            title: to_words(&lyric.title).into_iter().collect(),
+            text,
+        }
+    }
+}
+
+/// Share of the items that the sets have in common, None if both are empty
+fn jaccard(a: &HashSet<String>, b: &HashSet<String>) -> Option<f32> {
+    let union = a.union(b).count();
+    (union > 0).then(|| a.intersection(b).count() as f32 / union as f32)
+}
+
+impl Fingerprint {
+    fn similarity(&self, other: &Fingerprint) -> f32 {
+        let title = jaccard(&self.title, &other.title).unwrap_or(1.0);
+        match jaccard(&self.text, &other.text) {
+            Some(text) => TITLE_WEIGHT * title + (1.0 - TITLE_WEIGHT) * text,
+            None => title,
+        }
+    }
+}
+
+/// Similarity of the lyrics from 0 to 1, comparing the words of the titles and the pairs of consecutive words of the text.
+/// Case, diacritics and punctuation are ignored.
+pub fn similarity(a: &Lyric, b: &Lyric) -> f32 {
+    Fingerprint::from(a).similarity(&Fingerprint::from(b))
+}
+
+fn root(parents: &mut [usize], index: usize) -> usize {
+    let mut index = index;
+    while parents[index] != index {
+        parents[index] = parents[parents[index]];
+        index = parents[index];
+    }
+    index
+}
+
+/// Groups the lyrics with a similarity of at least the threshold to another lyric of the group.
+/// Only groups with more than one lyric are returned, ordered by the title of their first lyric.
+pub fn find_duplicates(lyrics: &[Lyric], threshold: f32) -> Vec<DuplicateCluster> {
+    let fingerprints = lyrics.iter().map(Fingerprint::from).collect::<Vec<_>>();
+    let mut parents = (0..lyrics.len()).collect::<Vec<_>>();
+    let mut links = vec![];
+    for i in 0..lyrics.len() {
+        for j in i + 1..lyrics.len() {
+            let similarity = fingerprints[i].similarity(&fingerprints[j]);
+            if similarity >= threshold {
+                let (a, b) = (root(&mut parents, i), root(&mut parents, j));
+                parents[a] = b;
+                links.push((i, similarity));
+            }
+        }
+    }
+
+    let mut clusters = vec![];
+    for index in 0..lyrics.len() {
+        if root(&mut parents, index) != index {
+            continue;
+        }
+        let mut members = (0..lyrics.len()).filter(|i| root(&mut parents, *i) == index).map(|i| &lyrics[i]).collect::<Vec<_>>();
+        if members.len() < 2 {
+            continue;
+        }
+        members.sort_by(|a, b| by_title(*a, *b));
+        let similarity =
+            links
+            .iter()
+            .filter(|(i, _)| root(&mut parents, *i) == index)
+            .map(|(_, similarity)| *similarity)
+            .fold(1.0, f32::min);
+        clusters.push(DuplicateCluster { lyrics: members.into_iter().map(HasSummary::summary).collect(), similarity });
+    }
+    clusters.sort_by(|a, b| a.lyrics[0].title.cmp(&b.lyrics[0].title).then_with(|| a.lyrics[0].id.cmp(&b.lyrics[0].id)));
+    clusters
+}
+
+/// The upserts that point the playlists at the lyric to keep instead of the duplicates, followed by the deletes of the duplicates.
+/// Applied as one batch nothing is changed if one of the duplicates does not exist.
+pub fn merge_transactions(merge: &Merge, playlists: Vec<Playlist>) -> Vec<Transaction> {
+    let duplicates = merge.duplicates.iter().filter(|id| **id != merge.keep).cloned().collect::<Vec<_>>();
+    playlists
+    .into_iter()
+    .filter_map(|mut playlist| playlist.replace_members(&duplicates, merge.keep).then_some(playlist))
+    .map(Transaction::PlaylistUpsert)
+    .chain(duplicates.iter().cloned().map(Transaction::LyricDelete))
+    .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{find_duplicates, merge_transactions, similarity, Merge, DEFAULT_THRESHOLD};
+    use crate::{Lyric, LyricPost, Playlist, PlaylistEntry, PlaylistPost, Transaction};
+
+    fn lyric(title: &str, text: &str) -> Lyric {
+        LyricPost::from((title, text)).into()
+    }
+
+    #[test]
+    fn similar_lyrics() {
+        let original = lyric("Roodkapje", "Zeg Roodkapje, waar ga je heen?\nZo alleen, zo alleen\n\nNaar grootmoeder in het bos");
+        let copy = lyric("Roodkapje (2)", "Zeg roodkapje waar ga je heen\nzo alleen, zo alleen\n\nNaar grootmoeder in het bos");
+        let other = lyric("Daar bij die molen", "Daar bij die molen, die mooie molen\nDaar woont het meisje");
+        assert!(similarity(&original, &copy) >= DEFAULT_THRESHOLD);
+        assert!(similarity(&original, &other) < 0.1);
+        assert_eq!(similarity(&lyric("Één", ""), &lyric("een", "")), 1.0);
+    }
+
+    #[test]
+    fn clusters() {
+        let lyrics = vec![
+            lyric("Roodkapje", "Zeg roodkapje waar ga je heen\nzo alleen"),
+            lyric("Daar bij die molen", "Daar bij die molen, die mooie molen"),
+            lyric("Zeg Roodkapje", "Zeg roodkapje, waar ga je heen?\nZo alleen"),
+            lyric("Roodkapje!", "Zeg roodkapje waar ga je heen\nzo alleen"),
+        ];
+        let clusters = find_duplicates(&lyrics, DEFAULT_THRESHOLD);
+        assert_eq!(clusters.len(), 1);
+        assert_eq!(clusters[0].lyrics.iter().map(|summary| summary.title.as_str()).collect::<Vec<_>>(), vec!["Roodkapje", "Roodkapje!", "Zeg Roodkapje"]);
+        assert!(clusters[0].similarity >= DEFAULT_THRESHOLD);
+        assert!(find_duplicates(&lyrics, 1.1).is_empty());
+    }
+
+    #[test]
+    fn merge() {
+        let (keep, duplicate, other) = (lyric("Roodkapje", ""), lyric("Roodkapje (2)", ""), lyric("Daar bij die molen", ""));
+        let plain: Playlist = PlaylistPost { title: "Kinderliedjes".to_owned(), members: vec![duplicate.id, other.id], entries: vec![], event: None }.into();
+        let entries = vec![PlaylistEntry { key: Some("D".to_owned()), ..PlaylistEntry::lyric(duplicate.id) }, PlaylistEntry::divider("Pauze")];
+        let with_entries: Playlist = PlaylistPost { title: "Zondag".to_owned(), members: vec![], entries, event: None }.into();
+        let untouched: Playlist = PlaylistPost { title: "Molens".to_owned(), members: vec![other.id], entries: vec![], event: None }.into();
+
+        let transactions = merge_transactions(&Merge { keep: keep.id, duplicates: vec![duplicate.id, keep.id] }, vec![plain, with_entries, untouched]);
+        assert_eq!(transactions.len(), 3);
+        let Transaction::PlaylistUpsert(plain) = &transactions[0] else { panic!("expected a playlist upsert") };
+        assert_eq!(plain.members, vec![keep.id, other.id]);
+        let Transaction::PlaylistUpsert(with_entries) = &transactions[1] else { panic!("expected a playlist upsert") };
+        assert_eq!(with_entries.members, vec![keep.id]);
+        assert_eq!(with_entries.entries[0], PlaylistEntry { key: Some("D".to_owned()), ..PlaylistEntry::lyric(keep.id) });
+        assert!(matches!(transactions[2], Transaction::LyricDelete(id) if id == duplicate.id));
+    }
+}
@@ -0,0 +1,137 @@
+use serde::{Deserialize, Serialize};
+use crate::{Playlist, Uuid};
+
+/// An item of a playlist: a lyric, or a divider with a text like `Sermon` when there is no lyric.
+/// For a lyric the entry can select the parts to sing, counted from 1, and the key to sing it in.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub struct PlaylistEntry {
+    #[serde(default)]
+    pub lyric: Option<Uuid>,
+    #[serde(default)]
+    pub text: Option<String>,
+    #[serde(default)]
+    pub note: Option<String>,
+    #[serde(default)]
+    pub parts: Vec<usize>,
+    #[serde(default)]
+    pub key: Option<String>,
+}
+
+impl PlaylistEntry {
+    pub fn lyric(id: Uuid) -> Self {
+        Self { lyric: Some(id), ..Default::default() }
+    }
+
+    pub fn divider(text: &str) -> Self {
+        Self { text: Some(text.to_owned()), ..Default::default() }
+    }
+
+    /// A lyric without note, selected parts or key, which is all a member says
+    pub fn is_plain(&self) -> bool {
+        self.lyric.is_some() && self.text.is_none() && self.note.is_none() && self.parts.is_empty() && self.key.is_none()
+    }
+}
+
+/// The lyrics of the entries in order, the members for clients that do not know about entries
+pub fn entry_members(entries: &[PlaylistEntry]) -> Vec<Uuid> {
+    entries.iter().filter_map(|entry| entry.lyric).collect()
+}
+
+/// Entries that only list lyrics are left out, the members say the same
+pub fn normalized_entries(entries: Vec<PlaylistEntry>) -> Vec<PlaylistEntry> {
+    if entries.iter().all(PlaylistEntry::is_plain) { vec![] } else { entries }
+}
+
+impl Playlist {
+    /// The entries, or an entry for every member if the playlist has no more than members
+    pub fn entries(&self) -> Vec<PlaylistEntry> {
+        if self.entries.is_empty() {
+            self.members.iter().cloned().map(PlaylistEntry::lyric).collect()
+        }
+        else {
+            self.entries.clone()
+        }
+    }
+
+    /// Replaces the entries, the members follow the lyrics of the entries
+    pub fn set_entries(&mut self, entries: Vec<PlaylistEntry>) {
+        self.members = entry_members(&entries);
+        self.entries = normalized_entries(entries);
+    }
+
+    /// Keeps the members, and the entries of lyrics, for which keep returns true. Dividers are kept.
+    pub fn retain_members(&mut self, mut keep: impl FnMut(&Uuid) -> bool) {
+        if self.entries.is_empty() {
+            self.members.retain(|member| keep(member));
+        }
+        else {
+            let entries = self.entries.drain(..).filter(|entry| entry.lyric.as_ref().map(&mut keep).unwrap_or(true)).collect();
+            self.set_entries(entries);
+        }
+    }
+
+    /// Points the members, and the entries of lyrics, that are one of the lyrics at the lyric with id instead.
+    /// Returns true if the playlist changed.
+    pub fn replace_members(&mut self, lyrics: &[Uuid], id: Uuid) -> bool {
+        if !self.members.iter().any(|member| lyrics.contains(member)) {
+            return false;
+        }
+        let entries =
+            self.entries()
+            .into_iter()
+            .map(|entry| match entry.lyric {
+                Some(lyric) if lyrics.contains(&lyric) => PlaylistEntry { lyric: Some(id), ..entry },
+                _ => entry,
+            })
+            .collect();
+        self.set_entries(entries);
+        true
+    }
+
+    /// Inserts a lyric at the index in the members, or at the end if there are fewer members.
+    /// In the entries it goes before the entry of the member that was at the index.
+    pub fn insert_member(&mut self, index: usize, id: Uuid) {
+        let index = index.min(self.members.len());
+        if !self.entries.is_empty() {
+            let position =
+                self.entries
+                .iter()
+                .enumerate()
+                .filter(|(_, entry)| entry.lyric.is_some())
+                .nth(index)
+                .map(|(position, _)| position)
+                .unwrap_or(self.entries.len());
+            self.entries.insert(position, PlaylistEntry::lyric(id));
+        }
+        self.members.insert(index, id);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{Playlist, PlaylistEntry, PlaylistPost, Uuid};
+
+    #[test]
+    fn entries_and_members() {
+        let (first, second) = (Uuid::default(), Uuid::default());
+        let sermon = PlaylistEntry::divider("Preek");
+        let verses = PlaylistEntry { parts: vec![1, 3], key: Some("D".to_owned()), ..PlaylistEntry::lyric(second) };
+        let mut playlist = Playlist::from(
+            PlaylistPost { title: "Zondag".to_owned(), members: vec![], entries: vec![PlaylistEntry::lyric(first), sermon.clone(), verses.clone()], event: None }
+        );
+        assert_eq!(playlist.members, vec![first, second]);
+
+        playlist.insert_member(1, first);
+        assert_eq!(playlist.members, vec![first, first, second]);
+        assert_eq!(playlist.entries[1], sermon);
+        assert_eq!(playlist.entries[2], PlaylistEntry::lyric(first));
+
+        playlist.retain_members(|member| *member != first);
+        assert_eq!(playlist.members, vec![second]);
+        assert_eq!(playlist.entries, vec![sermon, verses]);
+
+        playlist.set_entries(vec![PlaylistEntry::lyric(second)]);
+        assert!(playlist.entries.is_empty());
+        assert_eq!(playlist.entries(), vec![PlaylistEntry::lyric(second)]);
+    }
+}
@@ -12,8 +12,8 @@ pub enum Error {
     #[error("Yaml Error: {0}")]
     YamlError(#[from] serde_yaml::Error),
 
-    #[error("Lyric with id {1} not found. Cannot add to playlist with id {0}")]
-    PlaylistInvalidMember(String, String),
+    #[error("Playlist {0} has members that are not lyrics: {1:?}")]
+    InvalidMembers(Uuid, Vec<Uuid>),
 
     #[error("Cannot find directory {0:?}")]
     CannotFindDirectory(Option<String>),
@@ -36,6 +36,9 @@ pub enum Error {
     #[error("Argument error: {0}")]
     Argument(&'static str),
 
+    #[error("Transaction log is damaged: {0}")]
+    CorruptLog(String),
+
     #[error("Directory does not exist: {0}")]
     NonExistingDirectory(std::path::PathBuf),
 
@@ -74,6 +77,9 @@ pub enum Error {
     #[error("Occupied")]
     Occupied,
 
+    #[error("Etag mismatch for {0}")]
+    EtagMismatch(Uuid),
+
     #[error(transparent)]
     Warp(Box<dyn std::error::Error + Send + Sync>),
 
@@ -93,9 +99,6 @@ pub enum FileRepoError {
     #[error("IO Error: {0}")]
     IOError(#[from] std::io::Error),
 
-    #[error("Lyric with id {1} not found. Cannot add to playlist with id {0}")]
-    PlaylistInvalidMember(String, String),
-
     #[error("Cannot find directory {0:?}")]
     CannotFindDirectory(Option<String>),
 
@@ -137,6 +140,9 @@ pub enum PostgresRepoError {
 
     #[error("No results")]
     NoResults,
+
+    #[error("Json: {0}")]
+    Json(#[from] serde_json::Error),
 }
 
 #[cfg(feature = "redis")]
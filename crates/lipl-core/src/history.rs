@@ -0,0 +1,187 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{change::now, Etag, Lyric, LyricMetadata};
+
+/// A version of a lyric as it was written. Revisions of a lyric are numbered from 1 in the order they were written.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Revision {
+    pub rev: u64,
+    /// Milliseconds since the unix epoch
+    pub timestamp: u64,
+    pub lyric: Lyric,
+}
+
+impl Revision {
+    /// The revision to add to the history when the lyric is written, none if the lyric did not change
+    pub fn next(history: &[Revision], lyric: &Lyric) -> Option<Self> {
+        match history.last() {
+            Some(last) if last.lyric.etag() == lyric.etag() => None,
+            last => Some(
+                Self {
+                    rev: last.map(|revision| revision.rev).unwrap_or_default() + 1,
+                    timestamp: now(),
+                    lyric: lyric.clone(),
+                }
+            ),
+        }
+    }
+
+    /// Changes needed to get from this revision to the other
+    pub fn diff(&self, other: &Revision) -> LyricDiff {
+        diff(&self.lyric, &other.lyric)
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub enum LineDiff {
+    Same(String),
+    Added(String),
+    Removed(String),
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub enum PartDiff {
+    Same(Vec<String>),
+    Added(Vec<String>),
+    Removed(Vec<String>),
+    Changed(Vec<LineDiff>),
+}
+
+/// Differences between two versions of a lyric
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct LyricDiff {
+    /// Old and new title, if the title changed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<(String, String)>,
+    /// Old and new metadata, if the metadata changed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<(LyricMetadata, LyricMetadata)>,
+    pub parts: Vec<PartDiff>,
+}
+
+impl LyricDiff {
+    pub fn is_empty(&self) -> bool {
+        self.title.is_none()
+        && self.metadata.is_none()
+        && self.parts.iter().all(|part| matches!(part, PartDiff::Same(_)))
+    }
+}
+
+enum Edit<'a, T> {
+    Same(&'a T),
+    Added(&'a T),
+    Removed(&'a T),
+}
+
+/// Shortest edit script from old to new, based on the longest common subsequence
+fn edits<'a, T: PartialEq>(old: &'a [T], new: &'a [T]) -> Vec<Edit<'a, T>> {
+    let mut lengths = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lengths[i][j] =
+                if old[i] == new[j] { lengths[i + 1][j + 1] + 1 }
+                else { lengths[i + 1][j].max(lengths[i][j + 1]) };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let mut result = vec![];
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            result.push(Edit::Same(&old[i]));
+            i += 1;
+            j += 1;
+        }
+        else if lengths[i + 1][j] >= lengths[i][j + 1] {
+            result.push(Edit::Removed(&old[i]));
+            i += 1;
+        }
+        else {
+            result.push(Edit::Added(&new[j]));
+            j += 1;
+        }
+    }
+    result.extend(old[i..].iter().map(Edit::Removed));
+    result.extend(new[j..].iter().map(Edit::Added));
+    result
+}
+
+fn diff_lines(old: &[String], new: &[String]) -> Vec<LineDiff> {
+    edits(old, new)
+    .into_iter()
+    .map(|edit| match edit {
+        Edit::Same(line) => LineDiff::Same(line.clone()),
+        Edit::Added(line) => LineDiff::Added(line.clone()),
+        Edit::Removed(line) => LineDiff::Removed(line.clone()),
+    })
+    .collect()
+}
+
+/// Removed and added parts between two unchanged parts are paired up and diffed line by line
+fn flush_parts(result: &mut Vec<PartDiff>, removed: &mut Vec<&Vec<String>>, added: &mut Vec<&Vec<String>>) {
+    let paired = removed.len().min(added.len());
+    result.extend(removed.iter().zip(added.iter()).map(|(old, new)| PartDiff::Changed(diff_lines(old, new))));
+    result.extend(removed.drain(..).skip(paired).map(|part| PartDiff::Removed(part.clone())));
+    result.extend(added.drain(..).skip(paired).map(|part| PartDiff::Added(part.clone())));
+}
+
+/// Part and line level differences between two versions of a lyric
+pub fn diff(old: &Lyric, new: &Lyric) -> LyricDiff {
+    let mut parts = vec![];
+    let (mut removed, mut added) = (vec![], vec![]);
+    for edit in edits(&old.parts, &new.parts) {
+        match edit {
+            Edit::Same(part) => {
+                flush_parts(&mut parts, &mut removed, &mut added);
+                parts.push(PartDiff::Same(part.clone()));
+            },
+            Edit::Removed(part) => removed.push(part),
+            Edit::Added(part) => added.push(part),
+        }
+    }
+    flush_parts(&mut parts, &mut removed, &mut added);
+
+    LyricDiff {
+        title: (old.title != new.title).then(|| (old.title.clone(), new.title.clone())),
+        metadata: (old.metadata != new.metadata).then(|| (old.metadata.clone(), new.metadata.clone())),
+        parts,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{diff, LineDiff, PartDiff};
+    use crate::{Lyric, LyricPost};
+
+    #[test]
+    fn diff_parts() {
+        let old: Lyric = LyricPost::from(("Roodkapje", "Zeg roodkapje\nwaar ga je hene\n\nNaar grootmoeder\n\nIn het bos")).into();
+        let mut new = old.clone();
+        new.title = "Zeg roodkapje".to_owned();
+        new.parts = vec![
+            vec!["Zeg roodkapje".to_owned(), "waar ga je heen".to_owned()],
+            vec!["Naar grootmoeder".to_owned()],
+            vec!["Koekjes brengen".to_owned()],
+        ];
+
+        let diff = diff(&old, &new);
+        assert_eq!(diff.title, Some(("Roodkapje".to_owned(), "Zeg roodkapje".to_owned())));
+        assert!(diff.metadata.is_none());
+        assert_eq!(
+            diff.parts,
+            vec![
+                PartDiff::Changed(vec![
+                    LineDiff::Same("Zeg roodkapje".to_owned()),
+                    LineDiff::Removed("waar ga je hene".to_owned()),
+                    LineDiff::Added("waar ga je heen".to_owned()),
+                ]),
+                PartDiff::Same(vec!["Naar grootmoeder".to_owned()]),
+                PartDiff::Changed(vec![
+                    LineDiff::Removed("In het bos".to_owned()),
+                    LineDiff::Added("Koekjes brengen".to_owned()),
+                ]),
+            ]
+        );
+        assert!(super::diff(&old, &old).is_empty());
+    }
+}
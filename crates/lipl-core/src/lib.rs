@@ -14,13 +14,42 @@ use std::{cmp::Ordering};
 use async_trait::{async_trait};
 use serde::{Deserialize, Serialize};
 pub use crate::uuid::Uuid;
+pub use entry::PlaylistEntry;
+pub use batch::Transaction;
+pub use change::{Change, ChangeStream};
+pub use chords::Chords;
+pub use duplicate::{DuplicateCluster, Merge};
 pub use error::Error;
-
+pub use history::{LineDiff, LyricDiff, PartDiff, Revision};
+pub use page::{ListQuery, Page, SortField, SortOrder};
+pub use parts::{Accidental, Chord};
+pub use schedule::{DateRange, PlaylistEvent};
+pub use search::SearchHit;
+pub use tag::{TagCount, Tags};
+pub use trash::{PlaylistPosition, TrashItem, Trashed};
+pub use translation::{Translation, Translations};
+
+mod batch;
+pub mod change;
+mod arrangement;
+pub mod chordpro;
+pub mod chords;
 mod disk_format;
+pub mod duplicate;
+pub mod entry;
 pub mod error;
+pub mod history;
+#[cfg(feature = "transaction")]
+mod log_format;
+mod page;
 pub mod reexport;
+pub mod schedule;
+pub mod search;
+pub mod tag;
 #[cfg(feature = "transaction")]
 pub mod transaction;
+pub mod trash;
+mod translation;
 mod uuid;
 
 pub type Result<T> = core::result::Result<T, Error>;
@@ -29,14 +58,43 @@ pub type Result<T> = core::result::Result<T, Error>;
 pub trait LiplRepo: Send + Sync {
     async fn get_lyrics(&self) -> Result<Vec<Lyric>>;
     async fn get_lyric_summaries(&self) -> Result<Vec<Summary>>;
+    async fn get_lyric_summaries_page(&self, query: ListQuery) -> Result<Page<Summary>>;
     async fn get_lyric(&self, id: Uuid) -> Result<Lyric>;
     async fn upsert_lyric(&self, lyric: Lyric) -> Result<Lyric>;
     async fn delete_lyric(&self, id: Uuid) -> Result<()>;
+    async fn search_lyrics(&self, query: &str) -> Result<Vec<SearchHit>>;
+    /// Every tag of the lyrics with the number of lyrics that have it, ordered by tag
+    async fn get_tags(&self) -> Result<Vec<TagCount>>;
+    /// Every version of the lyric that was written, oldest first
+    async fn get_lyric_history(&self, id: Uuid) -> Result<Vec<Revision>>;
+    async fn get_lyric_revision(&self, id: Uuid, rev: u64) -> Result<Revision>;
+    /// Writes the lyric as it was in the revision, which adds a new revision
+    async fn revert_lyric(&self, id: Uuid, rev: u64) -> Result<Lyric>;
+    /// Points every playlist at the lyric to keep instead of the duplicates and moves the duplicates to the trash, all or nothing
+    async fn merge_lyrics(&self, merge: Merge) -> Result<Lyric>;
     async fn get_playlists(&self) -> Result<Vec<Playlist>>;
     async fn get_playlist_summaries(&self) -> Result<Vec<Summary>>;
+    async fn get_playlist_summaries_page(&self, query: ListQuery) -> Result<Page<Summary>>;
     async fn get_playlist(&self, id: Uuid) -> Result<Playlist>;
+    /// Playlists with an event in the range, ordered by the start of the event
+    async fn get_scheduled_playlists(&self, range: DateRange) -> Result<Vec<Playlist>>;
     async fn upsert_playlist(&self, playlist: Playlist) -> Result<Playlist>;
     async fn delete_playlist(&self, id: Uuid) -> Result<()>;
+    async fn upsert_lyric_if_match(&self, lyric: Lyric, etag: String) -> Result<Lyric>;
+    async fn delete_lyric_if_match(&self, id: Uuid, etag: String) -> Result<()>;
+    async fn upsert_playlist_if_match(&self, playlist: Playlist, etag: String) -> Result<Playlist>;
+    async fn delete_playlist_if_match(&self, id: Uuid, etag: String) -> Result<()>;
+    /// Applies all transactions in order, or none of them if one fails
+    async fn apply_batch(&self, batch: Vec<Transaction>) -> Result<()>;
+    /// Stream with the changes committed after the call
+    async fn watch(&self) -> Result<ChangeStream>;
+    /// Deleted lyrics and playlists, most recently deleted first
+    async fn list_trash(&self) -> Result<Vec<Trashed>>;
+    /// Undoes the delete. A lyric is also put back in the playlists at the positions it had.
+    /// Fails with Occupied when an item with the same id was upserted after the delete.
+    async fn restore(&self, id: Uuid) -> Result<()>;
+    /// Removes a lyric or playlist from the trash permanently, together with the history of a lyric
+    async fn purge(&self, id: Uuid) -> Result<()>;
     async fn stop(&self) -> Result<()>;
 }
 
@@ -54,6 +112,41 @@ pub struct Lyric {
     pub id: Uuid,
     pub title: String,
     pub parts: Vec<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub chords: Chords,
+    /// Label per part, like `Refrein` or `Verse 2`. Empty if none of the parts has a label.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub labels: Vec<Option<String>>,
+    /// Labels of the parts in the order they are sung. Empty if they are sung in the order of the parts.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub arrangement: Vec<String>,
+    /// Title and parts in other languages, by language code
+    #[serde(default, skip_serializing_if = "Translations::is_empty")]
+    pub translations: Translations,
+    /// Themes like `kerst` or `kinderliedjes` to group lyrics by
+    #[serde(default, skip_serializing_if = "Tags::is_empty")]
+    pub tags: Tags,
+    #[serde(default, skip_serializing_if = "LyricMetadata::is_empty")]
+    pub metadata: LyricMetadata,
+}
+
+/// Descriptive information about a lyric that is not part of the text itself
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct LyricMetadata {
+    pub sub_title: Option<String>,
+    pub lyricist: Option<String>,
+    pub composer: Option<String>,
+    pub language: Option<String>,
+    pub year: Option<u16>,
+    pub copyright: Option<String>,
+    pub source: Option<String>,
+}
+
+impl LyricMetadata {
+    pub fn is_empty(&self) -> bool {
+        *self == LyricMetadata::default()
+    }
 }
 
 impl HasSummary for Lyric {
@@ -69,13 +162,38 @@ pub fn by_title<T>(a: &T, b: &T) -> Ordering
 where
     T: HasSummary,
 {
-    a.summary().title.cmp(&b.summary().title)
+    let (a, b) = (a.summary(), b.summary());
+    a.title.cmp(&b.title).then_with(|| a.id.cmp(&b.id))
+}
+
+pub fn sorted_by_title<T>(mut list: Vec<T>) -> Vec<T>
+where
+    T: HasSummary,
+{
+    list.sort_by(by_title);
+    list
 }
 
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
 pub struct LyricPost {
     pub title: String,
     pub parts: Vec<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub chords: Chords,
+    /// Label per part, like `Refrein` or `Verse 2`. Empty if none of the parts has a label.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub labels: Vec<Option<String>>,
+    /// Labels of the parts in the order they are sung. Empty if they are sung in the order of the parts.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub arrangement: Vec<String>,
+    /// Title and parts in other languages, by language code
+    #[serde(default, skip_serializing_if = "Translations::is_empty")]
+    pub translations: Translations,
+    /// Themes like `kerst` or `kinderliedjes` to group lyrics by
+    #[serde(default, skip_serializing_if = "Tags::is_empty")]
+    pub tags: Tags,
+    #[serde(default, skip_serializing_if = "LyricMetadata::is_empty")]
+    pub metadata: LyricMetadata,
 }
 
 impl From<(Option<Uuid>, LyricPost)> for Lyric {
@@ -84,6 +202,12 @@ impl From<(Option<Uuid>, LyricPost)> for Lyric {
             id: data.0.unwrap_or_default(),
             title: data.1.title,
             parts: data.1.parts,
+            chords: data.1.chords,
+            labels: data.1.labels,
+            arrangement: data.1.arrangement,
+            translations: data.1.translations,
+            tags: data.1.tags,
+            metadata: data.1.metadata,
         }
     }
 }
@@ -94,21 +218,51 @@ impl From<LyricPost> for Lyric {
             id: Default::default(),
             title: lyric_post.title,
             parts: lyric_post.parts,
+            chords: lyric_post.chords,
+            labels: lyric_post.labels,
+            arrangement: lyric_post.arrangement,
+            translations: lyric_post.translations,
+            tags: lyric_post.tags,
+            metadata: lyric_post.metadata,
         }
     }
 }
 
+impl LyricPost {
+    /// Id derived from the title, for a lyric that gets the same id every time it is imported: `(Some(post.title_id()), post).into()`
+    pub fn title_id(&self) -> Uuid {
+        Uuid::from_title(&Uuid::LYRIC_NAMESPACE, &self.title)
+    }
+}
+
 impl From<Lyric> for LyricPost {
     fn from(lyric: Lyric) -> Self {
-        Self { title: lyric.title, parts: lyric.parts }
+        Self {
+            title: lyric.title,
+            parts: lyric.parts,
+            chords: lyric.chords,
+            labels: lyric.labels,
+            arrangement: lyric.arrangement,
+            translations: lyric.translations,
+            tags: lyric.tags,
+            metadata: lyric.metadata,
+        }
     }
 }
 
 impl From<(&str, &str)> for LyricPost {
+    /// Title and text, with the labels and the arrangement taken from the headings in the text
     fn from(value: (&str, &str)) -> Self {
+        let labeled = parts::to_labeled_parts(value.1.to_owned());
         Self {
             title: value.0.to_owned(),
-            parts: parts::to_parts(value.1.to_owned()),
+            parts: labeled.parts,
+            chords: vec![],
+            labels: labeled.labels,
+            arrangement: labeled.arrangement,
+            translations: Translations::new(),
+            tags: Tags::new(),
+            metadata: LyricMetadata::default(),
         }
     }
 }
@@ -118,6 +272,11 @@ pub struct Playlist {
     pub id: Uuid,
     pub title: String,
     pub members: Vec<Uuid>,
+    /// Members with notes, selected parts, keys and dividers. Empty if the playlist has no more than members.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub entries: Vec<PlaylistEntry>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub event: Option<PlaylistEvent>,
 }
 
 impl HasSummary for Playlist {
@@ -136,19 +295,38 @@ impl From<PlaylistPost> for Playlist {
 }
 
 impl From<(Option<Uuid>, PlaylistPost)> for Playlist {
+    /// The members of a post with entries are taken from the entries
     fn from(data: (Option<Uuid>, PlaylistPost)) -> Playlist {
-        Playlist {
+        let mut playlist = Playlist {
             id: data.0.unwrap_or_default(),
             title: data.1.title,
             members: data.1.members,
+            entries: vec![],
+            event: data.1.event,
+        };
+        if !data.1.entries.is_empty() {
+            playlist.set_entries(data.1.entries);
         }
+        playlist
     }
 }
 
 #[derive(Clone, Deserialize, Serialize)]
 pub struct PlaylistPost {
     pub title: String,
+    #[serde(default)]
     pub members: Vec<Uuid>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub entries: Vec<PlaylistEntry>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub event: Option<PlaylistEvent>,
+}
+
+impl PlaylistPost {
+    /// Id derived from the title, for a playlist that gets the same id every time it is imported
+    pub fn title_id(&self) -> Uuid {
+        Uuid::from_title(&Uuid::PLAYLIST_NAMESPACE, &self.title)
+    }
 }
 
 impl From<Playlist> for PlaylistPost {
@@ -156,6 +334,8 @@ impl From<Playlist> for PlaylistPost {
         PlaylistPost {
             title: p.title,
             members: p.members,
+            entries: p.entries,
+            event: p.event,
         }
     }
 }
@@ -211,6 +391,14 @@ where
 #[derive(Deserialize, Serialize)]
 pub struct LyricMeta {
     pub title: String,
+    #[serde(flatten)]
+    pub metadata: LyricMetadata,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub labels: Vec<Option<String>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub arrangement: Vec<String>,
+    #[serde(default, skip_serializing_if = "Tags::is_empty")]
+    pub tags: Tags,
     pub hash: Option<String>,
 }
 
@@ -218,6 +406,10 @@ impl From<&Lyric> for LyricMeta {
     fn from(l: &Lyric) -> Self {
         LyricMeta {
             title: l.title.clone(),
+            metadata: l.metadata.clone(),
+            labels: l.labels.clone(),
+            arrangement: l.arrangement.clone(),
+            tags: l.tags.clone(),
             hash: l.etag()
         }
     }
@@ -227,6 +419,46 @@ pub trait Etag {
     fn etag(&self) -> Option<String>;
 }
 
+/// Evaluates an If-Match precondition against the etag of the current version of an entity.
+/// The precondition fails if there is no current version.
+pub fn if_match(expected: &str, current: Option<String>) -> bool {
+    current
+        .map(|current|
+            expected
+            .split(',')
+            .map(str::trim)
+            .any(|etag| etag == "*" || etag == current)
+        )
+        .unwrap_or_default()
+}
+
+/// Returns an error if the etag of the current version of an entity does not satisfy the If-Match precondition
+pub fn check_etag<T: Etag>(id: Uuid, expected: &str, current: Option<&T>) -> Result<()> {
+    if if_match(expected, current.and_then(Etag::etag)) {
+        Ok(())
+    }
+    else {
+        Err(Error::EtagMismatch(id))
+    }
+}
+
+/// A playlist can only have existing lyrics as members. Returns Error::InvalidMembers with every member that is not a lyric,
+/// so the playlist is rejected instead of stored without them.
+pub fn check_members(playlist: &Playlist, is_lyric: impl Fn(&Uuid) -> bool) -> Result<()> {
+    let mut invalid = Vec::<Uuid>::new();
+    for member in playlist.members.iter().filter(|member| !is_lyric(member)) {
+        if !invalid.contains(member) {
+            invalid.push(*member);
+        }
+    }
+    if invalid.is_empty() {
+        Ok(())
+    }
+    else {
+        Err(Error::InvalidMembers(playlist.id, invalid))
+    }
+}
+
 impl<T: Serialize> Etag for T {
     fn etag(&self) -> Option<String> {
         bincode::serialize(self)
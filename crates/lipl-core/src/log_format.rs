@@ -0,0 +1,562 @@
+use std::{fs::{File, OpenOptions}, io::{Read, Write}, path::Path, str::FromStr};
+
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use crate::{Chords, Error, LyricMetadata, Lyric, Playlist, PlaylistEntry, PlaylistEvent, Tags, Transaction, Translations, Uuid};
+
+/// First bytes of a log in the binary format
+const BINARY_MAGIC: &[u8; 4] = b"LPL1";
+/// Length and checksum in front of every binary record
+const FRAME_HEADER_LEN: usize = 8;
+
+/// Encoding of the transaction log. A log that already has records keeps the format it was written in,
+/// the format of the repo is used for new logs.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// One json record per line
+    #[default]
+    Json,
+    /// Length prefixed bincode records
+    Bincode,
+}
+
+impl FromStr for LogFormat {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(LogFormat::Json),
+            "bincode" => Ok(LogFormat::Bincode),
+            _ => Err(Error::Argument("log format must be json or bincode")),
+        }
+    }
+}
+
+/// The transactions of one commit as they are logged, a single change or all changes of a batch.
+/// Sequence numbers are counted from 1 and continue over compactions,
+/// the timestamp is the moment the record was written, in RFC3339 format.
+#[derive(Clone, Debug)]
+pub struct LogRecord {
+    pub sequence: u64,
+    pub timestamp: String,
+    pub transactions: Vec<Transaction>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct JsonRecord {
+    sequence: u64,
+    timestamp: String,
+    transaction: JsonTransactions,
+    checksum: u32,
+}
+
+/// A record with one transaction is written as the transaction itself, like before batches were logged as one record
+#[derive(Deserialize, Serialize)]
+#[allow(clippy::large_enum_variant)]
+#[serde(untagged)]
+enum JsonTransactions {
+    One(Transaction),
+    Batch(Vec<Transaction>),
+}
+
+impl From<Vec<Transaction>> for JsonTransactions {
+    fn from(mut transactions: Vec<Transaction>) -> Self {
+        match transactions.len() {
+            1 => JsonTransactions::One(transactions.remove(0)),
+            _ => JsonTransactions::Batch(transactions),
+        }
+    }
+}
+
+impl From<JsonTransactions> for Vec<Transaction> {
+    fn from(transactions: JsonTransactions) -> Self {
+        match transactions {
+            JsonTransactions::One(transaction) => vec![transaction],
+            JsonTransactions::Batch(transactions) => transactions,
+        }
+    }
+}
+
+/// Version of the wire form binary records are written in. A change to the wire form gets a new version.
+/// Version 1 records hold one transaction, version 2 records the list of transactions of a commit.
+const WIRE_VERSION: u8 = 2;
+
+/// Transaction with every field written, because bincode can not read back skipped fields
+#[allow(clippy::large_enum_variant)]
+#[derive(Deserialize, Serialize)]
+enum WireTransaction {
+    LyricDelete(Uuid),
+    LyricUpsert(WireLyric),
+    PlaylistDelete(Uuid),
+    PlaylistUpsert(WirePlaylist),
+}
+
+#[derive(Deserialize, Serialize)]
+struct WireLyric {
+    id: Uuid,
+    title: String,
+    parts: Vec<Vec<String>>,
+    chords: Chords,
+    labels: Vec<Option<String>>,
+    arrangement: Vec<String>,
+    translations: Translations,
+    tags: Tags,
+    metadata: LyricMetadata,
+}
+
+#[derive(Deserialize, Serialize)]
+struct WirePlaylist {
+    id: Uuid,
+    title: String,
+    members: Vec<Uuid>,
+    entries: Vec<WireEntry>,
+    event: Option<WireEvent>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct WireEntry {
+    lyric: Option<Uuid>,
+    text: Option<String>,
+    note: Option<String>,
+    parts: Vec<usize>,
+    key: Option<String>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct WireEvent {
+    start: NaiveDateTime,
+    location: Option<String>,
+    leader: Option<String>,
+}
+
+impl From<PlaylistEntry> for WireEntry {
+    fn from(entry: PlaylistEntry) -> Self {
+        WireEntry { lyric: entry.lyric, text: entry.text, note: entry.note, parts: entry.parts, key: entry.key }
+    }
+}
+
+impl From<WireEntry> for PlaylistEntry {
+    fn from(entry: WireEntry) -> Self {
+        PlaylistEntry { lyric: entry.lyric, text: entry.text, note: entry.note, parts: entry.parts, key: entry.key }
+    }
+}
+
+impl From<PlaylistEvent> for WireEvent {
+    fn from(event: PlaylistEvent) -> Self {
+        WireEvent { start: event.start, location: event.location, leader: event.leader }
+    }
+}
+
+impl From<WireEvent> for PlaylistEvent {
+    fn from(event: WireEvent) -> Self {
+        PlaylistEvent { start: event.start, location: event.location, leader: event.leader }
+    }
+}
+
+fn to_entries(entries: Vec<WireEntry>) -> Vec<PlaylistEntry> {
+    entries.into_iter().map(PlaylistEntry::from).collect()
+}
+
+impl From<Transaction> for WireTransaction {
+    fn from(transaction: Transaction) -> Self {
+        match transaction {
+            Transaction::LyricDelete(id) => WireTransaction::LyricDelete(id),
+            Transaction::LyricUpsert(lyric) =>
+                WireTransaction::LyricUpsert(
+                    WireLyric {
+                        id: lyric.id,
+                        title: lyric.title,
+                        parts: lyric.parts,
+                        chords: lyric.chords,
+                        labels: lyric.labels,
+                        arrangement: lyric.arrangement,
+                        translations: lyric.translations,
+                        tags: lyric.tags,
+                        metadata: lyric.metadata,
+                    }
+                ),
+            Transaction::PlaylistDelete(id) => WireTransaction::PlaylistDelete(id),
+            Transaction::PlaylistUpsert(playlist) =>
+                WireTransaction::PlaylistUpsert(
+                    WirePlaylist {
+                        id: playlist.id,
+                        title: playlist.title,
+                        members: playlist.members,
+                        entries: playlist.entries.into_iter().map(WireEntry::from).collect(),
+                        event: playlist.event.map(WireEvent::from),
+                    }
+                ),
+        }
+    }
+}
+
+impl From<WireTransaction> for Transaction {
+    fn from(transaction: WireTransaction) -> Self {
+        match transaction {
+            WireTransaction::LyricDelete(id) => Transaction::LyricDelete(id),
+            WireTransaction::LyricUpsert(lyric) =>
+                Transaction::LyricUpsert(
+                    Lyric {
+                        id: lyric.id,
+                        title: lyric.title,
+                        parts: lyric.parts,
+                        chords: lyric.chords,
+                        labels: lyric.labels,
+                        arrangement: lyric.arrangement,
+                        translations: lyric.translations,
+                        tags: lyric.tags,
+                        metadata: lyric.metadata,
+                    }
+                ),
+            WireTransaction::PlaylistDelete(id) => Transaction::PlaylistDelete(id),
+            WireTransaction::PlaylistUpsert(playlist) =>
+                Transaction::PlaylistUpsert(
+                    Playlist {
+                        id: playlist.id,
+                        title: playlist.title,
+                        members: playlist.members,
+                        entries: to_entries(playlist.entries),
+                        event: playlist.event.map(PlaylistEvent::from),
+                    }
+                ),
+        }
+    }
+}
+
+fn json_error<E>(error: E) -> Error
+where
+    E: std::error::Error + Send + Sync + 'static,
+{
+    Error::Json(Box::new(error))
+}
+
+fn json_checksum(sequence: u64, timestamp: &str, transaction: &JsonTransactions) -> Result<u32, Error> {
+    serde_json::to_vec(&(sequence, timestamp, transaction))
+    .map(|payload| crc32fast::hash(&payload))
+    .map_err(json_error)
+}
+
+impl LogRecord {
+    fn to_json_line(&self) -> Result<Vec<u8>, Error> {
+        let transaction = JsonTransactions::from(self.transactions.clone());
+        let record = JsonRecord {
+            sequence: self.sequence,
+            timestamp: self.timestamp.clone(),
+            checksum: json_checksum(self.sequence, &self.timestamp, &transaction)?,
+            transaction,
+        };
+        let mut line = serde_json::to_vec(&record).map_err(json_error)?;
+        line.push(b'\n');
+        Ok(line)
+    }
+
+    /// Parses a line, checking the checksum. Lines written before records had sequence numbers and checksums are
+    /// accepted and numbered after the previous record.
+    fn from_json_line(line: &[u8], previous: u64) -> Result<Self, Error> {
+        match serde_json::from_slice::<JsonRecord>(line) {
+            Ok(record) => {
+                if json_checksum(record.sequence, &record.timestamp, &record.transaction)? != record.checksum {
+                    return Err(Error::CorruptLog(format!("checksum mismatch for record {}", record.sequence)));
+                }
+                Ok(LogRecord { sequence: record.sequence, timestamp: record.timestamp, transactions: record.transaction.into() })
+            },
+            Err(error) =>
+                serde_json::from_slice::<(String, Transaction)>(line)
+                .map(|(timestamp, transaction)| LogRecord { sequence: previous + 1, timestamp, transactions: vec![transaction] })
+                .map_err(|_| json_error(error)),
+        }
+    }
+
+    fn to_frame(&self) -> Result<Vec<u8>, Error> {
+        let transactions = self.transactions.iter().cloned().map(WireTransaction::from).collect::<Vec<_>>();
+        let payload = bincode::serialize(&(self.sequence, &self.timestamp, WIRE_VERSION, bincode::serialize(&transactions)?))?;
+        let mut frame = Vec::with_capacity(FRAME_HEADER_LEN + payload.len());
+        frame.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        frame.extend_from_slice(&crc32fast::hash(&payload).to_le_bytes());
+        frame.extend_from_slice(&payload);
+        Ok(frame)
+    }
+
+    /// Reads the record at the start of bytes and returns it with the length of its frame
+    fn from_frame(bytes: &[u8]) -> Result<(Self, usize), Error> {
+        if bytes.len() < FRAME_HEADER_LEN {
+            return Err(Error::CorruptLog("incomplete record header".to_owned()));
+        }
+        let len = u32::from_le_bytes(bytes[0..4].try_into().unwrap()) as usize;
+        let checksum = u32::from_le_bytes(bytes[4..8].try_into().unwrap());
+        let payload = bytes.get(FRAME_HEADER_LEN..FRAME_HEADER_LEN + len).ok_or_else(|| Error::CorruptLog("incomplete record".to_owned()))?;
+        if crc32fast::hash(payload) != checksum {
+            return Err(Error::CorruptLog("checksum mismatch".to_owned()));
+        }
+        let (sequence, timestamp, version, bytes) = bincode::deserialize::<(u64, String, u8, Vec<u8>)>(payload)?;
+        let transactions = match version {
+            1 => vec![bincode::deserialize::<WireTransaction>(&bytes)?.into()],
+            WIRE_VERSION => bincode::deserialize::<Vec<WireTransaction>>(&bytes)?.into_iter().map(Transaction::from).collect(),
+            _ => return Err(Error::CorruptLog(format!("unknown record version {version}"))),
+        };
+        Ok((LogRecord { sequence, timestamp, transactions }, FRAME_HEADER_LEN + len))
+    }
+}
+
+impl FromStr for LogRecord {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        LogRecord::from_json_line(s.as_bytes(), 0)
+    }
+}
+
+/// End of a log that could not be read, because a write was interrupted or the file is damaged
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DroppedTail {
+    /// Position of the first byte that could not be read
+    pub offset: u64,
+    pub bytes: u64,
+    pub reason: String,
+}
+
+/// The records of a log that could be read
+#[derive(Clone, Debug, Default)]
+pub struct LogContents {
+    pub records: Vec<LogRecord>,
+    pub dropped: Option<DroppedTail>,
+}
+
+impl LogContents {
+    pub fn last_sequence(&self) -> Option<u64> {
+        self.records.last().map(|record| record.sequence)
+    }
+}
+
+fn detect_format(bytes: &[u8]) -> Option<LogFormat> {
+    if bytes.is_empty() {
+        None
+    }
+    else if bytes.starts_with(BINARY_MAGIC) {
+        Some(LogFormat::Bincode)
+    }
+    else {
+        Some(LogFormat::Json)
+    }
+}
+
+/// Reads the records of a log in either format. Reading stops at the first record that is incomplete or has a wrong checksum,
+/// that record and everything after it are reported as dropped. Legacy records without sequence numbers are numbered after previous.
+pub fn decode_log(bytes: &[u8], previous: u64) -> Result<LogContents, Error> {
+    let mut contents = LogContents::default();
+    let mut offset = 0;
+    let mut last = previous;
+    let dropped_tail = |offset: usize, reason: String| Some(DroppedTail { offset: offset as u64, bytes: (bytes.len() - offset) as u64, reason });
+
+    match detect_format(bytes) {
+        None => {},
+        Some(LogFormat::Json) => {
+            while offset < bytes.len() {
+                let Some(end) = bytes[offset..].iter().position(|byte| *byte == b'\n') else {
+                    contents.dropped = dropped_tail(offset, "incomplete record".to_owned());
+                    break;
+                };
+                let line = &bytes[offset..offset + end];
+                if !line.iter().all(u8::is_ascii_whitespace) {
+                    match LogRecord::from_json_line(line, last) {
+                        Ok(record) => {
+                            last = record.sequence;
+                            contents.records.push(record);
+                        },
+                        Err(error) => {
+                            let later = bytes[offset + end + 1..].split(|byte| *byte == b'\n').any(|line| LogRecord::from_json_line(line, last).is_ok());
+                            if later {
+                                return Err(Error::CorruptLog(format!("record at byte {offset} is damaged, but records after it are not: {error}")));
+                            }
+                            contents.dropped = dropped_tail(offset, error.to_string());
+                            break;
+                        },
+                    }
+                }
+                offset += end + 1;
+            }
+        },
+        Some(LogFormat::Bincode) => {
+            offset = BINARY_MAGIC.len();
+            while offset < bytes.len() {
+                match LogRecord::from_frame(&bytes[offset..]) {
+                    Ok((record, len)) => {
+                        contents.records.push(record);
+                        offset += len;
+                    },
+                    Err(error) => {
+                        contents.dropped = dropped_tail(offset, error.to_string());
+                        break;
+                    },
+                }
+            }
+        },
+    }
+    Ok(contents)
+}
+
+/// Reads the records of a log file, none if it does not exist
+pub fn read_log_file(path: &Path, previous: u64) -> Result<LogContents, Error> {
+    if !path.exists() {
+        return Ok(LogContents::default());
+    }
+    let mut bytes = vec![];
+    File::open(path)?.read_to_end(&mut bytes)?;
+    decode_log(&bytes, previous)
+}
+
+/// Path the dropped end of a log is moved to
+pub fn quarantine_path(log_path: &Path) -> std::path::PathBuf {
+    let mut path = log_path.as_os_str().to_os_string();
+    path.push(".corrupt");
+    path.into()
+}
+
+/// Reads the log and moves an end that can not be read to the quarantine file, so new records are appended after the
+/// last complete one. The dropped end is reported as a warning and in the returned contents.
+pub fn recover_log(log_path: &Path, previous: u64) -> Result<LogContents, Error> {
+    let contents = read_log_file(log_path, previous)?;
+    if let Some(dropped) = contents.dropped.as_ref() {
+        let mut bytes = vec![];
+        File::open(log_path)?.read_to_end(&mut bytes)?;
+        OpenOptions::new().append(true).create(true).open(quarantine_path(log_path))?.write_all(&bytes[dropped.offset as usize..])?;
+        OpenOptions::new().write(true).open(log_path)?.set_len(dropped.offset)?;
+        tracing::warn!(
+            "Moved {} bytes after record {} from {} to {}: {}",
+            dropped.bytes,
+            contents.last_sequence().unwrap_or(previous),
+            log_path.to_string_lossy(),
+            quarantine_path(log_path).to_string_lossy(),
+            dropped.reason,
+        );
+    }
+    Ok(contents)
+}
+
+/// Appends records to a log, numbering them after the last record written
+pub struct LogWriter {
+    file: File,
+    format: LogFormat,
+    last_sequence: u64,
+}
+
+impl LogWriter {
+    /// Opens the log for appending. An empty log is written in the given format, a log with records in the format it has.
+    pub fn open(log_path: &Path, format: LogFormat, last_sequence: u64) -> Result<Self, Error> {
+        let mut file = OpenOptions::new().read(true).append(true).create(true).open(log_path)?;
+        let mut start = [0u8; BINARY_MAGIC.len()];
+        let read = file.read(&mut start)?;
+        let format = detect_format(&start[..read]).unwrap_or(format);
+        if read == 0 && format == LogFormat::Bincode {
+            file.write_all(BINARY_MAGIC)?;
+            file.flush()?;
+        }
+        Ok(Self { file, format, last_sequence })
+    }
+
+    pub fn last_sequence(&self) -> u64 {
+        self.last_sequence
+    }
+
+    /// Appends the transactions as one record, so they are recovered and replayed together or not at all
+    pub fn append(&mut self, transactions: Vec<Transaction>, timestamp: String) -> Result<(), Error> {
+        let record = LogRecord { sequence: self.last_sequence + 1, timestamp, transactions };
+        let bytes = match self.format {
+            LogFormat::Json => record.to_json_line()?,
+            LogFormat::Bincode => record.to_frame()?,
+        };
+        self.file.write_all(&bytes)?;
+        self.file.flush()?;
+        self.last_sequence = record.sequence;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{decode_log, LogFormat, LogRecord, BINARY_MAGIC, FRAME_HEADER_LEN, WIRE_VERSION};
+    use crate::{Chord, Lyric, LyricPost, Playlist, PlaylistEntry, PlaylistEvent, PlaylistPost, Transaction, Translation, Uuid};
+
+    fn records() -> Vec<LogRecord> {
+        let lyric: Lyric = LyricPost::from(("Roodkapje", "Zeg roodkapje\nwaar ga je heen")).into();
+        let with_chords = Lyric { chords: vec![vec![vec![Chord { position: 0, name: "C".to_owned() }], vec![]]], ..lyric.clone() };
+        let arranged: Lyric = LyricPost::from(("Roodkapje", "Zeg roodkapje\n\nRefrein:\nwaar ga je heen\n\nRefrein")).into();
+        let mut translated = lyric.clone();
+        translated.translations.insert("en".to_owned(), Translation { title: "Red Riding Hood".to_owned(), parts: vec![vec!["Say red riding hood".to_owned()]] });
+        let mut tagged = translated.clone();
+        tagged.tags.insert("kinderliedjes".to_owned());
+        let entries = vec![PlaylistEntry::divider("Zingen"), PlaylistEntry { key: Some("D".to_owned()), ..PlaylistEntry::lyric(lyric.id) }];
+        let playlist = Playlist::from(PlaylistPost { title: "Kinderliedjes".to_owned(), members: vec![], entries, event: None });
+        let start = chrono::NaiveDate::from_ymd_opt(2023, 1, 8).unwrap().and_hms_opt(10, 0, 0).unwrap();
+        let scheduled = Playlist { event: Some(PlaylistEvent { start, location: None, leader: Some("Anne".to_owned()) }), ..playlist.clone() };
+        vec![
+            LogRecord { sequence: 1, timestamp: "2023-01-01T10:00:00.000000Z".to_owned(), transactions: vec![Transaction::LyricUpsert(lyric.clone())] },
+            LogRecord { sequence: 2, timestamp: "2023-01-01T10:00:01.000000Z".to_owned(), transactions: vec![Transaction::LyricUpsert(with_chords)] },
+            LogRecord { sequence: 3, timestamp: "2023-01-01T10:00:02.000000Z".to_owned(), transactions: vec![Transaction::LyricUpsert(arranged)] },
+            LogRecord { sequence: 4, timestamp: "2023-01-01T10:00:03.000000Z".to_owned(), transactions: vec![Transaction::LyricUpsert(translated)] },
+            LogRecord { sequence: 5, timestamp: "2023-01-01T10:00:04.000000Z".to_owned(), transactions: vec![Transaction::LyricUpsert(tagged)] },
+            LogRecord { sequence: 6, timestamp: "2023-01-01T10:00:05.000000Z".to_owned(), transactions: vec![Transaction::PlaylistUpsert(playlist)] },
+            LogRecord { sequence: 7, timestamp: "2023-01-01T10:00:06.000000Z".to_owned(), transactions: vec![Transaction::PlaylistUpsert(scheduled.clone())] },
+            LogRecord {
+                sequence: 8,
+                timestamp: "2023-01-01T10:00:07.000000Z".to_owned(),
+                transactions: vec![Transaction::PlaylistDelete(scheduled.id), Transaction::LyricDelete(lyric.id)],
+            },
+        ]
+    }
+
+    fn encode(format: LogFormat, records: &[LogRecord]) -> Vec<u8> {
+        let mut bytes = if format == LogFormat::Bincode { BINARY_MAGIC.to_vec() } else { vec![] };
+        for record in records {
+            bytes.extend(match format {
+                LogFormat::Json => record.to_json_line().unwrap(),
+                LogFormat::Bincode => record.to_frame().unwrap(),
+            });
+        }
+        bytes
+    }
+
+    #[test]
+    fn torn_tail() {
+        for format in [LogFormat::Json, LogFormat::Bincode] {
+            let records = records();
+            let bytes = encode(format, &records);
+            let contents = decode_log(&bytes, 0).unwrap();
+            assert_eq!(format!("{:?}", contents.records), format!("{:?}", records));
+            assert!(contents.dropped.is_none());
+
+            let torn = &bytes[..bytes.len() - 3];
+            let contents = decode_log(torn, 0).unwrap();
+            assert_eq!(format!("{:?}", contents.records), format!("{:?}", &records[..7]));
+            let dropped = contents.dropped.unwrap();
+            assert_eq!(dropped.offset + dropped.bytes, torn.len() as u64);
+        }
+    }
+
+    #[test]
+    fn damaged_record() {
+        let mut bytes = encode(LogFormat::Json, &records());
+        let position = bytes.iter().position(|byte| *byte == b'Z').unwrap();
+        bytes[position - 1] = b'9';
+        assert!(decode_log(&bytes, 0).is_err(), "a damaged record followed by a good one should not be dropped silently");
+
+        let legacy = format!("[\"2023-01-01T10:00:00.000000Z\",{{\"LyricDelete\":\"{}\"}}]\n", Uuid::default());
+        let contents = decode_log(legacy.as_bytes(), 7).unwrap();
+        assert_eq!(contents.records[0].sequence, 8);
+    }
+
+    #[test]
+    fn record_versions() {
+        let record = &records()[1];
+        let transaction = bincode::serialize(&super::WireTransaction::from(record.transactions[0].clone())).unwrap();
+        let frame = |version: u8| {
+            let payload = bincode::serialize(&(record.sequence, &record.timestamp, version, transaction.clone())).unwrap();
+            let mut frame = Vec::with_capacity(FRAME_HEADER_LEN + payload.len());
+            frame.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+            frame.extend_from_slice(&crc32fast::hash(&payload).to_le_bytes());
+            frame.extend_from_slice(&payload);
+            frame
+        };
+        let (read, _) = LogRecord::from_frame(&frame(1)).unwrap();
+        assert_eq!(format!("{:?}", read), format!("{:?}", record), "records with one transaction should still be read");
+        assert!(LogRecord::from_frame(&frame(WIRE_VERSION + 1)).is_err());
+    }
+}
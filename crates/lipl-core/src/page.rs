@@ -0,0 +1,188 @@
+use std::cmp::Ordering;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{tag::has_tags, HasSummary, Lyric, Summary};
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SortField {
+    #[default]
+    Title,
+    Id,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+/// Selects a page of summaries. Titles are compared bytewise and ties are broken on id, so every backend returns the same order.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct ListQuery {
+    pub offset: usize,
+    pub limit: Option<usize>,
+    pub sort: SortField,
+    pub order: SortOrder,
+    /// Case insensitive title prefix
+    pub prefix: Option<String>,
+    /// Only lyrics with all of these tags, not used for playlists
+    #[serde(with = "crate::tag::comma_separated", skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+/// One page of a listing, with the number of items matching the filter over all pages
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub total: usize,
+}
+
+impl ListQuery {
+    /// Lowercased prefix, or the empty string if there is no prefix
+    pub fn prefix_lowercase(&self) -> String {
+        self.prefix.as_deref().unwrap_or_default().to_lowercase()
+    }
+
+    pub fn matches(&self, summary: &Summary) -> bool {
+        summary.title.to_lowercase().starts_with(&self.prefix_lowercase())
+    }
+
+    pub fn compare(&self, a: &Summary, b: &Summary) -> Ordering {
+        let ordering = match self.sort {
+            SortField::Title => a.title.cmp(&b.title).then_with(|| a.id.cmp(&b.id)),
+            SortField::Id => a.id.cmp(&b.id),
+        };
+        match self.order {
+            SortOrder::Asc => ordering,
+            SortOrder::Desc => ordering.reverse(),
+        }
+    }
+
+    /// Filters, sorts and slices the summaries in memory
+    pub fn apply(&self, summaries: Vec<Summary>) -> Page<Summary> {
+        let mut summaries = summaries.into_iter().filter(|summary| self.matches(summary)).collect::<Vec<_>>();
+        summaries.sort_by(|a, b| self.compare(a, b));
+        let total = summaries.len();
+        Page {
+            items:
+                summaries
+                .into_iter()
+                .skip(self.offset)
+                .take(self.limit.unwrap_or(usize::MAX))
+                .collect(),
+            total,
+        }
+    }
+
+    /// Filters the lyrics on the tags, then filters, sorts and slices their summaries in memory
+    pub fn apply_lyrics(&self, lyrics: Vec<Lyric>) -> Page<Summary> {
+        self.apply(
+            lyrics
+            .into_iter()
+            .filter(|lyric| has_tags(&lyric.tags, &self.tags))
+            .map(|lyric| lyric.summary())
+            .collect()
+        )
+    }
+
+    /// Pattern for a LIKE on the lowercased title
+    #[cfg(feature = "postgres")]
+    pub fn like_pattern(&self) -> String {
+        self.prefix_lowercase().replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_") + "%"
+    }
+
+    /// ORDER BY clause giving the same order as compare
+    #[cfg(feature = "postgres")]
+    pub fn order_by(&self) -> &'static str {
+        match (self.sort, self.order) {
+            (SortField::Title, SortOrder::Asc) => r#"title COLLATE "C" ASC, id ASC"#,
+            (SortField::Title, SortOrder::Desc) => r#"title COLLATE "C" DESC, id DESC"#,
+            (SortField::Id, SortOrder::Asc) => "id ASC",
+            (SortField::Id, SortOrder::Desc) => "id DESC",
+        }
+    }
+
+    fn with_offset(&self, offset: usize) -> Self {
+        Self { offset, ..self.clone() }
+    }
+
+    fn link(&self, path: &str, rel: &str) -> String {
+        format!(
+            "<{}?{}>; rel=\"{}\"",
+            path,
+            serde_urlencoded::to_string(self).unwrap_or_default(),
+            rel,
+        )
+    }
+}
+
+impl<T> Page<T> {
+    /// Value for the Link header with first, prev, next and last pages. None if the query has no limit.
+    pub fn link_header(&self, path: &str, query: &ListQuery) -> Option<String> {
+        let limit = query.limit.filter(|limit| *limit > 0)?;
+        let last = self.total.saturating_sub(1) / limit * limit;
+        let mut links = vec![query.with_offset(0).link(path, "first")];
+        if query.offset > 0 {
+            links.push(query.with_offset(query.offset.saturating_sub(limit)).link(path, "prev"));
+        }
+        if query.offset + limit < self.total {
+            links.push(query.with_offset(query.offset + limit).link(path, "next"));
+        }
+        links.push(query.with_offset(last).link(path, "last"));
+        Some(links.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ListQuery, SortOrder};
+    use crate::{Summary, Uuid};
+
+    fn summaries() -> Vec<Summary> {
+        ["Roodkapje", "Daar bij die molen", "Alle eendjes", "'t Knaapje", "Altijd is Kortjakje ziek"]
+        .into_iter()
+        .map(|title| Summary { id: Uuid::default(), title: title.to_owned() })
+        .collect()
+    }
+
+    #[test]
+    fn apply_sorts_filters_and_slices() {
+        let query = ListQuery { offset: 1, limit: Some(2), ..Default::default() };
+        let page = query.apply(summaries());
+        assert_eq!(page.total, 5);
+        assert_eq!(page.items.iter().map(|s| s.title.as_str()).collect::<Vec<_>>(), vec!["Alle eendjes", "Altijd is Kortjakje ziek"]);
+
+        let query = ListQuery { prefix: Some("al".to_owned()), order: SortOrder::Desc, ..Default::default() };
+        let page = query.apply(summaries());
+        assert_eq!(page.total, 2);
+        assert_eq!(page.items[0].title, "Altijd is Kortjakje ziek");
+    }
+
+    #[test]
+    fn link_header() {
+        let query = ListQuery { offset: 2, limit: Some(2), ..Default::default() };
+        let page = query.apply(summaries());
+        assert_eq!(
+            page.link_header("/api/v1/lyric", &query),
+            Some(
+                [
+                    "</api/v1/lyric?offset=0&limit=2&sort=title&order=asc>; rel=\"first\"",
+                    "</api/v1/lyric?offset=0&limit=2&sort=title&order=asc>; rel=\"prev\"",
+                    "</api/v1/lyric?offset=4&limit=2&sort=title&order=asc>; rel=\"next\"",
+                    "</api/v1/lyric?offset=4&limit=2&sort=title&order=asc>; rel=\"last\"",
+                ]
+                .join(", ")
+            )
+        );
+        assert_eq!(page.link_header("/api/v1/lyric", &ListQuery::default()), None);
+
+        let query = ListQuery { limit: Some(2), tags: vec!["kerst".to_owned(), "kinderliedjes".to_owned()], ..Default::default() };
+        let link = query.apply(summaries()).link_header("/api/v1/lyric", &query).unwrap();
+        assert!(link.starts_with("</api/v1/lyric?offset=0&limit=2&sort=title&order=asc&tags=kerst%2Ckinderliedjes>; rel=\"first\""));
+    }
+}
@@ -0,0 +1,172 @@
+use std::collections::HashMap;
+use chrono::{NaiveDate, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use crate::{Playlist, PlaylistEntry, Summary, Uuid};
+
+pub const CONTENT_TYPE: &str = "text/calendar";
+/// Longest line in an iCalendar file, in bytes without the line break
+const LINE_LENGTH: usize = 75;
+const DATE_TIME_FORMAT: &str = "%Y%m%dT%H%M%S";
+
+/// The service or other event a playlist is sung at.
+/// The start is a local time like `2026-10-25T10:00:00`, without a time zone.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct PlaylistEvent {
+    pub start: NaiveDateTime,
+    #[serde(default)]
+    pub location: Option<String>,
+    #[serde(default)]
+    pub leader: Option<String>,
+}
+
+/// Dates to select scheduled playlists with, both included. A missing date leaves the range open on that side.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub struct DateRange {
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+}
+
+impl DateRange {
+    pub fn contains(&self, start: &NaiveDateTime) -> bool {
+        let date = start.date();
+        self.from.map(|from| from <= date).unwrap_or(true) && self.to.map(|to| date <= to).unwrap_or(true)
+    }
+
+    /// The playlists with an event in the range, ordered by start and then like the other lists
+    pub fn apply(&self, playlists: Vec<Playlist>) -> Vec<Playlist> {
+        let mut scheduled =
+            playlists
+            .into_iter()
+            .filter(|playlist| playlist.event.as_ref().map(|event| self.contains(&event.start)).unwrap_or_default())
+            .collect::<Vec<_>>();
+        scheduled.sort_by(|a, b| a.event.as_ref().map(|event| event.start).cmp(&b.event.as_ref().map(|event| event.start)).then_with(|| crate::by_title(a, b)));
+        scheduled
+    }
+}
+
+/// Escapes the characters that have a meaning in iCalendar text values
+fn escape(text: &str) -> String {
+    text
+    .replace('\\', "\\\\")
+    .replace(';', "\\;")
+    .replace(',', "\\,")
+    .replace('\n', "\\n")
+}
+
+/// Writes the property, folding it over lines of at most 75 bytes
+fn property(calendar: &mut String, name: &str, value: &str) {
+    let line = format!("{name}:{value}");
+    let mut length = 0;
+    for c in line.chars() {
+        if length + c.len_utf8() > LINE_LENGTH {
+            calendar.push_str("\r\n ");
+            length = 1;
+        }
+        calendar.push(c);
+        length += c.len_utf8();
+    }
+    calendar.push_str("\r\n");
+}
+
+fn entry_line(entry: &PlaylistEntry, titles: &HashMap<Uuid, String>) -> String {
+    let mut line = match (entry.lyric, &entry.text) {
+        (Some(lyric), _) => titles.get(&lyric).cloned().unwrap_or_else(|| lyric.to_string()),
+        (None, Some(text)) => format!("-- {text} --"),
+        (None, None) => String::new(),
+    };
+    let mut details = vec![];
+    if !entry.parts.is_empty() {
+        details.push(format!("parts {}", entry.parts.iter().map(usize::to_string).collect::<Vec<_>>().join(", ")));
+    }
+    if let Some(key) = &entry.key {
+        details.push(format!("in {key}"));
+    }
+    if let Some(note) = &entry.note {
+        details.push(note.clone());
+    }
+    if !details.is_empty() {
+        line.push_str(&format!(" ({})", details.join("; ")));
+    }
+    line
+}
+
+/// The setlist of the playlist, with the leader on the first line if there is one
+fn description(playlist: &Playlist, event: &PlaylistEvent, titles: &HashMap<Uuid, String>) -> String {
+    event
+    .leader
+    .iter()
+    .map(|leader| format!("Leader: {leader}"))
+    .chain(playlist.entries().iter().map(|entry| entry_line(entry, titles)))
+    .collect::<Vec<_>>()
+    .join("\n")
+}
+
+/// Calendar with an event for every scheduled playlist, with the titles of the lyrics from summaries as setlist
+pub fn to_icalendar(playlists: &[Playlist], summaries: &[Summary]) -> String {
+    let titles = summaries.iter().map(|summary| (summary.id, summary.title.clone())).collect::<HashMap<_, _>>();
+    let stamp = Utc::now().naive_utc().format(DATE_TIME_FORMAT).to_string() + "Z";
+    let mut calendar = String::new();
+    property(&mut calendar, "BEGIN", "VCALENDAR");
+    property(&mut calendar, "VERSION", "2.0");
+    property(&mut calendar, "PRODID", "-//lipl//playlists//EN");
+    for playlist in playlists {
+        if let Some(event) = &playlist.event {
+            property(&mut calendar, "BEGIN", "VEVENT");
+            property(&mut calendar, "UID", &format!("{}@lipl", playlist.id));
+            property(&mut calendar, "DTSTAMP", &stamp);
+            property(&mut calendar, "DTSTART", &event.start.format(DATE_TIME_FORMAT).to_string());
+            property(&mut calendar, "SUMMARY", &escape(&playlist.title));
+            if let Some(location) = &event.location {
+                property(&mut calendar, "LOCATION", &escape(location));
+            }
+            property(&mut calendar, "DESCRIPTION", &escape(&description(playlist, event, &titles)));
+            property(&mut calendar, "END", "VEVENT");
+        }
+    }
+    property(&mut calendar, "END", "VCALENDAR");
+    calendar
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::NaiveDate;
+    use super::{to_icalendar, DateRange, PlaylistEvent};
+    use crate::{Playlist, PlaylistEntry, PlaylistPost, Summary, Uuid};
+
+    fn scheduled(title: &str, day: u32, members: Vec<Uuid>) -> Playlist {
+        let mut playlist = Playlist::from(PlaylistPost { title: title.to_owned(), members, entries: vec![], event: None });
+        playlist.event = Some(
+            PlaylistEvent {
+                start: NaiveDate::from_ymd_opt(2026, 10, day).unwrap().and_hms_opt(10, 0, 0).unwrap(),
+                location: Some("Grote Kerk, Dokkum".to_owned()),
+                leader: Some("Anne".to_owned()),
+            }
+        );
+        playlist
+    }
+
+    #[test]
+    fn date_range() {
+        let unscheduled = Playlist::from(PlaylistPost { title: "Altijd".to_owned(), members: vec![], entries: vec![], event: None });
+        let playlists = vec![scheduled("Laat", 25, vec![]), unscheduled, scheduled("Vroeg", 18, vec![]), scheduled("Eerder", 11, vec![])];
+        let range = DateRange { from: NaiveDate::from_ymd_opt(2026, 10, 18), to: NaiveDate::from_ymd_opt(2026, 10, 25) };
+        let titles = range.apply(playlists.clone()).into_iter().map(|playlist| playlist.title).collect::<Vec<_>>();
+        assert_eq!(titles, vec!["Vroeg", "Laat"]);
+        assert_eq!(DateRange::default().apply(playlists).len(), 3);
+    }
+
+    #[test]
+    fn icalendar() {
+        let lyric = Summary { id: Uuid::default(), title: "Roodkapje".to_owned() };
+        let mut playlist = scheduled("Zondag", 25, vec![]);
+        playlist.set_entries(vec![PlaylistEntry::divider("Preek"), PlaylistEntry { key: Some("D".to_owned()), ..PlaylistEntry::lyric(lyric.id) }]);
+        let calendar = to_icalendar(&[playlist.clone()], &[lyric]);
+        assert!(calendar.starts_with("BEGIN:VCALENDAR\r\n"));
+        assert!(calendar.ends_with("END:VCALENDAR\r\n"));
+        assert!(calendar.contains(&format!("UID:{}@lipl\r\n", playlist.id)));
+        assert!(calendar.contains("DTSTART:20261025T100000\r\n"));
+        assert!(calendar.contains("LOCATION:Grote Kerk\\, Dokkum\r\n"));
+        assert!(calendar.contains("DESCRIPTION:Leader: Anne\\n-- Preek --\\nRoodkapje (in D)\r\n"));
+        assert!(calendar.split("\r\n").all(|line| line.len() <= 75));
+    }
+}
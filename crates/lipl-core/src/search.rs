@@ -0,0 +1,200 @@
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+
+use parts::to_words;
+use serde::{Deserialize, Serialize};
+
+use crate::{Lyric, Summary, Uuid};
+
+const TITLE_WEIGHT: f32 = 2.0;
+
+/// A lyric found by search_lyrics, with the line that best matches the query
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct SearchHit {
+    #[serde(flatten)]
+    pub summary: Summary,
+    pub line: Option<String>,
+    pub rank: f32,
+}
+
+/// Normalized and deduplicated words of a search query
+pub fn terms(query: &str) -> Vec<String> {
+    let mut terms = to_words(query);
+    terms.sort();
+    terms.dedup();
+    terms
+}
+
+fn words(lyric: &Lyric) -> impl Iterator<Item = String> + '_ {
+    to_words(&lyric.title)
+        .into_iter()
+        .chain(lyric.parts.iter().flatten().flat_map(|line| to_words(line)))
+}
+
+/// Words a lyric can be found by: the words of the title and of all lines
+pub fn lyric_words(lyric: &Lyric) -> HashSet<String> {
+    words(lyric).collect()
+}
+
+/// Normalized words of title and text separated by spaces, for storage in a full text search column
+pub fn to_search_text(lyric: &Lyric) -> String {
+    words(lyric).collect::<Vec<_>>().join(" ")
+}
+
+fn count_terms(words: &[String], terms: &[String]) -> usize {
+    terms.iter().filter(|term| words.contains(term)).count()
+}
+
+/// The first line containing most of the terms
+pub fn matching_line(parts: &[Vec<String>], terms: &[String]) -> Option<String> {
+    parts
+        .iter()
+        .flatten()
+        .map(|line| (count_terms(&to_words(line), terms), line))
+        .filter(|(count, _)| *count > 0)
+        .fold(None, |best: Option<(usize, &String)>, (count, line)| match best {
+            Some((best_count, _)) if best_count >= count => best,
+            _ => Some((count, line)),
+        })
+        .map(|(_, line)| line.clone())
+}
+
+/// Ranks the lyric against the terms. Returns None if not all terms occur in the title or text.
+pub fn rank(lyric: &Lyric, terms: &[String]) -> Option<SearchHit> {
+    if terms.is_empty() {
+        return None;
+    }
+    let words = lyric_words(lyric);
+    if !terms.iter().all(|term| words.contains(term)) {
+        return None;
+    }
+    let title_words = to_words(&lyric.title);
+    let title_count = count_terms(&title_words, terms) as f32;
+    let line_count =
+        lyric.parts
+        .iter()
+        .flatten()
+        .map(|line| count_terms(&to_words(line), terms))
+        .sum::<usize>() as f32;
+
+    Some(
+        SearchHit {
+            summary: Summary { id: lyric.id, title: lyric.title.clone() },
+            line: matching_line(&lyric.parts, terms),
+            rank: TITLE_WEIGHT * title_count + line_count,
+        }
+    )
+}
+
+/// Orders hits by descending rank, then by title
+pub fn by_rank(a: &SearchHit, b: &SearchHit) -> Ordering {
+    b.rank
+        .partial_cmp(&a.rank)
+        .unwrap_or(Ordering::Equal)
+        .then_with(|| a.summary.title.cmp(&b.summary.title))
+}
+
+/// Searches the lyrics by ranking every one of them
+pub fn search<'a>(lyrics: impl Iterator<Item = &'a Lyric>, query: &str) -> Vec<SearchHit> {
+    let terms = terms(query);
+    let mut hits = lyrics.filter_map(|lyric| rank(lyric, &terms)).collect::<Vec<_>>();
+    hits.sort_by(by_rank);
+    hits
+}
+
+/// In-process inverted index from normalized words to the lyrics containing them
+#[derive(Clone, Debug, Default)]
+pub struct SearchIndex {
+    words: HashMap<String, HashSet<Uuid>>,
+    lyrics: HashMap<Uuid, HashSet<String>>,
+}
+
+impl SearchIndex {
+    pub fn new<'a>(lyrics: impl Iterator<Item = &'a Lyric>) -> Self {
+        let mut index = Self::default();
+        lyrics.for_each(|lyric| index.insert(lyric));
+        index
+    }
+
+    pub fn insert(&mut self, lyric: &Lyric) {
+        self.remove(&lyric.id);
+        let words = lyric_words(lyric);
+        for word in words.iter() {
+            self.words.entry(word.clone()).or_default().insert(lyric.id);
+        }
+        self.lyrics.insert(lyric.id, words);
+    }
+
+    pub fn remove(&mut self, id: &Uuid) {
+        if let Some(words) = self.lyrics.remove(id) {
+            for word in words {
+                if let Some(ids) = self.words.get_mut(&word) {
+                    ids.remove(id);
+                    if ids.is_empty() {
+                        self.words.remove(&word);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Ids of the lyrics containing all the terms
+    pub fn candidates(&self, terms: &[String]) -> HashSet<Uuid> {
+        let mut sets = terms.iter().map(|term| self.words.get(term));
+        match sets.next() {
+            Some(Some(first)) =>
+                sets.try_fold(first.clone(), |acc, set|
+                    set.map(|set| acc.intersection(set).cloned().collect())
+                )
+                .unwrap_or_default(),
+            _ => HashSet::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{search, terms, SearchIndex};
+    use crate::{Lyric, LyricPost};
+
+    fn knaapje() -> Lyric {
+        LyricPost::from(("'t Knaapje", "Er was eens een knaapje\nDat zag een roosje staan\n\nHet knaapje sprak: ik breek je")).into()
+    }
+
+    fn molen() -> Lyric {
+        LyricPost::from(("Daar bij die molen", "Daar bij die molen, die mooie molen\nDaar woont het meisje")).into()
+    }
+
+    #[test]
+    fn search_accent_and_case_insensitive() {
+        let lyrics = [knaapje(), molen()];
+        let hits = search(lyrics.iter(), "T KNÁAPJE");
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].summary.title, "'t Knaapje");
+
+        let hits = search(lyrics.iter(), "roosje staan");
+        assert_eq!(hits[0].line, Some("Dat zag een roosje staan".to_owned()));
+    }
+
+    #[test]
+    fn search_ranked() {
+        let lyrics = [knaapje(), molen()];
+        let hits = search(lyrics.iter(), "het");
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].summary.title, "'t Knaapje");
+        assert!(search(lyrics.iter(), "").is_empty());
+    }
+
+    #[test]
+    fn index_candidates() {
+        let knaapje = knaapje();
+        let molen = molen();
+        let mut index = SearchIndex::new([knaapje.clone(), molen.clone()].iter());
+        assert_eq!(index.candidates(&terms("daar molen")).into_iter().collect::<Vec<_>>(), vec![molen.id]);
+        assert_eq!(index.candidates(&terms("het")).len(), 2);
+
+        index.remove(&molen.id);
+        assert!(index.candidates(&terms("molen")).is_empty());
+        assert!(index.candidates(&terms("")).is_empty());
+    }
+}
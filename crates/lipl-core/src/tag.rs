@@ -0,0 +1,99 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use serde::{Deserialize, Serialize};
+use crate::{Lyric, SearchHit};
+
+/// Tags of a lyric, ordered and without doubles
+pub type Tags = BTreeSet<String>;
+
+/// A tag with the number of lyrics that have it
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct TagCount {
+    pub tag: String,
+    pub count: usize,
+}
+
+/// Every tag with the number of tag sets that contain it, ordered by tag
+pub fn count_tags<'a>(tags: impl IntoIterator<Item = &'a Tags>) -> Vec<TagCount> {
+    let mut counts = BTreeMap::<&str, usize>::new();
+    for tag in tags.into_iter().flatten() {
+        *counts.entry(tag).or_default() += 1;
+    }
+    counts
+    .into_iter()
+    .map(|(tag, count)| TagCount { tag: tag.to_owned(), count })
+    .collect()
+}
+
+/// True if the tags contain every one of the wanted tags
+pub fn has_tags(tags: &Tags, wanted: &[String]) -> bool {
+    wanted.iter().all(|tag| tags.contains(tag))
+}
+
+/// The hits on lyrics with all of the wanted tags, in the order of the hits
+pub fn tagged_hits(hits: Vec<SearchHit>, lyrics: &[Lyric], wanted: &[String]) -> Vec<SearchHit> {
+    let tagged = lyrics.iter().filter(|lyric| has_tags(&lyric.tags, wanted)).map(|lyric| lyric.id).collect::<HashSet<_>>();
+    hits.into_iter().filter(|hit| tagged.contains(&hit.summary.id)).collect()
+}
+
+/// A list of tags in a query string, separated by commas like `tags=kerst,kinderliedjes`
+pub mod comma_separated {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(tags: &[String], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&tags.join(","))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+        let tags = Option::<String>::deserialize(deserializer)?.unwrap_or_default();
+        Ok(
+            tags
+            .split(',')
+            .map(str::trim)
+            .filter(|tag| !tag.is_empty())
+            .map(str::to_owned)
+            .collect()
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{count_tags, has_tags, tagged_hits, TagCount, Tags};
+    use crate::{search::search, Lyric, LyricPost};
+
+    fn tags(list: &[&str]) -> Tags {
+        list.iter().map(|tag| tag.to_string()).collect()
+    }
+
+    #[test]
+    fn counts() {
+        let lyrics = [tags(&["kerst", "kinderliedjes"]), tags(&["sinterklaas", "kinderliedjes"]), Tags::new()];
+        assert_eq!(
+            count_tags(&lyrics),
+            vec![
+                TagCount { tag: "kerst".to_owned(), count: 1 },
+                TagCount { tag: "kinderliedjes".to_owned(), count: 2 },
+                TagCount { tag: "sinterklaas".to_owned(), count: 1 },
+            ]
+        );
+    }
+
+    #[test]
+    fn filter() {
+        let lyric = tags(&["kerst", "kinderliedjes"]);
+        assert!(has_tags(&lyric, &[]));
+        assert!(has_tags(&lyric, &["kerst".to_owned(), "kinderliedjes".to_owned()]));
+        assert!(!has_tags(&lyric, &["kerst".to_owned(), "sinterklaas".to_owned()]));
+    }
+
+    #[test]
+    fn filter_hits() {
+        let lyric = |title: &str, tags: &[&str]| Lyric { tags: self::tags(tags), ..LyricPost::from((title, "Sinterklaas kapoentje")).into() };
+        let lyrics = [lyric("Sinterklaas kapoentje", &["sinterklaas", "kinderliedjes"]), lyric("Sinterklaasje kom maar binnen", &["sinterklaas"])];
+        let hits = search(lyrics.iter(), "sinterklaas");
+        assert_eq!(hits.len(), 2);
+        assert_eq!(tagged_hits(hits.clone(), &lyrics, &[]), hits);
+        let tagged = tagged_hits(hits, &lyrics, &["kinderliedjes".to_owned()]);
+        assert_eq!(tagged.iter().map(|hit| hit.summary.id).collect::<Vec<_>>(), vec![lyrics[0].id]);
+    }
+}
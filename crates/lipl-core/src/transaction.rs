@@ -1,12 +1,64 @@
-use std::{io::{BufReader, BufRead}, thread::JoinHandle};
+use std::{fs::File, path::{Path, PathBuf}, thread::JoinHandle};
 
 use chrono::SecondsFormat;
 use serde::{Deserialize, Serialize};
-use crate::{Lyric, Playlist, Summary, Uuid, LiplRepo};
+use crate::{Error, Etag, Lyric, Merge, Playlist, RepoDb, Revision, SearchHit, Summary, Trashed, Uuid, LiplRepo};
+pub use crate::Transaction;
+pub use crate::log_format::{decode_log, quarantine_path, read_log_file, recover_log, DroppedTail, LogContents, LogFormat, LogRecord, LogWriter};
 
 pub type ResultSender<T> = futures::channel::oneshot::Sender<crate::Result<T>>;
 pub type OptionalTransaction = Option<Transaction>;
-type LogRecord = (String, Transaction);
+
+/// Moment to replay the transaction log up to: an RFC3339 timestamp, or the sequence number of the last record to replay
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ReplayUntil {
+    Timestamp(chrono::DateTime<chrono::Utc>),
+    Sequence(u64),
+}
+
+impl std::str::FromStr for ReplayUntil {
+    type Err = crate::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse::<u64>() {
+            Ok(sequence) => Ok(ReplayUntil::Sequence(sequence)),
+            Err(_) => 
+                chrono::DateTime::parse_from_rfc3339(s)
+                .map(|timestamp| ReplayUntil::Timestamp(timestamp.into()))
+                .map_err(|_| crate::Error::Argument("Expected an RFC3339 timestamp or a sequence number")),
+        }
+    }
+}
+
+impl ReplayUntil {
+    fn includes(&self, record: &LogRecord) -> bool {
+        match self {
+            ReplayUntil::Timestamp(until) =>
+                chrono::DateTime::parse_from_rfc3339(&record.timestamp)
+                .map(|timestamp| timestamp <= *until)
+                .unwrap_or(false),
+            ReplayUntil::Sequence(until) => record.sequence <= *until,
+        }
+    }
+}
+
+/// Lyrics and playlists as they were when the log was compacted. The log records up to the sequence number are part of the snapshot.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Snapshot {
+    pub timestamp: String,
+    #[serde(default)]
+    pub sequence: u64,
+    pub db: RepoDb,
+}
+
+#[allow(clippy::large_enum_variant)]
+pub enum LogMessage {
+    /// Writes the transactions of one commit as one record
+    Write(Vec<Transaction>),
+    /// Writes a snapshot with the db and rotates the log behind it
+    Checkpoint(RepoDb, ResultSender<()>),
+    /// Replies when all transactions sent before are written, and stops the log thread
+    Stop(ResultSender<()>),
+}
 
 #[derive(Debug)]
 pub enum Request {
@@ -15,22 +67,28 @@ pub enum Request {
     LyricItem(Uuid, ResultSender<Lyric>),
     LyricDelete(Uuid, ResultSender<()>),
     LyricPost(Lyric, ResultSender<Lyric>),
+    LyricSearch(String, ResultSender<Vec<SearchHit>>),
+    LyricHistory(Uuid, ResultSender<Vec<Revision>>),
+    LyricRevision(Uuid, u64, ResultSender<Revision>),
+    LyricRevert(Uuid, u64, ResultSender<Lyric>),
     PlaylistSummaries(ResultSender<Vec<Summary>>),
     PlaylistList(ResultSender<Vec<Playlist>>),
     PlaylistItem(Uuid, ResultSender<Playlist>),
     PlaylistDelete(Uuid, ResultSender<()>),
     PlaylistPost(Playlist, ResultSender<Playlist>),
+    LyricDeleteIfMatch(Uuid, String, ResultSender<()>),
+    LyricPostIfMatch(Lyric, String, ResultSender<Lyric>),
+    PlaylistDeleteIfMatch(Uuid, String, ResultSender<()>),
+    PlaylistPostIfMatch(Playlist, String, ResultSender<Playlist>),
+    Batch(Vec<Transaction>, ResultSender<()>),
+    LyricMerge(Merge, ResultSender<Lyric>),
+    TrashList(ResultSender<Vec<Trashed>>),
+    TrashRestore(Uuid, ResultSender<()>),
+    TrashPurge(Uuid, ResultSender<()>),
+    Compact(ResultSender<()>),
     Stop(ResultSender<()>),
 }
 
-#[derive(Deserialize, Serialize)]
-pub enum Transaction {
-    LyricDelete(Uuid),
-    LyricUpsert(Lyric),
-    PlaylistDelete(Uuid),
-    PlaylistUpsert(Playlist),
-}
-
 impl std::fmt::Display for Transaction {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", serde_json::to_string(&(now(), self)).unwrap())
@@ -40,10 +98,11 @@ impl std::fmt::Display for Transaction {
 impl std::str::FromStr for Transaction {
     type Err = crate::Error;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        serde_json::from_str::<LogRecord>(s)
-            .map(|(_, transaction)| transaction)
-            .map_err(Box::new)
-            .map_err(to_json_error)
+        s.parse::<LogRecord>()
+            .and_then(|mut record| match record.transactions.len() {
+                1 => Ok(record.transactions.remove(0)),
+                count => Err(Error::CorruptLog(format!("expected one transaction in record {}, found {count}", record.sequence))),
+            })
     }
 }
 
@@ -54,16 +113,22 @@ impl From<&Request> for OptionalTransaction {
             Request::LyricPost(lyric, _) => Some(Transaction::LyricUpsert(lyric.clone())),
             Request::PlaylistDelete(uuid, _) => Some(Transaction::PlaylistDelete(*uuid)),
             Request::PlaylistPost(playlist, _) => Some(Transaction::PlaylistUpsert(playlist.clone())),
+            Request::LyricDeleteIfMatch(uuid, _, _) => Some(Transaction::LyricDelete(*uuid)),
+            Request::LyricPostIfMatch(lyric, _, _) => Some(Transaction::LyricUpsert(lyric.clone())),
+            Request::PlaylistDeleteIfMatch(uuid, _, _) => Some(Transaction::PlaylistDelete(*uuid)),
+            Request::PlaylistPostIfMatch(playlist, _, _) => Some(Transaction::PlaylistUpsert(playlist.clone())),
             _ => None,
         }
     }
 }
 
-fn to_json_error<E>(error: E) -> crate::Error
-where
-    E: std::error::Error + Send + Sync + 'static,
-{
-    crate::Error::Json(Box::new(error))
+impl From<&Request> for Vec<Transaction> {
+    fn from(request: &Request) -> Self {
+        match request {
+            Request::Batch(batch, _) => batch.clone(),
+            _ => OptionalTransaction::from(request).into_iter().collect(),
+        }
+    }
 }
 
 fn now() -> String {
@@ -71,40 +136,56 @@ fn now() -> String {
         .to_rfc3339_opts(SecondsFormat::Micros, true)
 }
 
-fn write<W>(w: &mut W, json: String) -> crate::Result<()>
+/// Replays the log records after the sequence number.
+/// Deleting a lyric or playlist that is already gone is not an error, so a log can be replayed on a db that already has some of the records.
+/// Returns the number of records replayed.
+pub async fn build_from_log<DB>(records: &[LogRecord], db: DB, after: u64) -> crate::Result<usize>
 where
-    W: std::io::Write,
+    DB: LiplRepo,
 {
-    w.write_fmt(format_args!("{}\n", json))?;
-    w.flush()?;
-    Ok(())
+    let records = records.iter().filter(|record| record.sequence > after).cloned().collect::<Vec<_>>();
+    replay(&records, &db).await?;
+    Ok(records.len())
 }
 
-fn line_to_transaction(line: std::io::Result<String>) -> crate::Result<Transaction> {
-    line.map_err(crate::Error::from)
-        .and_then(|s| s.parse::<Transaction>())
+/// Rebuilds the lyrics and playlists in db as they were at the moment until, replaying the rotated logs and the log at
+/// log_path from the start. The db is expected to be empty. Returns the number of records replayed.
+/// The transactions of a batch are in one record, so a batch is replayed whole or not at all.
+pub async fn replay_until<DB>(log_path: &Path, db: &DB, until: &ReplayUntil) -> crate::Result<usize>
+where
+    DB: LiplRepo + ?Sized,
+{
+    let mut records = Vec::<LogRecord>::new();
+    for path in rotated_log_paths(log_path).iter().map(PathBuf::as_path).chain(std::iter::once(log_path)) {
+        let contents = read_log_file(path, records.last().map(|record| record.sequence).unwrap_or_default())?;
+        if let Some(dropped) = contents.dropped {
+            tracing::warn!("Skipped {} bytes at the end of {}: {}", dropped.bytes, path.to_string_lossy(), dropped.reason);
+        }
+        records.extend(contents.records);
+    }
+    let records = 
+        records
+        .into_iter()
+        .take_while(|record| until.includes(record))
+        .collect::<Vec<_>>();
+    replay(&records, db).await?;
+    Ok(records.len())
 }
 
-pub async fn build_from_log<R, DB>(r: R, db: DB) -> crate::Result<()>
+async fn replay<DB>(records: &[LogRecord], db: &DB) -> crate::Result<()>
 where
-    R: std::io::Read,
-    DB: LiplRepo,
+    DB: LiplRepo + ?Sized,
 {
-    let transactions = BufReader::new(r)
-        .lines()
-        .map(line_to_transaction)
-        .collect::<crate::Result<Vec<_>>>()?;
-    
-    for transaction in transactions {
+    for transaction in records.iter().flat_map(|record| record.transactions.iter().cloned()) {
         match transaction {
             Transaction::LyricDelete(id) => {
-                db.delete_lyric(id).await?;
+                db.delete_lyric(id).await.or_else(already_gone(id))?;
             },
             Transaction::LyricUpsert(lyric) => {
                 db.upsert_lyric(lyric).await?;
             },
             Transaction::PlaylistDelete(id) => {
-                db.delete_playlist(id).await?;
+                db.delete_playlist(id).await.or_else(already_gone(id))?;
             },
             Transaction::PlaylistUpsert(playlist) => {
                 db.upsert_playlist(playlist).await?;
@@ -115,26 +196,97 @@ where
     Ok(())
 }
 
-pub fn log_to_transaction<W>(mut writer: W) -> impl FnMut(Transaction) -> crate::Result<()>
+fn already_gone(id: Uuid) -> impl FnOnce(Error) -> crate::Result<()> {
+    move |error| match error {
+        Error::NotFound(not_found) if not_found == id => Ok(()),
+        error => Err(error),
+    }
+}
+
+/// Brings the lyrics and playlists in the db to the state of the snapshot, only writing the ones that differ.
+/// Lyrics and playlists that are not in the snapshot are kept.
+pub async fn restore_snapshot<DB>(snapshot: &RepoDb, db: &DB) -> crate::Result<()>
 where
-    W: std::io::Write,
+    DB: LiplRepo,
 {
-    move |transaction| {
-        write(&mut writer, transaction.to_string())
+    for lyric in snapshot.lyrics.iter() {
+        if db.get_lyric(lyric.id).await.ok().and_then(|current| current.etag()) != lyric.etag() {
+            db.upsert_lyric(lyric.clone()).await?;
+        }
+    }
+    for playlist in snapshot.playlists.iter() {
+        if db.get_playlist(playlist.id).await.ok().and_then(|current| current.etag()) != playlist.etag() {
+            db.upsert_playlist(playlist.clone()).await?;
+        }
     }
+    Ok(())
 }
 
-pub fn start_log_thread<W>(log: W) -> (JoinHandle<crate::Result<()>>, std::sync::mpsc::Sender<Transaction>)
-where
-    W: std::io::Write + Send + Sync + 'static,
+/// The latest snapshot, none if the log was never compacted
+pub fn read_snapshot<P: AsRef<Path>>(path: P) -> crate::Result<Option<Snapshot>> {
+    if !path.as_ref().exists() {
+        return Ok(None);
+    }
+    let snapshot = serde_yaml::from_reader::<_, Snapshot>(File::open(path)?)?;
+    Ok(Some(snapshot))
+}
+
+/// Writes the snapshot next to the current one and then moves it in place, so a crash leaves either the old or the new snapshot
+fn write_snapshot(path: &Path, snapshot: &Snapshot) -> crate::Result<()> {
+    let mut temporary = path.as_os_str().to_os_string();
+    temporary.push(".tmp");
+    std::fs::write(&temporary, serde_yaml::to_string(snapshot)?)?;
+    std::fs::rename(&temporary, path)?;
+    Ok(())
+}
+
+/// Path of a rotated log, numbered from 1 in the order the log was rotated
+pub fn rotated_log_path(log_path: &Path, number: usize) -> PathBuf {
+    let mut path = log_path.as_os_str().to_os_string();
+    path.push(format!(".{number}"));
+    PathBuf::from(path)
+}
+
+/// Paths of the rotated logs, oldest first
+pub fn rotated_log_paths(log_path: &Path) -> Vec<PathBuf> {
+    (1..)
+    .map(|number| rotated_log_path(log_path, number))
+    .take_while(|path| path.exists())
+    .collect()
+}
+
+/// Writes a snapshot and moves the log aside, so that a new log only has the records after the snapshot.
+/// The rotated log is kept, to have the full history of changes.
+fn checkpoint(log_path: &Path, snapshot_path: &Path, format: LogFormat, db: RepoDb, log: &LogWriter) -> crate::Result<LogWriter> {
+    write_snapshot(snapshot_path, &Snapshot { timestamp: now(), sequence: log.last_sequence(), db })?;
+    let rotated = rotated_log_path(log_path, rotated_log_paths(log_path).len() + 1);
+    std::fs::rename(log_path, rotated)?;
+    LogWriter::open(log_path, format, log.last_sequence())
+}
+
+/// Starts a thread that appends the transactions it receives to the log, numbered after last_sequence. A checkpoint writes
+/// the snapshot and rotates the log after the transactions received before it are written. New logs are written in format.
+pub fn start_log_thread(log_path: PathBuf, snapshot_path: PathBuf, format: LogFormat, last_sequence: u64) -> crate::Result<(JoinHandle<crate::Result<()>>, std::sync::mpsc::Sender<LogMessage>)>
 {
-    let (log_tx, log_rx) = std::sync::mpsc::channel::<Transaction>();
+    let mut log = LogWriter::open(&log_path, format, last_sequence)?;
+    let (log_tx, log_rx) = std::sync::mpsc::channel::<LogMessage>();
     let join_handle = std::thread::spawn(move || {
-        let mut write = log_to_transaction(log);
-        while let Ok(request) = log_rx.recv() {
-            write(request)?;
+        while let Ok(message) = log_rx.recv() {
+            match message {
+                LogMessage::Write(transactions) => {
+                    log.append(transactions, now())?;
+                },
+                LogMessage::Checkpoint(db, sender) => {
+                    let result = checkpoint(&log_path, &snapshot_path, format, db, &log).map(|rotated| { log = rotated; });
+                    let _ = sender.send(result);
+                },
+                LogMessage::Stop(sender) => {
+                    let _ = sender.send(Ok(()));
+                    break;
+                },
+            }
         };
         Ok::<(), crate::Error>(())
     });
-    (join_handle, log_tx)
-}
\ No newline at end of file
+    Ok((join_handle, log_tx))
+}
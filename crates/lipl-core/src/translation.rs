@@ -0,0 +1,136 @@
+use std::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+use crate::{Error, Lyric, Result};
+
+/// Title and text of a lyric in another language, with the parts and lines aligned to those of the original
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub struct Translation {
+    pub title: String,
+    pub parts: Vec<Vec<String>>,
+}
+
+/// Translations by language code, like `en` or `fy`
+pub type Translations = BTreeMap<String, Translation>;
+
+impl Lyric {
+    fn translation(&self, language: &str) -> Result<&Translation> {
+        self.translations.get(language).ok_or_else(|| Error::NoKey(language.to_owned()))
+    }
+
+    /// The lyric in the language, without the chords since those are placed on the original text.
+    /// The language of the original gives the lyric itself.
+    pub fn translated(&self, language: &str) -> Result<Lyric> {
+        if self.metadata.language.as_deref() == Some(language) {
+            return Ok(Lyric { translations: Translations::new(), ..self.clone() });
+        }
+        let translation = self.translation(language)?;
+        let mut metadata = self.metadata.clone();
+        metadata.language = Some(language.to_owned());
+        Ok(
+            Lyric {
+                id: self.id,
+                title: translation.title.clone(),
+                parts: translation.parts.clone(),
+                chords: vec![],
+                labels: self.labels.clone(),
+                arrangement: self.arrangement.clone(),
+                translations: Translations::new(),
+                tags: self.tags.clone(),
+                metadata,
+            }
+        )
+    }
+
+    /// The lyric with every line of the original followed by the line of the translation
+    pub fn side_by_side(&self, language: &str) -> Result<Lyric> {
+        let translation = self.translation(language)?;
+        let mut parts = vec![];
+        let mut chords = vec![];
+        for (i, part) in self.parts.iter().enumerate() {
+            let translated = translation.parts.get(i);
+            let length = part.len().max(translated.map(Vec::len).unwrap_or_default());
+            let mut lines = vec![];
+            let mut line_chords = vec![];
+            for j in 0..length {
+                if let Some(original) = part.get(j).cloned() {
+                    lines.push(original);
+                    line_chords.push(self.chords.get(i).and_then(|part| part.get(j)).cloned().unwrap_or_default());
+                }
+                if let Some(translated) = translated.and_then(|part| part.get(j)).cloned() {
+                    lines.push(translated);
+                    line_chords.push(vec![]);
+                }
+            }
+            parts.push(lines);
+            chords.push(line_chords);
+        }
+        let title =
+            if translation.title.is_empty() || translation.title == self.title {
+                self.title.clone()
+            }
+            else {
+                format!("{} / {}", self.title, translation.title)
+            };
+        Ok(
+            Lyric {
+                id: self.id,
+                title,
+                parts,
+                chords: if self.chords.is_empty() { vec![] } else { chords },
+                labels: self.labels.clone(),
+                arrangement: self.arrangement.clone(),
+                translations: Translations::new(),
+                tags: self.tags.clone(),
+                metadata: self.metadata.clone(),
+            }
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{Chord, Error, Lyric, LyricPost, Translation};
+
+    fn roodkapje() -> Lyric {
+        let mut lyric: Lyric = LyricPost::from(("Roodkapje", "Zeg roodkapje\nwaar ga je heen\n\nNaar grootmoeder")).into();
+        lyric.metadata.language = Some("nl".to_owned());
+        lyric.chords = vec![vec![vec![Chord { position: 0, name: "C".to_owned() }], vec![]]];
+        lyric.translations.insert(
+            "en".to_owned(),
+            Translation {
+                title: "Little Red Riding Hood".to_owned(),
+                parts: vec![vec!["Say little red riding hood".to_owned(), "where are you going".to_owned()], vec!["To grandmother".to_owned()]],
+            },
+        );
+        lyric
+    }
+
+    #[test]
+    fn translated() {
+        let lyric = roodkapje();
+        let english = lyric.translated("en").unwrap();
+        assert_eq!(english.title, "Little Red Riding Hood");
+        assert_eq!(english.parts, lyric.translations["en"].parts);
+        assert_eq!(english.metadata.language.as_deref(), Some("en"));
+        assert!(english.chords.is_empty());
+
+        assert_eq!(lyric.translated("nl").unwrap().parts, lyric.parts);
+        assert!(matches!(lyric.translated("fy"), Err(Error::NoKey(language)) if language == "fy"));
+    }
+
+    #[test]
+    fn side_by_side() {
+        let lyric = roodkapje().side_by_side("en").unwrap();
+        assert_eq!(lyric.title, "Roodkapje / Little Red Riding Hood");
+        assert_eq!(
+            lyric.parts,
+            vec![
+                vec!["Zeg roodkapje", "Say little red riding hood", "waar ga je heen", "where are you going"],
+                vec!["Naar grootmoeder", "To grandmother"],
+            ],
+        );
+        assert_eq!(lyric.chords[0][0][0].name, "C");
+        assert!(lyric.chords[0][1].is_empty());
+        assert!(lyric.translations.is_empty());
+    }
+}
@@ -0,0 +1,114 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{change::now, HasSummary, Lyric, Playlist, Summary, Uuid};
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[allow(clippy::large_enum_variant)]
+pub enum TrashItem {
+    Lyric(Lyric),
+    Playlist(Playlist),
+}
+
+/// Index a deleted lyric had in the members of a playlist
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct PlaylistPosition {
+    pub playlist: Uuid,
+    pub index: usize,
+}
+
+/// A deleted lyric or playlist, kept until it is restored or purged
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Trashed {
+    pub item: TrashItem,
+    /// Milliseconds since the unix epoch
+    pub deleted: u64,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub positions: Vec<PlaylistPosition>,
+}
+
+impl Trashed {
+    /// Trashes a lyric, remembering its positions in the playlists
+    pub fn lyric<'a>(lyric: Lyric, playlists: impl Iterator<Item = &'a Playlist>) -> Self {
+        let positions =
+            playlists
+            .flat_map(|playlist|
+                playlist
+                .members
+                .iter()
+                .enumerate()
+                .filter(|(_, member)| **member == lyric.id)
+                .map(|(index, _)| PlaylistPosition { playlist: playlist.id, index })
+            )
+            .collect();
+        Self { item: TrashItem::Lyric(lyric), deleted: now(), positions }
+    }
+
+    pub fn playlist(playlist: Playlist) -> Self {
+        Self { item: TrashItem::Playlist(playlist), deleted: now(), positions: vec![] }
+    }
+
+    pub fn id(&self) -> Uuid {
+        match &self.item {
+            TrashItem::Lyric(lyric) => lyric.id,
+            TrashItem::Playlist(playlist) => playlist.id,
+        }
+    }
+
+    /// Ids of the playlists the lyric was a member of
+    pub fn playlist_ids(&self) -> Vec<Uuid> {
+        let mut ids = self.positions.iter().map(|position| position.playlist).collect::<Vec<_>>();
+        ids.dedup();
+        ids
+    }
+
+    /// Puts the lyric back at its old positions in the playlist, or at the end if the playlist got shorter.
+    /// Returns false if the lyric was not a member of the playlist or still is.
+    pub fn restore_positions(&self, playlist: &mut Playlist) -> bool {
+        if playlist.members.contains(&self.id()) {
+            return false;
+        }
+        let mut restored = false;
+        let id = playlist.id;
+        for position in self.positions.iter().filter(|position| position.playlist == id) {
+            playlist.insert_member(position.index, self.id());
+            restored = true;
+        }
+        restored
+    }
+}
+
+impl HasSummary for Trashed {
+    fn summary(&self) -> Summary {
+        match &self.item {
+            TrashItem::Lyric(lyric) => lyric.summary(),
+            TrashItem::Playlist(playlist) => playlist.summary(),
+        }
+    }
+}
+
+/// Most recently deleted first
+pub fn sorted_by_deleted(mut trash: Vec<Trashed>) -> Vec<Trashed> {
+    trash.sort_by(|a, b| b.deleted.cmp(&a.deleted).then_with(|| a.id().cmp(&b.id())));
+    trash
+}
+
+#[cfg(test)]
+mod test {
+    use super::Trashed;
+    use crate::{Lyric, LyricPost, Playlist, Uuid};
+
+    #[test]
+    fn restore_positions() {
+        let lyric: Lyric = LyricPost::from(("Roodkapje", "")).into();
+        let (first, second) = (Uuid::default(), Uuid::default());
+        let mut playlist = Playlist { id: Uuid::default(), title: "Kinderliedjes".to_owned(), members: vec![first, lyric.id, second, lyric.id], entries: vec![], event: None };
+
+        let trashed = Trashed::lyric(lyric.clone(), std::iter::once(&playlist));
+        assert_eq!(trashed.positions.iter().map(|position| position.index).collect::<Vec<_>>(), vec![1, 3]);
+
+        playlist.members = vec![first];
+        assert!(trashed.restore_positions(&mut playlist));
+        assert_eq!(playlist.members, vec![first, lyric.id, lyric.id]);
+        assert!(!trashed.restore_positions(&mut playlist));
+    }
+}
@@ -9,9 +9,33 @@ use crate::error::{Error};
 pub struct Uuid(uuid::Uuid);
 
 impl Uuid {
+    /// Namespace for ids derived from the title of a lyric
+    pub const LYRIC_NAMESPACE: Uuid = Uuid(uuid::Uuid::from_u128(0x6c1d_8d2e_5a4f_4b0e_9f3a_1c7e_2b8d_4f61));
+    /// Namespace for ids derived from the title of a playlist
+    pub const PLAYLIST_NAMESPACE: Uuid = Uuid(uuid::Uuid::from_u128(0x3e9b_27c4_81d6_4a5f_b0e2_7d4c_9a16_e853));
+
     pub fn inner(&self) -> uuid::Uuid {
         self.0
     }
+
+    /// Name based (version 5) id, the same name in the same namespace always gives the same id
+    pub fn new_v5(namespace: &Uuid, name: &str) -> Self {
+        uuid::Uuid::new_v5(&namespace.0, name.as_bytes()).into()
+    }
+
+    /// Name based id for a title. Case, diacritics and surrounding or repeated whitespace are ignored.
+    pub fn from_title(namespace: &Uuid, title: &str) -> Self {
+        Self::new_v5(namespace, &normalize_title(title))
+    }
+
+    /// Canonical hyphenated form, like 67e55044-10b1-426f-9247-bb680e5fe0c8
+    pub fn hyphenated(&self) -> String {
+        self.0.hyphenated().to_string()
+    }
+}
+
+fn normalize_title(title: &str) -> String {
+    parts::normalize(title).split_whitespace().collect::<Vec<_>>().join(" ")
 }
 
 impl Display for Uuid {
@@ -35,11 +59,14 @@ fn bytes_to_uuid(bytes: Vec<u8>) -> Result<uuid::Uuid, Error> {
 impl FromStr for Uuid {
     type Err = Error;
 
+    /// Accepts base58 and the formats of uuid::Uuid::parse_str, like the canonical hyphenated form.
+    /// A base58 encoded id has at most 22 characters, so the formats can not be mistaken for each other.
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         decode(s)
             .into_vec()
             .map_err(Error::from)
             .and_then(bytes_to_uuid)
+            .or_else(|error| uuid::Uuid::parse_str(s).map_err(|_| error))
             .map(Uuid::from)
     }
 }
@@ -54,4 +81,28 @@ impl From<uuid::Uuid> for Uuid {
     fn from(uuid: uuid::Uuid) -> Self {
         Self(uuid)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod test {
+    use super::Uuid;
+
+    #[test]
+    fn parse_hyphenated() {
+        let uuid: Uuid = "67e55044-10b1-426f-9247-bb680e5fe0c8".parse().unwrap();
+        assert_eq!(uuid.hyphenated(), "67e55044-10b1-426f-9247-bb680e5fe0c8");
+        assert_eq!(uuid.to_string().parse::<Uuid>().unwrap(), uuid);
+        assert_eq!("67E55044-10B1-426F-9247-BB680E5FE0C8".parse::<Uuid>().unwrap(), uuid);
+        assert!("67e55044-10b1-426f-9247".parse::<Uuid>().is_err());
+    }
+
+    #[test]
+    fn from_title() {
+        let uuid = Uuid::from_title(&Uuid::LYRIC_NAMESPACE, "Daar bij die molen");
+        assert_eq!(uuid.inner().get_version_num(), 5);
+        assert_eq!(Uuid::from_title(&Uuid::LYRIC_NAMESPACE, "  daar bij  die\tMolen "), uuid);
+        assert_eq!(Uuid::from_title(&Uuid::LYRIC_NAMESPACE, "Daar bij díe mölen"), uuid);
+        assert_ne!(Uuid::from_title(&Uuid::LYRIC_NAMESPACE, "Daar bij de molen"), uuid);
+        assert_ne!(Uuid::from_title(&Uuid::PLAYLIST_NAMESPACE, "Daar bij die molen"), uuid);
+    }
+}
@@ -0,0 +1,152 @@
+use std::{collections::HashMap, time::{Duration, Instant}};
+use lipl_core::{HasSummary, Summary, Uuid};
+
+/// How long and how many lyrics and playlists are kept in memory. None means no limit.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CacheConfig {
+    pub ttl: Option<Duration>,
+    pub capacity: Option<usize>,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            ttl: Some(Duration::from_secs(60)),
+            capacity: Some(1000),
+        }
+    }
+}
+
+fn parse_limit(value: &str) -> Result<Option<u64>, lipl_core::Error> {
+    match value {
+        "none" => Ok(None),
+        _ => value.parse::<u64>().map(Some).map_err(|_| lipl_core::Error::Argument("cache limit must be a number or none")),
+    }
+}
+
+/// Parses a comma separated list of options, e.g. `ttl=30,capacity=500`. The ttl is in seconds.
+/// Options that are left out keep their default value.
+impl std::str::FromStr for CacheConfig {
+    type Err = lipl_core::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut config = CacheConfig::default();
+        for option in s.split(',').map(str::trim).filter(|option| !option.is_empty()) {
+            match option.split_once('=') {
+                Some(("ttl", value)) => { config.ttl = parse_limit(value)?.map(Duration::from_secs); },
+                Some(("capacity", value)) => { config.capacity = parse_limit(value)?.map(|capacity| capacity as usize); },
+                _ => return Err(lipl_core::Error::Argument("cache option must be ttl or capacity")),
+            }
+        }
+        Ok(config)
+    }
+}
+
+struct Entry<T> {
+    value: T,
+    created: Instant,
+    used: Instant,
+}
+
+impl<T: Clone> Entry<T> {
+    fn new(value: T) -> Self {
+        let now = Instant::now();
+        Self { value, created: now, used: now }
+    }
+
+    fn is_fresh(&self, config: &CacheConfig) -> bool {
+        config.ttl.map(|ttl| self.created.elapsed() < ttl).unwrap_or(true)
+    }
+}
+
+fn fresh<T: Clone>(entry: &mut Option<Entry<T>>, config: &CacheConfig) -> Option<T> {
+    match entry {
+        Some(cached) if cached.is_fresh(config) => Some(cached.value.clone()),
+        _ => {
+            *entry = None;
+            None
+        },
+    }
+}
+
+/// The cached list, summaries and items of either the lyrics or the playlists
+pub(crate) struct Cached<T> {
+    list: Option<Entry<Vec<T>>>,
+    summaries: Option<Entry<Vec<Summary>>>,
+    items: HashMap<Uuid, Entry<T>>,
+}
+
+impl<T> Default for Cached<T> {
+    fn default() -> Self {
+        Self {
+            list: None,
+            summaries: None,
+            items: HashMap::new(),
+        }
+    }
+}
+
+impl<T: Clone + HasSummary> Cached<T> {
+    pub fn list(&mut self, config: &CacheConfig) -> Option<Vec<T>> {
+        fresh(&mut self.list, config)
+    }
+
+    /// Keeps the list and the summaries, and every item of the list if they fit in the capacity
+    pub fn set_list(&mut self, config: &CacheConfig, list: &[T]) {
+        self.summaries = Some(Entry::new(list.iter().map(HasSummary::summary).collect()));
+        if config.capacity.map(|capacity| list.len() <= capacity).unwrap_or(true) {
+            self.list = Some(Entry::new(list.to_vec()));
+            self.items = list.iter().map(|item| (item.summary().id, Entry::new(item.clone()))).collect();
+        }
+    }
+
+    pub fn summaries(&mut self, config: &CacheConfig) -> Option<Vec<Summary>> {
+        fresh(&mut self.summaries, config)
+    }
+
+    pub fn set_summaries(&mut self, summaries: &[Summary]) {
+        self.summaries = Some(Entry::new(summaries.to_vec()));
+    }
+
+    pub fn item(&mut self, config: &CacheConfig, id: &Uuid) -> Option<T> {
+        match self.items.get_mut(id) {
+            Some(entry) if entry.is_fresh(config) => {
+                entry.used = Instant::now();
+                Some(entry.value.clone())
+            },
+            Some(_) => {
+                self.items.remove(id);
+                None
+            },
+            None => None,
+        }
+    }
+
+    /// Keeps the item, after removing the least recently used item if the cache is full
+    pub fn set_item(&mut self, config: &CacheConfig, item: &T) {
+        let id = item.summary().id;
+        if let Some(capacity) = config.capacity {
+            if capacity == 0 {
+                return;
+            }
+            if !self.items.contains_key(&id) && self.items.len() >= capacity {
+                let least_recently_used = self.items.iter().min_by_key(|(_, entry)| entry.used).map(|(id, _)| *id);
+                if let Some(least_recently_used) = least_recently_used {
+                    self.items.remove(&least_recently_used);
+                }
+            }
+        }
+        self.items.insert(id, Entry::new(item.clone()));
+    }
+
+    /// Forgets the item and the list and summaries it is part of
+    pub fn remove(&mut self, id: &Uuid) {
+        self.items.remove(id);
+        self.list = None;
+        self.summaries = None;
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+}
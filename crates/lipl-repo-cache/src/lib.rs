@@ -0,0 +1,313 @@
+/*!
+ Keeps lyrics and playlists of another LiplRepo in memory.
+
+ Lists, summaries and items are read from the wrapped repo once and served from memory until they expire.
+ Writes through the cache go to the wrapped repo and forget what they change. A read that is in flight while a write
+ finishes is not cached, so it can not bring back what the write changed. Writes that bypass the cache,
+ e.g. from another server on the same database, are only seen after the cached entries expire.
+
+ ```ignore
+ let repo = CachedRepo::new(repo, "ttl=30,capacity=500".parse::<CacheConfig>()?);
+ ```
+ */
+
+use std::sync::{Arc, Mutex};
+use async_trait::async_trait;
+use lipl_core::{tag, ChangeStream, DateRange, ListQuery, LiplRepo, Lyric, Merge, Page, Playlist, Result, Revision, SearchHit, Summary, TagCount, Transaction, Trashed, Uuid};
+use cache::Cached;
+
+pub use cache::CacheConfig;
+
+mod cache;
+
+#[derive(Default)]
+struct Cache {
+    lyrics: Cached<Lyric>,
+    playlists: Cached<Playlist>,
+    /// Counted up by every write, so a read or write that started before it leaves the cache alone
+    generation: u64,
+}
+
+/// LiplRepo that keeps the lists, summaries and items of the wrapped repo in memory
+pub struct CachedRepo<R: LiplRepo + ?Sized> {
+    inner: Arc<R>,
+    config: CacheConfig,
+    cache: Mutex<Cache>,
+}
+
+impl<R: LiplRepo + ?Sized> CachedRepo<R> {
+    pub fn new(inner: Arc<R>, config: CacheConfig) -> Self {
+        Self {
+            inner,
+            config,
+            cache: Mutex::new(Cache::default()),
+        }
+    }
+
+    fn with_cache<T>(&self, f: impl FnOnce(&mut Cache, &CacheConfig) -> T) -> T {
+        f(&mut self.cache.lock().unwrap(), &self.config)
+    }
+
+    fn generation(&self) -> u64 {
+        self.with_cache(|cache, _| cache.generation)
+    }
+
+    /// Caches what was read from the wrapped repo, unless a write finished since the read started at generation
+    fn fill(&self, generation: u64, f: impl FnOnce(&mut Cache, &CacheConfig)) {
+        self.with_cache(|cache, config| {
+            if cache.generation == generation {
+                f(cache, config);
+            }
+        });
+    }
+
+    /// Forgets what a write changed and counts up the generation, so the reads in flight are not cached
+    fn written(&self, f: impl FnOnce(&mut Cache, &CacheConfig)) {
+        self.with_cache(|cache, config| {
+            f(cache, config);
+            cache.generation += 1;
+        });
+    }
+
+    /// The lyric is cached if no other write finished since the upsert started at generation
+    fn lyric_written(&self, generation: u64, id: Uuid, result: &Result<Lyric>) {
+        self.written(|cache, config| {
+            cache.lyrics.remove(&id);
+            if let (Ok(lyric), true) = (result, cache.generation == generation) {
+                cache.lyrics.set_item(config, lyric);
+            }
+        });
+    }
+
+    /// Deleting a lyric also removes it from the playlists
+    fn lyric_deleted(&self, id: Uuid) {
+        self.written(|cache, _| {
+            cache.lyrics.remove(&id);
+            cache.playlists.clear();
+        });
+    }
+
+    /// The playlist is cached if no other write finished since the upsert started at generation
+    fn playlist_written(&self, generation: u64, id: Uuid, result: &Result<Playlist>) {
+        self.written(|cache, config| {
+            cache.playlists.remove(&id);
+            if let (Ok(playlist), true) = (result, cache.generation == generation) {
+                cache.playlists.set_item(config, playlist);
+            }
+        });
+    }
+
+    fn playlist_deleted(&self, id: Uuid) {
+        self.written(|cache, _| cache.playlists.remove(&id));
+    }
+
+    fn clear(&self) {
+        self.written(|cache, _| {
+            cache.lyrics.clear();
+            cache.playlists.clear();
+        });
+    }
+}
+
+#[async_trait]
+impl<R: LiplRepo + ?Sized> LiplRepo for CachedRepo<R> {
+    async fn get_lyrics(&self) -> Result<Vec<Lyric>> {
+        if let Some(lyrics) = self.with_cache(|cache, config| cache.lyrics.list(config)) {
+            return Ok(lyrics);
+        }
+        let generation = self.generation();
+        let lyrics = self.inner.get_lyrics().await?;
+        self.fill(generation, |cache, config| cache.lyrics.set_list(config, &lyrics));
+        Ok(lyrics)
+    }
+
+    async fn get_lyric_summaries(&self) -> Result<Vec<Summary>> {
+        if let Some(summaries) = self.with_cache(|cache, config| cache.lyrics.summaries(config)) {
+            return Ok(summaries);
+        }
+        let generation = self.generation();
+        let summaries = self.inner.get_lyric_summaries().await?;
+        self.fill(generation, |cache, _| cache.lyrics.set_summaries(&summaries));
+        Ok(summaries)
+    }
+
+    async fn get_lyric_summaries_page(&self, query: ListQuery) -> Result<Page<Summary>> {
+        if query.tags.is_empty() {
+            self.get_lyric_summaries()
+                .await
+                .map(|summaries| query.apply(summaries))
+        }
+        else {
+            self.get_lyrics()
+                .await
+                .map(|lyrics| query.apply_lyrics(lyrics))
+        }
+    }
+
+    async fn get_lyric(&self, id: Uuid) -> Result<Lyric> {
+        if let Some(lyric) = self.with_cache(|cache, config| cache.lyrics.item(config, &id)) {
+            return Ok(lyric);
+        }
+        let generation = self.generation();
+        let lyric = self.inner.get_lyric(id).await?;
+        self.fill(generation, |cache, config| cache.lyrics.set_item(config, &lyric));
+        Ok(lyric)
+    }
+
+    async fn upsert_lyric(&self, lyric: Lyric) -> Result<Lyric> {
+        let id = lyric.id;
+        let generation = self.generation();
+        let result = self.inner.upsert_lyric(lyric).await;
+        self.lyric_written(generation, id, &result);
+        result
+    }
+
+    async fn delete_lyric(&self, id: Uuid) -> Result<()> {
+        let result = self.inner.delete_lyric(id).await;
+        self.lyric_deleted(id);
+        result
+    }
+
+    async fn search_lyrics(&self, query: &str) -> Result<Vec<SearchHit>> {
+        self.inner.search_lyrics(query).await
+    }
+
+    async fn get_tags(&self) -> Result<Vec<TagCount>> {
+        self.get_lyrics()
+            .await
+            .map(|lyrics| tag::count_tags(lyrics.iter().map(|lyric| &lyric.tags)))
+    }
+
+    async fn get_lyric_history(&self, id: Uuid) -> Result<Vec<Revision>> {
+        self.inner.get_lyric_history(id).await
+    }
+
+    async fn get_lyric_revision(&self, id: Uuid, rev: u64) -> Result<Revision> {
+        self.inner.get_lyric_revision(id, rev).await
+    }
+
+    async fn revert_lyric(&self, id: Uuid, rev: u64) -> Result<Lyric> {
+        let generation = self.generation();
+        let result = self.inner.revert_lyric(id, rev).await;
+        self.lyric_written(generation, id, &result);
+        result
+    }
+
+    async fn merge_lyrics(&self, merge: Merge) -> Result<Lyric> {
+        let result = self.inner.merge_lyrics(merge).await;
+        self.clear();
+        result
+    }
+
+    async fn get_playlists(&self) -> Result<Vec<Playlist>> {
+        if let Some(playlists) = self.with_cache(|cache, config| cache.playlists.list(config)) {
+            return Ok(playlists);
+        }
+        let generation = self.generation();
+        let playlists = self.inner.get_playlists().await?;
+        self.fill(generation, |cache, config| cache.playlists.set_list(config, &playlists));
+        Ok(playlists)
+    }
+
+    async fn get_playlist_summaries(&self) -> Result<Vec<Summary>> {
+        if let Some(summaries) = self.with_cache(|cache, config| cache.playlists.summaries(config)) {
+            return Ok(summaries);
+        }
+        let generation = self.generation();
+        let summaries = self.inner.get_playlist_summaries().await?;
+        self.fill(generation, |cache, _| cache.playlists.set_summaries(&summaries));
+        Ok(summaries)
+    }
+
+    async fn get_playlist_summaries_page(&self, query: ListQuery) -> Result<Page<Summary>> {
+        self.get_playlist_summaries()
+            .await
+            .map(|summaries| query.apply(summaries))
+    }
+
+    async fn get_scheduled_playlists(&self, range: DateRange) -> Result<Vec<Playlist>> {
+        self.get_playlists()
+            .await
+            .map(|playlists| range.apply(playlists))
+    }
+
+    async fn get_playlist(&self, id: Uuid) -> Result<Playlist> {
+        if let Some(playlist) = self.with_cache(|cache, config| cache.playlists.item(config, &id)) {
+            return Ok(playlist);
+        }
+        let generation = self.generation();
+        let playlist = self.inner.get_playlist(id).await?;
+        self.fill(generation, |cache, config| cache.playlists.set_item(config, &playlist));
+        Ok(playlist)
+    }
+
+    async fn upsert_playlist(&self, playlist: Playlist) -> Result<Playlist> {
+        let id = playlist.id;
+        let generation = self.generation();
+        let result = self.inner.upsert_playlist(playlist).await;
+        self.playlist_written(generation, id, &result);
+        result
+    }
+
+    async fn delete_playlist(&self, id: Uuid) -> Result<()> {
+        let result = self.inner.delete_playlist(id).await;
+        self.playlist_deleted(id);
+        result
+    }
+
+    async fn upsert_lyric_if_match(&self, lyric: Lyric, etag: String) -> Result<Lyric> {
+        let id = lyric.id;
+        let generation = self.generation();
+        let result = self.inner.upsert_lyric_if_match(lyric, etag).await;
+        self.lyric_written(generation, id, &result);
+        result
+    }
+
+    async fn delete_lyric_if_match(&self, id: Uuid, etag: String) -> Result<()> {
+        let result = self.inner.delete_lyric_if_match(id, etag).await;
+        self.lyric_deleted(id);
+        result
+    }
+
+    async fn upsert_playlist_if_match(&self, playlist: Playlist, etag: String) -> Result<Playlist> {
+        let id = playlist.id;
+        let generation = self.generation();
+        let result = self.inner.upsert_playlist_if_match(playlist, etag).await;
+        self.playlist_written(generation, id, &result);
+        result
+    }
+
+    async fn delete_playlist_if_match(&self, id: Uuid, etag: String) -> Result<()> {
+        let result = self.inner.delete_playlist_if_match(id, etag).await;
+        self.playlist_deleted(id);
+        result
+    }
+
+    async fn apply_batch(&self, batch: Vec<Transaction>) -> Result<()> {
+        let result = self.inner.apply_batch(batch).await;
+        self.clear();
+        result
+    }
+
+    async fn watch(&self) -> Result<ChangeStream> {
+        self.inner.watch().await
+    }
+
+    async fn list_trash(&self) -> Result<Vec<Trashed>> {
+        self.inner.list_trash().await
+    }
+
+    async fn restore(&self, id: Uuid) -> Result<()> {
+        let result = self.inner.restore(id).await;
+        self.clear();
+        result
+    }
+
+    async fn purge(&self, id: Uuid) -> Result<()> {
+        self.inner.purge(id).await
+    }
+
+    async fn stop(&self) -> Result<()> {
+        self.inner.stop().await
+    }
+}
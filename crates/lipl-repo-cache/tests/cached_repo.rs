@@ -0,0 +1,54 @@
+use std::time::Duration;
+use lipl_core::{LiplRepo, Lyric};
+use lipl_repo_cache::{CacheConfig, CachedRepo};
+use lipl_repo_test::wrapped::{check_wrapper, lyric, memory_repo};
+
+#[tokio::test]
+async fn conformance() {
+    check_wrapper(|inner| CachedRepo::new(inner, CacheConfig::default())).await.unwrap();
+}
+
+#[test]
+fn config() {
+    assert_eq!("".parse::<CacheConfig>().unwrap(), CacheConfig::default());
+    assert_eq!(
+        "ttl=5, capacity=none".parse::<CacheConfig>().unwrap(),
+        CacheConfig { ttl: Some(Duration::from_secs(5)), capacity: None },
+    );
+    assert!("size=5".parse::<CacheConfig>().is_err());
+}
+
+#[tokio::test]
+async fn own_writes_invalidate() {
+    let inner = memory_repo();
+    let repo = CachedRepo::new(inner.clone(), CacheConfig::default());
+    let roodkapje = repo.upsert_lyric(lyric("Roodkapje")).await.unwrap();
+    assert_eq!(repo.get_lyric_summaries().await.unwrap().len(), 1);
+
+    inner.upsert_lyric(lyric("Daar bij die molen")).await.unwrap();
+    inner.upsert_lyric(Lyric { title: "Zeg roodkapje".to_owned(), ..roodkapje.clone() }).await.unwrap();
+    assert_eq!(repo.get_lyric_summaries().await.unwrap().len(), 1, "writes to the wrapped repo should not be seen");
+    assert_eq!(repo.get_lyric(roodkapje.id).await.unwrap().title, "Roodkapje");
+
+    repo.upsert_lyric(lyric("Sinterklaas kapoentje")).await.unwrap();
+    assert_eq!(repo.get_lyric_summaries().await.unwrap().len(), 3, "writes through the cache should invalidate the summaries");
+    repo.delete_lyric(roodkapje.id).await.unwrap();
+    assert!(repo.get_lyric(roodkapje.id).await.is_err());
+}
+
+#[tokio::test]
+async fn ttl_and_capacity() {
+    let inner = memory_repo();
+    let repo = CachedRepo::new(inner.clone(), CacheConfig { ttl: Some(Duration::from_millis(50)), capacity: Some(1) });
+    let first = repo.upsert_lyric(lyric("Roodkapje")).await.unwrap();
+    let second = repo.upsert_lyric(lyric("Daar bij die molen")).await.unwrap();
+    inner.upsert_lyric(Lyric { title: "Zeg roodkapje".to_owned(), ..first.clone() }).await.unwrap();
+    inner.upsert_lyric(Lyric { title: "Bij de molen".to_owned(), ..second.clone() }).await.unwrap();
+    assert_eq!(repo.get_lyric(first.id).await.unwrap().title, "Zeg roodkapje", "the least recently used lyric should be evicted");
+    assert_eq!(repo.get_lyric(second.id).await.unwrap().title, "Bij de molen");
+
+    inner.upsert_lyric(second.clone()).await.unwrap();
+    assert_eq!(repo.get_lyric(second.id).await.unwrap().title, "Bij de molen");
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(repo.get_lyric(second.id).await.unwrap().title, "Daar bij die molen", "expired lyrics should be read again");
+}
@@ -1,2 +1,8 @@
 pub const YAML_EXTENSION: &str = "yaml";
-pub const LYRIC_EXTENSION: &str = "md";
\ No newline at end of file
+pub const LYRIC_EXTENSION: &str = "md";
+pub const TRASH_DIR: &str = ".trash";
+pub const HISTORY_DIR: &str = ".history";
+pub const TRANSACTION_LOG: &str = ".transaction.log";
+pub const SNAPSHOT: &str = ".transaction.snapshot";
+/// Number of logged transactions after which the log is compacted
+pub const CHECKPOINT_INTERVAL: usize = 1000;
@@ -20,6 +20,7 @@ pub trait IO {
     async fn read_frontmatter(&self) -> Result<String>;
     async fn remove(&self) -> Result<()>;
     async fn write_string(&self, s: String) -> Result<()>;
+    async fn rename<Q: AsRef<Path> + Send + Sync>(&self, to: Q) -> Result<()>;
 
     async fn get_files<'a, F>(&self, filter: F) -> Result<Pin<Box<dyn Stream<Item=Result<PathBuf>> + Send + 'a>>>
     where 
@@ -68,6 +69,12 @@ where
         .await
     }
 
+    async fn rename<Q: AsRef<Path> + Send + Sync>(&self, to: Q) -> Result<()> {
+        tokio::fs::rename(self, to)
+        .err_into()
+        .await
+    }
+
     async fn get_files<'a, F>(&self, filter: F) -> Result<Pin<Box<dyn Stream<Item=Result<PathBuf>> + Send + 'a>>>
     where 
         F: Fn(&PathBuf) -> Ready<bool> + Send + 'a,
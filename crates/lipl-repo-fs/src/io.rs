@@ -2,7 +2,7 @@ use std::str::FromStr;
 use std::path::{Path, PathBuf};
 use futures::{TryFuture, TryStreamExt};
 
-use lipl_core::{Lyric, LyricPost, Playlist, PlaylistPost, Summary, LyricMeta, Uuid};
+use lipl_core::{reexport::serde_yaml, Lyric, LyricPost, Playlist, PlaylistPost, Revision, Summary, LyricMeta, Trashed, Uuid};
 use crate::fs::IO;
 
 use crate::FileRepoError;
@@ -36,6 +36,34 @@ where
     )
 }
 
+pub async fn get_trashed<P>(path: P) -> Result<Trashed>
+where
+    P: AsRef<Path> + Send + Sync,
+{
+    path
+    .read_string()
+    .await?
+    .parse::<Trashed>()
+    .map_err(|_| FileRepoError::Parse(path.as_ref().to_string_lossy().to_string()))
+}
+
+/// Revisions of a lyric, none if the lyric has no history yet
+pub async fn get_history<P>(path: P) -> Result<Vec<Revision>>
+where
+    P: AsRef<Path> + Send + Sync,
+{
+    if !path.as_ref().exists() {
+        return Ok(vec![]);
+    }
+    serde_yaml::from_str::<Vec<Revision>>(&path.read_string().await?)
+    .map_err(|_| FileRepoError::Parse(path.as_ref().to_string_lossy().to_string()))
+}
+
+pub fn history_to_string(history: &[Revision]) -> Result<String> {
+    serde_yaml::to_string(history)
+    .map_err(|error| FileRepoError::Parse(error.to_string()))
+}
+
 pub async fn get_list<P, T, F, Fut>(path: P, ext: &str, f: F) -> Result<Vec<T>> 
 where 
     P: AsRef<Path> + Send + Sync,
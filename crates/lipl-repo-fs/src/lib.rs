@@ -1,24 +1,30 @@
+use std::collections::{hash_map::Entry, HashMap, HashSet};
 use std::fmt::Debug;
-use std::fs::{OpenOptions};
 use std::str::FromStr;
 use std::path::{PathBuf, Path};
-use std::sync::Arc;
-use lipl_core::transaction::{OptionalTransaction, start_log_thread, build_from_log};
+use std::sync::{atomic::{AtomicBool, AtomicUsize, Ordering}, Arc, Mutex};
+use lipl_core::transaction::{build_from_log, read_snapshot, recover_log, restore_snapshot, start_log_thread, LogFormat, LogMessage};
 use tokio::task::JoinHandle;
 
 use async_trait::async_trait;
 
 pub use lipl_core::error::FileRepoError;
+pub use constant::TRANSACTION_LOG;
 use fs::IO;
 use futures::{channel::mpsc};
 use futures::{FutureExt, StreamExt, TryStreamExt, TryFutureExt};
 use lipl_core::{
-    transaction::Request,
-    LiplRepo, Lyric, Playlist, Summary, Uuid, ToRepo,
+    change::Broadcaster,
+    duplicate,
+    search::{self, SearchIndex},
+    tag,
+    transaction::{Request, ResultSender},
+    trash::sorted_by_deleted,
+    sorted_by_title, ChangeStream, DateRange, Etag, LiplRepo, ListQuery, Lyric, LyricPost, Merge, Page, Playlist, RepoDb, Revision, SearchHit, Summary, TagCount, Transaction, TrashItem, Trashed, Uuid, ToRepo,
 };
 use lipl_util::VecExt;
-use request::{delete_by_id, post, select, select_by_id};
-use constant::{LYRIC_EXTENSION, YAML_EXTENSION};
+use request::{apply, apply_merge, delete_by_id, delete_by_id_if_match, post, post_if_match, select, select_by_id, select_by_query, select_revision};
+use constant::{CHECKPOINT_INTERVAL, HISTORY_DIR, LYRIC_EXTENSION, SNAPSHOT, TRASH_DIR, YAML_EXTENSION};
 
 mod constant;
 mod fs;
@@ -28,21 +34,27 @@ mod request;
 #[derive(Clone)]
 pub struct FileRepoConfig {
     pub path: String,
+    pub log_format: LogFormat,
 }
 
+/// Parses a directory, optionally followed by the format of new transaction logs, e.g. `./data?format=bincode`
 impl FromStr for FileRepoConfig {
     type Err = lipl_core::Error;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        s.is_dir()
+        let (path, log_format) = match s.split_once("?format=") {
+            Some((path, format)) => (path, format.parse::<LogFormat>()?),
+            None => (s, LogFormat::default()),
+        };
+        path.is_dir()
             .map_err(lipl_core::Error::from)
-            .map(|_| FileRepoConfig { path: s.into() })
+            .map(|_| FileRepoConfig { path: path.into(), log_format })
     }
 }
 
 #[async_trait]
 impl ToRepo for FileRepoConfig {
     async fn to_repo(self) -> lipl_core::Result<Arc<dyn LiplRepo>> {
-        let repo = FileRepo::new(self.path).await?;
+        let repo = FileRepo::with_log_format(self.path, self.log_format).await?;
         Ok(
             Arc::new(repo)
         )
@@ -53,6 +65,7 @@ impl ToRepo for FileRepoConfig {
 pub struct FileRepo {
     tx: mpsc::Sender<Request>,
     path: String,
+    changes: Arc<Broadcaster>,
     _join_handle: Arc<JoinHandle<bool>>,
 }
 
@@ -62,26 +75,346 @@ impl Debug for FileRepo {
     }
 }
 
-fn check_members(playlist: &Playlist, lyric_ids: &[Uuid]) -> impl futures::Future<Output = Result<(), FileRepoError>> {
-    if let Some(member) = playlist.members.iter().find(|member| !lyric_ids.contains(member))
-    {
-        futures::future::ready(Err(FileRepoError::PlaylistInvalidMember(playlist.id.to_string(), member.to_string())))
+fn check_members(playlist: &Playlist, lyric_ids: &[Uuid]) -> Result<(), lipl_core::Error> {
+    lipl_core::check_members(playlist, |member| lyric_ids.contains(member))
+}
+
+/// A missing file means the lyric or playlist does not exist
+fn not_found(uuid: Uuid) -> impl FnOnce(FileRepoError) -> lipl_core::Error {
+    move |error| match error {
+        FileRepoError::IOError(error) if error.kind() == std::io::ErrorKind::NotFound => lipl_core::Error::NotFound(uuid),
+        error => error.into(),
+    }
+}
+
+fn check_etag<T>(id: Uuid, etag: &str) -> impl FnOnce(Result<T, FileRepoError>) -> futures::future::Ready<Result<(), lipl_core::Error>>
+where T: Etag,
+{
+    let etag = etag.to_owned();
+    move |current| futures::future::ready(lipl_core::check_etag(id, &etag, current.ok().as_ref()))
+}
+
+/// Sends the result back to the requester and reports whether the request succeeded
+#[allow(clippy::result_large_err)]
+fn reply<T>(sender: ResultSender<T>) -> impl FnOnce(lipl_core::Result<T>) -> Result<bool, lipl_core::Result<T>> {
+    move |result| {
+        let succeeded = result.is_ok();
+        sender.send(result).map(|_| succeeded)
+    }
+}
+
+/// The history is not written while replaying the log, the revisions were added when the upsert was committed
+async fn post_lyric(source_dir: &str, path: PathBuf, lyric: Lyric, index: Arc<Mutex<SearchIndex>>, replaying: bool) -> Result<Lyric, FileRepoError> {
+    io::post_item(&path, lyric).await?;
+    let lyric = io::get_lyric(&path).await?;
+    if !replaying {
+        let history_path = history_path(source_dir, &lyric.id);
+        let mut history = io::get_history(&history_path).await?;
+        if let Some(revision) = Revision::next(&history, &lyric) {
+            history.push(revision);
+            history_path.write_string(io::history_to_string(&history)?).await?;
+        }
+    }
+    index.lock().unwrap().insert(&lyric);
+    Ok(lyric)
+}
+
+fn history_path(source_dir: &str, uuid: &Uuid) -> PathBuf {
+    Path::new(source_dir).join(HISTORY_DIR).full_path(&uuid.to_string(), YAML_EXTENSION)
+}
+
+async fn get_history(source_dir: &str, uuid: Uuid) -> Result<Vec<Revision>, lipl_core::Error> {
+    let history = io::get_history(history_path(source_dir, &uuid)).await?;
+    if history.is_empty() {
+        Err(lipl_core::Error::NotFound(uuid))
     }
     else {
-        futures::future::ready(Ok(()))
+        Ok(history)
+    }
+}
+
+async fn get_revision(source_dir: &str, uuid: Uuid, rev: u64) -> Result<Revision, lipl_core::Error> {
+    get_history(source_dir, uuid)
+    .await?
+    .into_iter()
+    .find(|revision| revision.rev == rev)
+    .ok_or(lipl_core::Error::NotFound(uuid))
+}
+
+async fn search_lyrics<P>(lyric_path: P, query: String, index: Arc<Mutex<SearchIndex>>) -> Result<Vec<SearchHit>, FileRepoError>
+where P: Fn(&Uuid) -> PathBuf
+{
+    let terms = search::terms(&query);
+    let candidates = index.lock().unwrap().candidates(&terms);
+    let mut hits = vec![];
+    for uuid in candidates {
+        let lyric = io::get_lyric(lyric_path(&uuid)).await?;
+        hits.extend(search::rank(&lyric, &terms));
     }
+    hits.sort_by(search::by_rank);
+    Ok(hits)
 }
 
+async fn post_playlist(source_dir: &str, path: PathBuf, playlist: Playlist) -> Result<Playlist, lipl_core::Error> {
+    let summaries = io::get_list(source_dir, LYRIC_EXTENSION, io::get_lyric_summary).await?;
+    check_members(&playlist, &lipl_core::ids(summaries.into_iter()))?;
+    io::post_item(&path, playlist).await?;
+    io::get_playlist(&path).err_into().await
+}
 
-async fn handle_request<P, Q>(request: Request, source_dir: String, lyric_path: P, playlist_path: Q) -> Result<(), lipl_core::Error> 
+fn trash_path(source_dir: &str, uuid: &Uuid) -> PathBuf {
+    Path::new(source_dir).join(TRASH_DIR).full_path(&uuid.to_string(), YAML_EXTENSION)
+}
+
+/// The trash is not written while replaying the log, it was written when the delete was committed and may have been purged since
+async fn delete_lyric(source_dir: &str, path: PathBuf, uuid: Uuid, index: Arc<Mutex<SearchIndex>>, replaying: bool) -> Result<(), lipl_core::Error> {
+    let lyric = io::get_lyric(&path).await.map_err(not_found(uuid))?;
+    let playlists = io::get_list(source_dir, YAML_EXTENSION, io::get_playlist).await?;
+    if !replaying {
+        io::post_item(trash_path(source_dir, &uuid), Trashed::lyric(lyric, playlists.iter())).await?;
+    }
+    path.remove().await?;
+    index.lock().unwrap().remove(&uuid);
+    for mut playlist in playlists {
+        if playlist.members.contains(&uuid) {
+            playlist.retain_members(|member| *member != uuid);
+            io::post_item(
+                source_dir.full_path(&playlist.id.to_string(), YAML_EXTENSION),
+                playlist,
+            )
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+/// Like delete_lyric the trash is left alone while replaying the log
+async fn delete_playlist(source_dir: &str, path: PathBuf, uuid: Uuid, replaying: bool) -> Result<(), lipl_core::Error> {
+    let playlist = io::get_playlist(&path).await.map_err(not_found(uuid))?;
+    if !replaying {
+        io::post_item(trash_path(source_dir, &uuid), Trashed::playlist(playlist)).await?;
+    }
+    path.remove().await?;
+    Ok(())
+}
+
+async fn list_trash(source_dir: &str) -> Result<Vec<Trashed>, lipl_core::Error> {
+    io::get_list(Path::new(source_dir).join(TRASH_DIR), YAML_EXTENSION, io::get_trashed)
+    .map_ok(sorted_by_deleted)
+    .err_into()
+    .await
+}
+
+/// Reads the playlists and applies the merge as one batch within the same request, so no other change comes in between.
+/// Returns the lyric to keep and the transactions of the batch.
+async fn merge_lyrics<P, Q>(source_dir: &str, lyric_path: P, playlist_path: Q, merge: Merge, index: Arc<Mutex<SearchIndex>>, replaying: bool) -> Result<(Lyric, Vec<Transaction>), lipl_core::Error>
 where P: Fn(&Uuid) -> PathBuf, Q: Fn(&Uuid) -> PathBuf
 {
-    match request {
-        Request::Stop(sender) => {
-            async {
-                Ok::<(), lipl_core::Error>(())
+    let lyric = io::get_lyric(lyric_path(&merge.keep)).map_err(not_found(merge.keep)).await?;
+    let playlists = io::get_list(source_dir, YAML_EXTENSION, io::get_playlist).await?;
+    let transactions = duplicate::merge_transactions(&merge, playlists);
+    apply_batch(source_dir, lyric_path, playlist_path, transactions.clone(), index, replaying).await?;
+    Ok((lyric, transactions))
+}
+
+/// Writes the restored item and the playlists it was a member of as a batch and returns the upserts.
+/// Fails with Occupied when an item with the same id was stored after the delete.
+async fn restore<P, Q>(source_dir: &str, lyric_path: P, playlist_path: Q, uuid: Uuid, index: Arc<Mutex<SearchIndex>>) -> Result<Vec<Transaction>, lipl_core::Error>
+where P: Fn(&Uuid) -> PathBuf, Q: Fn(&Uuid) -> PathBuf
+{
+    let path = trash_path(source_dir, &uuid);
+    if !path.exists() {
+        return Err(lipl_core::Error::NotFound(uuid));
+    }
+    if lyric_path(&uuid).exists() || playlist_path(&uuid).exists() {
+        return Err(lipl_core::Error::Occupied);
+    }
+    let trashed = io::get_trashed(&path).await?;
+    let transactions = match &trashed.item {
+        TrashItem::Lyric(lyric) => 
+            std::iter::once(Transaction::LyricUpsert(lyric.clone()))
+            .chain(
+                io::get_list(source_dir, YAML_EXTENSION, io::get_playlist)
+                .await?
+                .into_iter()
+                .filter_map(|mut playlist| trashed.restore_positions(&mut playlist).then_some(playlist))
+                .map(Transaction::PlaylistUpsert)
+            )
+            .collect::<Vec<_>>(),
+        TrashItem::Playlist(playlist) => {
+            let lyric_ids = lipl_core::ids(io::get_list(source_dir, LYRIC_EXTENSION, io::get_lyric_summary).await?.into_iter());
+            let mut playlist = playlist.clone();
+            playlist.retain_members(|member| lyric_ids.contains(member));
+            vec![Transaction::PlaylistUpsert(playlist)]
+        },
+    };
+    apply_batch(source_dir, lyric_path, playlist_path, transactions.clone(), index, false).await?;
+    path.remove().await?;
+    Ok(transactions)
+}
+
+async fn purge(source_dir: &str, uuid: Uuid) -> Result<(), lipl_core::Error> {
+    let path = trash_path(source_dir, &uuid);
+    if !path.exists() {
+        return Err(lipl_core::Error::NotFound(uuid));
+    }
+    path.remove().await?;
+    let history_path = history_path(source_dir, &uuid);
+    if history_path.exists() {
+        history_path.remove().await?;
+    }
+    Ok(())
+}
+
+fn temporary_path(path: &Path) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".tmp");
+    path.with_file_name(file_name)
+}
+
+/// Stages the batch in memory, checking it the same way as the single requests. The new files are written next to
+/// the current ones with a temporary extension and only moved in place when all of them are written.
+/// While replaying the log the history and the trash are left alone, like in post_lyric and delete_lyric.
+async fn apply_batch<P, Q>(source_dir: &str, lyric_path: P, playlist_path: Q, batch: Vec<Transaction>, index: Arc<Mutex<SearchIndex>>, replaying: bool) -> Result<(), lipl_core::Error>
+where P: Fn(&Uuid) -> PathBuf, Q: Fn(&Uuid) -> PathBuf
+{
+    let mut lyric_ids = lipl_core::ids(io::get_list(source_dir, LYRIC_EXTENSION, io::get_lyric_summary).await?.into_iter());
+    let mut playlists = 
+        io::get_list(source_dir, YAML_EXTENSION, io::get_playlist)
+        .await?
+        .into_iter()
+        .map(|playlist| (playlist.id, playlist))
+        .collect::<HashMap<_, _>>();
+    let stored = lyric_ids.iter().chain(playlists.keys()).copied().collect::<HashSet<_>>();
+    let mut histories = HashMap::<Uuid, Vec<Revision>>::new();
+    let mut staged = HashMap::<PathBuf, Option<String>>::new();
+
+    for transaction in batch.iter() {
+        match transaction {
+            Transaction::LyricDelete(uuid) => {
+                if !lyric_ids.contains(uuid) {
+                    return Err(lipl_core::Error::NotFound(*uuid));
+                }
+                lyric_ids = lyric_ids.without(uuid);
+                if !replaying {
+                    let lyric = match staged.get(&lyric_path(uuid)) {
+                        Some(Some(contents)) => io::get_item::<LyricPost, Lyric>(contents.clone(), *uuid)?,
+                        _ => io::get_lyric(lyric_path(uuid)).await?,
+                    };
+                    staged.insert(trash_path(source_dir, uuid), Some(Trashed::lyric(lyric, playlists.values()).to_string()));
+                }
+                stage_removal(&mut staged, lyric_path(uuid), stored.contains(uuid));
+                for playlist in playlists.values_mut().filter(|playlist| playlist.members.contains(uuid)) {
+                    playlist.retain_members(|member| member != uuid);
+                    staged.insert(playlist_path(&playlist.id), Some(playlist.to_string()));
+                }
+            },
+            Transaction::LyricUpsert(lyric) => {
+                if !lyric_ids.contains(&lyric.id) {
+                    lyric_ids.push(lyric.id);
+                }
+                let contents = lyric.to_string();
+                let lyric = io::get_item::<LyricPost, Lyric>(contents.clone(), lyric.id)?;
+                staged.insert(lyric_path(&lyric.id), Some(contents));
+                if replaying {
+                    continue;
+                }
+                let history = match histories.entry(lyric.id) {
+                    Entry::Occupied(entry) => entry.into_mut(),
+                    Entry::Vacant(entry) => entry.insert(io::get_history(history_path(source_dir, &lyric.id)).await?),
+                };
+                if let Some(revision) = Revision::next(history, &lyric) {
+                    history.push(revision);
+                    staged.insert(history_path(source_dir, &lyric.id), Some(io::history_to_string(history)?));
+                }
+            },
+            Transaction::PlaylistDelete(uuid) => {
+                let playlist = playlists.remove(uuid).ok_or(lipl_core::Error::NotFound(*uuid))?;
+                if !replaying {
+                    staged.insert(trash_path(source_dir, uuid), Some(Trashed::playlist(playlist).to_string()));
+                }
+                stage_removal(&mut staged, playlist_path(uuid), stored.contains(uuid));
+            },
+            Transaction::PlaylistUpsert(playlist) => {
+                check_members(playlist, &lyric_ids)?;
+                playlists.insert(playlist.id, playlist.clone());
+                staged.insert(playlist_path(&playlist.id), Some(playlist.to_string()));
+            },
+        }
+    }
+
+    let mut written = vec![];
+    for (path, contents) in staged.iter() {
+        if let Some(contents) = contents {
+            let temporary = temporary_path(path);
+            if let Err(error) = temporary.write_string(contents.clone()).await {
+                for (temporary, _) in written {
+                    let _ = IO::remove(&temporary).await;
+                }
+                return Err(error.into());
             }
-            .map(|v| sender.send(v))
+            written.push((temporary, path));
+        }
+    }
+    for (temporary, path) in written {
+        temporary.rename(path).await?;
+    }
+    for (path, _) in staged.iter().filter(|(_, contents)| contents.is_none()) {
+        match IO::remove(path).await {
+            Err(FileRepoError::IOError(error)) if error.kind() == std::io::ErrorKind::NotFound => {},
+            result => result?,
+        }
+    }
+
+    let mut index = index.lock().unwrap();
+    for transaction in batch {
+        match transaction {
+            Transaction::LyricDelete(uuid) => index.remove(&uuid),
+            Transaction::LyricUpsert(lyric) => index.insert(&lyric),
+            _ => {},
+        }
+    }
+    Ok(())
+}
+
+/// Stages the removal of a file that existed before the batch. A file that was only written earlier in the batch
+/// is not written at all, so no removal is left to fail after the other files are renamed into place.
+fn stage_removal(staged: &mut HashMap<PathBuf, Option<String>>, path: PathBuf, stored: bool) {
+    if stored {
+        staged.insert(path, None);
+    } else {
+        staged.remove(&path);
+    }
+}
+
+/// Sends the current lyrics and playlists to the log thread, to be written as a snapshot
+async fn checkpoint(source_dir: &str, log_tx: &std::sync::mpsc::Sender<LogMessage>) -> Result<(), lipl_core::Error> {
+    let db = RepoDb {
+        lyrics: sorted_by_title(io::get_list(source_dir, LYRIC_EXTENSION, io::get_lyric).await?),
+        playlists: sorted_by_title(io::get_list(source_dir, YAML_EXTENSION, io::get_playlist).await?),
+    };
+    let (sender, receiver) = futures::channel::oneshot::channel();
+    log_tx.send(LogMessage::Checkpoint(db, sender)).map_err(|_| FileRepoError::SendFailed)?;
+    receiver.await?
+}
+
+/// Waits until the log thread has written the transactions sent before
+async fn stop_log(log_tx: &std::sync::mpsc::Sender<LogMessage>) -> Result<(), lipl_core::Error> {
+    let (sender, receiver) = futures::channel::oneshot::channel();
+    log_tx.send(LogMessage::Stop(sender)).map_err(|_| FileRepoError::SendFailed)?;
+    receiver.await?
+}
+
+/// Handles a single request. Returns the transactions committed by the request, so that they can be logged afterwards.
+/// Replaying is true for the requests that replay the snapshot and the log at start.
+async fn handle_request<P, Q>(request: Request, source_dir: String, lyric_path: P, playlist_path: Q, index: Arc<Mutex<SearchIndex>>, log_tx: std::sync::mpsc::Sender<LogMessage>, replaying: bool) -> Result<Vec<Transaction>, lipl_core::Error> 
+where P: Fn(&Uuid) -> PathBuf, Q: Fn(&Uuid) -> PathBuf
+{
+    let mut committed = Vec::<Transaction>::from(&request);
+    let succeeded = match request {
+        Request::Stop(sender) => {
+            stop_log(&log_tx)
+            .map(reply(sender))
             .map_err(|_| lipl_core::Error::SendFailed("Stop".to_string()))
             .await?;
             Err(lipl_core::Error::Stop)
@@ -93,7 +426,7 @@ where P: Fn(&Uuid) -> PathBuf, Q: Fn(&Uuid) -> PathBuf
                 io::get_lyric_summary,
             )
             .map_err(lipl_core::Error::from)
-            .map(|v|sender.send(v))
+            .map(reply(sender))
             .map_err(|_| lipl_core::Error::SendFailed("LyricSummaries".to_string()))
             .await
         }
@@ -104,58 +437,77 @@ where P: Fn(&Uuid) -> PathBuf, Q: Fn(&Uuid) -> PathBuf
                 io::get_lyric,
             )
             .map_err(lipl_core::Error::from)
-            .map(|v| sender.send(v))
+            .map(reply(sender))
             .map_err(|_| lipl_core::Error::SendFailed("LyricList".to_string()))
             .await
         }
         Request::LyricItem(uuid, sender) => {
             io::get_lyric(lyric_path(&uuid))
-            .map_err(lipl_core::Error::from)
-            .map(|v| sender.send(v))
+            .map_err(not_found(uuid))
+            .map(reply(sender))
             .map_err(|_| lipl_core::Error::SendFailed(format!("LyricItem {uuid}")))
             .await
         }
         Request::LyricDelete(uuid, sender) => {
-            async {
-                let playlists =
-                    lyric_path(&uuid)
-                    .remove()
-                    .and_then(|_|
-                        io::get_list(
-                            &source_dir,
-                            YAML_EXTENSION,
-                            io::get_playlist
-                        )
-                    )
-                    .await?;
-                for mut playlist in playlists {
-                    if playlist.members.contains(&uuid) {
-                        playlist.members = playlist.members.without(&uuid);
-                        io::post_item(
-                            source_dir.full_path(&uuid.to_string(), YAML_EXTENSION),
-                            playlist,
-                        )
-                        .await?;
-                    }
-                }
-                Ok::<(), lipl_core::Error>(())
-            }
-            .map(|v| sender.send(v))
+            delete_lyric(&source_dir, lyric_path(&uuid), uuid, index, replaying)
+            .map(reply(sender))
             .map_err(|_| lipl_core::Error::SendFailed(format!("LyricDelete {uuid}")))
             .await
         }
+        Request::LyricDeleteIfMatch(uuid, etag, sender) => {
+            io::get_lyric(lyric_path(&uuid))
+            .then(check_etag(uuid, &etag))
+            .and_then(|_| delete_lyric(&source_dir, lyric_path(&uuid), uuid, index, replaying))
+            .map(reply(sender))
+            .map_err(|_| lipl_core::Error::SendFailed(format!("LyricDeleteIfMatch {uuid}")))
+            .await
+        }
         Request::LyricPost(lyric, sender) => {
-            let path = lyric_path(&lyric.id);
-            io::post_item(
-                &path,
-                lyric,
-            )
-            .and_then(|_| io::get_lyric(&path))
+            post_lyric(&source_dir, lyric_path(&lyric.id), lyric, index, replaying)
             .map_err(lipl_core::Error::from)
-            .map(|v| sender.send(v))
+            .map(reply(sender))
             .map_err(|e| lipl_core::Error::SendFailed(format!("LyricPost {}", e.unwrap().title)))
             .await
         }
+        Request::LyricPostIfMatch(lyric, etag, sender) => {
+            let path = lyric_path(&lyric.id);
+            io::get_lyric(&path)
+            .then(check_etag(lyric.id, &etag))
+            .and_then(|_| post_lyric(&source_dir, path.clone(), lyric, index, replaying).err_into())
+            .map(reply(sender))
+            .map_err(|_| lipl_core::Error::SendFailed("LyricPostIfMatch".to_string()))
+            .await
+        }
+        Request::LyricSearch(query, sender) => {
+            search_lyrics(lyric_path, query, index)
+            .map_err(lipl_core::Error::from)
+            .map(reply(sender))
+            .map_err(|_| lipl_core::Error::SendFailed("LyricSearch".to_string()))
+            .await
+        }
+        Request::LyricHistory(uuid, sender) => {
+            get_history(&source_dir, uuid)
+            .map(reply(sender))
+            .map_err(|_| lipl_core::Error::SendFailed(format!("LyricHistory {uuid}")))
+            .await
+        }
+        Request::LyricRevision(uuid, rev, sender) => {
+            get_revision(&source_dir, uuid, rev)
+            .map(reply(sender))
+            .map_err(|_| lipl_core::Error::SendFailed(format!("LyricRevision {uuid}")))
+            .await
+        }
+        Request::LyricRevert(uuid, rev, sender) => {
+            get_revision(&source_dir, uuid, rev)
+            .and_then(|revision| post_lyric(&source_dir, lyric_path(&uuid), revision.lyric, index, replaying).err_into())
+            .map_ok(|lyric| {
+                committed = vec![Transaction::LyricUpsert(lyric.clone())];
+                lyric
+            })
+            .map(reply(sender))
+            .map_err(|_| lipl_core::Error::SendFailed(format!("LyricRevert {uuid}")))
+            .await
+        }
         Request::PlaylistSummaries(sender) => {
             io::get_list(
                 &source_dir,
@@ -164,7 +516,7 @@ where P: Fn(&Uuid) -> PathBuf, Q: Fn(&Uuid) -> PathBuf
             )
             .map_ok(lipl_core::to_summaries)
             .map_err(lipl_core::Error::from)
-            .map(|v| sender.send(v))
+            .map(reply(sender))
             .map_err(|_| lipl_core::Error::SendFailed("PlaylistSummaries".to_string()))
             .await
         }
@@ -175,50 +527,87 @@ where P: Fn(&Uuid) -> PathBuf, Q: Fn(&Uuid) -> PathBuf
                 io::get_playlist
             )
             .map_err(lipl_core::Error::from)
-            .map(|v| sender.send(v))
+            .map(reply(sender))
             .map_err(|_| lipl_core::Error::SendFailed("PlaylistList".to_string()))
             .await
         }
         Request::PlaylistItem(uuid, sender) => {
             io::get_playlist(playlist_path(&uuid))
-            .map_err(lipl_core::Error::from)
-            .map(|v| sender.send(v))
+            .map_err(not_found(uuid))
+            .map(reply(sender))
             .map_err(|_| lipl_core::Error::SendFailed(format!("PlaylistItem {uuid}")))
             .await
         }
         Request::PlaylistDelete(uuid, sender) => {
-            let path = playlist_path(&uuid);
-            path
-            .remove()
-            .map_err(lipl_core::Error::from)
-            .map(|v| sender.send(v))
+            delete_playlist(&source_dir, playlist_path(&uuid), uuid, replaying)
+            .map(reply(sender))
             .map_err(|_| lipl_core::Error::SendFailed(format!("PlaylistDelete {uuid}")))
             .await
         }
+        Request::PlaylistDeleteIfMatch(uuid, etag, sender) => {
+            let path = playlist_path(&uuid);
+            io::get_playlist(&path)
+            .then(check_etag(uuid, &etag))
+            .and_then(|_| delete_playlist(&source_dir, path.clone(), uuid, replaying))
+            .map(reply(sender))
+            .map_err(|_| lipl_core::Error::SendFailed(format!("PlaylistDeleteIfMatch {uuid}")))
+            .await
+        }
         Request::PlaylistPost(playlist, sender) => {
-            io::get_list(
-                &source_dir,
-                LYRIC_EXTENSION,
-                io::get_lyric_summary,
-            )
-            .map_ok(|summaries| lipl_core::ids(summaries.into_iter()))
-            .and_then(|ids| check_members(&playlist, &ids))
-            .and_then(
-                |_| io::post_item(
-                    playlist_path(&playlist.id),
-                    playlist.clone(),
-                )
-            )
-            .and_then(|_| io::get_playlist(
-                    playlist_path(&playlist.id)
-                )
-            )
-            .map_err(lipl_core::Error::from)
-            .map(|v| sender.send(v))
+            post_playlist(&source_dir, playlist_path(&playlist.id), playlist)
+            .map(reply(sender))
             .map_err(|e| lipl_core::Error::SendFailed(format!("PlaylistPost {}", e.unwrap().title)))
             .await
         }
-    }
+        Request::Batch(batch, sender) => {
+            apply_batch(&source_dir, lyric_path, playlist_path, batch, index, replaying)
+            .map(reply(sender))
+            .map_err(|_| lipl_core::Error::SendFailed("Batch".to_string()))
+            .await
+        }
+        Request::LyricMerge(merge, sender) => {
+            merge_lyrics(&source_dir, lyric_path, playlist_path, merge, index, replaying)
+            .map_ok(|(lyric, transactions)| { committed = transactions; lyric })
+            .map(reply(sender))
+            .map_err(|_| lipl_core::Error::SendFailed("LyricMerge".to_string()))
+            .await
+        }
+        Request::PlaylistPostIfMatch(playlist, etag, sender) => {
+            let path = playlist_path(&playlist.id);
+            io::get_playlist(&path)
+            .then(check_etag(playlist.id, &etag))
+            .and_then(|_| post_playlist(&source_dir, path.clone(), playlist))
+            .map(reply(sender))
+            .map_err(|_| lipl_core::Error::SendFailed("PlaylistPostIfMatch".to_string()))
+            .await
+        }
+        Request::TrashList(sender) => {
+            list_trash(&source_dir)
+            .map(reply(sender))
+            .map_err(|_| lipl_core::Error::SendFailed("TrashList".to_string()))
+            .await
+        }
+        Request::TrashRestore(uuid, sender) => {
+            restore(&source_dir, lyric_path, playlist_path, uuid, index)
+            .map_ok(|transactions| committed = transactions)
+            .map(reply(sender))
+            .map_err(|_| lipl_core::Error::SendFailed(format!("TrashRestore {uuid}")))
+            .await
+        }
+        Request::TrashPurge(uuid, sender) => {
+            purge(&source_dir, uuid)
+            .map(reply(sender))
+            .map_err(|_| lipl_core::Error::SendFailed(format!("TrashPurge {uuid}")))
+            .await
+        }
+        Request::Compact(sender) => {
+            checkpoint(&source_dir, &log_tx)
+            .map(reply(sender))
+            .map_err(|_| lipl_core::Error::SendFailed("Compact".to_string()))
+            .await
+        }
+    }?;
+    Ok(if succeeded { committed } else { vec![] })
 }
 
 fn path(source_dir: String, extension: &'static str) -> impl Fn(&Uuid) -> PathBuf {
@@ -226,35 +615,83 @@ fn path(source_dir: String, extension: &'static str) -> impl Fn(&Uuid) -> PathBu
 }
 
 impl FileRepo {
+    /// Starts a repo on the files in source_dir, with a transaction log in the JSON lines format
     pub async fn new(
         source_dir: String,
+    ) -> lipl_core::Result<FileRepo> {
+        FileRepo::with_log_format(source_dir, LogFormat::default()).await
+    }
+
+    /// Starts a repo on the files in source_dir. The lyrics and playlists of the latest snapshot are written back
+    /// if they differ from the files, and the transactions logged after the snapshot are replayed.
+    /// A damaged end of the log is moved to a quarantine file next to the log before replaying.
+    /// An existing log keeps its format, a new log is written in log_format.
+    pub async fn with_log_format(
+        source_dir: String,
+        log_format: LogFormat,
     ) -> lipl_core::Result<FileRepo> {
         let dir = source_dir.clone();
         let (tx, rx) = mpsc::channel::<Request>(10);
-        let transaction_log: PathBuf = PathBuf::from(source_dir.clone()).join(".transaction.log");
-
-        let log = OpenOptions::new().append(true).open(&transaction_log)?;
-
-        let (_log_join_handle, log_tx) = start_log_thread(log);
+        let transaction_log: PathBuf = PathBuf::from(source_dir.clone()).join(TRANSACTION_LOG);
+        let snapshot_path: PathBuf = PathBuf::from(source_dir.clone()).join(SNAPSHOT);
+
+        std::fs::create_dir_all(Path::new(&source_dir).join(TRASH_DIR))?;
+        std::fs::create_dir_all(Path::new(&source_dir).join(HISTORY_DIR))?;
+
+        let snapshot = read_snapshot(&snapshot_path)?;
+        let snapshot_sequence = snapshot.as_ref().map(|snapshot| snapshot.sequence).unwrap_or_default();
+        let log = recover_log(&transaction_log, snapshot_sequence)?;
+        let last_sequence = log.last_sequence().unwrap_or_default().max(snapshot_sequence);
+        let (_log_join_handle, log_tx) = start_log_thread(transaction_log, snapshot_path, log_format, last_sequence)?;
+
+        let lyrics = io::get_list(&source_dir, LYRIC_EXTENSION, io::get_lyric).await?;
+        let index = Arc::new(Mutex::new(SearchIndex::new(lyrics.iter())));
+        let changes = Arc::new(Broadcaster::default());
+        let changes_sender = changes.clone();
+        let replaying = Arc::new(AtomicBool::new(true));
+        let replaying_sender = replaying.clone();
+        let logged = Arc::new(AtomicUsize::new(0));
 
         let join_handle = tokio::spawn(async move {
             rx
             .map(Ok)
-            .inspect_ok(move |request| {
-                if let Some(transaction) = OptionalTransaction::from(request) {
-                    if let Err(error) = log_tx.send(transaction) {
-                        tracing::error!("Error transaction logging: {error}");
-                    }
-                }
-            })
-            .try_for_each(|request| 
+            .try_for_each(|request| {
+                let log_tx = log_tx.clone();
+                let changes = changes_sender.clone();
+                let replaying = replaying_sender.clone();
+                let logged = logged.clone();
+                let source_dir = source_dir.clone();
+                let is_replaying = replaying.load(Ordering::SeqCst);
                 handle_request(
                     request,
                     source_dir.clone(),
                     path(source_dir.clone(), LYRIC_EXTENSION),
                     path(source_dir.clone(), YAML_EXTENSION),
+                    index.clone(),
+                    log_tx.clone(),
+                    is_replaying,
                 )
-            )
+                .and_then(move |committed| async move {
+                    if is_replaying {
+                        return Ok(());
+                    }
+                    for transaction in committed.iter() {
+                        changes.send(transaction.clone());
+                    }
+                    if !committed.is_empty() {
+                        if let Err(error) = log_tx.send(LogMessage::Write(committed.clone())) {
+                            tracing::error!("Error transaction logging: {error}");
+                        }
+                    }
+                    if logged.fetch_add(committed.len(), Ordering::SeqCst) + committed.len() >= CHECKPOINT_INTERVAL {
+                        logged.store(0, Ordering::SeqCst);
+                        if let Err(error) = checkpoint(&source_dir, &log_tx).await {
+                            tracing::error!("Error compacting transaction log: {error}");
+                        }
+                    }
+                    Ok(())
+                })
+            })
             .await
             .is_ok()
         });
@@ -262,33 +699,59 @@ impl FileRepo {
         let file_repo = FileRepo {
             path: dir,
             tx,
+            changes,
             _join_handle: Arc::new(join_handle),
         };
 
-        if Path::exists(&transaction_log) {
-            let file = OpenOptions::new().read(true).open(&transaction_log)?;
-            build_from_log(file, file_repo.clone()).await?;
+        if let Some(snapshot) = snapshot.as_ref() {
+            restore_snapshot(&snapshot.db, &file_repo).await?;
+        }
+        let replayed = build_from_log(&log.records, file_repo.clone(), snapshot_sequence).await?;
+        replaying.store(false, Ordering::SeqCst);
+        if replayed >= CHECKPOINT_INTERVAL {
+            file_repo.compact().await?;
         }
 
         Ok(file_repo.clone())
     }
 
+    /// Writes a snapshot of the lyrics and playlists and starts a new transaction log, so the next start only replays
+    /// the transactions after it. The old log is kept next to the new one, numbered in the order of compaction.
+    pub async fn compact(&self) -> lipl_core::Result<()> {
+        select(self.tx.clone(), Request::Compact)
+        .await
+    }
 }
 
 #[async_trait]
 impl LiplRepo for FileRepo {
     async fn get_lyrics(&self) -> lipl_core::Result<Vec<Lyric>> {
         select(self.tx.clone(), Request::LyricList)
+        .map_ok(sorted_by_title)
         .err_into()
         .await
     }
 
     async fn get_lyric_summaries(&self) -> lipl_core::Result<Vec<Summary>> {
         select(self.tx.clone(), Request::LyricSummaries)
+        .map_ok(sorted_by_title)
         .err_into()
         .await
     }
 
+    async fn get_lyric_summaries_page(&self, query: ListQuery) -> lipl_core::Result<Page<Summary>> {
+        if query.tags.is_empty() {
+            self.get_lyric_summaries()
+            .map_ok(|summaries| query.apply(summaries))
+            .await
+        }
+        else {
+            self.get_lyrics()
+            .map_ok(|lyrics| query.apply_lyrics(lyrics))
+            .await
+        }
+    }
+
     async fn get_lyric(&self, id: Uuid) -> lipl_core::Result<Lyric> {
         select_by_id(self.tx.clone(), id, Request::LyricItem)
         .err_into()
@@ -307,24 +770,70 @@ impl LiplRepo for FileRepo {
         .await
     }
 
+    async fn search_lyrics(&self, query: &str) -> lipl_core::Result<Vec<SearchHit>> {
+        select_by_query(self.tx.clone(), query.to_owned(), Request::LyricSearch)
+        .await
+    }
+
+    async fn get_tags(&self) -> lipl_core::Result<Vec<TagCount>> {
+        self.get_lyrics()
+        .map_ok(|lyrics| tag::count_tags(lyrics.iter().map(|lyric| &lyric.tags)))
+        .await
+    }
+
+    async fn get_lyric_history(&self, id: Uuid) -> lipl_core::Result<Vec<Revision>> {
+        select_by_id(self.tx.clone(), id, Request::LyricHistory)
+        .await
+    }
+
+    async fn get_lyric_revision(&self, id: Uuid, rev: u64) -> lipl_core::Result<Revision> {
+        select_revision(self.tx.clone(), id, rev, Request::LyricRevision)
+        .await
+    }
+
+    async fn revert_lyric(&self, id: Uuid, rev: u64) -> lipl_core::Result<Lyric> {
+        select_revision(self.tx.clone(), id, rev, Request::LyricRevert)
+        .await
+    }
+
+    async fn merge_lyrics(&self, merge: Merge) -> lipl_core::Result<Lyric> {
+        apply_merge(self.tx.clone(), merge, Request::LyricMerge)
+        .await
+    }
+
     async fn get_playlists(&self) -> lipl_core::Result<Vec<Playlist>> {
         select(self.tx.clone(), Request::PlaylistList)
+        .map_ok(sorted_by_title)
         .err_into()
         .await
     }
 
     async fn get_playlist_summaries(&self) -> lipl_core::Result<Vec<Summary>> {
         select(self.tx.clone(), Request::PlaylistSummaries)
+        .map_ok(sorted_by_title)
         .err_into()
         .await
     }
 
+    async fn get_playlist_summaries_page(&self, query: ListQuery) -> lipl_core::Result<Page<Summary>> {
+        self.get_playlist_summaries()
+        .map_ok(|summaries| query.apply(summaries))
+        .await
+    }
+
     async fn get_playlist(&self, id: Uuid) -> lipl_core::Result<Playlist> {
         select_by_id(self.tx.clone(), id, Request::PlaylistItem)
         .err_into()
         .await
     }
 
+    async fn get_scheduled_playlists(&self, range: DateRange) -> lipl_core::Result<Vec<Playlist>> {
+        select(self.tx.clone(), Request::PlaylistList)
+        .map_ok(|playlists| range.apply(playlists))
+        .err_into()
+        .await
+    }
+
     async fn upsert_playlist(&self, playlist: Playlist) -> lipl_core::Result<Playlist> {
         post(self.tx.clone(), playlist, Request::PlaylistPost)
         .err_into()
@@ -337,6 +846,50 @@ impl LiplRepo for FileRepo {
         .await
     }
 
+    async fn upsert_lyric_if_match(&self, lyric: Lyric, etag: String) -> lipl_core::Result<Lyric> {
+        post_if_match(self.tx.clone(), lyric, etag, Request::LyricPostIfMatch)
+        .await
+    }
+
+    async fn delete_lyric_if_match(&self, id: Uuid, etag: String) -> lipl_core::Result<()> {
+        delete_by_id_if_match(self.tx.clone(), id, etag, Request::LyricDeleteIfMatch)
+        .await
+    }
+
+    async fn upsert_playlist_if_match(&self, playlist: Playlist, etag: String) -> lipl_core::Result<Playlist> {
+        post_if_match(self.tx.clone(), playlist, etag, Request::PlaylistPostIfMatch)
+        .await
+    }
+
+    async fn delete_playlist_if_match(&self, id: Uuid, etag: String) -> lipl_core::Result<()> {
+        delete_by_id_if_match(self.tx.clone(), id, etag, Request::PlaylistDeleteIfMatch)
+        .await
+    }
+
+    async fn apply_batch(&self, batch: Vec<Transaction>) -> lipl_core::Result<()> {
+        apply(self.tx.clone(), batch, Request::Batch)
+        .await
+    }
+
+    async fn watch(&self) -> lipl_core::Result<ChangeStream> {
+        Ok(self.changes.subscribe())
+    }
+
+    async fn list_trash(&self) -> lipl_core::Result<Vec<Trashed>> {
+        select(self.tx.clone(), Request::TrashList)
+        .await
+    }
+
+    async fn restore(&self, id: Uuid) -> lipl_core::Result<()> {
+        select_by_id(self.tx.clone(), id, Request::TrashRestore)
+        .await
+    }
+
+    async fn purge(&self, id: Uuid) -> lipl_core::Result<()> {
+        delete_by_id(self.tx.clone(), id, Request::TrashPurge)
+        .await
+    }
+
     async fn stop(&self) -> lipl_core::Result<()> {
         select(self.tx.clone(), Request::Stop)
         .err_into()
@@ -1,7 +1,7 @@
 use std::{fmt::Debug};
 use futures::channel::{mpsc, oneshot};
 use lipl_core::transaction::{Request, ResultSender};
-use lipl_core::Error;
+use lipl_core::{Error, Lyric, Merge, Transaction};
 use crate::{Uuid};
 use crate::FileRepoError;
 
@@ -24,6 +24,18 @@ pub async fn select_by_id<T>(mut tx: mpsc::Sender<Request>, uuid: Uuid, f: fn(Uu
     oneshot_rx.await?
 }
 
+pub async fn select_revision<T>(mut tx: mpsc::Sender<Request>, uuid: Uuid, rev: u64, f: fn(Uuid, u64, ResultSender<T>) -> Request) -> Result<T> {
+    let (oneshot_tx, oneshot_rx) = oneshot::channel::<Result<T>>();
+    tx.try_send(f(uuid, rev, oneshot_tx)).map_err(send_failed)?;
+    oneshot_rx.await?
+}
+
+pub async fn select_by_query<T>(mut tx: mpsc::Sender<Request>, query: String, f: fn(String, ResultSender<T>) -> Request) -> Result<T> {
+    let (oneshot_tx, oneshot_rx) = oneshot::channel::<Result<T>>();
+    tx.try_send(f(query, oneshot_tx)).map_err(send_failed)?;
+    oneshot_rx.await?
+}
+
 pub async fn delete_by_id(mut tx: mpsc::Sender<Request>, uuid: Uuid, f: fn(Uuid, ResultSender<()>) -> Request) -> Result<()> {
     let (oneshot_tx, oneshot_rx) = oneshot::channel::<Result<()>>();
     tx.try_send(f(uuid, oneshot_tx)).map_err(send_failed)?;
@@ -35,3 +47,27 @@ pub async fn post<T: Debug>(mut tx: mpsc::Sender<Request>, t: T, f: fn(T, Result
     tx.try_send(f(t, oneshot_tx)).map_err(send_failed)?;
     oneshot_rx.await?
 }
+
+pub async fn delete_by_id_if_match(mut tx: mpsc::Sender<Request>, uuid: Uuid, etag: String, f: fn(Uuid, String, ResultSender<()>) -> Request) -> Result<()> {
+    let (oneshot_tx, oneshot_rx) = oneshot::channel::<Result<()>>();
+    tx.try_send(f(uuid, etag, oneshot_tx)).map_err(send_failed)?;
+    oneshot_rx.await?
+}
+
+pub async fn post_if_match<T: Debug>(mut tx: mpsc::Sender<Request>, t: T, etag: String, f: fn(T, String, ResultSender<T>) -> Request) -> Result<T> {
+    let (oneshot_tx, oneshot_rx) = oneshot::channel::<Result<T>>();
+    tx.try_send(f(t, etag, oneshot_tx)).map_err(send_failed)?;
+    oneshot_rx.await?
+}
+
+pub async fn apply(mut tx: mpsc::Sender<Request>, batch: Vec<Transaction>, f: fn(Vec<Transaction>, ResultSender<()>) -> Request) -> Result<()> {
+    let (oneshot_tx, oneshot_rx) = oneshot::channel::<Result<()>>();
+    tx.try_send(f(batch, oneshot_tx)).map_err(send_failed)?;
+    oneshot_rx.await?
+}
+
+pub async fn apply_merge(mut tx: mpsc::Sender<Request>, merge: Merge, f: fn(Merge, ResultSender<Lyric>) -> Request) -> Result<Lyric> {
+    let (oneshot_tx, oneshot_rx) = oneshot::channel::<Result<Lyric>>();
+    tx.try_send(f(merge, oneshot_tx)).map_err(send_failed)?;
+    oneshot_rx.await?
+}
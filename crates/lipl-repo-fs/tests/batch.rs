@@ -0,0 +1,33 @@
+use lipl_core::{Error, LiplRepo, Lyric, LyricPost, Playlist, PlaylistPost, Transaction, Uuid};
+use lipl_repo_fs::FileRepo;
+
+#[tokio::test]
+async fn upsert_and_delete_in_one_batch() {
+    let dir = std::env::temp_dir().join(format!("lipl-repo-fs-{}", Uuid::default()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let repo = FileRepo::new(dir.to_string_lossy().to_string()).await.unwrap();
+
+    let kept: Lyric = LyricPost::from(("Roodkapje", "")).into();
+    let lyric: Lyric = LyricPost::from(("Daar bij die molen", "")).into();
+    let playlist = Playlist::from(PlaylistPost { title: "Kinderliedjes".to_owned(), members: vec![lyric.id], entries: vec![], event: None });
+    repo.apply_batch(
+        vec![
+            Transaction::LyricUpsert(kept.clone()),
+            Transaction::LyricUpsert(lyric.clone()),
+            Transaction::PlaylistUpsert(playlist.clone()),
+            Transaction::PlaylistDelete(playlist.id),
+            Transaction::LyricDelete(lyric.id),
+        ]
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(repo.get_lyric(kept.id).await.unwrap().id, kept.id, "the other transactions of the batch should be committed");
+    assert!(matches!(repo.get_lyric(lyric.id).await, Err(Error::NotFound(id)) if id == lyric.id));
+    assert!(matches!(repo.get_playlist(playlist.id).await, Err(Error::NotFound(id)) if id == playlist.id));
+    assert!(!dir.join(format!("{}.md", lyric.id)).exists());
+    assert!(!dir.join(format!("{}.yaml", playlist.id)).exists());
+    repo.stop().await.unwrap();
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,14 @@
+use lipl_core::Uuid;
+use lipl_repo_fs::FileRepoConfig;
+
+#[tokio::test]
+async fn conformance() {
+    let dir = std::env::temp_dir().join(format!("lipl-repo-fs-{}", Uuid::default()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::File::create(dir.join(".transaction.log")).unwrap();
+
+    let config = dir.to_string_lossy().parse::<FileRepoConfig>().unwrap();
+    let result = lipl_repo_test::check_config(config).await;
+    std::fs::remove_dir_all(&dir).unwrap();
+    result.unwrap();
+}
@@ -0,0 +1,168 @@
+use std::io::Write;
+
+use lipl_core::{transaction::{quarantine_path, read_log_file, replay_until, LogFormat, ReplayUntil}, LiplRepo, Lyric, LyricPost, Revision, Transaction, Uuid};
+use lipl_repo_fs::{FileRepo, TRANSACTION_LOG};
+
+fn lyric(title: &str) -> Lyric {
+    LyricPost::from((title, "")).into()
+}
+
+fn temp_dir() -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("lipl-repo-fs-{}", Uuid::default()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[tokio::test]
+async fn compact_and_restart() {
+    let dir = temp_dir();
+    let log = dir.join(".transaction.log");
+    let source_dir = dir.to_string_lossy().to_string();
+
+    let repo = FileRepo::new(source_dir.clone()).await.unwrap();
+    let kept = repo.upsert_lyric(lyric("Roodkapje")).await.unwrap();
+    let deleted = repo.upsert_lyric(lyric("Daar bij die molen")).await.unwrap();
+    repo.delete_lyric(deleted.id).await.unwrap();
+    repo.stop().await.unwrap();
+    assert_eq!(std::fs::read_to_string(&log).unwrap().lines().count(), 3);
+
+    let repo = FileRepo::new(source_dir.clone()).await.unwrap();
+    assert_eq!(repo.get_lyric_summaries().await.unwrap().len(), 1);
+    repo.compact().await.unwrap();
+    let changed = Lyric { title: "Zeg roodkapje".to_owned(), ..kept.clone() };
+    repo.upsert_lyric(changed.clone()).await.unwrap();
+    repo.stop().await.unwrap();
+    assert_eq!(std::fs::read_to_string(&log).unwrap().lines().count(), 1, "replaying should not log again and compacting should start a new log");
+    assert_eq!(std::fs::read_to_string(dir.join(".transaction.log.1")).unwrap().lines().count(), 3, "the old log should be kept");
+    assert!(dir.join(".transaction.snapshot").exists());
+
+    std::fs::remove_file(dir.join(format!("{}.md", kept.id))).unwrap();
+    let repo = FileRepo::new(source_dir).await.unwrap();
+    assert_eq!(repo.get_lyric(kept.id).await.unwrap().title, changed.title, "the snapshot and the newer records should be replayed");
+    repo.stop().await.unwrap();
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn replay_until_sequence_and_timestamp() {
+    let (dir, target_dir) = (temp_dir(), temp_dir());
+    let repo = FileRepo::new(dir.to_string_lossy().to_string()).await.unwrap();
+    let first = repo.upsert_lyric(lyric("Roodkapje")).await.unwrap();
+    repo.compact().await.unwrap();
+    let second = repo.upsert_lyric(lyric("Daar bij die molen")).await.unwrap();
+    repo.stop().await.unwrap();
+    let log = dir.join(TRANSACTION_LOG);
+    let until = read_log_file(&log, 0).unwrap().records.pop().unwrap().timestamp.parse::<ReplayUntil>().unwrap();
+    let repo = FileRepo::new(dir.to_string_lossy().to_string()).await.unwrap();
+    repo.delete_lyric(first.id).await.unwrap();
+    repo.stop().await.unwrap();
+
+    let target = FileRepo::new(target_dir.to_string_lossy().to_string()).await.unwrap();
+    assert_eq!(replay_until(&log, &target, &ReplayUntil::Sequence(1)).await.unwrap(), 1);
+    assert_eq!(target.get_lyric_summaries().await.unwrap().len(), 1, "only the first record should be replayed");
+    assert_eq!(replay_until(&log, &target, &until).await.unwrap(), 2);
+    assert_eq!(target.get_lyric(second.id).await.unwrap().id, second.id, "records from the rotated and the current log should be replayed");
+    assert_eq!(target.get_lyric(first.id).await.unwrap().id, first.id, "records after the timestamp should not be replayed");
+    target.stop().await.unwrap();
+
+    std::fs::remove_dir_all(&dir).unwrap();
+    std::fs::remove_dir_all(&target_dir).unwrap();
+}
+
+#[tokio::test]
+async fn torn_tail_is_quarantined() {
+    for format in [LogFormat::Json, LogFormat::Bincode] {
+        let dir = temp_dir();
+        let log = dir.join(TRANSACTION_LOG);
+        let source_dir = dir.to_string_lossy().to_string();
+
+        let repo = FileRepo::with_log_format(source_dir.clone(), format).await.unwrap();
+        let kept = repo.upsert_lyric(lyric("Roodkapje")).await.unwrap();
+        repo.stop().await.unwrap();
+        let length = std::fs::metadata(&log).unwrap().len();
+        std::fs::OpenOptions::new().append(true).open(&log).unwrap().write_all(b"{\"sequence\":2,\"timest").unwrap();
+
+        std::fs::remove_file(dir.join(format!("{}.md", kept.id))).unwrap();
+        let repo = FileRepo::with_log_format(source_dir.clone(), format).await.unwrap();
+        assert_eq!(repo.get_lyric(kept.id).await.unwrap().title, kept.title, "the complete records should be replayed");
+        assert_eq!(std::fs::read(quarantine_path(&log)).unwrap(), b"{\"sequence\":2,\"timest", "the torn tail should be quarantined");
+        repo.upsert_lyric(lyric("Daar bij die molen")).await.unwrap();
+        repo.stop().await.unwrap();
+
+        let contents = read_log_file(&log, 0).unwrap();
+        assert!(std::fs::metadata(&log).unwrap().len() > length);
+        assert!(contents.dropped.is_none(), "new records should follow the last complete record");
+        assert_eq!(contents.records.iter().map(|record| record.sequence).collect::<Vec<_>>(), vec![1, 2]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
+
+#[tokio::test]
+async fn restart_keeps_history() {
+    let dir = temp_dir();
+    let source_dir = dir.to_string_lossy().to_string();
+
+    let repo = FileRepo::new(source_dir.clone()).await.unwrap();
+    let first = repo.upsert_lyric(lyric("Roodkapje")).await.unwrap();
+    repo.upsert_lyric(Lyric { title: "Zeg roodkapje".to_owned(), ..first.clone() }).await.unwrap();
+    let revisions = |history: Vec<Revision>| history.into_iter().map(|revision| (revision.rev, revision.timestamp)).collect::<Vec<_>>();
+    let history = revisions(repo.get_lyric_history(first.id).await.unwrap());
+    assert_eq!(history.len(), 2);
+    repo.stop().await.unwrap();
+
+    for _ in 0..2 {
+        let repo = FileRepo::new(source_dir.clone()).await.unwrap();
+        assert_eq!(revisions(repo.get_lyric_history(first.id).await.unwrap()), history, "replaying the log should not add revisions");
+        repo.stop().await.unwrap();
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn restart_keeps_trash() {
+    let dir = temp_dir();
+    let source_dir = dir.to_string_lossy().to_string();
+
+    let repo = FileRepo::new(source_dir.clone()).await.unwrap();
+    let purged = repo.upsert_lyric(lyric("Roodkapje")).await.unwrap();
+    let trashed = repo.upsert_lyric(lyric("Daar bij die molen")).await.unwrap();
+    repo.delete_lyric(purged.id).await.unwrap();
+    repo.delete_lyric(trashed.id).await.unwrap();
+    repo.purge(purged.id).await.unwrap();
+    let deleted = repo.list_trash().await.unwrap().into_iter().map(|trashed| (trashed.id(), trashed.deleted)).collect::<Vec<_>>();
+    assert_eq!(deleted.len(), 1);
+    repo.stop().await.unwrap();
+
+    for _ in 0..2 {
+        let repo = FileRepo::new(source_dir.clone()).await.unwrap();
+        let trash = repo.list_trash().await.unwrap().into_iter().map(|trashed| (trashed.id(), trashed.deleted)).collect::<Vec<_>>();
+        assert_eq!(trash, deleted, "replaying the log should not bring back purged items or change the deletion time");
+        repo.stop().await.unwrap();
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn batch_is_one_record() {
+    let (dir, target_dir) = (temp_dir(), temp_dir());
+    let repo = FileRepo::new(dir.to_string_lossy().to_string()).await.unwrap();
+    let first = repo.upsert_lyric(lyric("Roodkapje")).await.unwrap();
+    let batch = vec![Transaction::LyricDelete(first.id), Transaction::LyricUpsert(lyric("Daar bij die molen"))];
+    repo.apply_batch(batch).await.unwrap();
+    repo.stop().await.unwrap();
+    let log = dir.join(TRANSACTION_LOG);
+    let records = read_log_file(&log, 0).unwrap().records;
+    assert_eq!(records.iter().map(|record| record.transactions.len()).collect::<Vec<_>>(), vec![1, 2], "a batch should be logged as one record");
+
+    let target = FileRepo::new(target_dir.to_string_lossy().to_string()).await.unwrap();
+    assert_eq!(replay_until(&log, &target, &records[1].timestamp.parse::<ReplayUntil>().unwrap()).await.unwrap(), 2);
+    assert_eq!(target.get_lyric_summaries().await.unwrap().len(), 1, "the whole batch should be replayed");
+    target.stop().await.unwrap();
+
+    std::fs::remove_dir_all(&dir).unwrap();
+    std::fs::remove_dir_all(&target_dir).unwrap();
+}
@@ -0,0 +1,193 @@
+/*!
+ Traces and measures the calls to another LiplRepo.
+
+ Every call runs in a tracing span `lipl_repo` with the backend, the operation and, where the call has them, the id and title.
+ The duration of every call is recorded in the histogram `lipl_repo_duration_seconds` and failed calls are counted in
+ `lipl_repo_errors_total`, both labeled with backend and operation. The errors also have a label with the kind of error.
+ The metrics go to the recorder installed with the `metrics` crate, and are dropped if there is none.
+
+ ```ignore
+ let repo = InstrumentedRepo::new(repo, "postgres");
+ ```
+ */
+
+use std::{future::Future, sync::Arc, time::Instant};
+use async_trait::async_trait;
+use lipl_core::{ChangeStream, DateRange, Error, ListQuery, LiplRepo, Lyric, Merge, Page, Playlist, Result, Revision, SearchHit, Summary, TagCount, Transaction, Trashed, Uuid};
+use tracing::{field, Instrument, Span};
+
+pub const DURATION: &str = "lipl_repo_duration_seconds";
+pub const ERRORS: &str = "lipl_repo_errors_total";
+/// Upper bounds in seconds of the histogram buckets for the duration, from a memory lookup to a slow query
+pub const DURATION_BUCKETS: &[f64] = &[0.0001, 0.0005, 0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0];
+
+/// LiplRepo that traces and measures every call to the wrapped repo
+pub struct InstrumentedRepo<R: LiplRepo + ?Sized> {
+    inner: Arc<R>,
+    backend: &'static str,
+}
+
+/// Label for the kind of error, to tell failures of the backend apart from requests that can not be fulfilled
+fn error_kind(error: &Error) -> &'static str {
+    match error {
+        Error::NotFound(_) => "not_found",
+        Error::EtagMismatch(_) => "etag_mismatch",
+        Error::InvalidMembers(_, _) => "invalid_members",
+        Error::Occupied => "occupied",
+        _ => "backend",
+    }
+}
+
+impl<R: LiplRepo + ?Sized> InstrumentedRepo<R> {
+    pub fn new(inner: Arc<R>, backend: &'static str) -> Self {
+        Self { inner, backend }
+    }
+
+    fn span(&self, operation: &'static str, id: Option<Uuid>, title: Option<String>) -> Span {
+        let span = tracing::info_span!("lipl_repo", backend = self.backend, operation, id = field::Empty, title = field::Empty);
+        if let Some(id) = id {
+            span.record("id", field::display(id));
+        }
+        if let Some(title) = title {
+            span.record("title", title.as_str());
+        }
+        span
+    }
+
+    async fn observe<T, F>(&self, operation: &'static str, id: Option<Uuid>, title: Option<String>, call: F) -> Result<T>
+    where
+        F: Future<Output = Result<T>> + Send,
+    {
+        let span = self.span(operation, id, title);
+        let start = Instant::now();
+        let result = call.instrument(span.clone()).await;
+        metrics::histogram!(DURATION, "backend" => self.backend, "operation" => operation)
+            .record(start.elapsed().as_secs_f64());
+        if let Err(error) = result.as_ref() {
+            let kind = error_kind(error);
+            metrics::counter!(ERRORS, "backend" => self.backend, "operation" => operation, "kind" => kind)
+                .increment(1);
+            span.in_scope(|| tracing::debug!("{operation} failed: {error}"));
+        }
+        result
+    }
+}
+
+#[async_trait]
+impl<R: LiplRepo + ?Sized> LiplRepo for InstrumentedRepo<R> {
+    async fn get_lyrics(&self) -> Result<Vec<Lyric>> {
+        self.observe("get_lyrics", None, None, self.inner.get_lyrics()).await
+    }
+
+    async fn get_lyric_summaries(&self) -> Result<Vec<Summary>> {
+        self.observe("get_lyric_summaries", None, None, self.inner.get_lyric_summaries()).await
+    }
+
+    async fn get_lyric_summaries_page(&self, query: ListQuery) -> Result<Page<Summary>> {
+        self.observe("get_lyric_summaries_page", None, None, self.inner.get_lyric_summaries_page(query)).await
+    }
+
+    async fn get_lyric(&self, id: Uuid) -> Result<Lyric> {
+        self.observe("get_lyric", Some(id), None, self.inner.get_lyric(id)).await
+    }
+
+    async fn upsert_lyric(&self, lyric: Lyric) -> Result<Lyric> {
+        self.observe("upsert_lyric", Some(lyric.id), Some(lyric.title.clone()), self.inner.upsert_lyric(lyric)).await
+    }
+
+    async fn delete_lyric(&self, id: Uuid) -> Result<()> {
+        self.observe("delete_lyric", Some(id), None, self.inner.delete_lyric(id)).await
+    }
+
+    async fn search_lyrics(&self, query: &str) -> Result<Vec<SearchHit>> {
+        self.observe("search_lyrics", None, None, self.inner.search_lyrics(query)).await
+    }
+
+    async fn get_tags(&self) -> Result<Vec<TagCount>> {
+        self.observe("get_tags", None, None, self.inner.get_tags()).await
+    }
+
+    async fn get_lyric_history(&self, id: Uuid) -> Result<Vec<Revision>> {
+        self.observe("get_lyric_history", Some(id), None, self.inner.get_lyric_history(id)).await
+    }
+
+    async fn get_lyric_revision(&self, id: Uuid, rev: u64) -> Result<Revision> {
+        self.observe("get_lyric_revision", Some(id), None, self.inner.get_lyric_revision(id, rev)).await
+    }
+
+    async fn revert_lyric(&self, id: Uuid, rev: u64) -> Result<Lyric> {
+        self.observe("revert_lyric", Some(id), None, self.inner.revert_lyric(id, rev)).await
+    }
+
+    async fn merge_lyrics(&self, merge: Merge) -> Result<Lyric> {
+        self.observe("merge_lyrics", Some(merge.keep), None, self.inner.merge_lyrics(merge)).await
+    }
+
+    async fn get_playlists(&self) -> Result<Vec<Playlist>> {
+        self.observe("get_playlists", None, None, self.inner.get_playlists()).await
+    }
+
+    async fn get_playlist_summaries(&self) -> Result<Vec<Summary>> {
+        self.observe("get_playlist_summaries", None, None, self.inner.get_playlist_summaries()).await
+    }
+
+    async fn get_playlist_summaries_page(&self, query: ListQuery) -> Result<Page<Summary>> {
+        self.observe("get_playlist_summaries_page", None, None, self.inner.get_playlist_summaries_page(query)).await
+    }
+
+    async fn get_playlist(&self, id: Uuid) -> Result<Playlist> {
+        self.observe("get_playlist", Some(id), None, self.inner.get_playlist(id)).await
+    }
+
+    async fn get_scheduled_playlists(&self, range: DateRange) -> Result<Vec<Playlist>> {
+        self.observe("get_scheduled_playlists", None, None, self.inner.get_scheduled_playlists(range)).await
+    }
+
+    async fn upsert_playlist(&self, playlist: Playlist) -> Result<Playlist> {
+        self.observe("upsert_playlist", Some(playlist.id), Some(playlist.title.clone()), self.inner.upsert_playlist(playlist)).await
+    }
+
+    async fn delete_playlist(&self, id: Uuid) -> Result<()> {
+        self.observe("delete_playlist", Some(id), None, self.inner.delete_playlist(id)).await
+    }
+
+    async fn upsert_lyric_if_match(&self, lyric: Lyric, etag: String) -> Result<Lyric> {
+        self.observe("upsert_lyric_if_match", Some(lyric.id), Some(lyric.title.clone()), self.inner.upsert_lyric_if_match(lyric, etag)).await
+    }
+
+    async fn delete_lyric_if_match(&self, id: Uuid, etag: String) -> Result<()> {
+        self.observe("delete_lyric_if_match", Some(id), None, self.inner.delete_lyric_if_match(id, etag)).await
+    }
+
+    async fn upsert_playlist_if_match(&self, playlist: Playlist, etag: String) -> Result<Playlist> {
+        self.observe("upsert_playlist_if_match", Some(playlist.id), Some(playlist.title.clone()), self.inner.upsert_playlist_if_match(playlist, etag)).await
+    }
+
+    async fn delete_playlist_if_match(&self, id: Uuid, etag: String) -> Result<()> {
+        self.observe("delete_playlist_if_match", Some(id), None, self.inner.delete_playlist_if_match(id, etag)).await
+    }
+
+    async fn apply_batch(&self, batch: Vec<Transaction>) -> Result<()> {
+        self.observe("apply_batch", None, None, self.inner.apply_batch(batch)).await
+    }
+
+    async fn watch(&self) -> Result<ChangeStream> {
+        self.observe("watch", None, None, self.inner.watch()).await
+    }
+
+    async fn list_trash(&self) -> Result<Vec<Trashed>> {
+        self.observe("list_trash", None, None, self.inner.list_trash()).await
+    }
+
+    async fn restore(&self, id: Uuid) -> Result<()> {
+        self.observe("restore", Some(id), None, self.inner.restore(id)).await
+    }
+
+    async fn purge(&self, id: Uuid) -> Result<()> {
+        self.observe("purge", Some(id), None, self.inner.purge(id)).await
+    }
+
+    async fn stop(&self) -> Result<()> {
+        self.observe("stop", None, None, self.inner.stop()).await
+    }
+}
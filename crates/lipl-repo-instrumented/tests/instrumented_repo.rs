@@ -0,0 +1,35 @@
+use lipl_core::{LiplRepo, Uuid};
+use lipl_repo_instrumented::{InstrumentedRepo, DURATION, ERRORS};
+use lipl_repo_test::wrapped::{check_wrapper, lyric, memory_repo};
+use metrics_util::{debugging::{DebugValue, DebuggingRecorder}, MetricKind};
+
+#[tokio::test]
+async fn conformance() {
+    check_wrapper(|inner| InstrumentedRepo::new(inner, "memory")).await.unwrap();
+}
+
+#[test]
+fn duration_and_errors() {
+    let recorder = DebuggingRecorder::new();
+    let snapshotter = recorder.snapshotter();
+    let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+    metrics::with_local_recorder(&recorder, || runtime.block_on(async {
+        let repo = InstrumentedRepo::new(memory_repo(), "memory");
+        let lyric = lyric("Roodkapje");
+        repo.upsert_lyric(lyric.clone()).await.unwrap();
+        repo.get_lyric(lyric.id).await.unwrap();
+        assert!(repo.get_lyric(Uuid::default()).await.is_err());
+    }));
+
+    let metrics = snapshotter.snapshot().into_vec();
+    let labels = |key: &metrics_util::CompositeKey| key.key().labels().map(|label| (label.key().to_owned(), label.value().to_owned())).collect::<Vec<_>>();
+    let durations = metrics.iter().filter(|(key, ..)| key.kind() == MetricKind::Histogram && key.key().name() == DURATION).collect::<Vec<_>>();
+    let get_lyric = durations.iter().find(|(key, ..)| labels(key).contains(&("operation".to_owned(), "get_lyric".to_owned()))).unwrap();
+    assert!(labels(&get_lyric.0).contains(&("backend".to_owned(), "memory".to_owned())));
+    assert!(matches!(&get_lyric.3, DebugValue::Histogram(values) if values.len() == 2));
+
+    let errors = metrics.iter().filter(|(key, ..)| key.key().name() == ERRORS).collect::<Vec<_>>();
+    assert_eq!(errors.len(), 1);
+    assert!(labels(&errors[0].0).contains(&("kind".to_owned(), "not_found".to_owned())));
+    assert!(matches!(errors[0].3, DebugValue::Counter(1)));
+}
@@ -1,22 +1,39 @@
 use std::{collections::HashMap, sync::{RwLock, Arc}, iter::empty};
 use async_trait::async_trait;
 use lipl_core::{
+    duplicate,
+    ChangeStream,
+    DateRange,
     Error,
     LiplRepo,
     Lyric,
     LyricPost,
+    Merge,
     Playlist,
     PlaylistPost,
+    ListQuery,
+    Revision,
+    Page,
     Result,
+    SearchHit,
     Summary,
+    TagCount,
+    Transaction,
+    TrashItem,
+    Trashed,
     Uuid,
     Yaml,
     RepoDb,
-    reexport::serde_yaml, by_title, ToRepo, HasSummary,
+    reexport::serde_yaml, by_title, check_etag, ToRepo, HasSummary,
+    change::Broadcaster,
+    search::{self, SearchIndex},
+    tag,
+    trash::sorted_by_deleted,
 };
 use lipl_util::VecExt;
 
 #[derive(Clone)]
+#[allow(clippy::large_enum_variant)]
 enum Record {
     Lyric(LyricPost),
     Playlist(PlaylistPost),
@@ -62,7 +79,9 @@ impl ToRepo for MemoryRepoConfig {
 
 #[derive(Clone)]
 pub struct MemoryRepo {
-    db: Arc<RwLock<HashMap<Uuid, Record>>>,
+    db: Arc<RwLock<Db>>,
+    index: Arc<RwLock<SearchIndex>>,
+    changes: Arc<Broadcaster>,
 }
 
 impl From<RepoDb> for MemoryRepo {
@@ -79,21 +98,179 @@ fn playlist_to_tuple(playlist: Playlist) -> (Uuid, Record) {
     (playlist.id, Record::Playlist(playlist.into()))
 }
 
+#[derive(Clone, Default)]
+struct Db {
+    records: HashMap<Uuid, Record>,
+    trash: HashMap<Uuid, Trashed>,
+    history: HashMap<Uuid, Vec<Revision>>,
+}
+
+fn find_lyric(db: &Db, uuid: Uuid) -> Option<Lyric> {
+    db.records.get(&uuid)
+    .and_then(|record| {
+        match record {
+            Record::Lyric(lyric_post) => Some(Lyric::from((Some(uuid), lyric_post.clone()))),
+            _ => None
+        }
+    })
+}
+
+fn find_playlist(db: &Db, uuid: Uuid) -> Option<Playlist> {
+    db.records.get(&uuid)
+    .and_then(|record| {
+        match record {
+            Record::Playlist(playlist_post) => Some(Playlist::from((Some(uuid), playlist_post.clone()))),
+            _ => None,
+        }
+    })
+}
+
+fn all_playlists(db: &Db) -> Vec<Playlist> {
+    db.records.iter().filter_map(|(key, record)| {
+        match record {
+            Record::Playlist(playlist_post) => Some(Playlist::from((Some(*key), playlist_post.clone()))),
+            _ => None
+        }
+    })
+    .collect()
+}
+
+fn check_members(db: &Db, playlist: &Playlist) -> Result<()> {
+    lipl_core::check_members(playlist, |member| find_lyric(db, *member).is_some())
+}
+
+fn insert_lyric(db: &mut Db, lyric: &Lyric) {
+    db.records.insert(lyric.id, Record::Lyric(lyric.clone().into()));
+    let history = db.history.entry(lyric.id).or_default();
+    history.extend(Revision::next(history, lyric));
+}
+
+fn get_history(db: &Db, uuid: Uuid) -> Result<Vec<Revision>> {
+    db.history.get(&uuid).cloned().ok_or(Error::NotFound(uuid))
+}
+
+fn get_revision(db: &Db, uuid: Uuid, rev: u64) -> Result<Revision> {
+    get_history(db, uuid)?
+    .into_iter()
+    .find(|revision| revision.rev == rev)
+    .ok_or(Error::NotFound(uuid))
+}
+
+fn remove_lyric(db: &mut Db, uuid: Uuid) -> Result<()> {
+    let lyric = find_lyric(db, uuid).ok_or(Error::NotFound(uuid))?;
+    db.trash.insert(uuid, Trashed::lyric(lyric, all_playlists(db).iter()));
+    db.records.remove(&uuid);
+    db.records.iter_mut().for_each(|(_, record)| {
+        if let Record::Playlist(playlist_post) = record {
+            let mut playlist = Playlist::from(playlist_post.clone());
+            playlist.retain_members(|member| *member != uuid);
+            *playlist_post = playlist.into();
+        }
+    });
+    Ok(())
+}
+
+fn remove_playlist(db: &mut Db, uuid: Uuid) -> Result<()> {
+    let playlist = find_playlist(db, uuid).ok_or(Error::NotFound(uuid))?;
+    db.trash.insert(uuid, Trashed::playlist(playlist));
+    db.records.remove(&uuid);
+    Ok(())
+}
+
+/// Moves the item out of the trash and returns the upserts needed to restore it.
+/// Fails with Occupied when an item with the same id was stored after the delete.
+fn restore_item(db: &mut Db, uuid: Uuid) -> Result<Vec<Transaction>> {
+    if db.trash.contains_key(&uuid) && db.records.contains_key(&uuid) {
+        return Err(Error::Occupied);
+    }
+    let trashed = db.trash.remove(&uuid).ok_or(Error::NotFound(uuid))?;
+    let transactions = match &trashed.item {
+        TrashItem::Lyric(lyric) => 
+            std::iter::once(Transaction::LyricUpsert(lyric.clone()))
+            .chain(
+                all_playlists(db)
+                .into_iter()
+                .filter_map(|mut playlist| trashed.restore_positions(&mut playlist).then_some(playlist))
+                .map(Transaction::PlaylistUpsert)
+            )
+            .collect::<Vec<_>>(),
+        TrashItem::Playlist(playlist) => {
+            let mut playlist = playlist.clone();
+            playlist.retain_members(|member| find_lyric(db, *member).is_some());
+            vec![Transaction::PlaylistUpsert(playlist)]
+        },
+    };
+    for transaction in transactions.iter() {
+        apply_transaction(db, transaction)?;
+    }
+    Ok(transactions)
+}
+
+fn apply_transaction(db: &mut Db, transaction: &Transaction) -> Result<()> {
+    match transaction {
+        Transaction::LyricDelete(uuid) => remove_lyric(db, *uuid),
+        Transaction::LyricUpsert(lyric) => {
+            insert_lyric(db, lyric);
+            Ok(())
+        },
+        Transaction::PlaylistDelete(uuid) => remove_playlist(db, *uuid),
+        Transaction::PlaylistUpsert(playlist) => {
+            check_members(db, playlist)?;
+            db.records.insert(playlist.id, Record::Playlist(playlist.clone().into()));
+            Ok(())
+        },
+    }
+}
+
 impl MemoryRepo {
     pub fn new(lyrics: impl Iterator<Item = Lyric>, playlists: impl Iterator<Item = Playlist>) -> Self {
+        let lyrics = lyrics.collect::<Vec<_>>();
         Self {
+            index: Arc::new(
+                RwLock::new(
+                    SearchIndex::new(lyrics.iter())
+                )
+            ),
             db: Arc::new(
                 RwLock::new(
-                    HashMap::from_iter(
-                        lyrics.map(lyric_to_tuple).chain(playlists.map(playlist_to_tuple)),
-                    )
+                    Db {
+                        history: HashMap::from_iter(
+                            lyrics.iter().map(|lyric| (lyric.id, Revision::next(&[], lyric).into_iter().collect())),
+                        ),
+                        records: HashMap::from_iter(
+                            lyrics.into_iter().map(lyric_to_tuple).chain(playlists.map(playlist_to_tuple)),
+                        ),
+                        trash: HashMap::new(),
+                    }
                 )
             ),
+            changes: Arc::new(Broadcaster::default()),
         }
     }
 
+    /// Applies the batch to a staged copy, so nothing changes if one of the transactions fails
+    fn commit(&self, db: &mut Db, batch: Vec<Transaction>) -> Result<()> {
+        let mut staged = db.clone();
+        for transaction in batch.iter() {
+            apply_transaction(&mut staged, transaction)?;
+        }
+        *db = staged;
+
+        let mut index = self.index.write().unwrap();
+        for transaction in batch {
+            match &transaction {
+                Transaction::LyricDelete(uuid) => index.remove(uuid),
+                Transaction::LyricUpsert(lyric) => index.insert(lyric),
+                _ => {},
+            }
+            self.changes.send(transaction);
+        }
+        Ok(())
+    }
+
     fn to_repo_db(&self) -> RepoDb {
         self.db.read().unwrap()
+            .records
             .iter()
             .fold(
                 (Vec::<Lyric>::new(), Vec::<Playlist>::new()),
@@ -151,8 +328,14 @@ impl LiplRepo for MemoryRepo {
         )
     }
 
+    async fn get_lyric_summaries_page(&self, query: ListQuery) -> Result<Page<Summary>> {
+        self.get_lyrics()
+            .await
+            .map(|lyrics| query.apply_lyrics(lyrics))
+    }
+
     async fn get_lyrics(&self) ->  Result<Vec<Lyric>> {
-        let mut lyrics = self.db.read().unwrap().iter().filter_map(|(key, record)| {
+        let mut lyrics = self.db.read().unwrap().records.iter().filter_map(|(key, record)| {
                 if let Record::Lyric(lyric_post) = record {
                     Some(Lyric::from((Some(*key), lyric_post.clone())))
                 }
@@ -167,41 +350,69 @@ impl LiplRepo for MemoryRepo {
     }
 
     async fn get_lyric(&self, uuid: Uuid) -> Result<Lyric> {
-        self.db.read().unwrap()
-        .get(&uuid)
-        .and_then(|record| {
-            match record {
-                Record::Lyric(lyric_post) => Some(Lyric::from((Some(uuid), lyric_post.clone()))),
-                _ => None
-            }
-        })
+        find_lyric(&self.db.read().unwrap(), uuid)
         .ok_or(Error::NotFound(uuid))
     }
 
     async fn upsert_lyric(&self, lyric: Lyric) ->  Result<Lyric> {
-        self.db.write().unwrap()
-            .entry(lyric.clone().id)
-            .and_modify(|lyric_post| *lyric_post = Record::Lyric(lyric.clone().into()))
-            .or_insert_with(|| Record::Lyric(lyric.clone().into()));
+        let mut db = self.db.write().unwrap();
+        insert_lyric(&mut db, &lyric);
+        self.index.write().unwrap().insert(&lyric);
+        self.changes.send(Transaction::LyricUpsert(lyric.clone()));
         Ok(lyric)
     }
 
     async fn delete_lyric(&self, uuid: Uuid) -> Result<()> {
         let mut db = self.db.write().unwrap();
-        if db.remove(&uuid).is_some() {
-            db.iter_mut().for_each(|(_, record)| {
-                if let Record::Playlist(playlist_post) = record {
-                    *playlist_post = PlaylistPost {
-                        title: playlist_post.title.clone(),
-                        members: playlist_post.members.clone().without(&uuid)
-                    }
-                }
-            });
-            Ok(())
-        }
-        else {
-            Err(Error::NotFound(uuid))
-        }
+        remove_lyric(&mut db, uuid)?;
+        self.index.write().unwrap().remove(&uuid);
+        self.changes.send(Transaction::LyricDelete(uuid));
+        Ok(())
+    }
+
+    async fn search_lyrics(&self, query: &str) -> Result<Vec<SearchHit>> {
+        let terms = search::terms(query);
+        let candidates = self.index.read().unwrap().candidates(&terms);
+        let db = self.db.read().unwrap();
+        let mut hits =
+            candidates
+            .into_iter()
+            .filter_map(|uuid| find_lyric(&db, uuid))
+            .filter_map(|lyric| search::rank(&lyric, &terms))
+            .collect::<Vec<_>>();
+        hits.sort_by(search::by_rank);
+        Ok(hits)
+    }
+
+    async fn get_tags(&self) -> Result<Vec<TagCount>> {
+        self.get_lyrics()
+            .await
+            .map(|lyrics| tag::count_tags(lyrics.iter().map(|lyric| &lyric.tags)))
+    }
+
+    async fn get_lyric_history(&self, uuid: Uuid) -> Result<Vec<Revision>> {
+        get_history(&self.db.read().unwrap(), uuid)
+    }
+
+    async fn get_lyric_revision(&self, uuid: Uuid, rev: u64) -> Result<Revision> {
+        get_revision(&self.db.read().unwrap(), uuid, rev)
+    }
+
+    async fn revert_lyric(&self, uuid: Uuid, rev: u64) -> Result<Lyric> {
+        let mut db = self.db.write().unwrap();
+        let lyric = get_revision(&db, uuid, rev)?.lyric;
+        insert_lyric(&mut db, &lyric);
+        self.index.write().unwrap().insert(&lyric);
+        self.changes.send(Transaction::LyricUpsert(lyric.clone()));
+        Ok(lyric)
+    }
+
+    async fn merge_lyrics(&self, merge: Merge) -> Result<Lyric> {
+        let mut db = self.db.write().unwrap();
+        let lyric = find_lyric(&db, merge.keep).ok_or(Error::NotFound(merge.keep))?;
+        let transactions = duplicate::merge_transactions(&merge, all_playlists(&db));
+        self.commit(&mut db, transactions)?;
+        Ok(lyric)
     }
 
     async fn get_playlist_summaries(&self) -> Result<Vec<Summary>> {
@@ -210,40 +421,110 @@ impl LiplRepo for MemoryRepo {
             .map(|playlists| playlists.map(|p| p.summary()))
     }
 
-    async fn get_playlists(&self) -> Result<Vec<Playlist>> {
-        let mut playlists = self.db.read().unwrap().iter().filter_map(|(key, record)| {
-            match record {
-                Record::Playlist(playlist_post) => Some(Playlist::from((Some(*key), playlist_post.clone()))),
-                _ => None
-            }
-        })
-        .collect::<Vec<_>>();
+    async fn get_playlist_summaries_page(&self, query: ListQuery) -> Result<Page<Summary>> {
+        self.get_playlist_summaries()
+            .await
+            .map(|summaries| query.apply(summaries))
+    }
 
+    async fn get_playlists(&self) -> Result<Vec<Playlist>> {
+        let mut playlists = all_playlists(&self.db.read().unwrap());
         playlists.sort_by(by_title);
         Ok(playlists)
     }
 
     async fn get_playlist(&self, uuid: Uuid) -> Result<Playlist> {
-        self.db.read().unwrap().get(&uuid)
-        .and_then(|record| {
-            match record {
-                Record::Playlist(playlist_post) => Some(Playlist::from((Some(uuid), playlist_post.clone()))),
-                _ => None,
-            }
-        })
+        find_playlist(&self.db.read().unwrap(), uuid)
         .ok_or(Error::NotFound(uuid))
     }
 
+    async fn get_scheduled_playlists(&self, range: DateRange) -> Result<Vec<Playlist>> {
+        Ok(range.apply(all_playlists(&self.db.read().unwrap())))
+    }
+
     async fn upsert_playlist(&self, playlist: Playlist) -> Result<Playlist> {
-        self.db.write().unwrap()
+        let mut db = self.db.write().unwrap();
+        check_members(&db, &playlist)?;
+        db
+            .records
             .entry(playlist.clone().id)
             .and_modify(|record| *record = Record::Playlist(playlist.clone().into()))
             .or_insert_with(|| Record::Playlist(playlist.clone().into()));
+        self.changes.send(Transaction::PlaylistUpsert(playlist.clone()));
         Ok(playlist) 
     }
 
     async fn delete_playlist(&self, uuid: Uuid) -> Result<()> {
-        self.db.write().unwrap().remove(&uuid).ok_or(Error::NotFound(uuid)).map(|_| ())
+        let mut db = self.db.write().unwrap();
+        remove_playlist(&mut db, uuid)?;
+        self.changes.send(Transaction::PlaylistDelete(uuid));
+        Ok(())
+    }
+
+    async fn upsert_lyric_if_match(&self, lyric: Lyric, etag: String) -> Result<Lyric> {
+        let mut db = self.db.write().unwrap();
+        check_etag(lyric.id, &etag, find_lyric(&db, lyric.id).as_ref())?;
+        insert_lyric(&mut db, &lyric);
+        self.index.write().unwrap().insert(&lyric);
+        self.changes.send(Transaction::LyricUpsert(lyric.clone()));
+        Ok(lyric)
+    }
+
+    async fn delete_lyric_if_match(&self, uuid: Uuid, etag: String) -> Result<()> {
+        let mut db = self.db.write().unwrap();
+        check_etag(uuid, &etag, find_lyric(&db, uuid).as_ref())?;
+        remove_lyric(&mut db, uuid)?;
+        self.index.write().unwrap().remove(&uuid);
+        self.changes.send(Transaction::LyricDelete(uuid));
+        Ok(())
+    }
+
+    async fn upsert_playlist_if_match(&self, playlist: Playlist, etag: String) -> Result<Playlist> {
+        let mut db = self.db.write().unwrap();
+        check_etag(playlist.id, &etag, find_playlist(&db, playlist.id).as_ref())?;
+        check_members(&db, &playlist)?;
+        db.records.insert(playlist.id, Record::Playlist(playlist.clone().into()));
+        self.changes.send(Transaction::PlaylistUpsert(playlist.clone()));
+        Ok(playlist)
+    }
+
+    async fn delete_playlist_if_match(&self, uuid: Uuid, etag: String) -> Result<()> {
+        let mut db = self.db.write().unwrap();
+        check_etag(uuid, &etag, find_playlist(&db, uuid).as_ref())?;
+        remove_playlist(&mut db, uuid)?;
+        self.changes.send(Transaction::PlaylistDelete(uuid));
+        Ok(())
+    }
+
+    async fn apply_batch(&self, batch: Vec<Transaction>) -> Result<()> {
+        let mut db = self.db.write().unwrap();
+        self.commit(&mut db, batch)
+    }
+
+    async fn watch(&self) -> Result<ChangeStream> {
+        Ok(self.changes.subscribe())
+    }
+
+    async fn list_trash(&self) -> Result<Vec<Trashed>> {
+        Ok(sorted_by_deleted(self.db.read().unwrap().trash.values().cloned().collect()))
+    }
+
+    async fn restore(&self, uuid: Uuid) -> Result<()> {
+        let mut db = self.db.write().unwrap();
+        for transaction in restore_item(&mut db, uuid)? {
+            if let Transaction::LyricUpsert(lyric) = &transaction {
+                self.index.write().unwrap().insert(lyric);
+            }
+            self.changes.send(transaction);
+        }
+        Ok(())
+    }
+
+    async fn purge(&self, uuid: Uuid) -> Result<()> {
+        let mut db = self.db.write().unwrap();
+        db.trash.remove(&uuid).ok_or(Error::NotFound(uuid))?;
+        db.history.remove(&uuid);
+        Ok(())
     }
 
     async fn stop(&self) -> Result<()> {
@@ -254,7 +535,8 @@ impl LiplRepo for MemoryRepo {
 #[cfg(test)]
 mod tests {
     use super::{MemoryRepo};
-    use lipl_core::{LiplRepo, PlaylistPost, LyricPost};
+    use futures::StreamExt;
+    use lipl_core::{Error, Etag, LiplRepo, Lyric, Playlist, PlaylistPost, LyricPost, Transaction};
 
     #[tokio::test]
     async fn post_lyric() {
@@ -263,6 +545,12 @@ mod tests {
         let lyric_post = LyricPost {
             title: "Alle 13 goed".to_owned(),
             parts: vec![],
+            chords: vec![],
+            labels: vec![],
+            arrangement: vec![],
+            translations: Default::default(),
+            tags: Default::default(),
+            metadata: Default::default(),
         };
 
         let lyric = db.upsert_lyric((None, lyric_post).into()).await.unwrap();
@@ -279,6 +567,12 @@ mod tests {
         let lyric_post = LyricPost {
             title: "Alle 13 goed".to_owned(),
             parts: vec![],
+            chords: vec![],
+            labels: vec![],
+            arrangement: vec![],
+            translations: Default::default(),
+            tags: Default::default(),
+            metadata: Default::default(),
         };
 
         let mut lyric = db.upsert_lyric((None, lyric_post).into()).await.unwrap();
@@ -300,6 +594,8 @@ mod tests {
         let playlist_post = PlaylistPost {
             title: "Alle 13 goed".to_owned(),
             members: vec![],
+            entries: vec![],
+            event: None,
         };
 
         let playlist = db.upsert_playlist((None, playlist_post).into()).await.unwrap();
@@ -308,4 +604,139 @@ mod tests {
         assert_eq!(playlists[0].title, "Alle 13 goed".to_owned());
         assert_eq!(playlists[0].id, playlist.id);
     }
+
+    #[tokio::test]
+    async fn post_lyric_if_match() {
+        let db = MemoryRepo::default();
+
+        let lyric_post = LyricPost {
+            title: "Alle 13 goed".to_owned(),
+            parts: vec![],
+            chords: vec![],
+            labels: vec![],
+            arrangement: vec![],
+            translations: Default::default(),
+            tags: Default::default(),
+            metadata: Default::default(),
+        };
+
+        let mut lyric = db.upsert_lyric((None, lyric_post).into()).await.unwrap();
+        let etag = lyric.etag().unwrap();
+
+        lyric.title = "Alle 14 goed".to_owned();
+        lyric = db.upsert_lyric_if_match(lyric, etag.clone()).await.unwrap();
+        assert_eq!(db.get_lyric(lyric.id).await.unwrap().title, "Alle 14 goed".to_owned());
+
+        lyric.title = "Alle 15 goed".to_owned();
+        let result = db.upsert_lyric_if_match(lyric.clone(), etag.clone()).await;
+        assert!(matches!(result, Err(Error::EtagMismatch(id)) if id == lyric.id));
+
+        let result = db.delete_lyric_if_match(lyric.id, etag).await;
+        assert!(matches!(result, Err(Error::EtagMismatch(_))));
+        assert_eq!(db.get_lyric(lyric.id).await.unwrap().title, "Alle 14 goed".to_owned());
+    }
+
+    #[tokio::test]
+    async fn search_lyrics() {
+        let db = MemoryRepo::default();
+
+        let knaapje = db.upsert_lyric(LyricPost::from(("'t Knaapje", "Er was eens een knaapje\nDat zag een roosje staan")).into()).await.unwrap();
+        let molen = db.upsert_lyric(LyricPost::from(("Daar bij die molen", "Daar bij die molen, die mooie molen")).into()).await.unwrap();
+
+        let hits = db.search_lyrics("ROOSJE staan").await.unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].summary.id, knaapje.id);
+        assert_eq!(hits[0].line, Some("Dat zag een roosje staan".to_owned()));
+
+        db.delete_lyric(molen.id).await.unwrap();
+        assert!(db.search_lyrics("molen").await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn apply_batch() {
+        let db = MemoryRepo::default();
+
+        let lyric: Lyric = LyricPost::from(("Alle 13 goed", "Alle 13 goed")).into();
+        let playlist: Playlist = (None, PlaylistPost { title: "Kinderliedjes".to_owned(), members: vec![lyric.id], entries: vec![], event: None }).into();
+        db.apply_batch(vec![Transaction::LyricUpsert(lyric.clone()), Transaction::PlaylistUpsert(playlist.clone())]).await.unwrap();
+        assert_eq!(db.get_playlist(playlist.id).await.unwrap().members, vec![lyric.id]);
+        assert_eq!(db.search_lyrics("goed").await.unwrap().len(), 1);
+
+        let other: Lyric = LyricPost::from(("Roodkapje", "")).into();
+        let result = db.apply_batch(vec![Transaction::LyricUpsert(other.clone()), Transaction::LyricDelete(other.id), Transaction::LyricDelete(other.id)]).await;
+        assert!(matches!(result, Err(Error::NotFound(id)) if id == other.id));
+        assert_eq!(db.get_lyrics().await.unwrap().len(), 1);
+
+        db.apply_batch(vec![Transaction::LyricDelete(lyric.id)]).await.unwrap();
+        assert!(db.get_playlist(playlist.id).await.unwrap().members.is_empty());
+        assert!(db.search_lyrics("goed").await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn trash() {
+        let db = MemoryRepo::default();
+
+        let first = db.upsert_lyric(LyricPost::from(("Alle 13 goed", "")).into()).await.unwrap();
+        let second = db.upsert_lyric(LyricPost::from(("Roodkapje", "Roodkapje")).into()).await.unwrap();
+        let playlist = db.upsert_playlist((None, PlaylistPost { title: "Kinderliedjes".to_owned(), members: vec![first.id, second.id], entries: vec![], event: None }).into()).await.unwrap();
+
+        db.delete_lyric(first.id).await.unwrap();
+        db.delete_playlist(playlist.id).await.unwrap();
+        let trash = db.list_trash().await.unwrap();
+        assert_eq!(trash.len(), 2);
+        let trashed = trash.iter().find(|trashed| trashed.id() == first.id).unwrap();
+        assert_eq!(trashed.positions[0].index, 0);
+
+        db.restore(playlist.id).await.unwrap();
+        assert_eq!(db.get_playlist(playlist.id).await.unwrap().members, vec![second.id]);
+        db.restore(first.id).await.unwrap();
+        assert_eq!(db.get_playlist(playlist.id).await.unwrap().members, vec![first.id, second.id]);
+        assert!(db.list_trash().await.unwrap().is_empty());
+
+        db.delete_lyric(second.id).await.unwrap();
+        db.purge(second.id).await.unwrap();
+        assert!(matches!(db.restore(second.id).await, Err(Error::NotFound(id)) if id == second.id));
+        assert!(db.search_lyrics("roodkapje").await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn history() {
+        let db = MemoryRepo::default();
+
+        let mut lyric = db.upsert_lyric(LyricPost::from(("Roodkapje", "Zeg roodkapje")).into()).await.unwrap();
+        lyric.parts = vec![vec!["Zeg roodkapje waar ga je hene".to_owned()]];
+        db.upsert_lyric(lyric.clone()).await.unwrap();
+        db.upsert_lyric(lyric.clone()).await.unwrap();
+
+        let history = db.get_lyric_history(lyric.id).await.unwrap();
+        assert_eq!(history.iter().map(|revision| revision.rev).collect::<Vec<_>>(), vec![1, 2]);
+        assert!(!history[0].diff(&history[1]).is_empty());
+
+        let reverted = db.revert_lyric(lyric.id, 1).await.unwrap();
+        assert_eq!(reverted.parts, vec![vec!["Zeg roodkapje".to_owned()]]);
+        assert_eq!(db.get_lyric(lyric.id).await.unwrap().parts, reverted.parts);
+        assert_eq!(db.get_lyric_revision(lyric.id, 3).await.unwrap().lyric.parts, reverted.parts);
+        assert!(matches!(db.get_lyric_revision(lyric.id, 4).await, Err(Error::NotFound(id)) if id == lyric.id));
+
+        db.delete_lyric(lyric.id).await.unwrap();
+        assert_eq!(db.get_lyric_history(lyric.id).await.unwrap().len(), 3);
+        db.purge(lyric.id).await.unwrap();
+        assert!(db.get_lyric_history(lyric.id).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn watch() {
+        let db = MemoryRepo::default();
+        let mut changes = db.watch().await.unwrap();
+
+        let lyric = db.upsert_lyric(LyricPost::from(("Alle 13 goed", "")).into()).await.unwrap();
+        db.delete_lyric(lyric.id).await.unwrap();
+
+        let change = changes.next().await.unwrap();
+        assert_eq!(change.sequence, 1);
+        assert!(matches!(change.transaction, Transaction::LyricUpsert(upserted) if upserted.id == lyric.id));
+        let change = changes.next().await.unwrap();
+        assert_eq!(change.sequence, 2);
+        assert!(matches!(change.transaction, Transaction::LyricDelete(id) if id == lyric.id));
+    }
 }
@@ -0,0 +1,6 @@
+use lipl_repo_memory::MemoryRepoConfig;
+
+#[tokio::test]
+async fn conformance() {
+    lipl_repo_test::check_config(MemoryRepoConfig::default()).await.unwrap();
+}
@@ -0,0 +1,46 @@
+/// When a write returns: after the secondaries tried it, or right after the primary committed it
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MirrorMode {
+    #[default]
+    Sync,
+    Async,
+}
+
+impl std::str::FromStr for MirrorMode {
+    type Err = lipl_core::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sync" => Ok(MirrorMode::Sync),
+            "async" => Ok(MirrorMode::Async),
+            _ => Err(lipl_core::Error::Argument("mirror mode must be sync or async")),
+        }
+    }
+}
+
+/// How writes are mirrored, and whether reads fall back to a secondary when the primary fails
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MirrorConfig {
+    pub mode: MirrorMode,
+    pub fallback: bool,
+}
+
+/// Parses a comma separated list of options, e.g. `mode=async,fallback=true`.
+/// Options that are left out keep their default value.
+impl std::str::FromStr for MirrorConfig {
+    type Err = lipl_core::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut config = MirrorConfig::default();
+        for option in s.split(',').map(str::trim).filter(|option| !option.is_empty()) {
+            match option.split_once('=') {
+                Some(("mode", value)) => { config.mode = value.parse()?; },
+                Some(("fallback", value)) => {
+                    config.fallback = value.parse().map_err(|_| lipl_core::Error::Argument("fallback must be false or true"))?;
+                },
+                _ => return Err(lipl_core::Error::Argument("mirror option must be mode or fallback")),
+            }
+        }
+        Ok(config)
+    }
+}
@@ -0,0 +1,283 @@
+/*!
+ Keeps copies of a primary LiplRepo in one or more secondary repos.
+
+ Writes go to the primary first. When the primary committed a write, the write is repeated on every secondary, in the same order.
+ In sync mode a write returns after every secondary tried it, in async mode right after the primary committed it.
+ A secondary that fails a write retries it in the background, and the writes after it wait in a queue.
+ Reads come from the primary. With fallback, reads that fail on the primary are tried on the secondaries.
+
+ ```ignore
+ let repo = MirrorRepo::new(postgres, vec![file], "mode=async,fallback=true".parse::<MirrorConfig>()?);
+ ```
+ */
+
+use std::{collections::HashMap, future::Future, pin::Pin, sync::Arc};
+use async_trait::async_trait;
+use lipl_core::{ChangeStream, DateRange, Etag, HasSummary, ListQuery, LiplRepo, Lyric, Merge, Page, Playlist, Result, Revision, SearchHit, Summary, TagCount, Transaction, Trashed, Uuid};
+use secondary::{is_rejected, Secondary, Write};
+
+pub use config::{MirrorConfig, MirrorMode};
+
+mod config;
+mod secondary;
+
+/// How a lyric or playlist on a secondary differs from the primary
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Difference {
+    /// Only on the primary
+    Missing,
+    /// Only on the secondary
+    Extra,
+    /// On both, with a different etag
+    Changed,
+}
+
+/// A lyric or playlist that differs between the primary and the secondary with the index
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Divergence {
+    pub secondary: usize,
+    pub id: Uuid,
+    pub difference: Difference,
+}
+
+/// Writes that wait for, and writes that were dropped by the secondary with the index
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SecondaryStatus {
+    pub secondary: usize,
+    pub pending: usize,
+    pub dropped: usize,
+}
+
+type Read<'a, T> = Pin<Box<dyn Future<Output = Result<T>> + Send + 'a>>;
+
+fn etags<T: HasSummary + Etag>(items: Vec<T>) -> HashMap<Uuid, Option<String>> {
+    items.into_iter().map(|item| (item.summary().id, item.etag())).collect()
+}
+
+fn compare(secondary: usize, primary: &HashMap<Uuid, Option<String>>, copy: &HashMap<Uuid, Option<String>>) -> Vec<Divergence> {
+    let divergence = |id: &Uuid, difference| Divergence { secondary, id: *id, difference };
+    let mut divergences =
+        primary
+        .iter()
+        .filter_map(|(id, etag)| match copy.get(id) {
+            None => Some(divergence(id, Difference::Missing)),
+            Some(copy_etag) if copy_etag != etag => Some(divergence(id, Difference::Changed)),
+            _ => None,
+        })
+        .chain(
+            copy
+            .keys()
+            .filter(|id| !primary.contains_key(id))
+            .map(|id| divergence(id, Difference::Extra))
+        )
+        .collect::<Vec<_>>();
+    divergences.sort_by_key(|divergence| divergence.id.to_string());
+    divergences
+}
+
+/// LiplRepo that writes to a primary and repeats the writes on the secondaries
+pub struct MirrorRepo {
+    primary: Arc<dyn LiplRepo>,
+    secondaries: Vec<Secondary>,
+    config: MirrorConfig,
+    /// Keeps the order of the writes on the secondaries the same as on the primary
+    writing: tokio::sync::Mutex<()>,
+}
+
+impl MirrorRepo {
+    /// Starts a background task per secondary, so it has to be called from within a tokio runtime
+    pub fn new(primary: Arc<dyn LiplRepo>, secondaries: Vec<Arc<dyn LiplRepo>>, config: MirrorConfig) -> Self {
+        Self {
+            primary,
+            secondaries: secondaries.into_iter().enumerate().map(|(index, repo)| Secondary::new(index, repo)).collect(),
+            config,
+            writing: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    /// The lyrics and playlists that differ between the primary and each secondary.
+    /// Writes that are still pending also show up as a difference.
+    pub async fn divergence(&self) -> Result<Vec<Divergence>> {
+        let lyrics = etags(self.primary.get_lyrics().await?);
+        let playlists = etags(self.primary.get_playlists().await?);
+        let mut divergences = vec![];
+        for (index, secondary) in self.secondaries.iter().enumerate() {
+            divergences.extend(compare(index, &lyrics, &etags(secondary.repo.get_lyrics().await?)));
+            divergences.extend(compare(index, &playlists, &etags(secondary.repo.get_playlists().await?)));
+        }
+        Ok(divergences)
+    }
+
+    pub fn status(&self) -> Vec<SecondaryStatus> {
+        self.secondaries
+            .iter()
+            .enumerate()
+            .map(|(index, secondary)| SecondaryStatus { secondary: index, pending: secondary.pending(), dropped: secondary.dropped() })
+            .collect()
+    }
+
+    /// Commits the write on the primary and queues the write returned by mirrored on the secondaries
+    async fn write<T, F>(&self, write: F, mirrored: impl FnOnce(&T) -> Write) -> Result<T>
+    where
+        F: Future<Output = Result<T>> + Send,
+    {
+        let (result, done) = {
+            let _writing = self.writing.lock().await;
+            let result = write.await?;
+            let mirrored = mirrored(&result);
+            let done = self.secondaries.iter().map(|secondary| secondary.send(mirrored.clone())).collect::<Vec<_>>();
+            (result, done)
+        };
+        if self.config.mode == MirrorMode::Sync {
+            for done in done {
+                let _ = done.await;
+            }
+        }
+        Ok(result)
+    }
+
+    /// Reads from the primary, and from the secondaries in order if the primary fails and fallback is configured
+    async fn read<'a, T>(&'a self, read: impl Fn(&'a dyn LiplRepo) -> Read<'a, T>) -> Result<T> {
+        match read(self.primary.as_ref()).await {
+            Err(error) if self.config.fallback && !is_rejected(&error) => {
+                tracing::warn!("Primary failed, reading from the secondaries: {error}");
+                let mut last_error = error;
+                for secondary in self.secondaries.iter() {
+                    match read(secondary.repo.as_ref()).await {
+                        Ok(result) => return Ok(result),
+                        Err(error) => { last_error = error; },
+                    }
+                }
+                Err(last_error)
+            },
+            result => result,
+        }
+    }
+}
+
+#[async_trait]
+impl LiplRepo for MirrorRepo {
+    async fn get_lyrics(&self) -> Result<Vec<Lyric>> {
+        self.read(|repo| repo.get_lyrics()).await
+    }
+
+    async fn get_lyric_summaries(&self) -> Result<Vec<Summary>> {
+        self.read(|repo| repo.get_lyric_summaries()).await
+    }
+
+    async fn get_lyric_summaries_page(&self, query: ListQuery) -> Result<Page<Summary>> {
+        self.read(|repo| repo.get_lyric_summaries_page(query.clone())).await
+    }
+
+    async fn get_lyric(&self, id: Uuid) -> Result<Lyric> {
+        self.read(|repo| repo.get_lyric(id)).await
+    }
+
+    async fn upsert_lyric(&self, lyric: Lyric) -> Result<Lyric> {
+        self.write(self.primary.upsert_lyric(lyric), |lyric| Write::Batch(vec![Transaction::LyricUpsert(lyric.clone())])).await
+    }
+
+    async fn delete_lyric(&self, id: Uuid) -> Result<()> {
+        self.write(self.primary.delete_lyric(id), |_| Write::Batch(vec![Transaction::LyricDelete(id)])).await
+    }
+
+    async fn search_lyrics(&self, query: &str) -> Result<Vec<SearchHit>> {
+        self.read(|repo| repo.search_lyrics(query)).await
+    }
+
+    async fn get_tags(&self) -> Result<Vec<TagCount>> {
+        self.read(|repo| repo.get_tags()).await
+    }
+
+    async fn get_lyric_history(&self, id: Uuid) -> Result<Vec<Revision>> {
+        self.read(|repo| repo.get_lyric_history(id)).await
+    }
+
+    async fn get_lyric_revision(&self, id: Uuid, rev: u64) -> Result<Revision> {
+        self.read(|repo| repo.get_lyric_revision(id, rev)).await
+    }
+
+    async fn revert_lyric(&self, id: Uuid, rev: u64) -> Result<Lyric> {
+        self.write(self.primary.revert_lyric(id, rev), |lyric| Write::Batch(vec![Transaction::LyricUpsert(lyric.clone())])).await
+    }
+
+    async fn merge_lyrics(&self, merge: Merge) -> Result<Lyric> {
+        let mirrored = Write::Merge(merge.clone());
+        self.write(self.primary.merge_lyrics(merge), |_| mirrored).await
+    }
+
+    async fn get_playlists(&self) -> Result<Vec<Playlist>> {
+        self.read(|repo| repo.get_playlists()).await
+    }
+
+    async fn get_playlist_summaries(&self) -> Result<Vec<Summary>> {
+        self.read(|repo| repo.get_playlist_summaries()).await
+    }
+
+    async fn get_playlist_summaries_page(&self, query: ListQuery) -> Result<Page<Summary>> {
+        self.read(|repo| repo.get_playlist_summaries_page(query.clone())).await
+    }
+
+    async fn get_playlist(&self, id: Uuid) -> Result<Playlist> {
+        self.read(|repo| repo.get_playlist(id)).await
+    }
+
+    async fn get_scheduled_playlists(&self, range: DateRange) -> Result<Vec<Playlist>> {
+        self.read(|repo| repo.get_scheduled_playlists(range.clone())).await
+    }
+
+    async fn upsert_playlist(&self, playlist: Playlist) -> Result<Playlist> {
+        self.write(self.primary.upsert_playlist(playlist), |playlist| Write::Batch(vec![Transaction::PlaylistUpsert(playlist.clone())])).await
+    }
+
+    async fn delete_playlist(&self, id: Uuid) -> Result<()> {
+        self.write(self.primary.delete_playlist(id), |_| Write::Batch(vec![Transaction::PlaylistDelete(id)])).await
+    }
+
+    async fn upsert_lyric_if_match(&self, lyric: Lyric, etag: String) -> Result<Lyric> {
+        self.write(self.primary.upsert_lyric_if_match(lyric, etag), |lyric| Write::Batch(vec![Transaction::LyricUpsert(lyric.clone())])).await
+    }
+
+    async fn delete_lyric_if_match(&self, id: Uuid, etag: String) -> Result<()> {
+        self.write(self.primary.delete_lyric_if_match(id, etag), |_| Write::Batch(vec![Transaction::LyricDelete(id)])).await
+    }
+
+    async fn upsert_playlist_if_match(&self, playlist: Playlist, etag: String) -> Result<Playlist> {
+        self.write(self.primary.upsert_playlist_if_match(playlist, etag), |playlist| Write::Batch(vec![Transaction::PlaylistUpsert(playlist.clone())])).await
+    }
+
+    async fn delete_playlist_if_match(&self, id: Uuid, etag: String) -> Result<()> {
+        self.write(self.primary.delete_playlist_if_match(id, etag), |_| Write::Batch(vec![Transaction::PlaylistDelete(id)])).await
+    }
+
+    async fn apply_batch(&self, batch: Vec<Transaction>) -> Result<()> {
+        let mirrored = Write::Batch(batch.clone());
+        self.write(self.primary.apply_batch(batch), |_| mirrored).await
+    }
+
+    async fn watch(&self) -> Result<ChangeStream> {
+        self.primary.watch().await
+    }
+
+    async fn list_trash(&self) -> Result<Vec<Trashed>> {
+        self.read(|repo| repo.list_trash()).await
+    }
+
+    async fn restore(&self, id: Uuid) -> Result<()> {
+        self.write(self.primary.restore(id), |_| Write::Restore(id)).await
+    }
+
+    async fn purge(&self, id: Uuid) -> Result<()> {
+        self.write(self.primary.purge(id), |_| Write::Purge(id)).await
+    }
+
+    /// Stops the secondaries after their queued writes, and then the primary
+    async fn stop(&self) -> Result<()> {
+        for secondary in self.secondaries.iter() {
+            if let Err(error) = secondary.stop().await {
+                tracing::error!("Error stopping secondary: {error}");
+            }
+        }
+        self.primary.stop().await
+    }
+}
@@ -0,0 +1,146 @@
+use std::{sync::{atomic::{AtomicBool, AtomicUsize, Ordering}, Arc}, time::Duration};
+use lipl_core::{Error, LiplRepo, Merge, Result, Transaction, Uuid};
+use tokio::{sync::{mpsc, oneshot}, task::JoinHandle};
+
+const FIRST_RETRY: Duration = Duration::from_millis(100);
+const LAST_RETRY: Duration = Duration::from_secs(10);
+
+/// A write that is committed on the primary and has to be repeated on the secondaries
+#[derive(Clone, Debug)]
+pub(crate) enum Write {
+    Batch(Vec<Transaction>),
+    Restore(Uuid),
+    Merge(Merge),
+    Purge(Uuid),
+}
+
+enum Message {
+    Write(Write, Option<oneshot::Sender<()>>),
+    Stop(oneshot::Sender<()>),
+}
+
+/// Errors the secondary will give again when the write is retried
+pub(crate) fn is_rejected(error: &Error) -> bool {
+    matches!(error, Error::NotFound(_) | Error::EtagMismatch(_) | Error::InvalidMembers(_, _) | Error::Occupied | Error::Argument(_))
+}
+
+async fn apply(repo: &dyn LiplRepo, write: &Write) -> Result<()> {
+    let already_gone = |result: Result<()>| match result {
+        Err(Error::NotFound(_)) => Ok(()),
+        result => result,
+    };
+    match write {
+        Write::Batch(batch) => match &batch[..] {
+            [Transaction::LyricUpsert(lyric)] => repo.upsert_lyric(lyric.clone()).await.map(|_| ()),
+            [Transaction::LyricDelete(id)] => already_gone(repo.delete_lyric(*id).await),
+            [Transaction::PlaylistUpsert(playlist)] => repo.upsert_playlist(playlist.clone()).await.map(|_| ()),
+            [Transaction::PlaylistDelete(id)] => already_gone(repo.delete_playlist(*id).await),
+            _ => repo.apply_batch(batch.clone()).await,
+        },
+        Write::Restore(id) => repo.restore(*id).await,
+        Write::Merge(merge) => repo.merge_lyrics(merge.clone()).await.map(|_| ()),
+        Write::Purge(id) => already_gone(repo.purge(*id).await),
+    }
+}
+
+/// Repeats the writes on a secondary in the order they were committed on the primary.
+/// A write that fails is retried with an increasing delay, and the writes after it wait.
+/// A write the secondary rejects is dropped and counted, because the secondary has diverged from the primary.
+pub(crate) struct Secondary {
+    pub repo: Arc<dyn LiplRepo>,
+    tx: mpsc::UnboundedSender<Message>,
+    pending: Arc<AtomicUsize>,
+    dropped: Arc<AtomicUsize>,
+    stopping: Arc<AtomicBool>,
+    _join_handle: JoinHandle<()>,
+}
+
+impl Secondary {
+    pub fn new(index: usize, repo: Arc<dyn LiplRepo>) -> Self {
+        let (tx, mut rx) = mpsc::unbounded_channel::<Message>();
+        let pending = Arc::new(AtomicUsize::new(0));
+        let dropped = Arc::new(AtomicUsize::new(0));
+        let stopping = Arc::new(AtomicBool::new(false));
+        let join_handle = tokio::spawn({
+            let (repo, pending, dropped, stopping) = (repo.clone(), pending.clone(), dropped.clone(), stopping.clone());
+            async move {
+                while let Some(message) = rx.recv().await {
+                    match message {
+                        Message::Write(write, mut done) => {
+                            let mut delay = FIRST_RETRY;
+                            loop {
+                                match apply(repo.as_ref(), &write).await {
+                                    Ok(()) => break,
+                                    Err(error) if is_rejected(&error) => {
+                                        dropped.fetch_add(1, Ordering::SeqCst);
+                                        tracing::warn!("Secondary {index} rejected {write:?}, it differs from the primary: {error}");
+                                        break;
+                                    },
+                                    Err(error) if stopping.load(Ordering::SeqCst) => {
+                                        dropped.fetch_add(1, Ordering::SeqCst);
+                                        tracing::warn!("Secondary {index} stopped before {write:?} succeeded: {error}");
+                                        break;
+                                    },
+                                    Err(error) => {
+                                        tracing::warn!("Secondary {index} failed {write:?}, retrying in {delay:?}: {error}");
+                                        if let Some(done) = done.take() {
+                                            let _ = done.send(());
+                                        }
+                                        tokio::time::sleep(delay).await;
+                                        delay = (delay * 2).min(LAST_RETRY);
+                                    },
+                                }
+                            }
+                            pending.fetch_sub(1, Ordering::SeqCst);
+                            if let Some(done) = done {
+                                let _ = done.send(());
+                            }
+                        },
+                        Message::Stop(done) => {
+                            let _ = done.send(());
+                            break;
+                        },
+                    }
+                }
+            }
+        });
+        Self {
+            repo,
+            tx,
+            pending,
+            dropped,
+            stopping,
+            _join_handle: join_handle,
+        }
+    }
+
+    /// Queues the write. The receiver is notified after the first attempt, whether it succeeded or not.
+    pub fn send(&self, write: Write) -> oneshot::Receiver<()> {
+        let (done_tx, done_rx) = oneshot::channel();
+        self.pending.fetch_add(1, Ordering::SeqCst);
+        if self.tx.send(Message::Write(write, Some(done_tx))).is_err() {
+            self.pending.fetch_sub(1, Ordering::SeqCst);
+        }
+        done_rx
+    }
+
+    /// Number of writes that wait for the secondary
+    pub fn pending(&self) -> usize {
+        self.pending.load(Ordering::SeqCst)
+    }
+
+    /// Number of writes that the secondary rejected or that did not succeed before it was stopped
+    pub fn dropped(&self) -> usize {
+        self.dropped.load(Ordering::SeqCst)
+    }
+
+    /// Tries the queued writes once more before the secondary is stopped
+    pub async fn stop(&self) -> Result<()> {
+        self.stopping.store(true, Ordering::SeqCst);
+        let (done_tx, done_rx) = oneshot::channel();
+        if self.tx.send(Message::Stop(done_tx)).is_ok() {
+            let _ = done_rx.await;
+        }
+        self.repo.stop().await
+    }
+}
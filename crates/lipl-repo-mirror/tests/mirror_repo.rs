@@ -0,0 +1,121 @@
+use std::{sync::{atomic::{AtomicBool, Ordering}, Arc}, time::Duration};
+use async_trait::async_trait;
+use lipl_core::{ChangeStream, DateRange, Error, ListQuery, LiplRepo, Lyric, Merge, Page, Playlist, Result, Revision, SearchHit, Summary, TagCount, Transaction, Trashed, Uuid};
+use lipl_repo_memory::MemoryRepo;
+use lipl_repo_mirror::{Difference, Divergence, MirrorConfig, MirrorMode, MirrorRepo, SecondaryStatus};
+use lipl_repo_test::wrapped::{check_wrapper, lyric, memory_repo};
+
+/// Memory repo that fails every call while it is down
+struct Flaky {
+    inner: Arc<MemoryRepo>,
+    down: AtomicBool,
+}
+
+impl Flaky {
+    fn new() -> Self {
+        Self { inner: memory_repo(), down: AtomicBool::new(false) }
+    }
+
+    fn set_down(&self, down: bool) {
+        self.down.store(down, Ordering::SeqCst);
+    }
+
+    fn check(&self) -> Result<()> {
+        if self.down.load(Ordering::SeqCst) {
+            Err(Error::IOError(std::io::ErrorKind::ConnectionRefused.into()))
+        }
+        else {
+            Ok(())
+        }
+    }
+}
+
+#[async_trait]
+impl LiplRepo for Flaky {
+    async fn get_lyrics(&self) -> Result<Vec<Lyric>> { self.check()?; self.inner.get_lyrics().await }
+    async fn get_lyric_summaries(&self) -> Result<Vec<Summary>> { self.check()?; self.inner.get_lyric_summaries().await }
+    async fn get_lyric_summaries_page(&self, query: ListQuery) -> Result<Page<Summary>> { self.check()?; self.inner.get_lyric_summaries_page(query).await }
+    async fn get_lyric(&self, id: Uuid) -> Result<Lyric> { self.check()?; self.inner.get_lyric(id).await }
+    async fn upsert_lyric(&self, lyric: Lyric) -> Result<Lyric> { self.check()?; self.inner.upsert_lyric(lyric).await }
+    async fn delete_lyric(&self, id: Uuid) -> Result<()> { self.check()?; self.inner.delete_lyric(id).await }
+    async fn search_lyrics(&self, query: &str) -> Result<Vec<SearchHit>> { self.check()?; self.inner.search_lyrics(query).await }
+    async fn get_tags(&self) -> Result<Vec<TagCount>> { self.check()?; self.inner.get_tags().await }
+    async fn get_lyric_history(&self, id: Uuid) -> Result<Vec<Revision>> { self.check()?; self.inner.get_lyric_history(id).await }
+    async fn get_lyric_revision(&self, id: Uuid, rev: u64) -> Result<Revision> { self.check()?; self.inner.get_lyric_revision(id, rev).await }
+    async fn revert_lyric(&self, id: Uuid, rev: u64) -> Result<Lyric> { self.check()?; self.inner.revert_lyric(id, rev).await }
+    async fn merge_lyrics(&self, merge: Merge) -> Result<Lyric> { self.check()?; self.inner.merge_lyrics(merge).await }
+    async fn get_playlists(&self) -> Result<Vec<Playlist>> { self.check()?; self.inner.get_playlists().await }
+    async fn get_playlist_summaries(&self) -> Result<Vec<Summary>> { self.check()?; self.inner.get_playlist_summaries().await }
+    async fn get_playlist_summaries_page(&self, query: ListQuery) -> Result<Page<Summary>> { self.check()?; self.inner.get_playlist_summaries_page(query).await }
+    async fn get_playlist(&self, id: Uuid) -> Result<Playlist> { self.check()?; self.inner.get_playlist(id).await }
+    async fn get_scheduled_playlists(&self, range: DateRange) -> Result<Vec<Playlist>> { self.check()?; self.inner.get_scheduled_playlists(range).await }
+    async fn upsert_playlist(&self, playlist: Playlist) -> Result<Playlist> { self.check()?; self.inner.upsert_playlist(playlist).await }
+    async fn delete_playlist(&self, id: Uuid) -> Result<()> { self.check()?; self.inner.delete_playlist(id).await }
+    async fn upsert_lyric_if_match(&self, lyric: Lyric, etag: String) -> Result<Lyric> { self.check()?; self.inner.upsert_lyric_if_match(lyric, etag).await }
+    async fn delete_lyric_if_match(&self, id: Uuid, etag: String) -> Result<()> { self.check()?; self.inner.delete_lyric_if_match(id, etag).await }
+    async fn upsert_playlist_if_match(&self, playlist: Playlist, etag: String) -> Result<Playlist> { self.check()?; self.inner.upsert_playlist_if_match(playlist, etag).await }
+    async fn delete_playlist_if_match(&self, id: Uuid, etag: String) -> Result<()> { self.check()?; self.inner.delete_playlist_if_match(id, etag).await }
+    async fn apply_batch(&self, batch: Vec<Transaction>) -> Result<()> { self.check()?; self.inner.apply_batch(batch).await }
+    async fn watch(&self) -> Result<ChangeStream> { self.check()?; self.inner.watch().await }
+    async fn list_trash(&self) -> Result<Vec<Trashed>> { self.check()?; self.inner.list_trash().await }
+    async fn restore(&self, id: Uuid) -> Result<()> { self.check()?; self.inner.restore(id).await }
+    async fn purge(&self, id: Uuid) -> Result<()> { self.check()?; self.inner.purge(id).await }
+    async fn stop(&self) -> Result<()> { self.inner.stop().await }
+}
+
+#[tokio::test]
+async fn conformance() {
+    let secondary = memory_repo();
+    let repo = check_wrapper(|primary| MirrorRepo::new(primary, vec![secondary.clone()], MirrorConfig::default())).await.unwrap();
+    assert_eq!(repo.divergence().await.unwrap(), vec![], "sync writes should be on the secondary when they return");
+}
+
+#[test]
+fn config() {
+    assert_eq!("".parse::<MirrorConfig>().unwrap(), MirrorConfig::default());
+    assert_eq!(
+        "mode=async, fallback=true".parse::<MirrorConfig>().unwrap(),
+        MirrorConfig { mode: MirrorMode::Async, fallback: true },
+    );
+    assert!("mode=later".parse::<MirrorConfig>().is_err());
+}
+
+#[tokio::test]
+async fn retry_and_divergence() {
+    let secondary = Arc::new(Flaky::new());
+    let repo = MirrorRepo::new(memory_repo(), vec![secondary.clone()], MirrorConfig { mode: MirrorMode::Async, fallback: false });
+    secondary.set_down(true);
+    let first = repo.upsert_lyric(lyric("Roodkapje")).await.unwrap();
+    let second = repo.upsert_lyric(lyric("Daar bij die molen")).await.unwrap();
+    repo.delete_lyric(first.id).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert_eq!(repo.status(), vec![SecondaryStatus { secondary: 0, pending: 3, dropped: 0 }]);
+
+    secondary.set_down(false);
+    assert_eq!(
+        repo.divergence().await.unwrap(),
+        vec![Divergence { secondary: 0, id: second.id, difference: Difference::Missing }],
+    );
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    assert_eq!(repo.status(), vec![SecondaryStatus { secondary: 0, pending: 0, dropped: 0 }], "the queued writes should be retried in order");
+    assert_eq!(repo.divergence().await.unwrap(), vec![]);
+
+    secondary.inner.upsert_lyric(Lyric { title: "Bij de molen".to_owned(), ..second.clone() }).await.unwrap();
+    assert_eq!(
+        repo.divergence().await.unwrap(),
+        vec![Divergence { secondary: 0, id: second.id, difference: Difference::Changed }],
+    );
+    repo.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn fallback() {
+    let primary = Arc::new(Flaky::new());
+    let repo = MirrorRepo::new(primary.clone(), vec![memory_repo()], MirrorConfig { mode: MirrorMode::Sync, fallback: true });
+    let roodkapje = repo.upsert_lyric(lyric("Roodkapje")).await.unwrap();
+
+    primary.set_down(true);
+    assert_eq!(repo.get_lyric(roodkapje.id).await.unwrap().title, roodkapje.title, "reads should fall back to the secondary");
+    assert!(repo.upsert_lyric(lyric("Daar bij die molen")).await.is_err(), "writes should fail when the primary fails");
+    assert!(matches!(repo.get_lyric(Uuid::default()).await, Err(Error::NotFound(_))));
+}
@@ -1,5 +1,6 @@
-use lipl_core::{reexport, Lyric, Summary, Uuid, Playlist};
+use lipl_core::{chords, reexport, search::matching_line, Lyric, LyricMetadata, Revision, SearchHit, Summary, TagCount, Tags, Translations, Trashed, Uuid, Playlist, PlaylistEntry, PlaylistEvent};
 use lipl_util::VecExt;
+use serde::{Deserialize, Serialize};
 use tokio_postgres::Row;
 use crate::Result;
 
@@ -11,19 +12,131 @@ where
 }
 
 pub fn to_lyric(row: Row) -> Result<Lyric> {
+    let (parts, chords) = chords::from_text(row.try_get::<&str, String>(column::PARTS)?);
     Ok(Lyric {
         id: row.try_get::<&str, reexport::uuid::Uuid>(column::ID)?.into(),
         title: row.try_get::<&str, String>(column::TITLE)?,
-        parts: parts::to_parts(row.try_get::<&str, String>(column::PARTS)?),
+        parts,
+        chords,
+        labels: row.try_get::<&str, Option<Vec<Option<String>>>>(column::LABELS)?.unwrap_or_default(),
+        arrangement: row.try_get::<&str, Option<Vec<String>>>(column::ARRANGEMENT)?.unwrap_or_default(),
+        translations: to_translations(&row)?,
+        tags: to_tags(&row)?,
+        metadata: to_metadata(&row)?,
     })
 }
 
+/// Translations are stored as json, or null if there are none
+fn to_translations(row: &Row) -> Result<Translations> {
+    row.try_get::<&str, Option<String>>(column::TRANSLATIONS)?
+    .map(|json| serde_json::from_str(&json))
+    .transpose()
+    .map_err(Into::into)
+    .map(Option::unwrap_or_default)
+}
+
+fn to_tags(row: &Row) -> Result<Tags> {
+    Ok(row.try_get::<&str, Option<Vec<String>>>(column::TAGS)?.unwrap_or_default().into_iter().collect())
+}
+
+pub fn translations_to_text(translations: &Translations) -> Result<Option<String>> {
+    (!translations.is_empty())
+    .then(|| serde_json::to_string(translations))
+    .transpose()
+    .map_err(Into::into)
+}
+
+fn to_metadata(row: &Row) -> Result<LyricMetadata> {
+    Ok(LyricMetadata {
+        sub_title: row.try_get::<&str, Option<String>>(column::SUB_TITLE)?,
+        lyricist: row.try_get::<&str, Option<String>>(column::LYRICIST)?,
+        composer: row.try_get::<&str, Option<String>>(column::COMPOSER)?,
+        language: row.try_get::<&str, Option<String>>(column::LANGUAGE)?,
+        year: row.try_get::<&str, Option<i32>>(column::YEAR)?.and_then(|year| u16::try_from(year).ok()),
+        copyright: row.try_get::<&str, Option<String>>(column::COPYRIGHT)?,
+        source: row.try_get::<&str, Option<String>>(column::SOURCE)?,
+    })
+}
+
+pub fn to_revision(row: Row) -> Result<Revision> {
+    Ok(Revision {
+        rev: row.try_get::<&str, i64>(column::REV)? as u64,
+        timestamp: row.try_get::<&str, i64>(column::MODIFIED)? as u64,
+        lyric: to_lyric(row)?,
+    })
+}
+
+pub fn to_id(row: Row) -> Result<Uuid> {
+    Ok(row.try_get::<&str, reexport::uuid::Uuid>(column::ID)?.into())
+}
+
+pub fn to_trashed(row: Row) -> lipl_core::Result<Trashed> {
+    let data = row.try_get::<&str, String>(column::DATA).map_err(crate::PostgresRepoError::from)?;
+    serde_json::from_str::<Trashed>(&data)
+    .map_err(|error| lipl_core::Error::Json(Box::new(error)))
+}
+
 pub fn to_playlist(row: Row) -> Result<Playlist> {
-    Ok(Playlist {
+    let mut playlist = Playlist {
         id: row.try_get::<&str, reexport::uuid::Uuid>(column::ID)?.into(),
         title: row.try_get::<&str, String>(column::TITLE)?,
-        members: row.try_get::<&str, Option<Vec<reexport::uuid::Uuid>>>(column::MEMBERS)?.unwrap_or_default().map(Uuid::from),
+        members: vec![],
+        entries: vec![],
+        event: to_event(&row)?,
+    };
+    playlist.set_entries(to_entries(&row)?);
+    Ok(playlist)
+}
+
+/// A row of the member table as json, with the lyric id in the hyphenated form postgres uses
+#[derive(Deserialize, Serialize)]
+struct Member {
+    lyric: Option<String>,
+    text: Option<String>,
+    note: Option<String>,
+    parts: Option<Vec<usize>>,
+    key: Option<String>,
+}
+
+/// The members of a playlist as json, or null if there are none
+fn to_entries(row: &Row) -> Result<Vec<PlaylistEntry>> {
+    row.try_get::<&str, Option<String>>(column::ENTRIES)?
+    .map(|json| serde_json::from_str::<Vec<Member>>(&json))
+    .transpose()?
+    .unwrap_or_default()
+    .try_map(|member|
+        Ok(PlaylistEntry {
+            lyric: member.lyric.map(|lyric| reexport::uuid::Uuid::parse_str(&lyric)).transpose()?.map(Uuid::from),
+            text: member.text,
+            note: member.note,
+            parts: member.parts.unwrap_or_default(),
+            key: member.key,
+        })
+    )
+}
+
+/// The event of a playlist as json, or null if the playlist is not scheduled
+fn to_event(row: &Row) -> Result<Option<PlaylistEvent>> {
+    row.try_get::<&str, Option<String>>(column::EVENT)?
+    .map(|json| serde_json::from_str::<PlaylistEvent>(&json))
+    .transpose()
+    .map_err(Into::into)
+}
+
+pub fn event_to_text(event: Option<&PlaylistEvent>) -> Result<Option<String>> {
+    event.map(serde_json::to_string).transpose().map_err(Into::into)
+}
+
+pub fn entries_to_text(entries: &[PlaylistEntry]) -> Result<String> {
+    let members = entries.iter().map(|entry| Member {
+        lyric: entry.lyric.as_ref().map(Uuid::hyphenated),
+        text: entry.text.clone(),
+        note: entry.note.clone(),
+        parts: Some(entry.parts.clone()),
+        key: entry.key.clone(),
     })
+    .collect::<Vec<_>>();
+    serde_json::to_string(&members).map_err(Into::into)
 }
 
 pub fn to_summary(row: Row) -> Result<Summary> {
@@ -33,6 +146,24 @@ pub fn to_summary(row: Row) -> Result<Summary> {
     })
 }
 
+pub fn to_search_hit(terms: &[String]) -> impl Fn(Row) -> Result<SearchHit> + Copy + '_ {
+    move |row| Ok(SearchHit {
+        summary: Summary {
+            id: row.try_get::<&str, reexport::uuid::Uuid>(column::ID)?.into(),
+            title: row.try_get::<&str, String>(column::TITLE)?,
+        },
+        line: matching_line(&chords::from_text(row.try_get::<&str, String>(column::PARTS)?).0, terms),
+        rank: row.try_get::<&str, f32>(column::RANK)?,
+    })
+}
+
+pub fn to_tag_count(row: Row) -> Result<TagCount> {
+    Ok(TagCount {
+        tag: row.try_get::<&str, String>(column::TAG)?,
+        count: row.try_get::<&str, i64>(column::COUNT)? as usize,
+    })
+}
+
 pub fn to_inner(uuid: Uuid) -> reexport::uuid::Uuid {
     uuid.inner()
 }
@@ -41,5 +172,23 @@ mod column {
     pub const ID: &str = "id";
     pub const PARTS: &str = "parts";
     pub const TITLE: &str = "title";
-    pub const MEMBERS: &str = "members";
+    pub const ENTRIES: &str = "entries";
+    pub const EVENT: &str = "event";
+    pub const SUB_TITLE: &str = "sub_title";
+    pub const LYRICIST: &str = "lyricist";
+    pub const COMPOSER: &str = "composer";
+    pub const LANGUAGE: &str = "language";
+    pub const YEAR: &str = "year";
+    pub const COPYRIGHT: &str = "copyright";
+    pub const SOURCE: &str = "source";
+    pub const LABELS: &str = "labels";
+    pub const ARRANGEMENT: &str = "arrangement";
+    pub const TRANSLATIONS: &str = "translations";
+    pub const TAGS: &str = "tags";
+    pub const TAG: &str = "tag";
+    pub const COUNT: &str = "count";
+    pub const RANK: &str = "rank";
+    pub const DATA: &str = "data";
+    pub const REV: &str = "rev";
+    pub const MODIFIED: &str = "modified";
 }
@@ -2,72 +2,185 @@ CREATE TABLE IF NOT EXISTS lyric (
     id UUID PRIMARY KEY,
     title VARCHAR UNIQUE NOT NULL,
     sub_title VARCHAR,
-    parts VARCHAR
+    parts VARCHAR,
+    lyricist VARCHAR,
+    composer VARCHAR,
+    language VARCHAR,
+    year INTEGER,
+    copyright VARCHAR,
+    source VARCHAR,
+    search_text VARCHAR,
+    labels VARCHAR[],
+    arrangement VARCHAR[],
+    translations VARCHAR,
+    tags VARCHAR[]
 );
 
+ALTER TABLE lyric
+    ADD COLUMN IF NOT EXISTS lyricist VARCHAR,
+    ADD COLUMN IF NOT EXISTS composer VARCHAR,
+    ADD COLUMN IF NOT EXISTS language VARCHAR,
+    ADD COLUMN IF NOT EXISTS year INTEGER,
+    ADD COLUMN IF NOT EXISTS copyright VARCHAR,
+    ADD COLUMN IF NOT EXISTS source VARCHAR,
+    ADD COLUMN IF NOT EXISTS search_text VARCHAR,
+    ADD COLUMN IF NOT EXISTS labels VARCHAR[],
+    ADD COLUMN IF NOT EXISTS arrangement VARCHAR[],
+    ADD COLUMN IF NOT EXISTS translations VARCHAR,
+    ADD COLUMN IF NOT EXISTS tags VARCHAR[];
+
+CREATE INDEX IF NOT EXISTS lyric_search_text ON lyric USING GIN (to_tsvector('simple', coalesce(search_text, '')));
+
+CREATE INDEX IF NOT EXISTS lyric_tags ON lyric USING GIN (tags);
+
 CREATE TABLE IF NOT EXISTS playlist (
     id UUID PRIMARY KEY,
-    title VARCHAR UNIQUE NOT NULL
+    title VARCHAR UNIQUE NOT NULL,
+    event_start TIMESTAMP,
+    location VARCHAR,
+    leader VARCHAR
 );
 
+ALTER TABLE playlist
+    ADD COLUMN IF NOT EXISTS event_start TIMESTAMP,
+    ADD COLUMN IF NOT EXISTS location VARCHAR,
+    ADD COLUMN IF NOT EXISTS leader VARCHAR;
+
+CREATE INDEX IF NOT EXISTS playlist_event_start ON playlist (event_start);
+
 CREATE TABLE IF NOT EXISTS member (
     id SERIAL PRIMARY KEY,
-    lyric_id UUID NOT NULL REFERENCES lyric ON DELETE CASCADE,
+    lyric_id UUID REFERENCES lyric ON DELETE CASCADE,
     playlist_id UUID NOT NULL REFERENCES playlist ON DELETE CASCADE,
-    ordering INTEGER NOT NULL
+    ordering INTEGER NOT NULL,
+    text VARCHAR,
+    note VARCHAR,
+    parts INTEGER[],
+    key VARCHAR
 );
 
+ALTER TABLE member
+    ALTER COLUMN lyric_id DROP NOT NULL,
+    ADD COLUMN IF NOT EXISTS text VARCHAR,
+    ADD COLUMN IF NOT EXISTS note VARCHAR,
+    ADD COLUMN IF NOT EXISTS parts INTEGER[],
+    ADD COLUMN IF NOT EXISTS key VARCHAR;
+
 CREATE INDEX IF NOT EXISTS member_lyric_id ON member (lyric_id);
 
 CREATE INDEX IF NOT EXISTS member_playlist_id ON member (playlist_id);
 
-CREATE OR REPLACE FUNCTION fn_upsert_lyric(new_id uuid, new_title text, new_parts text)
-RETURNS TABLE (
-    id uuid,
-    title text,
-    parts text
-) AS $$
+CREATE TABLE IF NOT EXISTS trash (
+    id UUID PRIMARY KEY,
+    deleted BIGINT NOT NULL,
+    data VARCHAR NOT NULL
+);
+
+DROP FUNCTION IF EXISTS fn_upsert_lyric(uuid, text, text);
+
+DROP FUNCTION IF EXISTS fn_upsert_lyric(uuid, text, text, text, text, text, text, integer, text, text);
+
+DROP FUNCTION IF EXISTS fn_upsert_lyric(uuid, text, text, text, text, text, text, integer, text, text, text);
+
+DROP FUNCTION IF EXISTS fn_upsert_lyric(uuid, text, text, text, text, text, text, integer, text, text, text, text[], text[]);
+
+DROP FUNCTION IF EXISTS fn_upsert_lyric(uuid, text, text, text, text, text, text, integer, text, text, text, text[], text[], text);
+
+CREATE OR REPLACE FUNCTION fn_upsert_lyric(
+    new_id uuid,
+    new_title text,
+    new_parts text,
+    new_sub_title text,
+    new_lyricist text,
+    new_composer text,
+    new_language text,
+    new_year integer,
+    new_copyright text,
+    new_source text,
+    new_search_text text,
+    new_labels text[],
+    new_arrangement text[],
+    new_translations text,
+    new_tags text[]
+)
+RETURNS SETOF lyric AS $$
 BEGIN
-    INSERT INTO lyric (id, title, parts)
-    VALUES (new_id, new_title, new_parts)
+    INSERT INTO lyric (id, title, parts, sub_title, lyricist, composer, language, year, copyright, source, search_text, labels, arrangement, translations, tags)
+    VALUES (new_id, new_title, new_parts, new_sub_title, new_lyricist, new_composer, new_language, new_year, new_copyright, new_source, new_search_text, new_labels, new_arrangement, new_translations, new_tags)
     ON CONFLICT ON CONSTRAINT lyric_pkey
     DO
-    UPDATE SET title = new_title, parts = new_parts;
-    RETURN QUERY SELECT new_id AS id, new_title AS title, new_parts AS parts;
+    UPDATE SET
+        title = new_title,
+        parts = new_parts,
+        sub_title = new_sub_title,
+        lyricist = new_lyricist,
+        composer = new_composer,
+        language = new_language,
+        year = new_year,
+        copyright = new_copyright,
+        source = new_source,
+        search_text = new_search_text,
+        labels = new_labels,
+        arrangement = new_arrangement,
+        translations = new_translations,
+        tags = new_tags;
+    RETURN QUERY SELECT * FROM lyric WHERE lyric.id = new_id;
 END;
 $$ LANGUAGE plpgsql;
 
-CREATE OR REPLACE FUNCTION fn_upsert_playlist(new_id uuid, new_title text, new_members uuid[]) 
+DROP FUNCTION IF EXISTS fn_upsert_playlist(uuid, text, uuid[]);
+
+DROP FUNCTION IF EXISTS fn_upsert_playlist(uuid, text, text);
+
+CREATE OR REPLACE FUNCTION fn_upsert_playlist(new_id uuid, new_title text, new_entries text, new_event text) 
 RETURNS TABLE (
     id uuid,
     title text,
-    members uuid[]
+    entries text,
+    event text
 ) AS $$
 DECLARE
-    l_id uuid;
+    entry json;
     counter integer := 0;
-    members uuid[];
 BEGIN
     counter := 0;
-    INSERT INTO playlist (id, title)
-    VALUES(new_id, new_title)
+    INSERT INTO playlist (id, title, event_start, location, leader)
+    VALUES(
+        new_id,
+        new_title,
+        (new_event::json->>'start')::timestamp,
+        new_event::json->>'location',
+        new_event::json->>'leader'
+    )
     ON CONFLICT ON CONSTRAINT playlist_pkey
     DO
-    UPDATE SET title = new_title;
+    UPDATE SET
+        title = new_title,
+        event_start = EXCLUDED.event_start,
+        location = EXCLUDED.location,
+        leader = EXCLUDED.leader;
 
     DELETE FROM member WHERE playlist_id = new_id;
     RAISE NOTICE 'Members deleted';
-    FOREACH l_id IN ARRAY new_members
+    FOR entry IN SELECT json_array_elements(new_entries::json)
     LOOP
         counter := counter + 1;
         BEGIN
-            INSERT INTO MEMBER (playlist_id, lyric_id, ordering) VALUES (new_id, l_id, counter);
-            members := members || l_id;
+            INSERT INTO MEMBER (playlist_id, lyric_id, ordering, text, note, parts, key)
+            VALUES (
+                new_id,
+                (entry->>'lyric')::uuid,
+                counter,
+                entry->>'text',
+                entry->>'note',
+                ARRAY(SELECT json_array_elements_text(entry->'parts')::integer),
+                entry->>'key'
+            );
         END;
-        RAISE NOTICE 'Lyric with id % added', l_id;
+        RAISE NOTICE 'Entry % added', counter;
     END LOOP;
 
-    RETURN QUERY SELECT new_id AS id, new_title AS title, members AS members;
+    RETURN QUERY SELECT new_id AS id, new_title AS title, new_entries AS entries, new_event AS event;
 END;
 $$ LANGUAGE plpgsql;
 
@@ -88,3 +201,99 @@ BEGIN
     SELECT lyric_id INTO members FROM member WHERE playlist_id = selected_id ORDER BY ordering;
 END;
 $$ LANGUAGE plpgsql;
+
+CREATE SEQUENCE IF NOT EXISTS change_sequence;
+
+CREATE OR REPLACE FUNCTION fn_notify_change() RETURNS trigger AS $$
+DECLARE
+    changed_id uuid;
+BEGIN
+    IF TG_OP = 'DELETE' THEN
+        changed_id := OLD.id;
+    ELSE
+        changed_id := NEW.id;
+    END IF;
+    PERFORM pg_notify(
+        'lipl_change',
+        concat_ws(
+            ' ',
+            nextval('change_sequence'),
+            (extract(epoch FROM clock_timestamp()) * 1000)::bigint,
+            TG_TABLE_NAME,
+            TG_OP,
+            changed_id
+        )
+    );
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS lyric_change ON lyric;
+
+CREATE TRIGGER lyric_change AFTER INSERT OR UPDATE OR DELETE ON lyric FOR EACH ROW EXECUTE FUNCTION fn_notify_change();
+
+DROP TRIGGER IF EXISTS playlist_change ON playlist;
+
+CREATE TRIGGER playlist_change AFTER INSERT OR UPDATE OR DELETE ON playlist FOR EACH ROW EXECUTE FUNCTION fn_notify_change();
+
+CREATE TABLE IF NOT EXISTS lyric_revision (
+    lyric_id UUID NOT NULL,
+    rev BIGINT NOT NULL,
+    modified BIGINT NOT NULL,
+    title VARCHAR NOT NULL,
+    sub_title VARCHAR,
+    parts VARCHAR,
+    lyricist VARCHAR,
+    composer VARCHAR,
+    language VARCHAR,
+    year INTEGER,
+    copyright VARCHAR,
+    source VARCHAR,
+    labels VARCHAR[],
+    arrangement VARCHAR[],
+    translations VARCHAR,
+    tags VARCHAR[],
+    PRIMARY KEY (lyric_id, rev)
+);
+
+ALTER TABLE lyric_revision
+    ADD COLUMN IF NOT EXISTS labels VARCHAR[],
+    ADD COLUMN IF NOT EXISTS arrangement VARCHAR[],
+    ADD COLUMN IF NOT EXISTS translations VARCHAR,
+    ADD COLUMN IF NOT EXISTS tags VARCHAR[];
+
+CREATE OR REPLACE FUNCTION fn_lyric_revision() RETURNS trigger AS $$
+DECLARE
+    last lyric_revision%ROWTYPE;
+BEGIN
+    SELECT * INTO last FROM lyric_revision WHERE lyric_id = NEW.id ORDER BY rev DESC LIMIT 1;
+    IF FOUND AND (last.title, last.sub_title, last.parts, last.lyricist, last.composer, last.language, last.year, last.copyright, last.source, last.labels, last.arrangement, last.translations, last.tags)
+        IS NOT DISTINCT FROM (NEW.title, NEW.sub_title, NEW.parts, NEW.lyricist, NEW.composer, NEW.language, NEW.year, NEW.copyright, NEW.source, NEW.labels, NEW.arrangement, NEW.translations, NEW.tags) THEN
+        RETURN NULL;
+    END IF;
+    INSERT INTO lyric_revision (lyric_id, rev, modified, title, sub_title, parts, lyricist, composer, language, year, copyright, source, labels, arrangement, translations, tags)
+    VALUES (
+        NEW.id,
+        COALESCE(last.rev, 0) + 1,
+        (extract(epoch FROM clock_timestamp()) * 1000)::bigint,
+        NEW.title,
+        NEW.sub_title,
+        NEW.parts,
+        NEW.lyricist,
+        NEW.composer,
+        NEW.language,
+        NEW.year,
+        NEW.copyright,
+        NEW.source,
+        NEW.labels,
+        NEW.arrangement,
+        NEW.translations,
+        NEW.tags
+    );
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS lyric_revision ON lyric;
+
+CREATE TRIGGER lyric_revision AFTER INSERT OR UPDATE ON lyric FOR EACH ROW EXECUTE FUNCTION fn_lyric_revision();
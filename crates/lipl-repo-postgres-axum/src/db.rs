@@ -1,15 +1,17 @@
+use std::ops::Deref;
+
 use async_trait::async_trait;
 use futures_util::TryFutureExt;
-use lipl_core::{Error, LiplRepo, Lyric, Result, Summary, Uuid, Playlist, error::PostgresRepoError};
+use lipl_core::{duplicate, search, ChangeStream, DateRange, Error, LiplRepo, ListQuery, Lyric, Merge, Page, Result, Revision, SearchHit, Summary, TagCount, Transaction, TrashItem, Trashed, Uuid, Playlist, error::PostgresRepoError};
 use lipl_util::VecExt;
-use parts::to_text;
+use tokio_postgres::types::ToSql;
 
 use super::convert;
 use crate::PostgresConnectionPool;
 
 fn error_on_count(count: u64, uuid: Uuid) -> Result<()> {
     if count < 1 {
-        Err(Error::NoKey(uuid.to_string()))
+        Err(Error::NotFound(uuid))
     }
     else {
         Ok(())
@@ -19,7 +21,7 @@ fn error_on_count(count: u64, uuid: Uuid) -> Result<()> {
 fn pg_error_to_lipl_core(uuid: Uuid) -> impl Fn(PostgresRepoError) -> lipl_core::Error {
     move |pg_error| {
         if let PostgresRepoError::NoResults = pg_error {
-            Error::NoKey(uuid.to_string())
+            Error::NotFound(uuid)
         }
         else {
             pg_error.into()
@@ -27,6 +29,122 @@ fn pg_error_to_lipl_core(uuid: Uuid) -> impl Fn(PostgresRepoError) -> lipl_core:
     }
 }
 
+/// Moves the lyric with its playlist positions or the playlist to the trash. Nothing is trashed if it does not exist.
+async fn trash(transaction: &tokio_postgres::Transaction<'_>, item: &Transaction) -> Result<()> {
+    let trashed = match item {
+        Transaction::LyricDelete(uuid) => {
+            let lyric = 
+                transaction
+                .query_opt(lyric::ITEM_FOR_UPDATE, &[&uuid.inner()])
+                .await
+                .map_err(PostgresRepoError::from)?
+                .map(convert::to_lyric)
+                .transpose()?;
+            match lyric {
+                Some(lyric) => {
+                    let rows = transaction.query(playlist::LIST_FOR_LYRIC_FOR_UPDATE, &[&uuid.inner()]).await.map_err(PostgresRepoError::from)?;
+                    let playlists = convert::to_list(convert::to_playlist)(rows)?;
+                    Some(Trashed::lyric(lyric, playlists.iter()))
+                },
+                None => None,
+            }
+        },
+        Transaction::PlaylistDelete(uuid) =>
+            transaction
+            .query_opt(playlist::ITEM_FOR_UPDATE, &[&uuid.inner()])
+            .await
+            .map_err(PostgresRepoError::from)?
+            .map(convert::to_playlist)
+            .transpose()?
+            .map(Trashed::playlist),
+        _ => None,
+    };
+    if let Some(trashed) = trashed {
+        let data = serde_json::to_string(&trashed).map_err(|error| Error::Json(Box::new(error)))?;
+        let statement = transaction.prepare_typed(trash::UPSERT, trash::UPSERT_TYPES).await.map_err(PostgresRepoError::from)?;
+        transaction.execute(&statement, &[&trashed.id().inner(), &(trashed.deleted as i64), &data]).await.map_err(PostgresRepoError::from)?;
+    }
+    Ok(())
+}
+
+/// Rejects the playlist if one of the members is not a lyric
+type Params = Vec<Box<dyn ToSql + Sync + Send>>;
+
+/// The parameters of lyric::UPSERT, in the order of the statement
+fn lyric_params(lyric: &Lyric) -> Result<Params> {
+    Ok(
+        vec![
+            Box::new(lyric.id.inner()),
+            Box::new(lyric.title.clone()),
+            Box::new(lyric.text()),
+            Box::new(lyric.metadata.sub_title.clone()),
+            Box::new(lyric.metadata.lyricist.clone()),
+            Box::new(lyric.metadata.composer.clone()),
+            Box::new(lyric.metadata.language.clone()),
+            Box::new(lyric.metadata.year.map(i32::from)),
+            Box::new(lyric.metadata.copyright.clone()),
+            Box::new(lyric.metadata.source.clone()),
+            Box::new(search::to_search_text(lyric)),
+            Box::new(lyric.labels.clone()),
+            Box::new(lyric.arrangement.clone()),
+            Box::new(convert::translations_to_text(&lyric.translations)?),
+            Box::new(lyric.tags.iter().cloned().collect::<Vec<_>>()),
+        ]
+    )
+}
+
+fn as_params(params: &Params) -> Vec<&(dyn ToSql + Sync)> {
+    params.iter().map(|param| param.as_ref() as &(dyn ToSql + Sync)).collect()
+}
+
+async fn check_members<C: tokio_postgres::GenericClient>(client: &C, playlist: &Playlist) -> Result<()> {
+    let rows = client.query(lyric::IDS, &[&playlist.members.clone().map(convert::to_inner).as_slice()]).await.map_err(PostgresRepoError::from)?;
+    let lyric_ids = convert::to_list(convert::to_id)(rows)?;
+    lipl_core::check_members(playlist, |member| lyric_ids.contains(member))
+}
+
+/// Executes the statement for a single transaction. Deleted items are moved to the trash first.
+pub(crate) async fn execute_transaction(transaction: &tokio_postgres::Transaction<'_>, item: Transaction) -> Result<()> {
+    trash(transaction, &item).await?;
+    match item {
+        Transaction::LyricDelete(uuid) => {
+            let statement = transaction.prepare_typed(lyric::DELETE, lyric::DELETE_TYPES).await.map_err(PostgresRepoError::from)?;
+            let count = transaction.execute(&statement, &[&uuid.inner()]).await.map_err(PostgresRepoError::from)?;
+            error_on_count(count, uuid)?;
+        },
+        Transaction::LyricUpsert(lyric) => {
+            let params = lyric_params(&lyric)?;
+            let statement = transaction.prepare_typed(lyric::UPSERT, lyric::UPSERT_TYPES).await.map_err(PostgresRepoError::from)?;
+            transaction.execute(&statement, &as_params(&params))
+            .await
+            .map_err(PostgresRepoError::from)?;
+        },
+        Transaction::PlaylistDelete(uuid) => {
+            let statement = transaction.prepare_typed(playlist::DELETE, playlist::DELETE_TYPES).await.map_err(PostgresRepoError::from)?;
+            let count = transaction.execute(&statement, &[&uuid.inner()]).await.map_err(PostgresRepoError::from)?;
+            error_on_count(count, uuid)?;
+        },
+        Transaction::PlaylistUpsert(playlist) => {
+            check_members(transaction, &playlist).await?;
+            let entries = convert::entries_to_text(&playlist.entries())?;
+            let event = convert::event_to_text(playlist.event.as_ref())?;
+            let statement = transaction.prepare_typed(playlist::UPSERT, playlist::UPSERT_TYPES).await.map_err(PostgresRepoError::from)?;
+            transaction.execute(
+                &statement,
+                &[
+                    &playlist.id.inner(),
+                    &playlist.title.clone(),
+                    &entries,
+                    &event,
+                ],
+            )
+            .await
+            .map_err(PostgresRepoError::from)?;
+        },
+    }
+    Ok(())
+}
+
 #[async_trait]
 impl LiplRepo for PostgresConnectionPool {
     async fn get_lyric_summaries(&self) -> Result<Vec<Summary>> {
@@ -41,6 +159,12 @@ impl LiplRepo for PostgresConnectionPool {
         .await
     }
 
+    async fn get_lyric_summaries_page(&self, query: ListQuery) -> Result<Page<Summary>> {
+        self.summaries_page(lyric::TABLE, query)
+        .err_into()
+        .await
+    }
+
     async fn get_lyric(&self, uuid: Uuid) -> Result<Lyric> {
         self.query_one(lyric::ITEM, lyric::ITEM_TYPES, convert::to_lyric, &[&uuid.inner()])
             .map_err(pg_error_to_lipl_core(uuid))
@@ -48,19 +172,76 @@ impl LiplRepo for PostgresConnectionPool {
     }
 
     async fn upsert_lyric(&self, lyric: Lyric) -> Result<Lyric> {
+        let params = lyric_params(&lyric)?;
         self.query_one(
             lyric::UPSERT,
             lyric::UPSERT_TYPES,
             convert::to_lyric,
-            &[&Uuid::default().inner(), &lyric.title.clone(), &to_text(&lyric.parts)],
+            &as_params(&params),
         )
         .err_into()
         .await
     }
 
     async fn delete_lyric(&self, uuid: Uuid) -> Result<()> {
-        let count = self.execute(lyric::DELETE, lyric::DELETE_TYPES, &[&uuid.inner()]).await?;
-        error_on_count(count, uuid)
+        self.apply_batch(vec![Transaction::LyricDelete(uuid)]).await
+    }
+
+    async fn search_lyrics(&self, query: &str) -> Result<Vec<SearchHit>> {
+        let terms = search::terms(query);
+        if terms.is_empty() {
+            return Ok(vec![]);
+        }
+        self.query(lyric::SEARCH, lyric::SEARCH_TYPES, convert::to_search_hit(&terms), &[&terms.join(" ")])
+        .err_into()
+        .await
+    }
+
+    async fn get_tags(&self) -> Result<Vec<TagCount>> {
+        self.query(lyric::TAGS, lyric::TAGS_TYPES, convert::to_tag_count, &[])
+        .err_into()
+        .await
+    }
+
+    async fn get_lyric_history(&self, uuid: Uuid) -> Result<Vec<Revision>> {
+        let history = self.query(revision::LIST, revision::LIST_TYPES, convert::to_revision, &[&uuid.inner()]).await?;
+        if history.is_empty() {
+            Err(Error::NotFound(uuid))
+        }
+        else {
+            Ok(history)
+        }
+    }
+
+    async fn get_lyric_revision(&self, uuid: Uuid, rev: u64) -> Result<Revision> {
+        self.query_one(revision::ITEM, revision::ITEM_TYPES, convert::to_revision, &[&uuid.inner(), &(rev as i64)])
+            .map_err(pg_error_to_lipl_core(uuid))
+            .await
+    }
+
+    async fn revert_lyric(&self, uuid: Uuid, rev: u64) -> Result<Lyric> {
+        let revision = self.get_lyric_revision(uuid, rev).await?;
+        self.upsert_lyric(revision.lyric).await
+    }
+
+    async fn merge_lyrics(&self, merge: Merge) -> Result<Lyric> {
+        let mut connection = self.inner.get().await.map_err(PostgresRepoError::from)?;
+        let transaction = connection.transaction().await.map_err(PostgresRepoError::from)?;
+        let lyric =
+            transaction
+            .query_opt(lyric::ITEM_FOR_UPDATE, &[&merge.keep.inner()])
+            .await
+            .map_err(PostgresRepoError::from)?
+            .map(convert::to_lyric)
+            .transpose()?
+            .ok_or(Error::NotFound(merge.keep))?;
+        let rows = transaction.query(playlist::LIST_FOR_LYRICS_FOR_UPDATE, &[&merge.duplicates.clone().map(convert::to_inner).as_slice()]).await.map_err(PostgresRepoError::from)?;
+        let playlists = convert::to_list(convert::to_playlist)(rows)?;
+        for item in duplicate::merge_transactions(&merge, playlists) {
+            execute_transaction(&transaction, item).await?;
+        }
+        transaction.commit().await.map_err(PostgresRepoError::from)?;
+        Ok(lyric)
     }
 
     async fn get_playlist_summaries(&self) -> Result<Vec<Summary>> {
@@ -75,70 +256,311 @@ impl LiplRepo for PostgresConnectionPool {
         .await
     }
 
+    async fn get_playlist_summaries_page(&self, query: ListQuery) -> Result<Page<Summary>> {
+        self.summaries_page(playlist::TABLE, query)
+        .err_into()
+        .await
+    }
+
     async fn get_playlist(&self, uuid: Uuid) -> Result<Playlist> {
         self.query_one(playlist::ITEM, playlist::ITEM_TYPES, convert::to_playlist, &[&uuid.inner()])
             .map_err(pg_error_to_lipl_core(uuid))
             .await
     }
 
+    async fn get_scheduled_playlists(&self, range: DateRange) -> Result<Vec<Playlist>> {
+        self.query(
+            playlist::SCHEDULED,
+            playlist::SCHEDULED_TYPES,
+            convert::to_playlist,
+            &[&range.from.map(|date| date.to_string()), &range.to.map(|date| date.to_string())],
+        )
+        .err_into()
+        .await
+    }
+
     async fn delete_playlist(&self, uuid: Uuid) -> Result<()> {
-        let count = self.execute(playlist::DELETE, playlist::DELETE_TYPES, &[&uuid.inner()]).await?;
-        error_on_count(count, uuid)
+        self.apply_batch(vec![Transaction::PlaylistDelete(uuid)]).await
     }
 
     async fn upsert_playlist(&self, playlist: Playlist) -> Result<Playlist> {
+        check_members(self.inner.get().await.map_err(PostgresRepoError::from)?.deref(), &playlist).await?;
+        let entries = convert::entries_to_text(&playlist.entries())?;
+        let event = convert::event_to_text(playlist.event.as_ref())?;
         self.query_one(
             playlist::UPSERT,
             playlist::UPSERT_TYPES,
             convert::to_playlist,
             &[
-                &Uuid::default().inner(),
+                &playlist.id.inner(),
                 &playlist.title.clone(),
-                &playlist.members.map(convert::to_inner).as_slice()
+                &entries,
+                &event,
             ])
             .err_into()
             .await
     }
 
+    async fn upsert_lyric_if_match(&self, lyric: Lyric, etag: String) -> Result<Lyric> {
+        let params = lyric_params(&lyric)?;
+        self.query_one_if_match(
+            lyric.id,
+            &etag,
+            lyric::ITEM_FOR_UPDATE,
+            convert::to_lyric,
+            lyric::UPSERT,
+            lyric::UPSERT_TYPES,
+            &as_params(&params),
+        )
+        .await
+    }
+
+    async fn delete_lyric_if_match(&self, uuid: Uuid, etag: String) -> Result<()> {
+        self.execute_if_match(
+            uuid,
+            &etag,
+            lyric::ITEM_FOR_UPDATE,
+            convert::to_lyric,
+            Transaction::LyricDelete(uuid),
+        )
+        .await
+    }
+
+    async fn upsert_playlist_if_match(&self, playlist: Playlist, etag: String) -> Result<Playlist> {
+        check_members(self.inner.get().await.map_err(PostgresRepoError::from)?.deref(), &playlist).await?;
+        let entries = convert::entries_to_text(&playlist.entries())?;
+        let event = convert::event_to_text(playlist.event.as_ref())?;
+        self.query_one_if_match(
+            playlist.id,
+            &etag,
+            playlist::ITEM_FOR_UPDATE,
+            convert::to_playlist,
+            playlist::UPSERT,
+            playlist::UPSERT_TYPES,
+            &[
+                &playlist.id.inner(),
+                &playlist.title.clone(),
+                &entries,
+                &event,
+            ],
+        )
+        .await
+    }
+
+    async fn delete_playlist_if_match(&self, uuid: Uuid, etag: String) -> Result<()> {
+        self.execute_if_match(
+            uuid,
+            &etag,
+            playlist::ITEM_FOR_UPDATE,
+            convert::to_playlist,
+            Transaction::PlaylistDelete(uuid),
+        )
+        .await
+    }
+
+    async fn apply_batch(&self, batch: Vec<Transaction>) -> Result<()> {
+        let mut connection = self.inner.get().await.map_err(PostgresRepoError::from)?;
+        let transaction = connection.transaction().await.map_err(PostgresRepoError::from)?;
+        for item in batch {
+            execute_transaction(&transaction, item).await?;
+        }
+        transaction.commit().await.map_err(PostgresRepoError::from)?;
+        Ok(())
+    }
+
+    async fn watch(&self) -> Result<ChangeStream> {
+        lipl_core::change::listen_changes(self.clone(), &self.connection).await
+    }
+
+    async fn list_trash(&self) -> Result<Vec<Trashed>> {
+        let connection = self.inner.get().await.map_err(PostgresRepoError::from)?;
+        let rows = connection.query(trash::LIST, &[]).await.map_err(PostgresRepoError::from)?;
+        rows.into_iter().map(convert::to_trashed).collect()
+    }
+
+    async fn restore(&self, uuid: Uuid) -> Result<()> {
+        let mut connection = self.inner.get().await.map_err(PostgresRepoError::from)?;
+        let transaction = connection.transaction().await.map_err(PostgresRepoError::from)?;
+        let trashed = 
+            transaction
+            .query_opt(trash::DELETE, &[&uuid.inner()])
+            .await
+            .map_err(PostgresRepoError::from)?
+            .map(convert::to_trashed)
+            .transpose()?
+            .ok_or(Error::NotFound(uuid))?;
+        let live = match &trashed.item {
+            TrashItem::Lyric(_) => lyric::ITEM_FOR_UPDATE,
+            TrashItem::Playlist(_) => playlist::ITEM_FOR_UPDATE,
+        };
+        if transaction.query_opt(live, &[&uuid.inner()]).await.map_err(PostgresRepoError::from)?.is_some() {
+            return Err(Error::Occupied);
+        }
+        let items = match &trashed.item {
+            TrashItem::Lyric(lyric) => {
+                let mut items = vec![Transaction::LyricUpsert(lyric.clone())];
+                for playlist_id in trashed.playlist_ids() {
+                    let playlist = 
+                        transaction
+                        .query_opt(playlist::ITEM_FOR_UPDATE, &[&playlist_id.inner()])
+                        .await
+                        .map_err(PostgresRepoError::from)?
+                        .map(convert::to_playlist)
+                        .transpose()?;
+                    if let Some(mut playlist) = playlist {
+                        trashed.restore_positions(&mut playlist);
+                        items.push(Transaction::PlaylistUpsert(playlist));
+                    }
+                }
+                items
+            },
+            TrashItem::Playlist(playlist) => {
+                let rows = transaction.query(lyric::IDS, &[&playlist.members.clone().map(convert::to_inner).as_slice()]).await.map_err(PostgresRepoError::from)?;
+                let lyric_ids = convert::to_list(convert::to_id)(rows)?;
+                let mut playlist = playlist.clone();
+                playlist.retain_members(|member| lyric_ids.contains(member));
+                vec![Transaction::PlaylistUpsert(playlist)]
+            },
+        };
+        for item in items {
+            execute_transaction(&transaction, item).await?;
+        }
+        transaction.commit().await.map_err(PostgresRepoError::from)?;
+        Ok(())
+    }
+
+    async fn purge(&self, uuid: Uuid) -> Result<()> {
+        let count = self.execute(trash::PURGE, trash::PURGE_TYPES, &[&uuid.inner()]).await?;
+        error_on_count(count, uuid)
+    }
+
     async fn stop(&self) -> Result<()> {
         Ok(())
     }
 }
 
+pub(crate) mod page {
+    use tokio_postgres::types::Type;
+
+    pub const COUNT_TYPES: &[Type] = &[Type::VARCHAR, Type::VARCHAR_ARRAY];
+    pub const SELECT_TYPES: &[Type] = &[Type::VARCHAR, Type::VARCHAR_ARRAY, Type::INT8, Type::INT8];
+
+    /// Title like $1 and, for lyrics, all the tags in $2. Playlists have no tags.
+    fn filter(table: &str) -> &'static str {
+        if table == super::lyric::TABLE {
+            "lower(title) LIKE $1 AND COALESCE(tags, '{}') @> $2"
+        }
+        else {
+            "lower(title) LIKE $1"
+        }
+    }
+
+    pub fn count(table: &str) -> String {
+        format!("SELECT COUNT(*) AS total FROM {table} WHERE {};", filter(table))
+    }
+
+    pub fn select(table: &str, order_by: &str) -> String {
+        format!("SELECT id, title FROM {table} WHERE {} ORDER BY {order_by} LIMIT $3 OFFSET $4;", filter(table))
+    }
+}
+
 mod lyric {
     use tokio_postgres::types::Type;
 
-    pub const LIST: &str = "SELECT id, title FROM lyric ORDER BY title;";
+    pub const TABLE: &str = "lyric";
+
+    pub const LIST: &str = "SELECT id, title FROM lyric ORDER BY title COLLATE \"C\", id;";
     pub const LIST_TYPES: &[Type] = &[];
 
-    pub const LIST_FULL: &str = "SELECT id, title, parts FROM lyric ORDER BY title;";
+    pub const LIST_FULL: &str = "SELECT * FROM lyric ORDER BY title COLLATE \"C\", id;";
     pub const LIST_FULL_TYPES: &[Type] = &[];
 
     pub const ITEM: &str = "SELECT * FROM lyric WHERE id = $1;";
     pub const ITEM_TYPES: &[Type] = &[Type::UUID];
 
+    pub const ITEM_FOR_UPDATE: &str = "SELECT * FROM lyric WHERE id = $1 FOR UPDATE;";
+
     pub const DELETE: &str = "DELETE FROM lyric WHERE id = $1;";
     pub const DELETE_TYPES: &[Type] = &[Type::UUID];
 
-    pub const UPSERT: &str = "SELECT * from fn_upsert_lyric($1, $2, $3)";
-    pub const UPSERT_TYPES: &[Type] = &[Type::UUID, Type::VARCHAR, Type::VARCHAR];
+    pub const UPSERT: &str = "SELECT * from fn_upsert_lyric($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15)";
+    pub const UPSERT_TYPES: &[Type] = &[
+        Type::UUID,
+        Type::VARCHAR,
+        Type::VARCHAR,
+        Type::VARCHAR,
+        Type::VARCHAR,
+        Type::VARCHAR,
+        Type::VARCHAR,
+        Type::INT4,
+        Type::VARCHAR,
+        Type::VARCHAR,
+        Type::VARCHAR,
+        Type::VARCHAR_ARRAY,
+        Type::VARCHAR_ARRAY,
+        Type::VARCHAR,
+        Type::VARCHAR_ARRAY,
+    ];
+
+    pub const SEARCH: &str = "SELECT id, title, parts, ts_rank(to_tsvector('simple', coalesce(search_text, '')), query) AS rank FROM lyric, plainto_tsquery('simple', $1) query WHERE to_tsvector('simple', coalesce(search_text, '')) @@ query ORDER BY rank DESC, title;";
+    pub const SEARCH_TYPES: &[Type] = &[Type::VARCHAR];
+
+    pub const IDS: &str = "SELECT id FROM lyric WHERE id = ANY($1);";
+
+    pub const TAGS: &str = "SELECT tag, COUNT(*) AS count FROM lyric, unnest(tags) AS tag GROUP BY tag ORDER BY tag COLLATE \"C\";";
+    pub const TAGS_TYPES: &[Type] = &[];
 }
 
 mod playlist {
     use tokio_postgres::types::Type;
 
-    pub const LIST: &str = "SELECT id, title FROM playlist ORDER BY title;";
+    pub const TABLE: &str = "playlist";
+
+    pub const LIST: &str = "SELECT id, title FROM playlist ORDER BY title COLLATE \"C\", id;";
     pub const LIST_TYPES: &[Type] = &[];
 
-    pub const LIST_FULL: &str = "SELECT playlist.id AS id, title, ARRAY_AGG(lyric_id ORDER BY ordering) members FROM playlist INNER JOIN member ON playlist.id = playlist_id GROUP BY playlist.id ORDER BY playlist.title;";
+    pub const LIST_FULL: &str = "SELECT id, title, (SELECT json_agg(json_build_object('lyric', lyric_id, 'text', text, 'note', note, 'parts', parts, 'key', key) ORDER BY ordering) FROM member WHERE playlist_id = playlist.id)::text AS entries, CASE WHEN event_start IS NULL THEN NULL ELSE json_build_object('start', event_start, 'location', location, 'leader', leader) END::text AS event FROM playlist ORDER BY title COLLATE \"C\", id;";
     pub const LIST_FULL_TYPES: &[Type] = &[];
 
-    pub const ITEM: &str = "SELECT playlist.id AS id, title, ARRAY_AGG(lyric_id ORDER BY ordering) members FROM playlist INNER JOIN member ON playlist.id = playlist_id GROUP BY playlist.id HAVING playlist.id = $1";
+    pub const SCHEDULED: &str = "SELECT id, title, (SELECT json_agg(json_build_object('lyric', lyric_id, 'text', text, 'note', note, 'parts', parts, 'key', key) ORDER BY ordering) FROM member WHERE playlist_id = playlist.id)::text AS entries, CASE WHEN event_start IS NULL THEN NULL ELSE json_build_object('start', event_start, 'location', location, 'leader', leader) END::text AS event FROM playlist WHERE event_start IS NOT NULL AND ($1::text IS NULL OR event_start::date >= $1::date) AND ($2::text IS NULL OR event_start::date <= $2::date) ORDER BY event_start, title COLLATE \"C\", id;";
+    pub const SCHEDULED_TYPES: &[Type] = &[Type::VARCHAR, Type::VARCHAR];
+
+    pub const ITEM: &str = "SELECT id, title, (SELECT json_agg(json_build_object('lyric', lyric_id, 'text', text, 'note', note, 'parts', parts, 'key', key) ORDER BY ordering) FROM member WHERE playlist_id = playlist.id)::text AS entries, CASE WHEN event_start IS NULL THEN NULL ELSE json_build_object('start', event_start, 'location', location, 'leader', leader) END::text AS event FROM playlist WHERE id = $1;";
     pub const ITEM_TYPES: &[Type] = &[Type::UUID];
 
+    pub const ITEM_FOR_UPDATE: &str = "SELECT id, title, (SELECT json_agg(json_build_object('lyric', lyric_id, 'text', text, 'note', note, 'parts', parts, 'key', key) ORDER BY ordering) FROM member WHERE playlist_id = playlist.id)::text AS entries, CASE WHEN event_start IS NULL THEN NULL ELSE json_build_object('start', event_start, 'location', location, 'leader', leader) END::text AS event FROM playlist WHERE id = $1 FOR UPDATE;";
+
+    pub const LIST_FOR_LYRIC_FOR_UPDATE: &str = "SELECT id, title, (SELECT json_agg(json_build_object('lyric', lyric_id, 'text', text, 'note', note, 'parts', parts, 'key', key) ORDER BY ordering) FROM member WHERE playlist_id = playlist.id)::text AS entries, CASE WHEN event_start IS NULL THEN NULL ELSE json_build_object('start', event_start, 'location', location, 'leader', leader) END::text AS event FROM playlist WHERE id IN (SELECT playlist_id FROM member WHERE lyric_id = $1) FOR UPDATE;";
+
+    pub const LIST_FOR_LYRICS_FOR_UPDATE: &str = "SELECT id, title, (SELECT json_agg(json_build_object('lyric', lyric_id, 'text', text, 'note', note, 'parts', parts, 'key', key) ORDER BY ordering) FROM member WHERE playlist_id = playlist.id)::text AS entries, CASE WHEN event_start IS NULL THEN NULL ELSE json_build_object('start', event_start, 'location', location, 'leader', leader) END::text AS event FROM playlist WHERE id IN (SELECT playlist_id FROM member WHERE lyric_id = ANY($1)) FOR UPDATE;";
+
     pub const DELETE: &str = "DELETE FROM playlist WHERE id = $1;";
     pub const DELETE_TYPES: &[Type] = &[Type::UUID];
 
-    pub const UPSERT: &str = "SELECT * from fn_upsert_playlist($1, $2, $3);";
-    pub const UPSERT_TYPES: &[Type] = &[Type::UUID, Type::VARCHAR, Type::UUID_ARRAY];
+    pub const UPSERT: &str = "SELECT * from fn_upsert_playlist($1, $2, $3, $4);";
+    pub const UPSERT_TYPES: &[Type] = &[Type::UUID, Type::VARCHAR, Type::VARCHAR, Type::VARCHAR];
+}
+
+mod trash {
+    use tokio_postgres::types::Type;
+
+    pub const LIST: &str = "SELECT data FROM trash ORDER BY deleted DESC, id;";
+
+    pub const UPSERT: &str = "INSERT INTO trash (id, deleted, data) VALUES ($1, $2, $3) ON CONFLICT (id) DO UPDATE SET deleted = $2, data = $3;";
+    pub const UPSERT_TYPES: &[Type] = &[Type::UUID, Type::INT8, Type::VARCHAR];
+
+    pub const DELETE: &str = "DELETE FROM trash WHERE id = $1 RETURNING data;";
+
+    pub const PURGE: &str = "WITH history AS (DELETE FROM lyric_revision WHERE lyric_id = $1) DELETE FROM trash WHERE id = $1;";
+    pub const PURGE_TYPES: &[Type] = &[Type::UUID];
+}
+
+mod revision {
+    use tokio_postgres::types::Type;
+
+    pub const LIST: &str = "SELECT rev, modified, lyric_id AS id, title, parts, sub_title, lyricist, composer, language, year, copyright, source, labels, arrangement, translations, tags FROM lyric_revision WHERE lyric_id = $1 ORDER BY rev;";
+    pub const LIST_TYPES: &[Type] = &[Type::UUID];
+
+    pub const ITEM: &str = "SELECT rev, modified, lyric_id AS id, title, parts, sub_title, lyricist, composer, language, year, copyright, source, labels, arrangement, translations, tags FROM lyric_revision WHERE lyric_id = $1 AND rev = $2;";
+    pub const ITEM_TYPES: &[Type] = &[Type::UUID, Type::INT8];
 }
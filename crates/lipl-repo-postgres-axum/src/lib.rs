@@ -1,6 +1,6 @@
 use bb8_postgres::{PostgresConnectionManager, bb8::{Pool}};
 use futures_util::{Future, TryFutureExt};
-use lipl_core::{LiplRepo, error::PostgresRepoError};
+use lipl_core::{check_etag, LiplRepo, ListQuery, Page, Summary, Transaction, Uuid, error::PostgresRepoError};
 use serde::Serialize;
 use tokio_postgres::{NoTls, types::{Type, ToSql}, Row};
 
@@ -15,12 +15,14 @@ pub const CREATE_DB: &str = include_str!("create_db.sql");
 #[derive(Clone)]
 pub struct PostgresConnectionPool {
     inner: ConnectionPool,
+    connection: String,
 }
 
-impl From<ConnectionPool> for PostgresConnectionPool {
-    fn from(pool: ConnectionPool) -> Self {
+impl From<(ConnectionPool, &str)> for PostgresConnectionPool {
+    fn from((pool, connection): (ConnectionPool, &str)) -> Self {
         Self {
             inner: pool,
+            connection: connection.to_owned(),
         }
     }
 }
@@ -83,13 +85,89 @@ impl PostgresConnectionPool {
             }
         }
     }
+
+    async fn lock_and_check<'a, T>(
+        transaction: &tokio_postgres::Transaction<'a>,
+        id: Uuid,
+        etag: &str,
+        select: &'static str,
+        convert: fn(Row) -> Result<T>,
+    ) -> lipl_core::Result<()>
+    where T: Serialize,
+    {
+        let current = 
+            transaction
+            .query_opt(select, &[&id.inner()])
+            .await
+            .map_err(PostgresRepoError::from)?
+            .map(convert)
+            .transpose()?;
+        check_etag(id, etag, current.as_ref())
+    }
+
+    async fn summaries_page(&self, table: &str, query: ListQuery) -> Result<Page<Summary>> {
+        let connection = self.inner.get().await?;
+        let pattern = query.like_pattern();
+        let statement = connection.prepare_typed(&db::page::count(table), db::page::COUNT_TYPES).await?;
+        let total = connection.query_one(&statement, &[&pattern, &query.tags]).await?.try_get::<&str, i64>("total")?;
+        let statement = connection.prepare_typed(&db::page::select(table, query.order_by()), db::page::SELECT_TYPES).await?;
+        let limit = query.limit.map(|limit| limit as i64);
+        let rows = connection.query(&statement, &[&pattern, &query.tags, &limit, &(query.offset as i64)]).await?;
+        Ok(
+            Page {
+                items: convert::to_list(convert::to_summary)(rows)?,
+                total: total as usize,
+            }
+        )
+    }
+
+    async fn execute_if_match<T>(
+        &self,
+        id: Uuid,
+        etag: &str,
+        select: &'static str,
+        convert: fn(Row) -> Result<T>,
+        item: Transaction,
+    ) -> lipl_core::Result<()>
+    where T: Serialize,
+    {
+        let mut connection = self.inner.get().await.map_err(PostgresRepoError::from)?;
+        let transaction = connection.transaction().await.map_err(PostgresRepoError::from)?;
+        Self::lock_and_check(&transaction, id, etag, select, convert).await?;
+        db::execute_transaction(&transaction, item).await?;
+        transaction.commit().await.map_err(PostgresRepoError::from)?;
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn query_one_if_match<T>(
+        &self,
+        id: Uuid,
+        etag: &str,
+        select: &'static str,
+        convert: fn(Row) -> Result<T>,
+        sql: &'static str,
+        types: &[Type],
+        params: &[&(dyn ToSql + Sync)],
+    ) -> lipl_core::Result<T>
+    where T: Serialize,
+    {
+        let mut connection = self.inner.get().await.map_err(PostgresRepoError::from)?;
+        let transaction = connection.transaction().await.map_err(PostgresRepoError::from)?;
+        Self::lock_and_check(&transaction, id, etag, select, convert).await?;
+        let statement = transaction.prepare_typed(sql, types).await.map_err(PostgresRepoError::from)?;
+        let row = transaction.query_opt(&statement, params).await.map_err(PostgresRepoError::from)?;
+        let result = row.ok_or(PostgresRepoError::NoResults).and_then(convert)?;
+        transaction.commit().await.map_err(PostgresRepoError::from)?;
+        Ok(result)
+    }
 }
 
 pub async fn connection_pool(connection: &str) -> Result<PostgresConnectionPool> {
     let manager = PostgresConnectionManager::new_from_stringlike(connection, NoTls)?;
     let pool = Pool::builder().build(manager).await?;
     
-    let postgres_connection_pool = PostgresConnectionPool::from(pool);
+    let postgres_connection_pool = PostgresConnectionPool::from((pool, connection));
     tracing::info!("About to execute database creation script");
     postgres_connection_pool.batch_execute(CREATE_DB).await?;
     tracing::info!("Finished executing database creation script");
@@ -0,0 +1,14 @@
+use std::sync::Arc;
+
+/// Needs a postgres server with the database in POSTGRES_HOST, POSTGRES_DB, POSTGRES_USER and POSTGRES_PASSWORD. Run with cargo test -- --ignored
+#[tokio::test]
+#[ignore]
+async fn conformance() {
+    let host = std::env::var("POSTGRES_HOST").unwrap();
+    let db = std::env::var("POSTGRES_DB").unwrap();
+    let user = std::env::var("POSTGRES_USER").unwrap();
+    let password = std::env::var("POSTGRES_PASSWORD").unwrap();
+    let repo = lipl_repo_postgres_axum::connection_pool(&format!("host={host} user={user} password={password} dbname={db}")).await.unwrap();
+
+    lipl_repo_test::check_repo(Arc::new(repo)).await.unwrap();
+}
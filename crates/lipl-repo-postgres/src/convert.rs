@@ -1,6 +1,6 @@
-use lipl_core::{Uuid, Lyric, Playlist, Summary};
-use parts::to_parts;
+use lipl_core::{chords, search::matching_line, Chords, Uuid, Lyric, LyricMetadata, Playlist, PlaylistEntry, PlaylistEvent, Revision, SearchHit, Summary, TagCount, Tags, Translations, Trashed};
 use bb8_postgres::tokio_postgres::Row;
+use serde::{Deserialize, Serialize};
 
 use crate::Result;
 
@@ -17,22 +17,122 @@ pub fn get_title(row: &Row) -> Result<String> {
     .map(std::convert::identity)
 }
 
-pub fn get_parts(row: &Row) -> Result<Vec<Vec<String>>> {
+pub fn get_parts(row: &Row) -> Result<(Vec<Vec<String>>, Chords)> {
     row.try_get::<&str, String>("parts")
     .map_err(Into::into)
-    .map(to_parts)
+    .map(chords::from_text)
 }
 
-pub fn get_members(row: &Row) -> Result<Vec<Uuid>> {
-    row.try_get::<&str, Vec<uuid::Uuid>>("members")
+pub fn get_labels(row: &Row) -> Result<Vec<Option<String>>> {
+    row.try_get::<&str, Option<Vec<Option<String>>>>("labels")
     .map_err(Into::into)
-    .map(convert_vec(Uuid::from))
+    .map(Option::unwrap_or_default)
 }
 
-pub fn convert_vec<F, T, U>(f: F) -> impl Fn(Vec<T>) -> Vec<U>
-where F: Fn(T) -> U + Copy
-{
-    move |v| v.into_iter().map(f).collect()
+pub fn get_arrangement(row: &Row) -> Result<Vec<String>> {
+    row.try_get::<&str, Option<Vec<String>>>("arrangement")
+    .map_err(Into::into)
+    .map(Option::unwrap_or_default)
+}
+
+pub fn get_tags(row: &Row) -> Result<Tags> {
+    row.try_get::<&str, Option<Vec<String>>>("tags")
+    .map_err(Into::into)
+    .map(|tags| tags.unwrap_or_default().into_iter().collect())
+}
+
+/// Translations are stored as json, or null if there are none
+pub fn get_translations(row: &Row) -> Result<Translations> {
+    row.try_get::<&str, Option<String>>("translations")?
+    .map(|json| serde_json::from_str(&json))
+    .transpose()
+    .map_err(Into::into)
+    .map(Option::unwrap_or_default)
+}
+
+pub fn translations_to_text(translations: &Translations) -> Result<Option<String>> {
+    (!translations.is_empty())
+    .then(|| serde_json::to_string(translations))
+    .transpose()
+    .map_err(Into::into)
+}
+
+fn get_optional_text(row: &Row, column: &str) -> Result<Option<String>> {
+    row.try_get::<&str, Option<String>>(column)
+    .map_err(Into::into)
+}
+
+pub fn get_metadata(row: &Row) -> Result<LyricMetadata> {
+    Ok(
+        LyricMetadata {
+            sub_title: get_optional_text(row, "sub_title")?,
+            lyricist: get_optional_text(row, "lyricist")?,
+            composer: get_optional_text(row, "composer")?,
+            language: get_optional_text(row, "language")?,
+            year: row.try_get::<&str, Option<i32>>("year")?.and_then(|year| u16::try_from(year).ok()),
+            copyright: get_optional_text(row, "copyright")?,
+            source: get_optional_text(row, "source")?,
+        }
+    )
+}
+
+/// A row of the member table as json, with the lyric id in the hyphenated form postgres uses
+#[derive(Deserialize, Serialize)]
+struct Member {
+    lyric: Option<String>,
+    text: Option<String>,
+    note: Option<String>,
+    parts: Option<Vec<usize>>,
+    key: Option<String>,
+}
+
+pub fn get_entries(row: &Row) -> Result<Vec<PlaylistEntry>> {
+    row.try_get::<&str, Option<String>>("entries")?
+    .map(|json| serde_json::from_str::<Vec<Member>>(&json))
+    .transpose()?
+    .unwrap_or_default()
+    .into_iter()
+    .map(|member|
+        Ok(
+            PlaylistEntry {
+                lyric: member.lyric.map(|lyric| uuid::Uuid::parse_str(&lyric)).transpose()?.map(Uuid::from),
+                text: member.text,
+                note: member.note,
+                parts: member.parts.unwrap_or_default(),
+                key: member.key,
+            }
+        )
+    )
+    .collect()
+}
+
+/// The event of a playlist as json, or null if the playlist is not scheduled
+pub fn get_event(row: &Row) -> Result<Option<PlaylistEvent>> {
+    row.try_get::<&str, Option<String>>("event")?
+    .map(|json| serde_json::from_str::<PlaylistEvent>(&json))
+    .transpose()
+    .map_err(Into::into)
+}
+
+pub fn event_to_text(event: Option<&PlaylistEvent>) -> Result<Option<String>> {
+    event.map(serde_json::to_string).transpose().map_err(Into::into)
+}
+
+pub fn entries_to_text(entries: &[PlaylistEntry]) -> Result<String> {
+    let members =
+        entries
+        .iter()
+        .map(|entry|
+            Member {
+                lyric: entry.lyric.as_ref().map(Uuid::hyphenated),
+                text: entry.text.clone(),
+                note: entry.note.clone(),
+                parts: Some(entry.parts.clone()),
+                key: entry.key.clone(),
+            }
+        )
+        .collect::<Vec<_>>();
+    serde_json::to_string(&members).map_err(Into::into)
 }
 
 pub fn try_convert_vec<F, T, U>(f: F) -> impl Fn(Vec<T>) -> Result<Vec<U>>
@@ -41,26 +141,57 @@ where F: Fn(T) -> Result<U> + Copy
     move |v| v.into_iter().map(f).collect()
 }
 
+pub fn try_convert_option<F, T, U>(f: F) -> impl Fn(Option<T>) -> Result<Option<U>>
+where F: Fn(T) -> Result<U> + Copy
+{
+    move |o| o.map(f).transpose()
+}
+
 pub fn to_lyric(row: Row) -> Result<Lyric> {
+    let (parts, chords) = get_parts(&row)?;
     Ok(
         Lyric {
             id: get_id(&row)?,
             title: get_title(&row)?,
-            parts: get_parts(&row)?,
+            parts,
+            chords,
+            labels: get_labels(&row)?,
+            arrangement: get_arrangement(&row)?,
+            translations: get_translations(&row)?,
+            tags: get_tags(&row)?,
+            metadata: get_metadata(&row)?,
         }
     )    
 }
 
-pub fn to_playlist(row: Row) -> Result<Playlist> {
+pub fn to_revision(row: Row) -> Result<Revision> {
     Ok(
-        Playlist {
-            id: get_id(&row)?,
-            title: get_title(&row)?,
-            members: get_members(&row)?,
+        Revision {
+            rev: row.try_get::<&str, i64>("rev")? as u64,
+            timestamp: row.try_get::<&str, i64>("modified")? as u64,
+            lyric: to_lyric(row)?,
         }
     )
 }
 
+pub fn to_trashed(row: Row) -> lipl_core::Result<Trashed> {
+    let data = row.try_get::<&str, String>("data").map_err(crate::PostgresRepoError::from)?;
+    serde_json::from_str::<Trashed>(&data)
+    .map_err(|error| lipl_core::Error::Json(Box::new(error)))
+}
+
+pub fn to_playlist(row: Row) -> Result<Playlist> {
+    let mut playlist = Playlist {
+        id: get_id(&row)?,
+        title: get_title(&row)?,
+        members: vec![],
+        entries: vec![],
+        event: get_event(&row)?,
+    };
+    playlist.set_entries(get_entries(&row)?);
+    Ok(playlist)
+}
+
 pub fn to_summary(row: Row) -> Result<Summary> {
     Ok(
         Summary {
@@ -70,6 +201,27 @@ pub fn to_summary(row: Row) -> Result<Summary> {
     )
 }
 
+pub fn to_search_hit(terms: &[String]) -> impl Fn(Row) -> Result<SearchHit> + Copy + '_ {
+    move |row| {
+        Ok(
+            SearchHit {
+                summary: Summary { id: get_id(&row)?, title: get_title(&row)? },
+                line: matching_line(&get_parts(&row)?.0, terms),
+                rank: row.try_get::<&str, f32>("rank")?,
+            }
+        )
+    }
+}
+
+pub fn to_tag_count(row: Row) -> Result<TagCount> {
+    Ok(
+        TagCount {
+            tag: row.try_get::<&str, String>("tag")?,
+            count: row.try_get::<&str, i64>("count")? as usize,
+        }
+    )
+}
+
 pub fn to_ok<T>(t: T) -> Result<T> {
     Ok(t)
 }
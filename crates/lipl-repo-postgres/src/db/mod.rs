@@ -4,6 +4,11 @@ pub const DROP: &[&str] = &[
     include_str!("./sql/drop/003_table_member.sql"),
     include_str!("./sql/drop/004_table_lyric.sql"),
     include_str!("./sql/drop/005_table_playlist.sql"),
+    include_str!("./sql/drop/006_function_notify_change.sql"),
+    include_str!("./sql/drop/007_sequence_change.sql"),
+    include_str!("./sql/drop/008_table_trash.sql"),
+    include_str!("./sql/drop/009_function_lyric_revision.sql"),
+    include_str!("./sql/drop/010_table_lyric_revision.sql"),
 ];
 
 pub const CREATE: &[&str] = &[
@@ -13,18 +18,55 @@ pub const CREATE: &[&str] = &[
     include_str!("./sql/create/004_index_member_lyric.sql"),
     include_str!("./sql/create/005_index_member_playlist.sql"),
     include_str!("./sql/create/006_view_membership.sql"),
-    include_str!("./sql/create/007_function_set_members.sql"),
+    include_str!("./sql/create/007_drop_function_upsert_playlist_members.sql"),
     include_str!("./sql/create/008_function_upsert_playlist.sql"),
+    include_str!("./sql/create/009_alter_table_lyric_metadata.sql"),
+    include_str!("./sql/create/010_alter_table_lyric_search_text.sql"),
+    include_str!("./sql/create/011_index_lyric_search_text.sql"),
+    include_str!("./sql/create/012_sequence_change.sql"),
+    include_str!("./sql/create/013_function_notify_change.sql"),
+    include_str!("./sql/create/014_trigger_lyric_change.sql"),
+    include_str!("./sql/create/015_trigger_playlist_change.sql"),
+    include_str!("./sql/create/016_table_trash.sql"),
+    include_str!("./sql/create/017_table_lyric_revision.sql"),
+    include_str!("./sql/create/018_function_lyric_revision.sql"),
+    include_str!("./sql/create/019_trigger_lyric_revision.sql"),
+    include_str!("./sql/create/020_alter_table_lyric_arrangement.sql"),
+    include_str!("./sql/create/021_alter_table_lyric_revision_arrangement.sql"),
+    include_str!("./sql/create/022_alter_table_lyric_translations.sql"),
+    include_str!("./sql/create/023_alter_table_lyric_revision_translations.sql"),
+    include_str!("./sql/create/024_alter_table_member_entries.sql"),
+    include_str!("./sql/create/025_alter_table_playlist_event.sql"),
+    include_str!("./sql/create/026_index_playlist_event_start.sql"),
+    include_str!("./sql/create/027_alter_table_lyric_tags.sql"),
+    include_str!("./sql/create/028_alter_table_lyric_revision_tags.sql"),
+    include_str!("./sql/create/029_index_lyric_tags.sql"),
 ];
 
 pub mod crud {
     use bb8_postgres::tokio_postgres::types::Type;
 
     pub const UPSERT_LYRIC: &str = include_str!("./sql/crud/upsert_lyric.sql");
-    pub const UPSERT_LYRIC_TYPES: &[Type] = &[Type::UUID, Type::TEXT, Type::TEXT];
+    pub const UPSERT_LYRIC_TYPES: &[Type] = &[
+        Type::UUID,
+        Type::TEXT,
+        Type::TEXT,
+        Type::TEXT,
+        Type::TEXT,
+        Type::TEXT,
+        Type::TEXT,
+        Type::INT4,
+        Type::TEXT,
+        Type::TEXT,
+        Type::TEXT,
+        Type::TEXT_ARRAY,
+        Type::TEXT_ARRAY,
+        Type::TEXT,
+        Type::TEXT_ARRAY,
+    ];
 
     pub const UPSERT_PLAYLIST: &str = include_str!("./sql/crud/upsert_playlist.sql");
-    pub const UPSERT_PLAYLIST_TYPES: &[Type] = &[Type::UUID, Type::TEXT, Type::UUID_ARRAY];
+    pub const UPSERT_PLAYLIST_TYPES: &[Type] = &[Type::UUID, Type::TEXT, Type::TEXT, Type::TEXT];
     
     pub const DELETE_LYRIC: &str = include_str!("./sql/crud/delete_lyric.sql");
     pub const DELETE_LYRIC_TYPES: &[Type] = &[Type::UUID];
@@ -40,6 +82,14 @@ pub mod crud {
     
     pub const SELECT_LYRIC_DETAIL: &str = include_str!("./sql/crud/select_lyric_detail.sql");
     pub const SELECT_LYRIC_DETAIL_TYPES: &[Type] = &[Type::UUID];
+
+    pub const SELECT_TAGS: &str = include_str!("./sql/crud/select_tags.sql");
+    pub const SELECT_TAGS_TYPES: &[Type] = &[];
+
+    pub const SEARCH_LYRICS: &str = include_str!("./sql/crud/search_lyrics.sql");
+    pub const SEARCH_LYRICS_TYPES: &[Type] = &[Type::TEXT];
+
+    pub const SELECT_LYRIC_DETAIL_FOR_UPDATE: &str = include_str!("./sql/crud/select_lyric_detail_for_update.sql");
     
     pub const SELECT_PLAYLIST_SUMMARIES: &str = include_str!("./sql/crud/select_playlist_summaries.sql");
     pub const SELECT_PLAYLIST_SUMMARIES_TYPES: &[Type] = &[];
@@ -47,6 +97,55 @@ pub mod crud {
     pub const SELECT_PLAYLISTS: &str = include_str!("./sql/crud/select_playlists.sql");
     pub const SELECT_PLAYLISTS_TYPES: &[Type] = &[];
 
+    pub const SELECT_SCHEDULED_PLAYLISTS: &str = include_str!("./sql/crud/select_scheduled_playlists.sql");
+    pub const SELECT_SCHEDULED_PLAYLISTS_TYPES: &[Type] = &[Type::TEXT, Type::TEXT];
+
     pub const SELECT_PLAYLIST_DETAIL: &str = include_str!("./sql/crud/select_playlist_detail.sql");
     pub const SELECT_PLAYLIST_DETAIL_TYPES: &[Type] = &[Type::UUID];
-}
\ No newline at end of file
+
+    pub const SELECT_PLAYLIST_DETAIL_FOR_UPDATE: &str = include_str!("./sql/crud/select_playlist_detail_for_update.sql");
+
+    pub const SELECT_LYRIC_PLAYLISTS_FOR_UPDATE: &str = include_str!("./sql/crud/select_lyric_playlists_for_update.sql");
+
+    pub const SELECT_LYRICS_PLAYLISTS_FOR_UPDATE: &str = include_str!("./sql/crud/select_lyrics_playlists_for_update.sql");
+
+    pub const SELECT_LYRIC_IDS: &str = include_str!("./sql/crud/select_lyric_ids.sql");
+
+    pub const UPSERT_TRASH: &str = include_str!("./sql/crud/upsert_trash.sql");
+    pub const UPSERT_TRASH_TYPES: &[Type] = &[Type::UUID, Type::INT8, Type::TEXT];
+
+    pub const SELECT_TRASH: &str = include_str!("./sql/crud/select_trash.sql");
+    pub const SELECT_TRASH_TYPES: &[Type] = &[];
+
+    pub const DELETE_TRASH: &str = include_str!("./sql/crud/delete_trash.sql");
+
+    pub const PURGE_TRASH: &str = include_str!("./sql/crud/purge_trash.sql");
+    pub const PURGE_TRASH_TYPES: &[Type] = &[Type::UUID];
+
+    pub const SELECT_LYRIC_HISTORY: &str = include_str!("./sql/crud/select_lyric_history.sql");
+    pub const SELECT_LYRIC_HISTORY_TYPES: &[Type] = &[Type::UUID];
+
+    pub const SELECT_LYRIC_REVISION: &str = include_str!("./sql/crud/select_lyric_revision.sql");
+    pub const SELECT_LYRIC_REVISION_TYPES: &[Type] = &[Type::UUID, Type::INT8];
+
+    pub const COUNT_SUMMARIES_TYPES: &[Type] = &[Type::TEXT, Type::TEXT_ARRAY];
+    pub const SELECT_SUMMARIES_PAGE_TYPES: &[Type] = &[Type::TEXT, Type::TEXT_ARRAY, Type::INT8, Type::INT8];
+
+    /// Title like $1 and, for lyrics, all the tags in $2. Playlists have no tags.
+    fn summaries_filter(table: &str) -> &'static str {
+        if table == "lyric" {
+            "lower(title) LIKE $1 AND COALESCE(tags, '{}') @> $2::varchar[]"
+        }
+        else {
+            "lower(title) LIKE $1"
+        }
+    }
+
+    pub fn count_summaries(table: &str) -> String {
+        format!("SELECT COUNT(*) AS total FROM {table} WHERE {};", summaries_filter(table))
+    }
+
+    pub fn select_summaries_page(table: &str, order_by: &str) -> String {
+        format!("SELECT id, title FROM {table} WHERE {} ORDER BY {order_by} LIMIT $3 OFFSET $4;", summaries_filter(table))
+    }
+}
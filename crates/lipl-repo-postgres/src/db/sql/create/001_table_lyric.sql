@@ -2,5 +2,16 @@ CREATE TABLE IF NOT EXISTS lyric (
     id UUID PRIMARY KEY,
     title VARCHAR UNIQUE NOT NULL,
     sub_title VARCHAR,
-    parts VARCHAR
+    parts VARCHAR,
+    lyricist VARCHAR,
+    composer VARCHAR,
+    language VARCHAR,
+    year INTEGER,
+    copyright VARCHAR,
+    source VARCHAR,
+    labels VARCHAR[],
+    arrangement VARCHAR[],
+    translations VARCHAR,
+    tags VARCHAR[],
+    search_text VARCHAR
 );
@@ -0,0 +1 @@
+DROP FUNCTION IF EXISTS fn_upsert_playlist(uuid, text, uuid[]), fn_upsert_playlist(uuid, text, text);
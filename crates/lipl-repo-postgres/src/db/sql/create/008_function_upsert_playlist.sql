@@ -1,31 +1,54 @@
-CREATE OR REPLACE FUNCTION fn_upsert_playlist(new_id uuid, new_title text, new_members uuid[]) 
+CREATE OR REPLACE FUNCTION fn_upsert_playlist(new_id uuid, new_title text, new_entries text, new_event text) 
 RETURNS TABLE (
     id uuid,
     title text,
     members uuid[]
 ) AS $$
 DECLARE
+    entry json;
     l_id uuid;
     counter integer := 0;
     members uuid[];
 BEGIN
     counter := 0;
-    INSERT INTO playlist (id, title)
-    VALUES(new_id, new_title)
+    INSERT INTO playlist (id, title, event_start, location, leader)
+    VALUES(
+        new_id,
+        new_title,
+        (new_event::json->>'start')::timestamp,
+        new_event::json->>'location',
+        new_event::json->>'leader'
+    )
     ON CONFLICT ON CONSTRAINT playlist_pkey
     DO
-    UPDATE SET title = new_title;
+    UPDATE SET
+        title = new_title,
+        event_start = EXCLUDED.event_start,
+        location = EXCLUDED.location,
+        leader = EXCLUDED.leader;
 
     DELETE FROM member WHERE playlist_id = new_id;
     RAISE NOTICE 'Members deleted';
-    FOREACH l_id IN ARRAY new_members
+    FOR entry IN SELECT json_array_elements(new_entries::json)
     LOOP
         counter := counter + 1;
+        l_id := (entry->>'lyric')::uuid;
         BEGIN
-            INSERT INTO MEMBER (playlist_id, lyric_id, ordering) VALUES (new_id, l_id, counter);
-            members := members || l_id;
+            INSERT INTO MEMBER (playlist_id, lyric_id, ordering, text, note, parts, key)
+            VALUES (
+                new_id,
+                l_id,
+                counter,
+                entry->>'text',
+                entry->>'note',
+                ARRAY(SELECT json_array_elements_text(entry->'parts')::integer),
+                entry->>'key'
+            );
+            IF l_id IS NOT NULL THEN
+                members := members || l_id;
+            END IF;
         END;
-        RAISE NOTICE 'Lyric with id % added', l_id;
+        RAISE NOTICE 'Entry % added', counter;
     END LOOP;
 
     RETURN QUERY SELECT new_id AS id, new_title AS title, members AS members;
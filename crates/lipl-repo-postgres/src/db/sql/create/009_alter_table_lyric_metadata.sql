@@ -0,0 +1,7 @@
+ALTER TABLE lyric
+    ADD COLUMN IF NOT EXISTS lyricist VARCHAR,
+    ADD COLUMN IF NOT EXISTS composer VARCHAR,
+    ADD COLUMN IF NOT EXISTS language VARCHAR,
+    ADD COLUMN IF NOT EXISTS year INTEGER,
+    ADD COLUMN IF NOT EXISTS copyright VARCHAR,
+    ADD COLUMN IF NOT EXISTS source VARCHAR;
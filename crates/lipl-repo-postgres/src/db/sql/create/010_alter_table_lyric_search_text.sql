@@ -0,0 +1,2 @@
+ALTER TABLE lyric
+    ADD COLUMN IF NOT EXISTS search_text VARCHAR;
\ No newline at end of file
@@ -0,0 +1 @@
+CREATE INDEX IF NOT EXISTS lyric_search_text ON lyric USING GIN (to_tsvector('simple', coalesce(search_text, '')));
\ No newline at end of file
@@ -0,0 +1 @@
+CREATE SEQUENCE IF NOT EXISTS change_sequence;
\ No newline at end of file
@@ -0,0 +1,23 @@
+CREATE OR REPLACE FUNCTION fn_notify_change() RETURNS trigger AS $$
+DECLARE
+    changed_id uuid;
+BEGIN
+    IF TG_OP = 'DELETE' THEN
+        changed_id := OLD.id;
+    ELSE
+        changed_id := NEW.id;
+    END IF;
+    PERFORM pg_notify(
+        'lipl_change',
+        concat_ws(
+            ' ',
+            nextval('change_sequence'),
+            (extract(epoch FROM clock_timestamp()) * 1000)::bigint,
+            TG_TABLE_NAME,
+            TG_OP,
+            changed_id
+        )
+    );
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
@@ -0,0 +1,7 @@
+DO $$
+BEGIN
+    IF NOT EXISTS (SELECT 1 FROM pg_trigger WHERE tgname = 'lyric_change') THEN
+        CREATE TRIGGER lyric_change AFTER INSERT OR UPDATE OR DELETE ON lyric FOR EACH ROW EXECUTE FUNCTION fn_notify_change();
+    END IF;
+END
+$$;
@@ -0,0 +1,5 @@
+CREATE TABLE IF NOT EXISTS trash (
+    id UUID PRIMARY KEY,
+    deleted BIGINT NOT NULL,
+    data VARCHAR NOT NULL
+);
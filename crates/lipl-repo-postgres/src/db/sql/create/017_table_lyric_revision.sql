@@ -0,0 +1,19 @@
+CREATE TABLE IF NOT EXISTS lyric_revision (
+    lyric_id UUID NOT NULL,
+    rev BIGINT NOT NULL,
+    modified BIGINT NOT NULL,
+    title VARCHAR NOT NULL,
+    sub_title VARCHAR,
+    parts VARCHAR,
+    lyricist VARCHAR,
+    composer VARCHAR,
+    language VARCHAR,
+    year INTEGER,
+    copyright VARCHAR,
+    source VARCHAR,
+    labels VARCHAR[],
+    arrangement VARCHAR[],
+    translations VARCHAR,
+    tags VARCHAR[],
+    PRIMARY KEY (lyric_id, rev)
+);
@@ -0,0 +1,31 @@
+CREATE OR REPLACE FUNCTION fn_lyric_revision() RETURNS trigger AS $$
+DECLARE
+    last lyric_revision%ROWTYPE;
+BEGIN
+    SELECT * INTO last FROM lyric_revision WHERE lyric_id = NEW.id ORDER BY rev DESC LIMIT 1;
+    IF FOUND AND (last.title, last.sub_title, last.parts, last.lyricist, last.composer, last.language, last.year, last.copyright, last.source, last.labels, last.arrangement, last.translations, last.tags)
+        IS NOT DISTINCT FROM (NEW.title, NEW.sub_title, NEW.parts, NEW.lyricist, NEW.composer, NEW.language, NEW.year, NEW.copyright, NEW.source, NEW.labels, NEW.arrangement, NEW.translations, NEW.tags) THEN
+        RETURN NULL;
+    END IF;
+    INSERT INTO lyric_revision (lyric_id, rev, modified, title, sub_title, parts, lyricist, composer, language, year, copyright, source, labels, arrangement, translations, tags)
+    VALUES (
+        NEW.id,
+        COALESCE(last.rev, 0) + 1,
+        (extract(epoch FROM clock_timestamp()) * 1000)::bigint,
+        NEW.title,
+        NEW.sub_title,
+        NEW.parts,
+        NEW.lyricist,
+        NEW.composer,
+        NEW.language,
+        NEW.year,
+        NEW.copyright,
+        NEW.source,
+        NEW.labels,
+        NEW.arrangement,
+        NEW.translations,
+        NEW.tags
+    );
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
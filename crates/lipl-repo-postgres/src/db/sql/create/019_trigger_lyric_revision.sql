@@ -0,0 +1,7 @@
+DO $$
+BEGIN
+    IF NOT EXISTS (SELECT 1 FROM pg_trigger WHERE tgname = 'lyric_revision') THEN
+        CREATE TRIGGER lyric_revision AFTER INSERT OR UPDATE ON lyric FOR EACH ROW EXECUTE FUNCTION fn_lyric_revision();
+    END IF;
+END
+$$;
@@ -0,0 +1,3 @@
+ALTER TABLE lyric_revision
+    ADD COLUMN IF NOT EXISTS labels VARCHAR[],
+    ADD COLUMN IF NOT EXISTS arrangement VARCHAR[];
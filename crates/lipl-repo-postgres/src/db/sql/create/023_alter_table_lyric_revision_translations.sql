@@ -0,0 +1,2 @@
+ALTER TABLE lyric_revision
+    ADD COLUMN IF NOT EXISTS translations VARCHAR;
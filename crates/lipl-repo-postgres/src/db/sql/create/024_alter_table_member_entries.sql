@@ -0,0 +1,6 @@
+ALTER TABLE member
+    ALTER COLUMN lyric_id DROP NOT NULL,
+    ADD COLUMN IF NOT EXISTS text VARCHAR,
+    ADD COLUMN IF NOT EXISTS note VARCHAR,
+    ADD COLUMN IF NOT EXISTS parts INTEGER[],
+    ADD COLUMN IF NOT EXISTS key VARCHAR;
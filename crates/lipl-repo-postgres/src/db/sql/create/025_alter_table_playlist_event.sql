@@ -0,0 +1,4 @@
+ALTER TABLE playlist
+    ADD COLUMN IF NOT EXISTS event_start TIMESTAMP,
+    ADD COLUMN IF NOT EXISTS location VARCHAR,
+    ADD COLUMN IF NOT EXISTS leader VARCHAR;
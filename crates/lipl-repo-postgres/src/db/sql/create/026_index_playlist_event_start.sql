@@ -0,0 +1 @@
+CREATE INDEX IF NOT EXISTS playlist_event_start ON playlist (event_start);
@@ -0,0 +1,2 @@
+ALTER TABLE lyric
+    ADD COLUMN IF NOT EXISTS tags VARCHAR[];
@@ -0,0 +1 @@
+CREATE INDEX IF NOT EXISTS lyric_tags ON lyric USING GIN (tags);
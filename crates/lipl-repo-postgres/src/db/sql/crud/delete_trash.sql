@@ -0,0 +1 @@
+DELETE FROM trash WHERE id = $1 RETURNING data;
\ No newline at end of file
@@ -0,0 +1,4 @@
+WITH history AS (
+    DELETE FROM lyric_revision WHERE lyric_id = $1
+)
+DELETE FROM trash WHERE id = $1 RETURNING data;
@@ -0,0 +1,4 @@
+SELECT id, title, parts, ts_rank(to_tsvector('simple', coalesce(search_text, '')), query) AS rank
+FROM lyric, plainto_tsquery('simple', $1) query
+WHERE to_tsvector('simple', coalesce(search_text, '')) @@ query
+ORDER BY rank DESC, title;
\ No newline at end of file
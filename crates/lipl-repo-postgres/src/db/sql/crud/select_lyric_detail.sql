@@ -1 +1 @@
-SELECT id, title, parts FROM lyric WHERE id = $1;
\ No newline at end of file
+SELECT id, title, parts, sub_title, lyricist, composer, language, year, copyright, source, labels, arrangement, translations, tags FROM lyric WHERE id = $1;
\ No newline at end of file
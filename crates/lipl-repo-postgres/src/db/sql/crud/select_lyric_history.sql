@@ -0,0 +1 @@
+SELECT rev, modified, lyric_id AS id, title, parts, sub_title, lyricist, composer, language, year, copyright, source, labels, arrangement, translations, tags FROM lyric_revision WHERE lyric_id = $1 ORDER BY rev;
\ No newline at end of file
@@ -0,0 +1 @@
+SELECT id FROM lyric WHERE id = ANY($1);
\ No newline at end of file
@@ -0,0 +1 @@
+SELECT p.id, p.title, (SELECT json_agg(json_build_object('lyric', lyric_id, 'text', text, 'note', note, 'parts', parts, 'key', key) ORDER BY ordering) FROM member WHERE playlist_id = p.id)::text AS entries, CASE WHEN p.event_start IS NULL THEN NULL ELSE json_build_object('start', p.event_start, 'location', p.location, 'leader', p.leader) END::text AS event from Playlist p WHERE p.id IN (SELECT playlist_id FROM member WHERE lyric_id = $1) FOR UPDATE;
\ No newline at end of file
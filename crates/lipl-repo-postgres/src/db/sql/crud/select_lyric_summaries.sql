@@ -1 +1 @@
-SELECT id, title FROM lyric ORDER BY title;
\ No newline at end of file
+SELECT id, title FROM lyric ORDER BY title COLLATE "C", id;
\ No newline at end of file
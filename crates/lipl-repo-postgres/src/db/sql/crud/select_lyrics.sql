@@ -1 +1 @@
-SELECT id, title, parts from lyric ORDER BY title;
+SELECT id, title, parts, sub_title, lyricist, composer, language, year, copyright, source, labels, arrangement, translations, tags from lyric ORDER BY title COLLATE "C", id;
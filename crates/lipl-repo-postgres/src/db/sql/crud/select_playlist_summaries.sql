@@ -1 +1 @@
-SELECT id, title FROM playlist ORDER BY title;
\ No newline at end of file
+SELECT id, title FROM playlist ORDER BY title COLLATE "C", id;
\ No newline at end of file
@@ -0,0 +1 @@
+SELECT tag, COUNT(*) AS count FROM lyric, unnest(tags) AS tag GROUP BY tag ORDER BY tag COLLATE "C";
\ No newline at end of file
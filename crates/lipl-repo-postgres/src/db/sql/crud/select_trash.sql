@@ -0,0 +1 @@
+SELECT data FROM trash ORDER BY deleted DESC, id;
\ No newline at end of file
@@ -1,5 +1,5 @@
-INSERT INTO lyric (id, title, parts)
-VALUES($1, $2, $3)
+INSERT INTO lyric (id, title, parts, sub_title, lyricist, composer, language, year, copyright, source, search_text, labels, arrangement, translations, tags)
+VALUES($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15)
 ON CONFLICT (id)
 DO
-  UPDATE SET title = $2, parts = $3;
+  UPDATE SET title = $2, parts = $3, sub_title = $4, lyricist = $5, composer = $6, language = $7, year = $8, copyright = $9, source = $10, search_text = $11, labels = $12, arrangement = $13, translations = $14, tags = $15;
@@ -1 +1 @@
-SELECT fn_upsert_playlist($1, $2, $3);
+SELECT fn_upsert_playlist($1, $2, $3, $4);
\ No newline at end of file
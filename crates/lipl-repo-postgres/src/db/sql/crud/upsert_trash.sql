@@ -0,0 +1,5 @@
+INSERT INTO trash (id, deleted, data)
+VALUES($1, $2, $3)
+ON CONFLICT (id)
+DO
+  UPDATE SET deleted = $2, data = $3;
@@ -0,0 +1 @@
+DROP FUNCTION IF EXISTS fn_notify_change;
\ No newline at end of file
@@ -0,0 +1 @@
+DROP SEQUENCE IF EXISTS change_sequence;
\ No newline at end of file
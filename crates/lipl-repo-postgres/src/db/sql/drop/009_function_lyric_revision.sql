@@ -0,0 +1 @@
+DROP FUNCTION IF EXISTS fn_lyric_revision;
\ No newline at end of file
@@ -0,0 +1 @@
+DROP TABLE IF EXISTS lyric_revision;
\ No newline at end of file
@@ -5,10 +5,9 @@ use std::sync::Arc;
 use async_trait::{async_trait};
 use bb8_postgres::PostgresConnectionManager;
 use bb8_postgres::bb8::{Pool};
-use futures_util::{TryFutureExt};
-use lipl_core::{Lyric, LiplRepo, Playlist, Summary, Uuid, ToRepo};
-use parts::{to_text};
-use bb8_postgres::tokio_postgres::{Row, NoTls};
+use futures_util::TryFutureExt;
+use lipl_core::{check_etag, check_members, duplicate, search, ChangeStream, DateRange, Etag, ListQuery, Page, Revision, SearchHit, Lyric, LiplRepo, Merge, Playlist, Summary, TagCount, Transaction, TrashItem, Trashed, Uuid, ToRepo};
+use bb8_postgres::tokio_postgres::{self, types::ToSql, Row, NoTls};
 
 use crate::db::crud;
 use crate::macros::query;
@@ -112,48 +111,171 @@ impl PostgresRepo {
         )
     }
 
+    async fn summaries_page(&self, table: &str, query: ListQuery) -> Result<Page<Summary>> {
+        let client = self.pool.get().await?;
+        let pattern = query.like_pattern();
+        let statement = client.prepare_typed(&crud::count_summaries(table), crud::COUNT_SUMMARIES_TYPES).await?;
+        let total = client.query_one(&statement, &[&pattern, &query.tags]).await?.try_get::<&str, i64>("total")?;
+        let statement = client.prepare_typed(&crud::select_summaries_page(table, query.order_by()), crud::SELECT_SUMMARIES_PAGE_TYPES).await?;
+        let limit = query.limit.map(|limit| limit as i64);
+        let rows = client.query(&statement, &[&pattern, &query.tags, &limit, &(query.offset as i64)]).await?;
+        Ok(
+            Page {
+                items: convert::try_convert_vec(convert::to_summary)(rows)?,
+                total: total as usize,
+            }
+        )
+    }
+
+    /// Executes the transaction, after checking the etag of the current version of the entity, locked by select
+    async fn execute_if_match<T>(
+        &self,
+        id: Uuid,
+        etag: &str,
+        select: &str,
+        convert: fn(Row) -> Result<T>,
+        item: Transaction,
+    ) -> lipl_core::Result<()>
+    where
+        T: Etag,
+    {
+        let mut client = self.pool.get().await.map_err(pg_error)?;
+        let transaction = client.transaction().await.map_err(pg_error)?;
+        let current = 
+            transaction
+            .query_opt(select, &[&id.inner()])
+            .await
+            .map_err(pg_error)?
+            .map(convert)
+            .transpose()?;
+        check_etag(id, etag, current.as_ref())?;
+        execute_transaction(&transaction, item).await?;
+        transaction.commit().await.map_err(pg_error)
+    }
+
+    /// Executes the statements for all transactions in a single database transaction
+    async fn execute_batch(&self, batch: Vec<Transaction>) -> lipl_core::Result<()> {
+        let mut client = self.pool.get().await.map_err(pg_error)?;
+        let transaction = client.transaction().await.map_err(pg_error)?;
+        for item in batch {
+            execute_transaction(&transaction, item).await?;
+        }
+        transaction.commit().await.map_err(pg_error)
+    }
+
+    /// Locks the lyric to keep and the playlists of the duplicates, then points those playlists at the lyric to keep and deletes the duplicates
+    async fn execute_merge(&self, merge: Merge) -> lipl_core::Result<Lyric> {
+        let mut client = self.pool.get().await.map_err(pg_error)?;
+        let transaction = client.transaction().await.map_err(pg_error)?;
+        let lyric =
+            transaction
+            .query_opt(crud::SELECT_LYRIC_DETAIL_FOR_UPDATE, &[&merge.keep.inner()])
+            .await
+            .map_err(pg_error)?
+            .map(convert::to_lyric)
+            .transpose()?
+            .ok_or(lipl_core::Error::NotFound(merge.keep))?;
+        let duplicates = merge.duplicates.iter().map(|uuid| uuid.inner()).collect::<Vec<_>>();
+        let rows = transaction.query(crud::SELECT_LYRICS_PLAYLISTS_FOR_UPDATE, &[&duplicates]).await.map_err(pg_error)?;
+        let playlists = convert::try_convert_vec(convert::to_playlist)(rows)?;
+        for item in duplicate::merge_transactions(&merge, playlists) {
+            execute_transaction(&transaction, item).await?;
+        }
+        transaction.commit().await.map_err(pg_error)?;
+        Ok(lyric)
+    }
+
+    /// Takes the item out of the trash and upserts it, together with the playlists a lyric was a member of.
+    /// Fails with Occupied when an item with the same id was stored after the delete.
+    async fn execute_restore(&self, id: Uuid) -> lipl_core::Result<()> {
+        let mut client = self.pool.get().await.map_err(pg_error)?;
+        let transaction = client.transaction().await.map_err(pg_error)?;
+        let trashed = 
+            transaction
+            .query_opt(crud::DELETE_TRASH, &[&id.inner()])
+            .await
+            .map_err(pg_error)?
+            .map(convert::to_trashed)
+            .transpose()?
+            .ok_or(lipl_core::Error::NotFound(id))?;
+        let live = match &trashed.item {
+            TrashItem::Lyric(_) => crud::SELECT_LYRIC_DETAIL_FOR_UPDATE,
+            TrashItem::Playlist(_) => crud::SELECT_PLAYLIST_DETAIL_FOR_UPDATE,
+        };
+        if transaction.query_opt(live, &[&id.inner()]).await.map_err(pg_error)?.is_some() {
+            return Err(lipl_core::Error::Occupied);
+        }
+        let items = match &trashed.item {
+            TrashItem::Lyric(lyric) => {
+                let mut items = vec![Transaction::LyricUpsert(lyric.clone())];
+                for playlist_id in trashed.playlist_ids() {
+                    let playlist =
+                        transaction
+                        .query_opt(crud::SELECT_PLAYLIST_DETAIL_FOR_UPDATE, &[&playlist_id.inner()])
+                        .await
+                        .map_err(pg_error)?
+                        .map(convert::to_playlist)
+                        .transpose()?;
+                    if let Some(mut playlist) = playlist {
+                        trashed.restore_positions(&mut playlist);
+                        items.push(Transaction::PlaylistUpsert(playlist));
+                    }
+                }
+                items
+            },
+            TrashItem::Playlist(playlist) => {
+                let members = playlist.members.iter().map(|uuid| uuid.inner()).collect::<Vec<_>>();
+                let rows = transaction.query(crud::SELECT_LYRIC_IDS, &[&members]).await.map_err(pg_error)?;
+                let lyric_ids = convert::try_convert_vec(|row: Row| convert::get_id(&row))(rows)?;
+                let mut playlist = playlist.clone();
+                playlist.retain_members(|member| lyric_ids.contains(member));
+                vec![Transaction::PlaylistUpsert(playlist)]
+            },
+        };
+        for item in items {
+            execute_transaction(&transaction, item).await?;
+        }
+        transaction.commit().await.map_err(pg_error)
+    }
+
     query! (
-        upsert_lyric,
-        execute,
-        u64,
-        crud::UPSERT_LYRIC,
-        crud::UPSERT_LYRIC_TYPES,
+        trash_purge,
+        query_opt,
+        Option<Row>,
+        crud::PURGE_TRASH,
+        crud::PURGE_TRASH_TYPES,
         convert::to_ok,
         id: uuid::Uuid,
-        title: String,
-        text: String,
     );
 
     query! (
-        upsert_playlist,
+        lyric_history,
         query,
-        Vec<Row>,
-        crud::UPSERT_PLAYLIST,
-        crud::UPSERT_PLAYLIST_TYPES,
-        convert::to_ok,
+        Vec<Revision>,
+        crud::SELECT_LYRIC_HISTORY,
+        crud::SELECT_LYRIC_HISTORY_TYPES,
+        convert::try_convert_vec(convert::to_revision),
         id: uuid::Uuid,
-        title: String,
-        members: Vec<uuid::Uuid>,
     );
 
     query! (
-        lyric_delete,
-        execute,
-        u64,
-        crud::DELETE_LYRIC,
-        crud::DELETE_LYRIC_TYPES,
+        lyric_revision,
+        query_opt,
+        Option<Row>,
+        crud::SELECT_LYRIC_REVISION,
+        crud::SELECT_LYRIC_REVISION_TYPES,
         convert::to_ok,
         id: uuid::Uuid,
+        rev: i64,
     );
 
     query! (
-        playlist_delete,
-        execute,
-        u64,
-        crud::DELETE_PLAYLIST,
-        crud::DELETE_PLAYLIST_TYPES,
+        trash,
+        query,
+        Vec<Row>,
+        crud::SELECT_TRASH,
+        crud::SELECT_TRASH_TYPES,
         convert::to_ok,
-        id: uuid::Uuid,
     );
 
     query! (
@@ -174,13 +296,32 @@ impl PostgresRepo {
         convert::try_convert_vec(convert::to_lyric),
     );
 
+    query! (
+        lyric_tags,
+        query,
+        Vec<TagCount>,
+        crud::SELECT_TAGS,
+        crud::SELECT_TAGS_TYPES,
+        convert::try_convert_vec(convert::to_tag_count),
+    );
+
+    query! (
+        lyric_search,
+        query,
+        Vec<Row>,
+        crud::SEARCH_LYRICS,
+        crud::SEARCH_LYRICS_TYPES,
+        convert::to_ok,
+        query: String,
+    );
+
     query! (
         lyric_detail,
-        query_one,
-        Lyric,
+        query_opt,
+        Option<Lyric>,
         crud::SELECT_LYRIC_DETAIL,
         crud::SELECT_LYRIC_DETAIL_TYPES,
-        convert::to_lyric,
+        convert::try_convert_option(convert::to_lyric),
         id: uuid::Uuid,
     );
 
@@ -193,13 +334,24 @@ impl PostgresRepo {
         convert::try_convert_vec(convert::to_playlist),
     }
 
+    query!{
+        scheduled_playlists,
+        query,
+        Vec<Playlist>,
+        crud::SELECT_SCHEDULED_PLAYLISTS,
+        crud::SELECT_SCHEDULED_PLAYLISTS_TYPES,
+        convert::try_convert_vec(convert::to_playlist),
+        from: Option<String>,
+        to: Option<String>,
+    }
+
     query!{
         playlist_detail,
-        query_one,
-        Playlist,
+        query_opt,
+        Option<Playlist>,
         crud::SELECT_PLAYLIST_DETAIL,
         crud::SELECT_PLAYLIST_DETAIL_TYPES,
-        convert::to_playlist,
+        convert::try_convert_option(convert::to_playlist),
         id: uuid::Uuid,
     }
 
@@ -231,33 +383,82 @@ impl LiplRepo for PostgresRepo {
         .await
     }
 
+    async fn get_lyric_summaries_page(&self, query: ListQuery) -> lipl_core::Result<Page<Summary>>
+    {
+        self.summaries_page("lyric", query)
+        .err_into()
+        .await
+    }
+
     async fn get_lyric(&self, id: Uuid) -> lipl_core::Result<Lyric>
     {
         self.lyric_detail(id.inner())
         .err_into()
         .await
+        .and_then(|lyric| lyric.ok_or(lipl_core::Error::NotFound(id)))
     }
 
     async fn upsert_lyric(&self, lyric: Lyric) -> lipl_core::Result<Lyric>
     {
-        self.upsert_lyric(
-            lyric.id.inner(),
-            lyric.title,
-            to_text(&lyric.parts[..])
-        )
+        let id = lyric.id;
+        self.execute_batch(vec![Transaction::LyricUpsert(lyric)])
         .and_then(
-            move |_| self.lyric_detail(lyric.id.inner())
+            move |_| LiplRepo::get_lyric(self, id)
         )
-        .err_into()
         .await
     }
 
     async fn delete_lyric(&self, id: Uuid) -> lipl_core::Result<()>
     {
-        self.lyric_delete(id.inner())
-            .map_ok(to_unit)
+        self.execute_batch(vec![Transaction::LyricDelete(id)])
+            .await
+    }
+
+    async fn search_lyrics(&self, query: &str) -> lipl_core::Result<Vec<SearchHit>>
+    {
+        let terms = search::terms(query);
+        if terms.is_empty() {
+            return Ok(vec![]);
+        }
+        self.lyric_search(terms.join(" "))
+            .and_then(|rows| async { convert::try_convert_vec(convert::to_search_hit(&terms))(rows) })
+            .err_into()
+            .await
+    }
+
+    async fn get_tags(&self) -> lipl_core::Result<Vec<TagCount>>
+    {
+        self.lyric_tags()
+            .err_into()
+            .await
+    }
+
+    async fn get_lyric_history(&self, id: Uuid) -> lipl_core::Result<Vec<Revision>>
+    {
+        self.lyric_history(id.inner())
             .err_into()
             .await
+            .and_then(|history: Vec<Revision>| if history.is_empty() { Err(lipl_core::Error::NotFound(id)) } else { Ok(history) })
+    }
+
+    async fn get_lyric_revision(&self, id: Uuid, rev: u64) -> lipl_core::Result<Revision>
+    {
+        self.lyric_revision(id.inner(), rev as i64)
+            .err_into()
+            .await
+            .and_then(|row| row.map(convert::to_revision).transpose()?.ok_or(lipl_core::Error::NotFound(id)))
+    }
+
+    async fn revert_lyric(&self, id: Uuid, rev: u64) -> lipl_core::Result<Lyric>
+    {
+        let revision = self.get_lyric_revision(id, rev).await?;
+        LiplRepo::upsert_lyric(self, revision.lyric)
+            .await
+    }
+
+    async fn merge_lyrics(&self, merge: Merge) -> lipl_core::Result<Lyric> {
+        self.execute_merge(merge)
+            .await
     }
 
     async fn get_playlists(&self) -> lipl_core::Result<Vec<Playlist>>
@@ -274,32 +475,124 @@ impl LiplRepo for PostgresRepo {
             .await
     }
 
+    async fn get_playlist_summaries_page(&self, query: ListQuery) -> lipl_core::Result<Page<Summary>>
+    {
+        self.summaries_page("playlist", query)
+            .err_into()
+            .await
+    }
+
     async fn get_playlist(&self, id: Uuid) -> lipl_core::Result<Playlist>
     {
         self.playlist_detail(id.inner())
             .err_into()
             .await
+            .and_then(|playlist| playlist.ok_or(lipl_core::Error::NotFound(id)))
     }
 
-    async fn upsert_playlist(&self, playlist: Playlist) -> lipl_core::Result<Playlist>
+    async fn get_scheduled_playlists(&self, range: DateRange) -> lipl_core::Result<Vec<Playlist>>
     {
-        let title = playlist.title.clone();
-        self.upsert_playlist(
-            playlist.id.inner(),
-            title,
-            playlist.members.iter().map(|uuid| uuid.inner()).collect()
-        )
+        self.scheduled_playlists(range.from.map(|date| date.to_string()), range.to.map(|date| date.to_string()))
         .err_into()
+        .await
+    }
+
+    async fn upsert_playlist(&self, playlist: Playlist) -> lipl_core::Result<Playlist>
+    {
+        self.execute_batch(vec![Transaction::PlaylistUpsert(playlist.clone())])
         .and_then(move |_| self.get_playlist(playlist.id))
         .await
     }
 
     async fn delete_playlist(&self, id: Uuid) -> lipl_core::Result<()>
     {
-        self.playlist_delete(id.inner())
-            .map_ok(to_unit)
+        self.execute_batch(vec![Transaction::PlaylistDelete(id)])
+            .await
+    }
+
+    async fn upsert_lyric_if_match(&self, lyric: Lyric, etag: String) -> lipl_core::Result<Lyric>
+    {
+        let id = lyric.id;
+        self.execute_if_match(
+            id,
+            &etag,
+            crud::SELECT_LYRIC_DETAIL_FOR_UPDATE,
+            convert::to_lyric,
+            Transaction::LyricUpsert(lyric),
+        )
+        .and_then(|_| self.get_lyric(id))
+        .await
+    }
+
+    async fn delete_lyric_if_match(&self, id: Uuid, etag: String) -> lipl_core::Result<()>
+    {
+        self.execute_if_match(
+            id,
+            &etag,
+            crud::SELECT_LYRIC_DETAIL_FOR_UPDATE,
+            convert::to_lyric,
+            Transaction::LyricDelete(id),
+        )
+        .await
+    }
+
+    async fn upsert_playlist_if_match(&self, playlist: Playlist, etag: String) -> lipl_core::Result<Playlist>
+    {
+        let id = playlist.id;
+        self.execute_if_match(
+            id,
+            &etag,
+            crud::SELECT_PLAYLIST_DETAIL_FOR_UPDATE,
+            convert::to_playlist,
+            Transaction::PlaylistUpsert(playlist),
+        )
+        .and_then(|_| self.get_playlist(id))
+        .await
+    }
+
+    async fn delete_playlist_if_match(&self, id: Uuid, etag: String) -> lipl_core::Result<()>
+    {
+        self.execute_if_match(
+            id,
+            &etag,
+            crud::SELECT_PLAYLIST_DETAIL_FOR_UPDATE,
+            convert::to_playlist,
+            Transaction::PlaylistDelete(id),
+        )
+        .await
+    }
+
+    async fn apply_batch(&self, batch: Vec<Transaction>) -> lipl_core::Result<()>
+    {
+        self.execute_batch(batch)
+            .await
+    }
+
+    async fn watch(&self) -> lipl_core::Result<ChangeStream>
+    {
+        lipl_core::change::listen_changes(self.clone(), &self.connection_string).await
+    }
+
+    async fn list_trash(&self) -> lipl_core::Result<Vec<Trashed>>
+    {
+        self.trash()
             .err_into()
             .await
+            .and_then(|rows| rows.into_iter().map(convert::to_trashed).collect())
+    }
+
+    async fn restore(&self, id: Uuid) -> lipl_core::Result<()>
+    {
+        self.execute_restore(id)
+            .await
+    }
+
+    async fn purge(&self, id: Uuid) -> lipl_core::Result<()>
+    {
+        self.trash_purge(id.inner())
+            .err_into()
+            .await
+            .and_then(|row| row.map(to_unit).ok_or(lipl_core::Error::NotFound(id)))
     }
 
     async fn stop(&self) -> lipl_core::Result<()>
@@ -312,6 +605,114 @@ impl LiplRepo for PostgresRepo {
 
 fn to_unit<T>(_: T) { }
 
+/// Moves the lyric with its playlist positions or the playlist to the trash. Nothing is trashed if it does not exist.
+async fn trash(transaction: &tokio_postgres::Transaction<'_>, item: &Transaction) -> lipl_core::Result<()> {
+    let trashed = match item {
+        Transaction::LyricDelete(id) => {
+            let lyric =
+                transaction
+                .query_opt(crud::SELECT_LYRIC_DETAIL_FOR_UPDATE, &[&id.inner()])
+                .await
+                .map_err(pg_error)?
+                .map(convert::to_lyric)
+                .transpose()?;
+            match lyric {
+                Some(lyric) => {
+                    let rows = transaction.query(crud::SELECT_LYRIC_PLAYLISTS_FOR_UPDATE, &[&id.inner()]).await.map_err(pg_error)?;
+                    let playlists = convert::try_convert_vec(convert::to_playlist)(rows)?;
+                    Some(Trashed::lyric(lyric, playlists.iter()))
+                },
+                None => None,
+            }
+        },
+        Transaction::PlaylistDelete(id) => 
+            transaction
+            .query_opt(crud::SELECT_PLAYLIST_DETAIL_FOR_UPDATE, &[&id.inner()])
+            .await
+            .map_err(pg_error)?
+            .map(convert::to_playlist)
+            .transpose()?
+            .map(Trashed::playlist),
+        _ => None,
+    };
+    if let Some(trashed) = trashed {
+        let data = serde_json::to_string(&trashed).map_err(|error| lipl_core::Error::Json(Box::new(error)))?;
+        let statement = transaction.prepare_typed(crud::UPSERT_TRASH, crud::UPSERT_TRASH_TYPES).await.map_err(pg_error)?;
+        transaction.execute(&statement, &[&trashed.id().inner(), &(trashed.deleted as i64), &data]).await.map_err(pg_error)?;
+    }
+    Ok(())
+}
+
+type Params = Vec<Box<dyn ToSql + Sync + Send>>;
+
+/// The parameters of crud::UPSERT_LYRIC, in the order of the statement
+fn lyric_params(lyric: &Lyric) -> lipl_core::Result<Params> {
+    Ok(
+        vec![
+            Box::new(lyric.id.inner()),
+            Box::new(lyric.title.clone()),
+            Box::new(lyric.text()),
+            Box::new(lyric.metadata.sub_title.clone()),
+            Box::new(lyric.metadata.lyricist.clone()),
+            Box::new(lyric.metadata.composer.clone()),
+            Box::new(lyric.metadata.language.clone()),
+            Box::new(lyric.metadata.year.map(i32::from)),
+            Box::new(lyric.metadata.copyright.clone()),
+            Box::new(lyric.metadata.source.clone()),
+            Box::new(search::to_search_text(lyric)),
+            Box::new(lyric.labels.clone()),
+            Box::new(lyric.arrangement.clone()),
+            Box::new(convert::translations_to_text(&lyric.translations)?),
+            Box::new(lyric.tags.iter().cloned().collect::<Vec<_>>()),
+        ]
+    )
+}
+
+fn as_params(params: &Params) -> Vec<&(dyn ToSql + Sync)> {
+    params.iter().map(|param| param.as_ref() as &(dyn ToSql + Sync)).collect()
+}
+
+/// Executes the statement for a single transaction. Deleted items are moved to the trash first, deleting a missing item fails.
+async fn execute_transaction(transaction: &tokio_postgres::Transaction<'_>, item: Transaction) -> lipl_core::Result<()> {
+    trash(transaction, &item).await?;
+    match item {
+        Transaction::LyricDelete(id) => {
+            let statement = transaction.prepare_typed(crud::DELETE_LYRIC, crud::DELETE_LYRIC_TYPES).await.map_err(pg_error)?;
+            if transaction.execute(&statement, &[&id.inner()]).await.map_err(pg_error)? == 0 {
+                return Err(lipl_core::Error::NotFound(id));
+            }
+        },
+        Transaction::LyricUpsert(lyric) => {
+            let params = lyric_params(&lyric)?;
+            let statement = transaction.prepare_typed(crud::UPSERT_LYRIC, crud::UPSERT_LYRIC_TYPES).await.map_err(pg_error)?;
+            transaction.execute(&statement, &as_params(&params))
+            .await
+            .map_err(pg_error)?;
+        },
+        Transaction::PlaylistDelete(id) => {
+            let statement = transaction.prepare_typed(crud::DELETE_PLAYLIST, crud::DELETE_PLAYLIST_TYPES).await.map_err(pg_error)?;
+            if transaction.execute(&statement, &[&id.inner()]).await.map_err(pg_error)? == 0 {
+                return Err(lipl_core::Error::NotFound(id));
+            }
+        },
+        Transaction::PlaylistUpsert(playlist) => {
+            let members = playlist.members.iter().map(|uuid| uuid.inner()).collect::<Vec<_>>();
+            let rows = transaction.query(crud::SELECT_LYRIC_IDS, &[&members]).await.map_err(pg_error)?;
+            let lyric_ids = convert::try_convert_vec(|row: Row| convert::get_id(&row))(rows)?;
+            check_members(&playlist, |member| lyric_ids.contains(member))?;
+            let entries = convert::entries_to_text(&playlist.entries())?;
+            let event = convert::event_to_text(playlist.event.as_ref())?;
+            let statement = transaction.prepare_typed(crud::UPSERT_PLAYLIST, crud::UPSERT_PLAYLIST_TYPES).await.map_err(pg_error)?;
+            transaction.execute(&statement, &[&playlist.id.inner(), &playlist.title, &entries, &event]).await.map_err(pg_error)?;
+        },
+    }
+    Ok(())
+}
+
+fn pg_error<E: Into<PostgresRepoError>>(error: E) -> lipl_core::Error {
+    lipl_core::Error::Postgres(error.into())
+}
+
 
 #[cfg(test)]
 mod test {
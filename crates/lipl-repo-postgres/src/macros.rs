@@ -8,6 +8,7 @@ macro_rules! query {
         $f:expr
         $(, $param_name:ident : $param_type:ty)* $(,)?
     ) => {
+        #[allow(clippy::too_many_arguments)]
         async fn $name(&self, $($param_name: $param_type,)*) -> Result<$return_type> {
             let client = self.pool.get().await?;
             let statement = client.prepare_typed($sql, $types,).await?;
@@ -0,0 +1,14 @@
+use lipl_repo_postgres::PostgresRepoConfig;
+
+/// Needs a postgres server with the database in POSTGRES_HOST, POSTGRES_DB, POSTGRES_USER and POSTGRES_PASSWORD. Run with cargo test -- --ignored
+#[tokio::test]
+#[ignore]
+async fn conformance() {
+    let host = std::env::var("POSTGRES_HOST").unwrap();
+    let db = std::env::var("POSTGRES_DB").unwrap();
+    let user = std::env::var("POSTGRES_USER").unwrap();
+    let password = std::env::var("POSTGRES_PASSWORD").unwrap();
+    let config = format!("host={host} user={user} password={password} dbname={db}").parse::<PostgresRepoConfig>().unwrap().clear(true);
+
+    lipl_repo_test::check_config(config).await.unwrap();
+}
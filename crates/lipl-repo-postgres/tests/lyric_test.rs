@@ -1,4 +1,5 @@
-use lipl_core::{LiplRepo, LyricPost, Lyric, Playlist, PlaylistPost};
+use futures_util::StreamExt;
+use lipl_core::{Error, Etag, ListQuery, LiplRepo, LyricPost, Lyric, Playlist, PlaylistPost, SortOrder, Transaction};
 use lipl_repo_postgres::{PostgresRepoConfig, PostgresRepo};
 
 const ROODKAPJE: &str = include_str!("./Roodkapje.md");
@@ -14,6 +15,7 @@ fn create_lyric(text: &str) -> Lyric {
 }
 
 #[tokio::test]
+#[allow(clippy::bool_assert_comparison)]
 async fn test_lyric() -> Result<(), Box<dyn std::error::Error>> {
     let host = std::env::var("POSTGRES_HOST").unwrap();
     let db = std::env::var("POSTGRES_DB").unwrap();
@@ -21,6 +23,7 @@ async fn test_lyric() -> Result<(), Box<dyn std::error::Error>> {
     let password = std::env::var("POSTGRES_PASSWORD").unwrap();
     let repo_config = format!("host={host} user={user} password={password} dbname={db}").parse::<PostgresRepoConfig>()?.clear(true);
     let repo = PostgresRepo::new(repo_config).await?;
+    let mut changes = repo.watch().await?;
 
     let lyric1 = create_lyric(ROODKAPJE);
 
@@ -28,6 +31,9 @@ async fn test_lyric() -> Result<(), Box<dyn std::error::Error>> {
         repo.upsert_lyric(lyric1.clone()).await?;
     assert_eq!(lyric1.id, lyric1_posted.id);
 
+    let change = changes.next().await.unwrap();
+    assert!(matches!(change.transaction, Transaction::LyricUpsert(lyric) if lyric.id == lyric1.id));
+
     let lyric2: Lyric = create_lyric(MOLEN);
     let posted_lyric2 = repo.upsert_lyric(lyric2.clone()).await?;
     assert_eq!(lyric2.id, posted_lyric2.id);
@@ -46,28 +52,62 @@ async fn test_lyric() -> Result<(), Box<dyn std::error::Error>> {
     let summaries: Vec<String> = repo.get_lyric_summaries().await?.into_iter().map(|s| s.title).collect();
     assert_eq!(summaries, vec!["Roodkapje".to_string(), "Sinterklaas".to_string()]);
 
+    let page = repo.get_lyric_summaries_page(ListQuery { limit: Some(1), order: SortOrder::Desc, ..Default::default() }).await?;
+    assert_eq!(page.total, 2);
+    assert_eq!(page.items[0].title, "Sinterklaas".to_owned());
+
+    let page = repo.get_lyric_summaries_page(ListQuery { prefix: Some("ROOD".to_owned()), ..Default::default() }).await?;
+    assert_eq!(page.items.iter().map(|s| s.id).collect::<Vec<_>>(), vec![lyric1.id]);
+
     let detail = repo.get_lyric(lyric3.id).await?;
     assert_eq!(detail.parts[0][0], "Zie ginds komt de stoomboot uit Spanje weer aan".to_owned());
+    assert_eq!(detail.metadata.lyricist, Some("Jan Schenkman".to_owned()));
+    assert_eq!(detail.metadata.year, Some(1850));
+
+    let hits = repo.search_lyrics("STOOMBOOT spánje").await?;
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].summary.id, lyric3.id);
+    assert_eq!(hits[0].line, Some("Zie ginds komt de stoomboot uit Spanje weer aan".to_owned()));
 
     let lyric4: Lyric = (
         None,
         LyricPost {
             title: "Sinterklaas".to_owned(),
             parts: vec![],
+            chords: vec![],
+            labels: vec![],
+            arrangement: vec![],
+            translations: Default::default(),
+            tags: Default::default(),
+            metadata: Default::default(),
         }
     )
     .into();
     let failed_insert = repo.upsert_lyric(lyric4).await;
     assert_eq!(failed_insert.is_ok(), false);
 
+    let etag = detail.etag().unwrap();
+    let mut lyric3_changed = detail.clone();
+    lyric3_changed.title = "Sinterklaas kapoentje".to_owned();
+    let mismatch = repo.upsert_lyric_if_match(lyric3_changed.clone(), "\"stale\"".to_owned()).await;
+    assert!(matches!(mismatch, Err(Error::EtagMismatch(_))));
+
+    let lyric3_updated = repo.upsert_lyric_if_match(lyric3_changed, etag.clone()).await?;
+    assert_eq!(lyric3_updated.title, "Sinterklaas kapoentje".to_owned());
+
+    let mismatch = repo.delete_lyric_if_match(lyric3.id, etag).await;
+    assert!(matches!(mismatch, Err(Error::EtagMismatch(_))));
+
     let playlist: Playlist = (
         None,
         PlaylistPost { 
             title: "Alles".to_owned(), 
             members: vec![
-                lyric3.id.clone(),
-                lyric1.id.clone(),
-            ]
+                lyric3.id,
+                lyric1.id,
+            ],
+            entries: vec![],
+            event: None,
         }
     )
     .into();
@@ -83,5 +123,34 @@ async fn test_lyric() -> Result<(), Box<dyn std::error::Error>> {
 
     repo.upsert_playlist(playlist2).await?;
 
+    let lyric5 = create_lyric(MOLEN);
+    let invalid: Playlist = (None, PlaylistPost { title: "Ongeldig".to_owned(), members: vec![lyric2.id], entries: vec![], event: None }).into();
+    let failed_batch = repo.apply_batch(vec![Transaction::LyricUpsert(lyric5.clone()), Transaction::PlaylistUpsert(invalid)]).await;
+    assert!(failed_batch.is_err());
+    assert!(repo.get_lyric(lyric5.id).await.is_err());
+
+    repo.apply_batch(vec![Transaction::LyricUpsert(lyric5.clone()), Transaction::LyricDelete(lyric1.id)]).await?;
+    assert_eq!(repo.get_lyric(lyric5.id).await?.title, "Daar bij die molen".to_owned());
+    assert_eq!(repo.get_playlist(playlist.id).await?.members, vec![lyric3.id]);
+
+    let trash = repo.list_trash().await?;
+    assert_eq!(trash.iter().map(|trashed| trashed.id()).collect::<Vec<_>>(), vec![lyric1.id, lyric2.id]);
+    repo.restore(lyric1.id).await?;
+    assert_eq!(repo.get_playlist(playlist.id).await?.members, vec![lyric3.id, lyric1.id]);
+
+    repo.purge(lyric2.id).await?;
+    assert!(matches!(repo.restore(lyric2.id).await, Err(Error::NotFound(id)) if id == lyric2.id));
+    assert!(repo.list_trash().await?.is_empty());
+    assert!(matches!(repo.get_lyric_history(lyric2.id).await, Err(Error::NotFound(id)) if id == lyric2.id));
+
+    assert_eq!(repo.get_lyric_history(lyric1.id).await?.len(), 1);
+    let history = repo.get_lyric_history(lyric3.id).await?;
+    assert_eq!(history.iter().map(|revision| revision.rev).collect::<Vec<_>>(), vec![1, 2]);
+    assert_eq!(history[0].diff(&history[1]).title, Some(("Sinterklaas".to_owned(), "Sinterklaas kapoentje".to_owned())));
+    let reverted = repo.revert_lyric(lyric3.id, 1).await?;
+    assert_eq!(reverted.title, "Sinterklaas".to_owned());
+    assert_eq!(repo.get_lyric_revision(lyric3.id, 3).await?.lyric.title, "Sinterklaas".to_owned());
+    assert!(matches!(repo.get_lyric_revision(lyric3.id, 4).await, Err(Error::NotFound(_))));
+
     Ok(())
 }
@@ -0,0 +1,19 @@
+local key = table.concat({'history', ARGV[1]}, ':')
+local last = redis.call('LINDEX', key, -1)
+local rev = 1
+
+if last then
+    local rev_end = string.find(last, ' ', 1, true)
+    local timestamp_end = string.find(last, ' ', rev_end + 1, true)
+    if string.sub(last, timestamp_end + 1) == ARGV[2] then
+        return 0
+    end
+    rev = tonumber(string.sub(last, 1, rev_end - 1)) + 1
+end
+
+local time = redis.call('TIME')
+local timestamp = string.format('%d', time[1] * 1000 + math.floor(time[2] / 1000))
+
+redis.call('RPUSH', key, table.concat({string.format('%d', rev), timestamp, ARGV[2]}, ' '))
+
+return rev
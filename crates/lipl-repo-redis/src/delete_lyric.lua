@@ -1,21 +1,46 @@
 local playlists = redis.call('KEYS', 'playlist:*')
+local positions = {}
 
 for i,playlist_key in ipairs(playlists) do
     local members = {}
     local needs_update = false
+    local index = 0
     for i in string.gmatch(redis.call('HGET', playlist_key, 'members'), '%S+') do
         if i == ARGV[1] then
             needs_update = true
+            table.insert(positions, table.concat({string.sub(playlist_key, 10), index}, ':'))
         else
             table.insert(members, i)
         end
+        index = index + 1
     end
     if needs_update then
         redis.call('HSET', playlist_key, 'members', table.concat(members, ' '))
+        local entries = redis.call('HGET', playlist_key, 'entries')
+        if entries then
+            local kept = {}
+            local lyric = table.concat({'"lyric":"', ARGV[1], '"'})
+            for entry in string.gmatch(entries, '[^\n]+') do
+                if not string.find(entry, lyric, 1, true) then
+                    table.insert(kept, entry)
+                end
+            end
+            redis.call('HSET', playlist_key, 'entries', table.concat(kept, '\n'))
+        end
     end
 end
 
+local words_key = table.concat({'words', ARGV[1]}, ':')
+for i,word in ipairs(redis.call('SMEMBERS', words_key)) do
+    redis.call('SREM', table.concat({'word', word}, ':'), ARGV[1])
+end
+redis.call('DEL', words_key)
+
 local lyric_key = table.concat({'lyric', ARGV[1]}, ':')
-redis.call('DEL', lyric_key)
+if redis.call('EXISTS', lyric_key) == 1 then
+    local trash_key = table.concat({'trash', ARGV[1]}, ':')
+    redis.call('RENAME', lyric_key, trash_key)
+    redis.call('HSET', trash_key, 'kind', 'lyric', 'deleted', ARGV[2], 'positions', table.concat(positions, ' '))
+end
 
 return
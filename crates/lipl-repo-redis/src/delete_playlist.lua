@@ -0,0 +1,8 @@
+local playlist_key = table.concat({'playlist', ARGV[1]}, ':')
+if redis.call('EXISTS', playlist_key) == 1 then
+    local trash_key = table.concat({'trash', ARGV[1]}, ':')
+    redis.call('RENAME', playlist_key, trash_key)
+    redis.call('HSET', trash_key, 'kind', 'playlist', 'deleted', ARGV[2])
+end
+
+return
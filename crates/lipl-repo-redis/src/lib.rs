@@ -11,6 +11,12 @@ pub fn new_lyric(title: &str, text: &str) -> Lyric {
         id: Uuid::default(),
         title: title.to_owned(),
         parts: to_parts(text.to_owned()),
+        chords: vec![],
+        labels: vec![],
+        arrangement: vec![],
+        translations: Default::default(),
+        tags: Default::default(),
+        metadata: Default::default(),
     }
 }
 
@@ -19,5 +25,7 @@ pub fn new_playlist(title: &str, members: Vec<Uuid>) -> Playlist {
         id: Uuid::default(),
         title: title.to_owned(),
         members,
+        entries: vec![],
+        event: None,
     }
 }
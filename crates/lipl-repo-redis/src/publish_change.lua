@@ -0,0 +1,7 @@
+local sequence = redis.call('INCR', 'change:sequence')
+local time = redis.call('TIME')
+local timestamp = string.format('%d', time[1] * 1000 + math.floor(time[2] / 1000))
+
+redis.call('PUBLISH', 'change', table.concat({sequence, timestamp, ARGV[1]}, ' '))
+
+return sequence
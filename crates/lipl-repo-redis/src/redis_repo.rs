@@ -1,31 +1,134 @@
 use async_trait::async_trait;
-use bb8_redis::{bb8::{Pool, PooledConnection}, RedisConnectionManager, redis::{cmd, IntoConnectionInfo}};
-use bb8_redis::redis::{AsyncCommands};
-use futures_util::{FutureExt, TryFutureExt, future::try_join_all};
-use parts::{to_parts, to_text};
-use std::{collections::{HashMap}, ops::DerefMut, sync::Arc, str::FromStr};
-use lipl_core::{Lyric, Uuid, error::RedisRepoError, Playlist, Summary, LiplRepo, by_title, ToRepo};
+use bb8_redis::{bb8::{Pool, PooledConnection}, RedisConnectionManager, redis::{cmd, Client, ConnectionInfo, IntoConnectionInfo}};
+use bb8_redis::redis::{AsyncCommands, Pipeline, pipe};
+use futures_util::{FutureExt, StreamExt, TryFutureExt, future::{ready, try_join_all}};
+use std::{collections::{HashMap, HashSet}, ops::DerefMut, sync::Arc, str::FromStr};
+use lipl_core::{change::now, check_etag, chords, duplicate, search, tag, trash::sorted_by_deleted, Change, ChangeStream, DateRange, Error, Etag, ListQuery, Lyric, Merge, Page, LyricMetadata, Uuid, error::RedisRepoError, Playlist, PlaylistEntry, PlaylistPosition, Revision, SearchHit, Summary, TagCount, LiplRepo, Transaction, TrashItem, Trashed, by_title, ToRepo};
 use crate::Result;
 
 const LYRIC: &str = "lyric";
 const PLAYLIST: &str = "playlist";
+const WORD: &str = "word";
+const WORDS: &str = "words";
+const CHANGE: &str = "change";
+const TRASH: &str = "trash";
+const HISTORY: &str = "history";
 const TEXT_ATTR: &str = "text";
 const TITLE_ATTR: &str = "title";
 const MEMBERS_ATTR: &str = "members";
+const SUB_TITLE_ATTR: &str = "sub_title";
+const LYRICIST_ATTR: &str = "lyricist";
+const COMPOSER_ATTR: &str = "composer";
+const LANGUAGE_ATTR: &str = "language";
+const YEAR_ATTR: &str = "year";
+const COPYRIGHT_ATTR: &str = "copyright";
+const SOURCE_ATTR: &str = "source";
+const LABELS_ATTR: &str = "labels";
+const ARRANGEMENT_ATTR: &str = "arrangement";
+const TRANSLATIONS_ATTR: &str = "translations";
+const TAGS_ATTR: &str = "tags";
+const ENTRIES_ATTR: &str = "entries";
+const EVENT_ATTR: &str = "event";
+const KIND_ATTR: &str = "kind";
+const DELETED_ATTR: &str = "deleted";
+const POSITIONS_ATTR: &str = "positions";
 const WILDCARD: &str = "*";
 const SEP: &str = ":";
 const LYRIC_ALL: [&str; 3] = [LYRIC, SEP, WILDCARD];
 const PLAYLIST_ALL: [&str; 3] = [PLAYLIST, SEP, WILDCARD];
+const TRASH_ALL: [&str; 3] = [TRASH, SEP, WILDCARD];
 
 fn bs58_to_uuid(r: Result<Vec<String>>) -> Result<Vec<Uuid>> {
     r.and_then(|keys| keys.iter().map(|s| key_to_uuid(s)).collect::<Result<Vec<_>>>())    
 }
 
+fn hashmap_to_metadata(hm: &HashMap<String, String>) -> LyricMetadata {
+    LyricMetadata {
+        sub_title: hm.get(SUB_TITLE_ATTR).cloned(),
+        lyricist: hm.get(LYRICIST_ATTR).cloned(),
+        composer: hm.get(COMPOSER_ATTR).cloned(),
+        language: hm.get(LANGUAGE_ATTR).cloned(),
+        year: hm.get(YEAR_ATTR).and_then(|year| year.parse::<u16>().ok()),
+        copyright: hm.get(COPYRIGHT_ATTR).cloned(),
+        source: hm.get(SOURCE_ATTR).cloned(),
+    }
+}
+
+fn metadata_to_attrs(metadata: &LyricMetadata) -> Vec<(&'static str, String)> {
+    [
+        (SUB_TITLE_ATTR, metadata.sub_title.clone()),
+        (LYRICIST_ATTR, metadata.lyricist.clone()),
+        (COMPOSER_ATTR, metadata.composer.clone()),
+        (LANGUAGE_ATTR, metadata.language.clone()),
+        (YEAR_ATTR, metadata.year.map(|year| year.to_string())),
+        (COPYRIGHT_ATTR, metadata.copyright.clone()),
+        (SOURCE_ATTR, metadata.source.clone()),
+    ]
+    .into_iter()
+    .filter_map(|(attr, value)| value.map(|value| (attr, value)))
+    .collect()
+}
+
+/// Labels, the arrangement and the tags are stored one per line, a part without a label as an empty line
+fn arrangement_to_attrs(lyric: &Lyric) -> Vec<(&'static str, String)> {
+    [
+        (LABELS_ATTR, lyric.labels.iter().map(|label| label.clone().unwrap_or_default()).collect::<Vec<_>>()),
+        (ARRANGEMENT_ATTR, lyric.arrangement.clone()),
+        (TAGS_ATTR, lyric.tags.iter().cloned().collect()),
+    ]
+    .into_iter()
+    .filter(|(_, lines)| !lines.is_empty())
+    .map(|(attr, lines)| (attr, lines.join("\n")))
+    .collect()
+}
+
+fn hashmap_to_lines(hm: &HashMap<String, String>, attr: &str) -> Vec<String> {
+    hm.get(attr).map(|lines| lines.split('\n').map(str::to_owned).collect()).unwrap_or_default()
+}
+
+fn lyric_to_attrs(lyric: &Lyric) -> Vec<(&'static str, String)> {
+    [
+        (TITLE_ATTR, lyric.title.clone()), 
+        (TEXT_ATTR, lyric.text()),
+    ]
+    .into_iter()
+    .chain(metadata_to_attrs(&lyric.metadata))
+    .chain(arrangement_to_attrs(lyric))
+    .chain(
+        Some(&lyric.translations)
+        .filter(|translations| !translations.is_empty())
+        .and_then(|translations| serde_json::to_string(translations).ok())
+        .map(|json| (TRANSLATIONS_ATTR, json))
+    )
+    .collect()
+}
+
+/// The entries are stored as one json object per line, so delete_lyric.lua can remove the lines of a lyric.
+/// The attribute is always written, empty if the playlist has no more than members, to replace entries stored before.
+/// The event is written the same way, as json or empty if the playlist is not scheduled.
+fn playlist_to_attrs(playlist: &Playlist) -> Vec<(&'static str, String)> {
+    vec![
+        (TITLE_ATTR, playlist.title.clone()),
+        (MEMBERS_ATTR, playlist.members.iter().map(|id| id.to_string()).collect::<Vec<_>>().join(" ")),
+        (ENTRIES_ATTR, playlist.entries.iter().filter_map(|entry| serde_json::to_string(entry).ok()).collect::<Vec<_>>().join("\n")),
+        (EVENT_ATTR, playlist.event.as_ref().and_then(|event| serde_json::to_string(event).ok()).unwrap_or_default()),
+    ]
+}
+
 fn hashmap_to_lyric(id: Uuid) -> impl Fn(HashMap<String, String>) -> Lyric {
-    move |hm| Lyric { 
-        id, 
-        title: hm.get(TITLE_ATTR).cloned().unwrap_or_default(), 
-        parts: to_parts(hm.get(TEXT_ATTR).cloned().unwrap_or_default()),
+    move |hm| {
+        let (parts, chords) = chords::from_text(hm.get(TEXT_ATTR).cloned().unwrap_or_default());
+        Lyric { 
+            id, 
+            title: hm.get(TITLE_ATTR).cloned().unwrap_or_default(), 
+            parts,
+            chords,
+            labels: hashmap_to_lines(&hm, LABELS_ATTR).into_iter().map(|label| Some(label).filter(|label| !label.is_empty())).collect(),
+            arrangement: hashmap_to_lines(&hm, ARRANGEMENT_ATTR),
+            translations: hm.get(TRANSLATIONS_ATTR).and_then(|json| serde_json::from_str(json).ok()).unwrap_or_default(),
+            tags: hashmap_to_lines(&hm, TAGS_ATTR).into_iter().collect(),
+            metadata: hashmap_to_metadata(&hm),
+        }
     }
 }
 
@@ -41,18 +144,70 @@ fn hashmap_to_playlist(id: Uuid) -> impl Fn(Result<HashMap<String, String>>) ->
         hm.get(MEMBERS_ATTR)
         .cloned()
         .unwrap_or_default()
-        .split(' ')
+        .split_whitespace()
         .map(|key| key.parse::<Uuid>().ok().ok_or(RedisRepoError::Key(key.to_owned())))
         .collect::<Result<Vec<Uuid>>>()
         .and_then(|members| hm.get(TITLE_ATTR).ok_or(RedisRepoError::Key(id.to_string())).cloned().map(|title| (members, title)))
-        .map(|(members, title)| Playlist {
-            id,
-            title,
-            members,
+        .map(|(members, title)| {
+            let event = hm.get(EVENT_ATTR).and_then(|json| serde_json::from_str(json).ok());
+            let mut playlist = Playlist { id, title, members, entries: vec![], event };
+            let entries =
+                hashmap_to_lines(&hm, ENTRIES_ATTR)
+                .into_iter()
+                .filter_map(|line| serde_json::from_str::<PlaylistEntry>(&line).ok())
+                .collect::<Vec<_>>();
+            if !entries.is_empty() {
+                playlist.set_entries(entries);
+            }
+            playlist
         })
     )
 }
 
+/// The trash hash is the renamed lyric or playlist hash, with the kind, the deletion time and for a lyric
+/// the positions in the playlists as playlist:index pairs, added by delete_lyric.lua and delete_playlist.lua
+fn hashmap_to_trashed(id: Uuid) -> impl Fn(HashMap<String, String>) -> Result<Trashed> {
+    move |hm| {
+        let item = match hm.get(KIND_ATTR).map(String::as_str) {
+            Some(LYRIC) => TrashItem::Lyric(hashmap_to_lyric(id)(hm.clone())),
+            _ => TrashItem::Playlist(hashmap_to_playlist(id)(Ok(hm.clone()))?),
+        };
+        let positions = 
+            hm.get(POSITIONS_ATTR)
+            .map(|positions| 
+                positions
+                .split_whitespace()
+                .map(|position| 
+                    position
+                    .split_once(SEP)
+                    .and_then(|(playlist, index)| Some(PlaylistPosition { playlist: playlist.parse().ok()?, index: index.parse().ok()? }))
+                    .ok_or(RedisRepoError::Key(position.to_owned()))
+                )
+                .collect::<Result<Vec<_>>>()
+            )
+            .transpose()?
+            .unwrap_or_default();
+        Ok(
+            Trashed {
+                item,
+                deleted: hm.get(DELETED_ATTR).and_then(|deleted| deleted.parse().ok()).unwrap_or_default(),
+                positions,
+            }
+        )
+    }
+}
+
+/// The members that are stored lyrics
+async fn existing_lyrics<C: AsyncCommands>(connection: &mut C, members: &[Uuid]) -> lipl_core::Result<HashSet<Uuid>> {
+    let mut existing = HashSet::new();
+    for member in members {
+        if connection.exists::<_, bool>(lyric_key(*member)).err_into::<RedisRepoError>().await? {
+            existing.insert(*member);
+        }
+    }
+    Ok(existing)
+}
+
 fn lyric_key(id: Uuid) -> String {
     format!("{}{}{}", LYRIC, SEP, id)
 }
@@ -61,6 +216,76 @@ fn playlist_key(id: Uuid) -> String {
     format!("{}{}{}", PLAYLIST, SEP, id)
 }
 
+fn trash_key(id: Uuid) -> String {
+    format!("{}{}{}", TRASH, SEP, id)
+}
+
+fn history_key(id: Uuid) -> String {
+    format!("{}{}{}", HISTORY, SEP, id)
+}
+
+fn word_key(word: &str) -> String {
+    format!("{}{}{}", WORD, SEP, word)
+}
+
+fn words_key(id: Uuid) -> String {
+    format!("{}{}{}", WORDS, SEP, id)
+}
+
+fn current_lyric(id: Uuid) -> impl Fn(HashMap<String, String>) -> Option<Lyric> {
+    move |hm| if hm.is_empty() { None } else { Some(hashmap_to_lyric(id)(hm)) }
+}
+
+fn upsert_lyric_pipeline(lyric: &Lyric, current: Option<&Lyric>) -> Pipeline {
+    let mut pipeline = pipe();
+    pipeline.atomic();
+    add_upsert_lyric(&mut pipeline, lyric, current);
+    pipeline
+}
+
+/// Replaces the lyric hash and updates the secondary search index, with sets of lyric ids per word
+/// and a set with the words per lyric, used by delete_lyric.lua
+fn add_upsert_lyric(pipeline: &mut Pipeline, lyric: &Lyric, current: Option<&Lyric>) {
+    let key = lyric_key(lyric.id);
+    let id = lyric.id.to_string();
+    let words = search::lyric_words(lyric);
+    let old_words = current.map(search::lyric_words).unwrap_or_default();
+
+    pipeline.del(&key).hset_multiple(&key, &lyric_to_attrs(lyric)).del(words_key(lyric.id));
+    for word in old_words.difference(&words) {
+        pipeline.srem(word_key(word), &id);
+    }
+    for word in words.iter() {
+        pipeline.sadd(word_key(word), &id);
+    }
+    if !words.is_empty() {
+        pipeline.sadd(words_key(lyric.id), words.into_iter().collect::<Vec<_>>());
+    }
+}
+
+fn to_json<T: lipl_core::reexport::serde::Serialize>(t: &T) -> lipl_core::Result<String> {
+    serde_json::to_string(t).map_err(|error| Error::Json(Box::new(error)))
+}
+
+/// Parses an entry of the history list written by add_revision.lua: revision, timestamp and the lyric as json
+fn entry_to_revision(entry: &str) -> lipl_core::Result<Revision> {
+    let mut fields = entry.splitn(3, ' ');
+    let mut next = || fields.next().ok_or_else(|| Error::from(RedisRepoError::Key(HISTORY.to_owned())));
+    let rev = next()?.parse::<u64>().map_err(|_| RedisRepoError::Key(HISTORY.to_owned()))?;
+    let timestamp = next()?.parse::<u64>().map_err(|_| RedisRepoError::Key(HISTORY.to_owned()))?;
+    let lyric = serde_json::from_str::<Lyric>(next()?).map_err(|error| Error::Json(Box::new(error)))?;
+    Ok(Revision { rev, timestamp, lyric })
+}
+
+/// Parses the message published by publish_change.lua: sequence, timestamp and the transaction as json
+fn message_to_change(message: &str) -> Option<Change> {
+    let mut fields = message.splitn(3, ' ');
+    let sequence = fields.next()?.parse::<u64>().ok()?;
+    let timestamp = fields.next()?.parse::<u64>().ok()?;
+    let transaction = serde_json::from_str::<Transaction>(fields.next()?).ok()?;
+    Some(Change { sequence, timestamp, transaction })
+}
+
 fn key_to_uuid(key: &str) -> Result<Uuid> {
     key.split(':')
         .collect::<Vec<&str>>()
@@ -133,7 +358,11 @@ where
 
 pub struct RedisRepo {
     pool: Pool<RedisConnectionManager>,
+    connection_info: ConnectionInfo,
     delete_lyric_sha: String,
+    delete_playlist_sha: String,
+    publish_change_sha: String,
+    add_revision_sha: String,
 }
 
 impl RedisRepo {
@@ -141,14 +370,15 @@ impl RedisRepo {
     where
         T: IntoConnectionInfo,
     {
-        let manager = bb8_redis::RedisConnectionManager::new(config.url).map_err(RedisRepoError::from)?;
+        let connection_info = config.url.into_connection_info().map_err(RedisRepoError::from)?;
+        let manager = bb8_redis::RedisConnectionManager::new(connection_info.clone()).map_err(RedisRepoError::from)?;
         let pool = bb8_redis::bb8::Pool::builder().build(manager).err_into::<RedisRepoError>().await?;
 
         let pool_clone = pool.clone();
         let mut connection = pool_clone.get().err_into::<RedisRepoError>().await?;
 
         if config.clear {
-            cmd("FLUSHALL").query_async(connection.deref_mut()).err_into::<RedisRepoError>().await?;
+            cmd("FLUSHALL").query_async::<_, ()>(connection.deref_mut()).err_into::<RedisRepoError>().await?;
 
         }
 
@@ -160,43 +390,303 @@ impl RedisRepo {
                 .err_into::<RedisRepoError>()
                 .await?;
 
+        let delete_playlist_sha: String = 
+            cmd("SCRIPT")
+                .arg("LOAD")
+                .arg(include_str!("delete_playlist.lua"))
+                .query_async(connection.deref_mut())
+                .err_into::<RedisRepoError>()
+                .await?;
+
+        let publish_change_sha: String = 
+            cmd("SCRIPT")
+                .arg("LOAD")
+                .arg(include_str!("publish_change.lua"))
+                .query_async(connection.deref_mut())
+                .err_into::<RedisRepoError>()
+                .await?;
+
+        let add_revision_sha: String = 
+            cmd("SCRIPT")
+                .arg("LOAD")
+                .arg(include_str!("add_revision.lua"))
+                .query_async(connection.deref_mut())
+                .err_into::<RedisRepoError>()
+                .await?;
+
         Ok(
-            Self { pool, delete_lyric_sha }
+            Self { pool, connection_info, delete_lyric_sha, delete_playlist_sha, publish_change_sha, add_revision_sha }
         )
     }
 
-    async fn delete_lyric_script(&self, id: Uuid) -> Result<()> {
-        let mut connection = self.connection().await?;
-        cmd("EVALSHA")
+    /// Moves the lyric to the trash, after removing it from the playlists and the search index
+    fn add_delete_lyric(&self, pipeline: &mut Pipeline, id: Uuid) {
+        pipeline
+            .cmd("EVALSHA")
             .arg(self.delete_lyric_sha.clone())
             .arg("0")
             .arg(id.to_string())
-            .query_async(connection.deref_mut())
+            .arg(now());
+    }
+
+    fn add_delete_playlist(&self, pipeline: &mut Pipeline, id: Uuid) {
+        pipeline
+            .cmd("EVALSHA")
+            .arg(self.delete_playlist_sha.clone())
+            .arg("0")
+            .arg(id.to_string())
+            .arg(now());
+    }
+
+    /// Fails with Error::NotFound if there is no hash stored at key
+    async fn check_exists(&self, key: String, id: Uuid) -> lipl_core::Result<()> {
+        let mut connection = self.connection().await?;
+        if connection.exists::<_, bool>(key).err_into::<RedisRepoError>().await? {
+            Ok(())
+        }
+        else {
+            Err(Error::NotFound(id))
+        }
+    }
+
+    /// Appends the lyric to its history, unless it is the same as the last revision
+    fn add_revision(&self, pipeline: &mut Pipeline, id: Uuid, json: String) {
+        pipeline
+            .cmd("EVALSHA")
+            .arg(self.add_revision_sha.clone())
+            .arg("0")
+            .arg(id.to_string())
+            .arg(json);
+    }
+
+    /// Publishes the transaction to the watchers when the pipeline is executed, so only if the other commands succeed
+    fn add_publish_change(&self, pipeline: &mut Pipeline, json: String) {
+        pipeline
+            .cmd("EVALSHA")
+            .arg(self.publish_change_sha.clone())
+            .arg("0")
+            .arg(json);
+    }
+
+    /// Executes the commands and publishes the transaction atomically
+    async fn execute_and_publish(&self, mut pipeline: Pipeline, transaction: &Transaction) -> lipl_core::Result<()> {
+        self.add_publish_change(&mut pipeline, to_json(transaction)?);
+        let mut connection = self.connection().await?;
+        pipeline
+            .query_async::<_, ()>(connection.deref_mut())
+            .err_into::<RedisRepoError>()
             .await?;
         Ok(())
     }
 
-    async fn connection(&self) -> Result<PooledConnection<RedisConnectionManager>> {
+    /// Executes the commands in a transaction, if the etag matches the current version of the hash stored at key.
+    /// The members of the playlist, if there is one, are checked to be stored lyrics. The key and the members are watched and
+    /// the transaction is retried if one of them is changed by another client in between, so a changed hash fails the etag check.
+    async fn execute_if_match<T, F, G>(&self, id: Uuid, key: String, etag: &str, playlist: Option<&Playlist>, convert: F, transaction: G) -> lipl_core::Result<()>
+    where
+        F: Fn(HashMap<String, String>) -> Option<T>,
+        G: Fn(Option<&T>) -> Pipeline,
+        T: Etag,
+    {
+        let members = playlist.map(|playlist| playlist.members.as_slice()).unwrap_or_default();
+        let mut connection = self.connection().await?;
+        loop {
+            let keys = std::iter::once(key.clone()).chain(members.iter().map(|member| lyric_key(*member))).collect::<Vec<_>>();
+            cmd("WATCH").arg(keys).query_async::<_, ()>(connection.deref_mut()).err_into::<RedisRepoError>().await?;
+            let hm: HashMap<String, String> = connection.hgetall(&key).err_into::<RedisRepoError>().await?;
+            let current = if hm.is_empty() { None } else { convert(hm) };
+            let existing = existing_lyrics(connection.deref_mut(), members).await?;
+            let checked = 
+                check_etag(id, etag, current.as_ref())
+                .and_then(|_| playlist.map_or(Ok(()), |playlist| lipl_core::check_members(playlist, |member| existing.contains(member))));
+            if let Err(error) = checked {
+                cmd("UNWATCH").query_async::<_, ()>(connection.deref_mut()).err_into::<RedisRepoError>().await?;
+                return Err(error);
+            }
+            let result = 
+                transaction(current.as_ref())
+                .query_async::<_, Option<()>>(connection.deref_mut())
+                .err_into::<RedisRepoError>()
+                .await?;
+            if result.is_some() {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Executes all transactions in one MULTI/EXEC block. The lyrics to be replaced are watched, because their current
+    /// words are needed to update the search index. The batch is retried if one of them is changed in between.
+    async fn execute_batch(&self, batch: &[Transaction]) -> lipl_core::Result<()> {
+        let mut connection = self.connection().await?;
+        loop {
+            if self.try_batch(connection.deref_mut(), batch).await?.is_some() {
+                return Ok(());
+            }
+        }
+    }
+
+    /// One attempt of execute_batch. Keys watched by the caller stay watched, so None is returned if one of those is changed as well.
+    async fn try_batch<C: AsyncCommands>(&self, connection: &mut C, batch: &[Transaction]) -> lipl_core::Result<Option<()>> {
+        let ids = batch.iter().filter_map(|transaction| match transaction {
+            Transaction::LyricUpsert(lyric) => Some(lyric.id),
+            _ => None,
+        })
+        .collect::<HashSet<_>>();
+        let mut current = HashMap::<Uuid, Option<Lyric>>::new();
+        if !ids.is_empty() {
+            cmd("WATCH").arg(ids.iter().map(|id| lyric_key(*id)).collect::<Vec<_>>()).query_async::<_, ()>(connection).err_into::<RedisRepoError>().await?;
+            for id in ids.iter() {
+                let lyric = connection.hgetall(lyric_key(*id)).map_ok(current_lyric(*id)).err_into::<RedisRepoError>().await?;
+                current.insert(*id, lyric);
+            }
+        }
+
+        let mut pipeline = pipe();
+        pipeline.atomic();
+        for transaction in batch {
+            match transaction {
+                Transaction::LyricDelete(id) => {
+                    current.insert(*id, None);
+                    self.add_delete_lyric(&mut pipeline, *id);
+                },
+                Transaction::LyricUpsert(lyric) => {
+                    let old = current.insert(lyric.id, Some(lyric.clone())).flatten();
+                    add_upsert_lyric(&mut pipeline, lyric, old.as_ref());
+                    self.add_revision(&mut pipeline, lyric.id, to_json(lyric)?);
+                },
+                Transaction::PlaylistDelete(id) => {
+                    self.add_delete_playlist(&mut pipeline, *id);
+                },
+                Transaction::PlaylistUpsert(playlist) => {
+                    let keys = playlist.members.iter().filter(|member| !current.contains_key(member)).map(|member| lyric_key(*member)).collect::<Vec<_>>();
+                    if !keys.is_empty() {
+                        cmd("WATCH").arg(keys).query_async::<_, ()>(connection).err_into::<RedisRepoError>().await?;
+                    }
+                    let existing = existing_lyrics(connection, &playlist.members).await?;
+                    let is_lyric = |member: &Uuid| current.get(member).map(Option::is_some).unwrap_or_else(|| existing.contains(member));
+                    if let Err(error) = lipl_core::check_members(playlist, is_lyric) {
+                        cmd("UNWATCH").query_async::<_, ()>(connection).err_into::<RedisRepoError>().await?;
+                        return Err(error);
+                    }
+                    pipeline.hset_multiple(playlist_key(playlist.id), &playlist_to_attrs(playlist));
+                },
+            }
+            self.add_publish_change(&mut pipeline, to_json(transaction)?);
+        }
+
+        pipeline
+        .query_async::<_, Option<()>>(connection)
+        .err_into::<RedisRepoError>()
+        .await
+        .map_err(Error::from)
+    }
+
+    /// Watches the lyric to keep and the playlists, and computes the merge from the playlists read under the watch.
+    /// The merge is retried if the lyric or one of the playlists is changed in between.
+    async fn execute_merge(&self, merge: &Merge) -> lipl_core::Result<Lyric> {
+        let mut connection = self.connection().await?;
+        loop {
+            let playlist_keys = connection.keys::<_, Vec<String>>(PLAYLIST_ALL.concat()).err_into::<RedisRepoError>().await?;
+            let keys = std::iter::once(lyric_key(merge.keep)).chain(playlist_keys.iter().cloned()).collect::<Vec<_>>();
+            cmd("WATCH").arg(keys).query_async::<_, ()>(connection.deref_mut()).err_into::<RedisRepoError>().await?;
+            let Some(lyric) = connection.hgetall(lyric_key(merge.keep)).map_ok(current_lyric(merge.keep)).err_into::<RedisRepoError>().await? else {
+                cmd("UNWATCH").query_async::<_, ()>(connection.deref_mut()).err_into::<RedisRepoError>().await?;
+                return Err(Error::NotFound(merge.keep));
+            };
+            let mut playlists = vec![];
+            for id in bs58_to_uuid(Ok(playlist_keys))? {
+                let hm: HashMap<String, String> = connection.hgetall(playlist_key(id)).err_into::<RedisRepoError>().await?;
+                if !hm.is_empty() {
+                    playlists.push(hashmap_to_playlist(id)(Ok(hm))?);
+                }
+            }
+            if self.try_batch(connection.deref_mut(), &duplicate::merge_transactions(merge, playlists)).await?.is_some() {
+                return Ok(lyric);
+            }
+        }
+    }
+
+    /// Takes the item out of the trash and upserts it, together with the playlists a lyric was a member of.
+    /// The keys read are watched and the restore is retried if one of them is changed in between.
+    /// Fails with Occupied when an item with the same id was stored after the delete.
+    async fn execute_restore(&self, id: Uuid) -> lipl_core::Result<()> {
+        let mut connection = self.connection().await?;
+        loop {
+            cmd("WATCH").arg(trash_key(id)).query_async::<_, ()>(connection.deref_mut()).err_into::<RedisRepoError>().await?;
+            let hm: HashMap<String, String> = connection.hgetall(trash_key(id)).err_into::<RedisRepoError>().await?;
+            if hm.is_empty() {
+                cmd("UNWATCH").query_async::<_, ()>(connection.deref_mut()).err_into::<RedisRepoError>().await?;
+                return Err(Error::NotFound(id));
+            }
+            let trashed = hashmap_to_trashed(id)(hm)?;
+            let key = match &trashed.item {
+                TrashItem::Lyric(_) => lyric_key(id),
+                TrashItem::Playlist(_) => playlist_key(id),
+            };
+            cmd("WATCH").arg(&key).query_async::<_, ()>(connection.deref_mut()).err_into::<RedisRepoError>().await?;
+            if connection.exists::<_, bool>(&key).err_into::<RedisRepoError>().await? {
+                cmd("UNWATCH").query_async::<_, ()>(connection.deref_mut()).err_into::<RedisRepoError>().await?;
+                return Err(Error::Occupied);
+            }
+
+            let mut pipeline = pipe();
+            pipeline.atomic();
+            let mut transactions = vec![];
+            match &trashed.item {
+                TrashItem::Lyric(lyric) => {
+                    let playlist_ids = trashed.playlist_ids();
+                    if !playlist_ids.is_empty() {
+                        let keys = playlist_ids.iter().map(|id| playlist_key(*id)).collect::<Vec<_>>();
+                        cmd("WATCH").arg(keys).query_async::<_, ()>(connection.deref_mut()).err_into::<RedisRepoError>().await?;
+                    }
+                    add_upsert_lyric(&mut pipeline, lyric, None);
+                    self.add_revision(&mut pipeline, lyric.id, to_json(lyric)?);
+                    transactions.push(Transaction::LyricUpsert(lyric.clone()));
+                    for playlist_id in playlist_ids {
+                        let hm: HashMap<String, String> = connection.hgetall(playlist_key(playlist_id)).err_into::<RedisRepoError>().await?;
+                        if !hm.is_empty() {
+                            let mut playlist = hashmap_to_playlist(playlist_id)(Ok(hm))?;
+                            trashed.restore_positions(&mut playlist);
+                            pipeline.hset_multiple(playlist_key(playlist.id), &playlist_to_attrs(&playlist));
+                            transactions.push(Transaction::PlaylistUpsert(playlist));
+                        }
+                    }
+                },
+                TrashItem::Playlist(playlist) => {
+                    let mut playlist = playlist.clone();
+                    if !playlist.members.is_empty() {
+                        let keys = playlist.members.iter().map(|id| lyric_key(*id)).collect::<Vec<_>>();
+                        cmd("WATCH").arg(&keys).query_async::<_, ()>(connection.deref_mut()).err_into::<RedisRepoError>().await?;
+                        let existing = existing_lyrics(connection.deref_mut(), &playlist.members).await?;
+                        playlist.retain_members(|member| existing.contains(member));
+                    }
+                    pipeline.hset_multiple(playlist_key(playlist.id), &playlist_to_attrs(&playlist));
+                    transactions.push(Transaction::PlaylistUpsert(playlist));
+                },
+            }
+            pipeline.del(trash_key(id));
+            for transaction in transactions.iter() {
+                self.add_publish_change(&mut pipeline, to_json(transaction)?);
+            }
+
+            let result = 
+                pipeline
+                .query_async::<_, Option<()>>(connection.deref_mut())
+                .err_into::<RedisRepoError>()
+                .await?;
+            if result.is_some() {
+                return Ok(());
+            }
+        }
+    }
+
+    async fn connection(&self) -> Result<PooledConnection<'_, RedisConnectionManager>> {
         self.pool
             .get()
             .err_into()
             .await
     }
 
-    async fn delete<F>(&self, id: Uuid, f: F) -> Result<()>
-    where
-        F: Fn(Uuid) -> String,
-    {
-        self.connection()
-            .and_then(|mut connection| async move {
-                connection.del(f(id))
-                .err_into()
-                .await
-            })
-            .await
-
-    }
-
     async fn get_summary<F>(&self, id: Uuid, key: F) -> Result<Summary>
     where
         F: Fn(Uuid) -> String,
@@ -229,15 +719,20 @@ impl RedisRepo {
 #[async_trait]
 impl LiplRepo for RedisRepo {
     async fn delete_lyric(&self, id: Uuid) -> lipl_core::Result<()> {
-        self.delete_lyric_script(id)
-            .err_into()
+        self.check_exists(lyric_key(id), id).await?;
+        let mut pipeline = pipe();
+        pipeline.atomic();
+        self.add_delete_lyric(&mut pipeline, id);
+        self.execute_and_publish(pipeline, &Transaction::LyricDelete(id))
             .await
     }
 
     async fn delete_playlist(&self, id: Uuid) -> lipl_core::Result<()> {
-        self
-            .delete(id, playlist_key)
-            .err_into()
+        self.check_exists(playlist_key(id), id).await?;
+        let mut pipeline = pipe();
+        pipeline.atomic();
+        self.add_delete_playlist(&mut pipeline, id);
+        self.execute_and_publish(pipeline, &Transaction::PlaylistDelete(id))
             .await
     }
 
@@ -246,23 +741,48 @@ impl LiplRepo for RedisRepo {
         .and_then(|mut connection| async move {
             connection.hgetall(lyric_key(id))
             .err_into()
-            .map_ok(hashmap_to_lyric(id))
+            .map_ok(current_lyric(id))
             .await
         })
-        .err_into()
-        .await
+        .err_into::<lipl_core::Error>()
+        .await?
+        .ok_or(Error::NotFound(id))
     }
 
     async fn get_playlist(&self, id: Uuid) -> lipl_core::Result<Playlist> {
         self.connection()
         .and_then(|mut connection| async move {
-            connection.hgetall(playlist_key(id))
+            connection.hgetall::<_, HashMap<String, String>>(playlist_key(id))
             .err_into()
-            .map(hashmap_to_playlist(id))
             .await
         })
-        .err_into()
+        .err_into::<lipl_core::Error>()
         .await
+        .and_then(|hm| if hm.is_empty() { Err(Error::NotFound(id)) } else { hashmap_to_playlist(id)(Ok(hm)).map_err(Error::from) })
+    }
+
+    async fn search_lyrics(&self, query: &str) -> lipl_core::Result<Vec<SearchHit>> {
+        let terms = search::terms(query);
+        if terms.is_empty() {
+            return Ok(vec![]);
+        }
+        let keys = terms.iter().map(|term| word_key(term)).collect::<Vec<_>>();
+        let ids = 
+            self.connection()
+            .and_then(|mut connection| async move {
+                connection
+                .sinter::<_, HashSet<String>>(keys)
+                .err_into()
+                .await
+            })
+            .await?
+            .into_iter()
+            .map(|id| id.parse::<Uuid>())
+            .collect::<lipl_core::Result<Vec<_>>>()?;
+        let lyrics = try_join_all(ids.into_iter().map(|id| self.get_lyric(id))).await?;
+        let mut hits = lyrics.iter().filter_map(|lyric| search::rank(lyric, &terms)).collect::<Vec<_>>();
+        hits.sort_by(search::by_rank);
+        Ok(hits)
     }
 
     async fn get_lyrics(&self) -> lipl_core::Result<Vec<Lyric>> {
@@ -301,6 +821,59 @@ impl LiplRepo for RedisRepo {
         Ok(summaries)
     }
 
+    async fn get_lyric_summaries_page(&self, query: ListQuery) -> lipl_core::Result<Page<Summary>> {
+        if query.tags.is_empty() {
+            self.get_lyric_summaries()
+                .map_ok(|summaries| query.apply(summaries))
+                .await
+        }
+        else {
+            self.get_lyrics()
+                .map_ok(|lyrics| query.apply_lyrics(lyrics))
+                .await
+        }
+    }
+
+    async fn get_tags(&self) -> lipl_core::Result<Vec<TagCount>> {
+        self.get_lyrics()
+            .map_ok(|lyrics| tag::count_tags(lyrics.iter().map(|lyric| &lyric.tags)))
+            .await
+    }
+
+    async fn get_playlist_summaries_page(&self, query: ListQuery) -> lipl_core::Result<Page<Summary>> {
+        self.get_playlist_summaries()
+            .map_ok(|summaries| query.apply(summaries))
+            .await
+    }
+
+    async fn get_lyric_history(&self, id: Uuid) -> lipl_core::Result<Vec<Revision>> {
+        let mut connection = self.connection().await?;
+        let entries: Vec<String> = connection.lrange(history_key(id), 0, -1).err_into::<RedisRepoError>().await?;
+        if entries.is_empty() {
+            return Err(Error::NotFound(id));
+        }
+        entries.iter().map(|entry| entry_to_revision(entry)).collect()
+    }
+
+    async fn get_lyric_revision(&self, id: Uuid, rev: u64) -> lipl_core::Result<Revision> {
+        self.get_lyric_history(id)
+            .await?
+            .into_iter()
+            .find(|revision| revision.rev == rev)
+            .ok_or(Error::NotFound(id))
+    }
+
+    async fn revert_lyric(&self, id: Uuid, rev: u64) -> lipl_core::Result<Lyric> {
+        let revision = self.get_lyric_revision(id, rev).await?;
+        self.upsert_lyric(revision.lyric)
+            .await
+    }
+
+    async fn merge_lyrics(&self, merge: Merge) -> lipl_core::Result<Lyric> {
+        self.execute_merge(&merge)
+            .await
+    }
+
     async fn get_playlists(&self) -> lipl_core::Result<Vec<Playlist>> {
         let mut playlists =
             self.get_keys(PLAYLIST_ALL.concat(), bs58_to_uuid)
@@ -319,6 +892,12 @@ impl LiplRepo for RedisRepo {
         Ok(playlists)
     }
 
+    async fn get_scheduled_playlists(&self, range: DateRange) -> lipl_core::Result<Vec<Playlist>> {
+        self.get_playlists()
+            .map_ok(|playlists| range.apply(playlists))
+            .await
+    }
+
     async fn get_playlist_summaries(&self) -> lipl_core::Result<Vec<Summary>> {
         let mut summaries = 
             self.get_keys(PLAYLIST_ALL.concat(), bs58_to_uuid)
@@ -336,43 +915,142 @@ impl LiplRepo for RedisRepo {
         Ok(summaries)
     }
 
+    /// Goes through execute_batch, so the words of the current lyric are read under WATCH and the search index stays consistent
     async fn upsert_lyric(&self, lyric: Lyric) -> lipl_core::Result<Lyric> {
-        self.connection()
-        .and_then(|mut connection| async move {
-            connection.hset_multiple::<String, &str, String, ()>(
-                lyric_key(lyric.id), 
-                &[
-                    (TITLE_ATTR, lyric.title.clone()), 
-                    (TEXT_ATTR, to_text(&lyric.parts)),
-                ]
-            )
-            .map_ok(|_| lyric)
-            .map_err(RedisRepoError::from)
-            .await    
-        })
-        .err_into()
-        .await
+        self.execute_batch(&[Transaction::LyricUpsert(lyric.clone())]).await?;
+        Ok(lyric)
     }
 
+    /// Goes through execute_batch, so the members are checked under WATCH
     async fn upsert_playlist(&self, playlist: Playlist) -> lipl_core::Result<Playlist> {
-        self.connection()
-            .and_then(|mut connection| async move {
-                connection
-                .hset_multiple::<String, &str, String, ()>(
-                    playlist_key(playlist.id),
-                    &[
-                        (TITLE_ATTR, playlist.title.clone()),
-                        (MEMBERS_ATTR, playlist.members.iter().map(|id| id.to_string()).collect::<Vec<_>>().join(" "))
-                    ]
-                )
-                .map_ok(|_| playlist)
-                .map_err(RedisRepoError::from)
-                .await
-            })
-            .err_into()
+        self.execute_batch(&[Transaction::PlaylistUpsert(playlist.clone())]).await?;
+        Ok(playlist)
+    }
+
+    async fn upsert_lyric_if_match(&self, lyric: Lyric, etag: String) -> lipl_core::Result<Lyric> {
+        let json = to_json(&Transaction::LyricUpsert(lyric.clone()))?;
+        let revision = to_json(&lyric)?;
+        self.execute_if_match(
+            lyric.id,
+            lyric_key(lyric.id),
+            &etag,
+            None,
+            current_lyric(lyric.id),
+            |current| {
+                let mut pipeline = upsert_lyric_pipeline(&lyric, current);
+                self.add_revision(&mut pipeline, lyric.id, revision.clone());
+                self.add_publish_change(&mut pipeline, json.clone());
+                pipeline
+            },
+        )
+        .await?;
+        Ok(lyric)
+    }
+
+    async fn delete_lyric_if_match(&self, id: Uuid, etag: String) -> lipl_core::Result<()> {
+        let json = to_json(&Transaction::LyricDelete(id))?;
+        self.execute_if_match(
+            id,
+            lyric_key(id),
+            &etag,
+            None,
+            current_lyric(id),
+            |_| {
+                let mut pipeline = pipe();
+                pipeline.atomic();
+                self.add_delete_lyric(&mut pipeline, id);
+                self.add_publish_change(&mut pipeline, json.clone());
+                pipeline
+            },
+        )
+        .await
+    }
+
+    async fn upsert_playlist_if_match(&self, playlist: Playlist, etag: String) -> lipl_core::Result<Playlist> {
+        let key = playlist_key(playlist.id);
+        let json = to_json(&Transaction::PlaylistUpsert(playlist.clone()))?;
+        self.execute_if_match(
+            playlist.id,
+            key.clone(),
+            &etag,
+            Some(&playlist),
+            |hm| hashmap_to_playlist(playlist.id)(Ok(hm)).ok(),
+            |_| {
+                let mut pipeline = pipe();
+                pipeline.atomic().hset_multiple(&key, &playlist_to_attrs(&playlist));
+                self.add_publish_change(&mut pipeline, json.clone());
+                pipeline
+            },
+        )
+        .await?;
+        Ok(playlist)
+    }
+
+    async fn delete_playlist_if_match(&self, id: Uuid, etag: String) -> lipl_core::Result<()> {
+        let key = playlist_key(id);
+        let json = to_json(&Transaction::PlaylistDelete(id))?;
+        self.execute_if_match(
+            id,
+            key.clone(),
+            &etag,
+            None,
+            |hm| hashmap_to_playlist(id)(Ok(hm)).ok(),
+            |_| {
+                let mut pipeline = pipe();
+                pipeline.atomic();
+                self.add_delete_playlist(&mut pipeline, id);
+                self.add_publish_change(&mut pipeline, json.clone());
+                pipeline
+            },
+        )
+        .await
+    }
+
+    async fn apply_batch(&self, batch: Vec<Transaction>) -> lipl_core::Result<()> {
+        self.execute_batch(&batch)
             .await
     }
 
+    async fn watch(&self) -> lipl_core::Result<ChangeStream> {
+        let client = Client::open(self.connection_info.clone()).map_err(RedisRepoError::from)?;
+        let mut pubsub = client.get_async_connection().err_into::<RedisRepoError>().await?.into_pubsub();
+        pubsub.subscribe(CHANGE).err_into::<RedisRepoError>().await?;
+        Ok(
+            pubsub
+            .into_on_message()
+            .filter_map(|message| ready(message.get_payload::<String>().ok().as_deref().and_then(message_to_change)))
+            .boxed()
+        )
+    }
+
+    async fn list_trash(&self) -> lipl_core::Result<Vec<Trashed>> {
+        let ids = self.get_keys(TRASH_ALL.concat(), bs58_to_uuid).await?;
+        let mut connection = self.connection().await?;
+        let mut trash = vec![];
+        for id in ids {
+            let hm: HashMap<String, String> = connection.hgetall(trash_key(id)).err_into::<RedisRepoError>().await?;
+            trash.push(hashmap_to_trashed(id)(hm)?);
+        }
+        Ok(sorted_by_deleted(trash))
+    }
+
+    async fn restore(&self, id: Uuid) -> lipl_core::Result<()> {
+        self.execute_restore(id)
+            .await
+    }
+
+    async fn purge(&self, id: Uuid) -> lipl_core::Result<()> {
+        let mut connection = self.connection().await?;
+        let count: u64 = connection.del(trash_key(id)).err_into::<RedisRepoError>().await?;
+        if count == 0 {
+            Err(Error::NotFound(id))
+        }
+        else {
+            connection.del::<_, ()>(history_key(id)).err_into::<RedisRepoError>().await?;
+            Ok(())
+        }
+    }
+
     async fn stop(&self) -> lipl_core::Result<()> {
         Ok(())
     }
@@ -0,0 +1,9 @@
+use lipl_repo_redis::RedisRepoConfig;
+
+/// Needs a redis server, at the url in REDIS_URL or on localhost. Run with cargo test -- --ignored
+#[tokio::test]
+#[ignore]
+async fn conformance() {
+    let url = std::env::var("REDIS_URL").unwrap_or_else(|_| "redis://127.0.0.1/".to_owned());
+    lipl_repo_test::check_config(RedisRepoConfig::new(true, url)).await.unwrap();
+}
@@ -0,0 +1,500 @@
+/*!
+ Behaviour every implementation of LiplRepo should have.
+
+ The checks panic with a message when a repo behaves differently and return the error when a repo call fails unexpectedly.
+ They only look at the lyrics and playlists they create themselves, so they can run against a repo that already has data.
+
+ ```ignore
+ #[tokio::test]
+ async fn conformance() {
+     lipl_repo_test::check_config(MemoryRepoConfig::default()).await.unwrap();
+ }
+ ```
+
+ With the memory feature the wrapped module has helpers for the tests of repos that wrap another repo.
+ */
+
+#[cfg(feature = "memory")]
+pub mod wrapped;
+
+use std::sync::Arc;
+use futures::future::join_all;
+use lipl_core::{by_title, Chord, DateRange, Error, Etag, HasSummary, LiplRepo, ListQuery, Lyric, LyricPost, Merge, Playlist, PlaylistEntry, PlaylistEvent, PlaylistPost, Result, Summary, TagCount, ToRepo, Transaction, Translation, Uuid};
+
+fn lyric(title: &str, text: &str) -> Lyric {
+    LyricPost::from((title, text)).into()
+}
+
+fn playlist(title: &str, members: Vec<Uuid>) -> Playlist {
+    (None, PlaylistPost { title: title.to_owned(), members, entries: vec![], event: None }).into()
+}
+
+fn ids<T: HasSummary>(list: &[T]) -> Vec<Uuid> {
+    list.iter().map(|item| item.summary().id).collect()
+}
+
+/// The items from list with an id in ids, in the order of list
+fn only<T: HasSummary + Clone>(list: &[T], ids: &[Uuid]) -> Vec<T> {
+    list.iter().filter(|item| ids.contains(&item.summary().id)).cloned().collect()
+}
+
+fn assert_not_found<T: std::fmt::Debug>(result: Result<T>, id: Uuid, call: &str) {
+    assert!(
+        matches!(result, Err(Error::NotFound(not_found)) if not_found == id),
+        "{call} should fail with NotFound({id}), got {result:?}",
+    );
+}
+
+/// Creates the repo from the config and runs all checks on it
+pub async fn check_config<T: ToRepo>(config: T) -> Result<()> {
+    let repo = config.to_repo().await?;
+    check_repo(repo.clone()).await?;
+    repo.stop().await
+}
+
+/// Runs all checks on the repo
+pub async fn check_repo(repo: Arc<dyn LiplRepo>) -> Result<()> {
+    lyric_crud(repo.as_ref()).await?;
+    lyric_chords(repo.as_ref()).await?;
+    lyric_labels(repo.as_ref()).await?;
+    lyric_translations(repo.as_ref()).await?;
+    lyric_tags(repo.as_ref()).await?;
+    playlist_crud(repo.as_ref()).await?;
+    playlist_entries(repo.as_ref()).await?;
+    scheduled_playlists(repo.as_ref()).await?;
+    ordering(repo.as_ref()).await?;
+    cascading_member_removal(repo.as_ref()).await?;
+    not_found(repo.as_ref()).await?;
+    invalid_members(repo.as_ref()).await?;
+    restore_occupied(repo.as_ref()).await?;
+    merge_lyrics(repo.as_ref()).await?;
+    concurrency(repo).await
+}
+
+/// Upserted lyrics can be read back, changed and deleted
+pub async fn lyric_crud(repo: &dyn LiplRepo) -> Result<()> {
+    let mut lyric = lyric("Roodkapje", "Zeg roodkapje\nwaar ga je heen\n\nNaar grootmoeder");
+
+    let posted = repo.upsert_lyric(lyric.clone()).await?;
+    assert_eq!(posted.etag(), lyric.etag(), "upsert_lyric should return the lyric");
+    assert_eq!(repo.get_lyric(lyric.id).await?.etag(), lyric.etag(), "get_lyric should return the upserted lyric");
+    assert!(repo.get_lyrics().await?.iter().any(|item| item.id == lyric.id), "get_lyrics should contain the upserted lyric");
+    assert!(
+        repo.get_lyric_summaries().await?.contains(&Summary { id: lyric.id, title: lyric.title.clone() }),
+        "get_lyric_summaries should contain the upserted lyric",
+    );
+
+    lyric.title = "Zeg roodkapje".to_owned();
+    lyric.parts.push(vec!["Koekjes brengen".to_owned()]);
+    repo.upsert_lyric(lyric.clone()).await?;
+    assert_eq!(repo.get_lyric(lyric.id).await?.etag(), lyric.etag(), "get_lyric should return the changed lyric");
+    assert_eq!(
+        repo.get_lyrics().await?.iter().filter(|item| item.id == lyric.id).count(),
+        1,
+        "changing a lyric should not add a lyric",
+    );
+
+    repo.delete_lyric(lyric.id).await?;
+    assert_not_found(repo.get_lyric(lyric.id).await, lyric.id, "get_lyric after delete_lyric");
+    assert!(!repo.get_lyrics().await?.iter().any(|item| item.id == lyric.id), "get_lyrics should not contain the deleted lyric");
+    Ok(())
+}
+
+/// Chords are kept with the lines they are played on
+pub async fn lyric_chords(repo: &dyn LiplRepo) -> Result<()> {
+    let chord = |position, name: &str| Chord { position, name: name.to_owned() };
+    let mut lyric = lyric("Daar bij die molen", "Daar bij die molen\ndie mooie molen\n\n[Refrein] twee keer");
+    lyric.chords = vec![vec![vec![chord(0, "C"), chord(13, "G7")], vec![]], vec![vec![chord(9, "Am")]]];
+
+    repo.upsert_lyric(lyric.clone()).await?;
+    let stored = repo.get_lyric(lyric.id).await?;
+    assert_eq!((&stored.parts, &stored.chords), (&lyric.parts, &lyric.chords), "get_lyric should return the lyric with its chords");
+    assert_eq!(stored.etag(), lyric.etag(), "the stored lyric should have the same etag");
+    repo.delete_lyric(lyric.id).await
+}
+
+/// Labels of the parts and the arrangement are kept
+pub async fn lyric_labels(repo: &dyn LiplRepo) -> Result<()> {
+    let lyric = lyric("Roodkapje", "Zeg roodkapje\n\nRefrein:\nNaar grootmoeder\n\nWat heb je in je mandje\n\nRefrein");
+    assert_eq!(lyric.arrangement, vec!["1", "Refrein", "3", "Refrein"]);
+
+    repo.upsert_lyric(lyric.clone()).await?;
+    let stored = repo.get_lyric(lyric.id).await?;
+    assert_eq!((&stored.labels, &stored.arrangement), (&lyric.labels, &lyric.arrangement), "get_lyric should return the lyric with its labels and arrangement");
+    assert_eq!(stored.etag(), lyric.etag(), "the stored lyric should have the same etag");
+    repo.delete_lyric(lyric.id).await
+}
+
+/// Translations are kept, also in the history
+pub async fn lyric_translations(repo: &dyn LiplRepo) -> Result<()> {
+    let mut lyric = lyric("Roodkapje", "Zeg roodkapje\nwaar ga je heen");
+    let english = Translation { title: "Red Riding Hood".to_owned(), parts: vec![vec!["Say red riding hood".to_owned(), "where are you going".to_owned()]] };
+    let frisian = Translation { title: "Reakapke".to_owned(), parts: vec![vec!["Sis reakapke".to_owned(), "wêr giest hinne".to_owned()]] };
+    lyric.translations.insert("en".to_owned(), english);
+    lyric.translations.insert("fy".to_owned(), frisian);
+
+    repo.upsert_lyric(lyric.clone()).await?;
+    let stored = repo.get_lyric(lyric.id).await?;
+    assert_eq!(stored.translations, lyric.translations, "get_lyric should return the lyric with its translations");
+    assert_eq!(stored.etag(), lyric.etag(), "the stored lyric should have the same etag");
+    let history = repo.get_lyric_history(lyric.id).await?;
+    assert_eq!(history.last().map(|revision| &revision.lyric.translations), Some(&lyric.translations), "the revision should have the translations");
+    repo.delete_lyric(lyric.id).await
+}
+
+/// Tags are kept, counted and used to filter the summaries on all of the tags
+pub async fn lyric_tags(repo: &dyn LiplRepo) -> Result<()> {
+    let mut kerst = lyric("Stille nacht", "Stille nacht\nheilige nacht");
+    let mut sinterklaas = lyric("Zie ginds komt de stoomboot", "Zie ginds komt de stoomboot\nuit Spanje weer aan");
+    let plain = lyric("Roodkapje", "Zeg roodkapje\nwaar ga je heen");
+    // Tags of their own, so tags in a repo that already has data are not counted
+    let tag = |name: &str| format!("{name}-{}", kerst.id);
+    let (christmas, children, saint) = (tag("kerst"), tag("kinderliedjes"), tag("sinterklaas"));
+    kerst.tags = [christmas.clone(), children.clone()].into_iter().collect();
+    sinterklaas.tags = [saint.clone(), children.clone()].into_iter().collect();
+
+    for lyric in [&kerst, &sinterklaas, &plain] {
+        repo.upsert_lyric(lyric.clone()).await?;
+    }
+    let stored = repo.get_lyric(kerst.id).await?;
+    assert_eq!(stored.tags, kerst.tags, "get_lyric should return the lyric with its tags");
+    assert_eq!(stored.etag(), kerst.etag(), "the stored lyric should have the same etag");
+    assert!(repo.get_lyric(plain.id).await?.tags.is_empty(), "a lyric without tags should have no tags");
+
+    let counts = repo.get_tags().await?.into_iter().filter(|count| [&christmas, &children, &saint].contains(&&count.tag)).collect::<Vec<_>>();
+    let mut expected = vec![
+        TagCount { tag: christmas.clone(), count: 1 },
+        TagCount { tag: children.clone(), count: 2 },
+        TagCount { tag: saint.clone(), count: 1 },
+    ];
+    expected.sort_by(|a, b| a.tag.cmp(&b.tag));
+    assert_eq!(counts, expected, "get_tags should count the lyrics per tag, ordered by tag");
+
+    let page = repo.get_lyric_summaries_page(ListQuery { tags: vec![children.clone()], ..Default::default() }).await?;
+    assert_eq!(page.total, 2, "the page should count the lyrics with the tag");
+    assert_eq!(ids(&page.items), ids(&[kerst.clone(), sinterklaas.clone()]), "the page should have the lyrics with the tag, ordered by title");
+    let page = repo.get_lyric_summaries_page(ListQuery { tags: vec![children.clone(), christmas.clone()], ..Default::default() }).await?;
+    assert_eq!(ids(&page.items), vec![kerst.id], "the page should only have the lyrics with all of the tags");
+    assert_eq!(page.total, 1, "the page should count the lyrics with all of the tags");
+
+    kerst.tags.clear();
+    repo.upsert_lyric(kerst.clone()).await?;
+    let page = repo.get_lyric_summaries_page(ListQuery { tags: vec![christmas.clone()], ..Default::default() }).await?;
+    assert!(page.items.is_empty(), "a lyric should lose a tag that is removed");
+    assert!(!repo.get_tags().await?.iter().any(|count| count.tag == christmas), "get_tags should not list a tag no lyric has");
+
+    for lyric in [kerst, sinterklaas, plain] {
+        repo.delete_lyric(lyric.id).await?;
+    }
+    Ok(())
+}
+
+/// Upserted playlists can be read back, with the members in order, changed and deleted
+pub async fn playlist_crud(repo: &dyn LiplRepo) -> Result<()> {
+    let first = repo.upsert_lyric(lyric("Alle 13 goed", "Alle 13 goed")).await?;
+    let second = repo.upsert_lyric(lyric("Daar bij die molen", "Daar bij die molen")).await?;
+    let mut playlist = playlist("Kinderliedjes", vec![second.id, first.id]);
+
+    let posted = repo.upsert_playlist(playlist.clone()).await?;
+    assert_eq!(posted.members, playlist.members, "upsert_playlist should return the playlist");
+    let stored = repo.get_playlist(playlist.id).await?;
+    assert_eq!((stored.title, stored.members), (playlist.title.clone(), playlist.members.clone()), "get_playlist should return the upserted playlist");
+    assert!(repo.get_playlists().await?.iter().any(|item| item.id == playlist.id), "get_playlists should contain the upserted playlist");
+
+    playlist.title = "Liedjes voor kinderen".to_owned();
+    playlist.members = vec![first.id, second.id];
+    repo.upsert_playlist(playlist.clone()).await?;
+    let stored = repo.get_playlist(playlist.id).await?;
+    assert_eq!((stored.title, stored.members), (playlist.title.clone(), playlist.members.clone()), "get_playlist should return the changed playlist");
+
+    repo.delete_playlist(playlist.id).await?;
+    assert_not_found(repo.get_playlist(playlist.id).await, playlist.id, "get_playlist after delete_playlist");
+    assert_eq!(repo.get_lyric(first.id).await?.id, first.id, "deleting a playlist should keep its members");
+
+    repo.delete_lyric(first.id).await?;
+    repo.delete_lyric(second.id).await
+}
+
+/// Entries with notes, selected parts, keys and dividers are kept, the members are the lyrics of the entries.
+/// A deleted lyric is removed from the entries, the dividers stay.
+pub async fn playlist_entries(repo: &dyn LiplRepo) -> Result<()> {
+    let first = repo.upsert_lyric(lyric("Alle 13 goed", "Alle 13 goed")).await?;
+    let second = repo.upsert_lyric(lyric("Daar bij die molen", "Daar bij die molen
+
+Daar woont een meisje
+
+Zo mooi")).await?;
+    let verses = PlaylistEntry { note: Some("Zachtjes".to_owned()), parts: vec![1, 3], key: Some("D".to_owned()), ..PlaylistEntry::lyric(second.id) };
+    let entries = vec![PlaylistEntry::lyric(first.id), PlaylistEntry::divider("Pauze"), verses.clone()];
+    let playlist: Playlist = (None, PlaylistPost { title: "Kinderliedjes".to_owned(), members: vec![], entries: entries.clone(), event: None }).into();
+
+    let posted = repo.upsert_playlist(playlist.clone()).await?;
+    assert_eq!(posted.entries, entries, "upsert_playlist should return the playlist with its entries");
+    let stored = repo.get_playlist(playlist.id).await?;
+    assert_eq!(stored.entries, entries, "get_playlist should return the entries in order");
+    assert_eq!(stored.members, vec![first.id, second.id], "the members should be the lyrics of the entries");
+    assert_eq!(stored.etag(), playlist.etag(), "the stored playlist should have the same etag");
+
+    repo.delete_lyric(first.id).await?;
+    let stored = repo.get_playlist(playlist.id).await?;
+    assert_eq!(stored.entries, vec![PlaylistEntry::divider("Pauze"), verses], "the deleted lyric should be removed from the entries");
+    assert_eq!(stored.members, vec![second.id], "the deleted lyric should be removed from the members");
+
+    repo.delete_playlist(playlist.id).await?;
+    repo.delete_lyric(second.id).await
+}
+
+/// The event of a playlist is kept, and playlists are selected by the date of the event, both ends of the range included.
+/// Scheduled playlists are ordered by the start of the event, playlists without an event are left out.
+pub async fn scheduled_playlists(repo: &dyn LiplRepo) -> Result<()> {
+    let event = |start: &str, location: Option<&str>| PlaylistEvent { start: start.parse().unwrap(), location: location.map(str::to_owned), leader: Some("Anne".to_owned()) };
+    let mut late = playlist("Avonddienst", vec![]);
+    late.event = Some(event("2026-10-25T19:00:00", None));
+    let mut early = playlist("Morgendienst", vec![]);
+    early.event = Some(event("2026-10-25T10:00:00", Some("Grote Kerk")));
+    let mut past = playlist("Vorige week", vec![]);
+    past.event = Some(event("2026-10-11T10:00:00", None));
+    let unscheduled = playlist("Ooit", vec![]);
+    for playlist in [&late, &early, &past, &unscheduled] {
+        repo.upsert_playlist(playlist.clone()).await?;
+    }
+
+    let stored = repo.get_playlist(early.id).await?;
+    assert_eq!(stored.event, early.event, "get_playlist should return the event");
+    assert_eq!(stored.etag(), early.etag(), "the stored playlist should have the same etag");
+
+    let range = DateRange { from: "2026-10-18".parse().ok(), to: "2026-10-25".parse().ok() };
+    let scheduled = repo.get_scheduled_playlists(range).await?;
+    assert_eq!(ids(&scheduled), vec![early.id, late.id], "get_scheduled_playlists should return the playlists in the range by start");
+    assert_eq!(scheduled[0].event, early.event, "get_scheduled_playlists should return the events");
+    let all = repo.get_scheduled_playlists(DateRange::default()).await?;
+    assert_eq!(ids(&all), vec![past.id, early.id, late.id], "an open range should return every scheduled playlist");
+
+    early.event = None;
+    repo.upsert_playlist(early.clone()).await?;
+    assert_eq!(repo.get_playlist(early.id).await?.event, None, "upsert_playlist should remove the event");
+
+    for playlist in [&late, &early, &past, &unscheduled] {
+        repo.delete_playlist(playlist.id).await?;
+    }
+    Ok(())
+}
+
+/// Lists are ordered by title and then by id, comparing titles byte by byte
+pub async fn ordering(repo: &dyn LiplRepo) -> Result<()> {
+    let mut lyrics = vec![];
+    for title in ["bravo", "Charlie", "alpha", "Bravo", "Alpha"] {
+        lyrics.push(repo.upsert_lyric(lyric(title, "")).await?);
+    }
+    let lyric_ids = ids(&lyrics);
+    lyrics.sort_by(by_title);
+    let expected = ids(&lyrics);
+
+    let listed = only(&repo.get_lyrics().await?, &lyric_ids);
+    assert_eq!(ids(&listed), expected, "get_lyrics should be ordered by title and id");
+    let listed = only(&repo.get_lyric_summaries().await?, &lyric_ids);
+    assert_eq!(ids(&listed), expected, "get_lyric_summaries should be ordered by title and id");
+
+    let mut playlists = vec![];
+    for title in ["Zomer", "Winter", "Herfst"] {
+        playlists.push(repo.upsert_playlist(playlist(title, vec![])).await?);
+    }
+    let playlist_ids = ids(&playlists);
+    playlists.sort_by(by_title);
+
+    let listed = only(&repo.get_playlists().await?, &playlist_ids);
+    assert_eq!(ids(&listed), ids(&playlists), "get_playlists should be ordered by title and id");
+    let listed = only(&repo.get_playlist_summaries().await?, &playlist_ids);
+    assert_eq!(ids(&listed), ids(&playlists), "get_playlist_summaries should be ordered by title and id");
+
+    for id in lyric_ids {
+        repo.delete_lyric(id).await?;
+    }
+    for id in playlist_ids {
+        repo.delete_playlist(id).await?;
+    }
+    Ok(())
+}
+
+/// A deleted lyric is removed from every playlist, keeping the order of the other members
+pub async fn cascading_member_removal(repo: &dyn LiplRepo) -> Result<()> {
+    let mut lyrics = vec![];
+    for title in ["Een", "Twee", "Drie"] {
+        lyrics.push(repo.upsert_lyric(lyric(title, title)).await?);
+    }
+    let lyric_ids = ids(&lyrics);
+    let all = repo.upsert_playlist(playlist("Alles", lyric_ids.clone())).await?;
+    let some = repo.upsert_playlist(playlist("Sommige", vec![lyric_ids[2], lyric_ids[1]])).await?;
+    let other = repo.upsert_playlist(playlist("Andere", vec![lyric_ids[0]])).await?;
+
+    repo.delete_lyric(lyric_ids[1]).await?;
+    assert_eq!(repo.get_playlist(all.id).await?.members, vec![lyric_ids[0], lyric_ids[2]], "the deleted lyric should be removed from the playlist");
+    assert_eq!(repo.get_playlist(some.id).await?.members, vec![lyric_ids[2]], "the deleted lyric should be removed from every playlist");
+    assert_eq!(repo.get_playlist(other.id).await?.members, vec![lyric_ids[0]], "playlists without the deleted lyric should not change");
+
+    for id in [lyric_ids[0], lyric_ids[2]] {
+        repo.delete_lyric(id).await?;
+    }
+    for id in [all.id, some.id, other.id] {
+        repo.delete_playlist(id).await?;
+    }
+    Ok(())
+}
+
+/// Reading or deleting a lyric or playlist that does not exist fails with Error::NotFound
+pub async fn not_found(repo: &dyn LiplRepo) -> Result<()> {
+    let id = Uuid::default();
+    assert_not_found(repo.get_lyric(id).await, id, "get_lyric");
+    assert_not_found(repo.get_playlist(id).await, id, "get_playlist");
+    assert_not_found(repo.delete_lyric(id).await, id, "delete_lyric");
+    assert_not_found(repo.delete_playlist(id).await, id, "delete_playlist");
+
+    let lyric = repo.upsert_lyric(lyric("Roodkapje", "")).await?;
+    assert_not_found(repo.get_playlist(lyric.id).await, lyric.id, "get_playlist with the id of a lyric");
+    repo.delete_lyric(lyric.id).await?;
+    assert_not_found(repo.delete_lyric(lyric.id).await, lyric.id, "delete_lyric for a deleted lyric");
+    Ok(())
+}
+
+/// A playlist never refers to a lyric that does not exist. Upserting one fails with Error::InvalidMembers, listing the
+/// unknown members, and leaves the playlist unchanged. Lyrics upserted earlier in the same batch are known.
+pub async fn invalid_members(repo: &dyn LiplRepo) -> Result<()> {
+    let lyric = repo.upsert_lyric(lyric("Roodkapje", "")).await?;
+    let unknown = Uuid::default();
+    let playlist = playlist("Kinderliedjes", vec![lyric.id, unknown, lyric.id, unknown]);
+    let assert_invalid_members = |result: Result<()>, call: &str| assert!(
+        matches!(&result, Err(Error::InvalidMembers(id, members)) if *id == playlist.id && *members == vec![unknown]),
+        "{call} should fail with InvalidMembers({}, [{unknown}]), got {result:?}",
+        playlist.id,
+    );
+
+    assert_invalid_members(repo.upsert_playlist(playlist.clone()).await.map(|_| ()), "upsert_playlist");
+    assert_not_found(repo.get_playlist(playlist.id).await, playlist.id, "get_playlist after a rejected upsert_playlist");
+    assert_invalid_members(
+        repo.apply_batch(vec![Transaction::PlaylistUpsert(playlist.clone())]).await,
+        "apply_batch",
+    );
+    assert_not_found(repo.get_playlist(playlist.id).await, playlist.id, "get_playlist after a rejected apply_batch");
+
+    let second = self::lyric("Daar bij die molen", "");
+    let batched = self::playlist("Kinderliedjes", vec![lyric.id, second.id]);
+    repo.apply_batch(vec![Transaction::LyricUpsert(second.clone()), Transaction::PlaylistUpsert(batched.clone())]).await?;
+    assert_eq!(repo.get_playlist(batched.id).await?.members, batched.members, "members upserted in the same batch should be known");
+
+    repo.delete_playlist(batched.id).await?;
+    repo.delete_lyric(second.id).await?;
+    repo.delete_lyric(lyric.id).await
+}
+
+/// Restoring an item fails with Error::Occupied when an item with the same id was upserted after the delete.
+/// The stored item and the trash are left alone.
+pub async fn restore_occupied(repo: &dyn LiplRepo) -> Result<()> {
+    let mut lyric = repo.upsert_lyric(lyric("Roodkapje", "Zeg roodkapje")).await?;
+    let mut playlist = repo.upsert_playlist(playlist("Kinderliedjes", vec![lyric.id])).await?;
+    repo.delete_playlist(playlist.id).await?;
+    repo.delete_lyric(lyric.id).await?;
+
+    lyric.title = "Zeg roodkapje".to_owned();
+    repo.upsert_lyric(lyric.clone()).await?;
+    playlist.title = "Liedjes".to_owned();
+    playlist.members = vec![];
+    repo.upsert_playlist(playlist.clone()).await?;
+
+    let result = repo.restore(lyric.id).await;
+    assert!(matches!(result, Err(Error::Occupied)), "restore of a lyric that was upserted again should fail with Occupied, got {result:?}");
+    assert_eq!(repo.get_lyric(lyric.id).await?.etag(), lyric.etag(), "a failed restore should leave the stored lyric alone");
+    let result = repo.restore(playlist.id).await;
+    assert!(matches!(result, Err(Error::Occupied)), "restore of a playlist that was upserted again should fail with Occupied, got {result:?}");
+    let stored = repo.get_playlist(playlist.id).await?;
+    assert_eq!((stored.title, stored.members), (playlist.title.clone(), playlist.members.clone()), "a failed restore should leave the stored playlist alone");
+    let trash = repo.list_trash().await?;
+    assert!(
+        [lyric.id, playlist.id].iter().all(|id| trash.iter().any(|trashed| trashed.id() == *id)),
+        "a failed restore should leave the trash alone",
+    );
+
+    repo.delete_playlist(playlist.id).await?;
+    repo.delete_lyric(lyric.id).await?;
+    repo.purge(playlist.id).await?;
+    repo.purge(lyric.id).await
+}
+
+/// Merging points every playlist at the lyric to keep instead of the duplicates and deletes the duplicates.
+/// If the lyric to keep or one of the duplicates does not exist the merge fails with Error::NotFound and nothing changes.
+pub async fn merge_lyrics(repo: &dyn LiplRepo) -> Result<()> {
+    let keep = repo.upsert_lyric(lyric("Roodkapje", "Zeg Roodkapje")).await?;
+    let duplicate = repo.upsert_lyric(lyric("Roodkapje (2)", "Zeg Roodkapje")).await?;
+    let other = repo.upsert_lyric(lyric("Daar bij die molen", "")).await?;
+    let playlist = repo.upsert_playlist(playlist("Kinderliedjes", vec![duplicate.id, other.id])).await?;
+
+    let unknown = Uuid::default();
+    assert_not_found(
+        repo.merge_lyrics(Merge { keep: keep.id, duplicates: vec![duplicate.id, unknown] }).await,
+        unknown,
+        "merge_lyrics with an unknown duplicate",
+    );
+    assert_eq!(repo.get_playlist(playlist.id).await?.members, playlist.members, "a failed merge should not change the playlists");
+    assert_eq!(repo.get_lyric(duplicate.id).await?.id, duplicate.id, "a failed merge should not delete the duplicates");
+    assert_not_found(
+        repo.merge_lyrics(Merge { keep: unknown, duplicates: vec![duplicate.id] }).await,
+        unknown,
+        "merge_lyrics with an unknown lyric to keep",
+    );
+
+    let kept = repo.merge_lyrics(Merge { keep: keep.id, duplicates: vec![duplicate.id] }).await?;
+    assert_eq!(kept.id, keep.id, "merge_lyrics should return the lyric to keep");
+    assert_eq!(repo.get_playlist(playlist.id).await?.members, vec![keep.id, other.id], "the duplicate should be replaced by the lyric to keep");
+    assert_not_found(repo.get_lyric(duplicate.id).await, duplicate.id, "get_lyric for a merged duplicate");
+
+    repo.delete_playlist(playlist.id).await?;
+    repo.delete_lyric(other.id).await?;
+    repo.delete_lyric(keep.id).await
+}
+
+/// Concurrent upserts are all stored and only leave the words of the last one in the search index, and of concurrent conditional upserts with the same etag only one succeeds
+pub async fn concurrency(repo: Arc<dyn LiplRepo>) -> Result<()> {
+    let lyrics = (0..16).map(|i| lyric(&format!("Couplet {i}"), "")).collect::<Vec<_>>();
+    join_all(lyrics.iter().map(|lyric| repo.upsert_lyric(lyric.clone())))
+    .await
+    .into_iter()
+    .collect::<Result<Vec<_>>>()?;
+
+    let lyric_ids = ids(&lyrics);
+    let stored = only(&repo.get_lyrics().await?, &lyric_ids);
+    assert_eq!(stored.len(), lyrics.len(), "all concurrently upserted lyrics should be stored");
+
+    let lyric = lyrics[0].clone();
+    let etag = lyric.etag().unwrap_or_default();
+    let results = join_all(
+        (0..8)
+        .map(|i| Lyric { title: format!("Refrein {i}"), ..lyric.clone() })
+        .map(|changed| repo.upsert_lyric_if_match(changed, etag.clone()))
+    )
+    .await;
+    assert_eq!(results.iter().filter(|result| result.is_ok()).count(), 1, "only one conditional upsert with the same etag should succeed");
+    assert!(
+        results.iter().all(|result| matches!(result, Ok(_) | Err(Error::EtagMismatch(_)))),
+        "the other conditional upserts should fail with EtagMismatch",
+    );
+
+    let lyric = lyrics[1].clone();
+    let word = |i: usize| format!("tovenaarsleerling{i}");
+    join_all(
+        (0..8)
+        .map(|i| Lyric { parts: vec![vec![word(i)]], ..lyric.clone() })
+        .map(|changed| repo.upsert_lyric(changed))
+    )
+    .await
+    .into_iter()
+    .collect::<Result<Vec<_>>>()?;
+    let stored = repo.get_lyric(lyric.id).await?;
+    for i in 0..8 {
+        let found = repo.search_lyrics(&word(i)).await?.iter().any(|hit| hit.summary.id == lyric.id);
+        assert_eq!(found, stored.parts == vec![vec![word(i)]], "search should only find the words of the last concurrent upsert");
+    }
+
+    for id in lyric_ids {
+        repo.delete_lyric(id).await?;
+    }
+    Ok(())
+}
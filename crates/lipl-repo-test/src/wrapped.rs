@@ -0,0 +1,24 @@
+//! Helpers for the tests of repos that wrap another repo, with a memory repo as the wrapped repo
+
+use std::sync::Arc;
+use lipl_core::{LiplRepo, Lyric, LyricPost, Result};
+use lipl_repo_memory::MemoryRepo;
+
+/// Lyric with the title and without text
+pub fn lyric(title: &str) -> Lyric {
+    LyricPost::from((title, "")).into()
+}
+
+/// Empty memory repo
+pub fn memory_repo() -> Arc<MemoryRepo> {
+    Arc::new(MemoryRepo::new(std::iter::empty(), std::iter::empty()))
+}
+
+/// Wraps an empty memory repo, runs all checks on the wrapper and returns it for further checks
+pub async fn check_wrapper<R, F>(wrap: F) -> Result<Arc<R>>
+where R: LiplRepo + 'static, F: FnOnce(Arc<MemoryRepo>) -> R
+{
+    let repo = Arc::new(wrap(memory_repo()));
+    crate::check_repo(repo.clone()).await?;
+    Ok(repo)
+}
@@ -31,6 +31,15 @@ where
     })
 }
 
+/// The lowercased titles of the playlists with the lyric, so that lyrics can be found by theme
+fn tags(title: &str, playlists: &[(String, Vec<String>)]) -> Vec<String> {
+    playlists
+    .iter()
+    .filter(|(_, member_titles)| member_titles.iter().any(|member_title| member_title == title))
+    .map(|(playlist_title, _)| playlist_title.to_lowercase())
+    .collect()
+}
+
 fn create_lyrics(lyric_files: &[PathBuf], playlists: Vec<(String, Vec<String>)>) -> TokenStream {
     let playlist_paths = playlists
         .iter()
@@ -46,6 +55,8 @@ fn create_lyrics(lyric_files: &[PathBuf], playlists: Vec<(String, Vec<String>)>)
                                 .map(|title| lyrics.iter().find(|lyric| lyric.title == *title).unwrap())
                                 .map(|lyric| lyric.id)
                                 .collect::<Vec<_>>(),
+                            entries: vec![],
+                            event: None,
                         }
                     )
                 )
@@ -56,6 +67,7 @@ fn create_lyrics(lyric_files: &[PathBuf], playlists: Vec<(String, Vec<String>)>)
         .map(|path| {
             let title = path.file_stem().unwrap().to_string_lossy().to_string();
             let file_path = path.to_string_lossy().to_string();
+            let tags = tags(&title, &playlists);
         
             quote! {
                 Lyric::from(
@@ -64,6 +76,12 @@ fn create_lyrics(lyric_files: &[PathBuf], playlists: Vec<(String, Vec<String>)>)
                         LyricPost {
                             title: #title.to_owned(),
                             parts: to_parts(include_str!(#file_path).to_owned()),
+                            chords: vec![],
+                            labels: vec![],
+                            arrangement: vec![],
+                            translations: Default::default(),
+                            tags: Tags::from_iter([#(#tags.to_owned()),*]),
+                            metadata: Default::default(),
                         }
                     )
                 )
@@ -87,7 +105,7 @@ fn create_lyrics(lyric_files: &[PathBuf], playlists: Vec<(String, Vec<String>)>)
 
 fn source_gen(hashmap: TokenStream) -> TokenStream {
     quote! {
-        use lipl_core::{Lyric, LyricPost, Playlist, PlaylistPost, RepoDb};
+        use lipl_core::{Lyric, LyricPost, Playlist, PlaylistPost, RepoDb, Tags};
         use parts::{to_parts};
  
         /// This function returns all lyrics from a directory read at build time.
@@ -10,4 +10,11 @@ mod tests {
             "Daar bij die molen".to_owned(),
         );
     }
+
+    #[test]
+    fn test_kerst_tags() {
+        let db = super::repo_db();
+        assert!(db.find_lyric_by_title("Stille nacht").unwrap().tags.contains("kerst"));
+        assert!(db.find_lyric_by_title("Daar bij die molen").unwrap().tags.is_empty());
+    }
 }
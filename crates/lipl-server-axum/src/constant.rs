@@ -1,4 +1,5 @@
 pub const PREFIX: &str = "/api/v1";
+pub const METRICS: &str = "/metrics";
 pub const DEFAULT_LOG_FILTER: &str = "info,tower_http=debug,tokio_postgres=warn";
 pub const PG_CONNECTION: &str = "host=/run/postgresql dbname=test user=paul";
 pub const RUST_LOG: &str = "RUST_LOG";
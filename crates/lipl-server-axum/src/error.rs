@@ -9,12 +9,24 @@ use thiserror::Error;
 #[derive(Deserialize, Serialize)]
 pub struct ErrorReport {
     error: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    invalid_members: Vec<lipl_core::Uuid>,
+}
+
+impl ErrorReport {
+    pub fn invalid_members(error: &lipl_core::Error, invalid_members: Vec<lipl_core::Uuid>) -> Self {
+        Self {
+            error: error.to_string(),
+            invalid_members,
+        }
+    }
 }
 
 impl<E: std::error::Error> From<E> for ErrorReport {
     fn from(error: E) -> Self {
         Self {
             error: error.to_string(),
+            invalid_members: vec![],
         }
     }
 }
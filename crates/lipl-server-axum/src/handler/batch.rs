@@ -0,0 +1,17 @@
+use std::sync::Arc;
+
+use super::{to_error_response, to_status_ok};
+use axum::{extract::State, Json, response::Response};
+use lipl_core::{LiplRepo, Transaction};
+
+/// Handler for applying a batch of upserts and deletes, all of them or none
+pub async fn post(
+    State(connection): State<Arc<dyn LiplRepo>>,
+    Json(batch): Json<Vec<Transaction>>,
+) -> Response
+{
+    connection
+        .apply_batch(batch)
+        .await
+        .map_or_else(to_error_response, to_status_ok)
+}
@@ -0,0 +1,37 @@
+use std::sync::Arc;
+
+use super::{to_json_response, to_error_response};
+use axum::{extract::{Query, State}, http::StatusCode, Json, response::Response};
+use futures_util::TryFutureExt;
+use lipl_core::{duplicate::{find_duplicates, DEFAULT_THRESHOLD}, LiplRepo, Merge};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+pub struct DuplicateQuery {
+    threshold: Option<f32>,
+}
+
+/// Handler for getting the clusters of lyrics that are probably the same song
+pub async fn list(
+    State(connection): State<Arc<dyn LiplRepo>>,
+    Query(query): Query<DuplicateQuery>,
+) -> Response
+{
+    connection
+        .get_lyrics()
+        .map_ok(|lyrics| find_duplicates(&lyrics, query.threshold.unwrap_or(DEFAULT_THRESHOLD)))
+        .map_ok_or_else(to_error_response, to_json_response(StatusCode::OK))
+        .await
+}
+
+/// Handler for merging duplicates into the lyric to keep, returns the kept lyric
+pub async fn merge(
+    State(connection): State<Arc<dyn LiplRepo>>,
+    Json(merge): Json<Merge>,
+) -> Response
+{
+    connection
+        .merge_lyrics(merge)
+        .map_ok_or_else(to_error_response, to_json_response(StatusCode::OK))
+        .await
+}
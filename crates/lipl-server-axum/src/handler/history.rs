@@ -0,0 +1,67 @@
+use std::sync::Arc;
+
+use super::{to_json_response, to_json_response_with_etag, to_error_response};
+use axum::{extract::{Path, Query, State}, http::StatusCode, response::Response};
+use futures_util::TryFutureExt;
+use lipl_core::{LiplRepo, LyricDiff, Uuid};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+pub struct DiffQuery {
+    to: Option<u64>,
+}
+
+/// Handler for getting all revisions of a lyric, oldest first
+pub async fn list(
+    State(connection): State<Arc<dyn LiplRepo>>,
+    Path(id): Path<Uuid>,
+) -> Response
+{
+    connection
+        .get_lyric_history(id)
+        .map_ok_or_else(to_error_response, to_json_response(StatusCode::OK))
+        .await
+}
+
+/// Handler for getting a specific revision of a lyric
+pub async fn item(
+    State(connection): State<Arc<dyn LiplRepo>>,
+    Path((id, rev)): Path<(Uuid, u64)>,
+) -> Response
+{
+    connection
+        .get_lyric_revision(id, rev)
+        .map_ok_or_else(to_error_response, to_json_response(StatusCode::OK))
+        .await
+}
+
+/// Handler for getting the differences between a revision and the revision in query parameter to, or the latest revision
+pub async fn diff(
+    State(connection): State<Arc<dyn LiplRepo>>,
+    Path((id, rev)): Path<(Uuid, u64)>,
+    Query(query): Query<DiffQuery>,
+) -> Response
+{
+    async {
+        let from = connection.get_lyric_revision(id, rev).await?;
+        let to = match query.to {
+            Some(to) => connection.get_lyric_revision(id, to).await?,
+            None => connection.get_lyric_history(id).await?.pop().ok_or(lipl_core::Error::NotFound(id))?,
+        };
+        Ok::<LyricDiff, lipl_core::Error>(from.diff(&to))
+    }
+    .map_ok_or_else(to_error_response, to_json_response(StatusCode::OK))
+    .await
+}
+
+/// Handler for writing a lyric as it was in a revision
+pub async fn revert(
+    State(connection): State<Arc<dyn LiplRepo>>,
+    Path((id, rev)): Path<(Uuid, u64)>,
+) -> Response
+{
+    connection
+        .revert_lyric(id, rev)
+        .map_ok_or_else(to_error_response, to_json_response_with_etag(StatusCode::OK))
+        .await
+}
@@ -1,46 +1,89 @@
 use std::sync::Arc;
 
-use super::{to_json_response, to_status_ok, to_error_response, Key};
+use super::{to_json_response, to_json_response_with_etag, to_page_response, to_status_ok, to_error_response, IfMatch, Key};
 use axum::{
     Json,
-    extract::{Query, State},
-    http::StatusCode,
-    response::{Response},
+    extract::{OriginalUri, Query, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
 };
-use futures_util::TryFutureExt;
-use lipl_core::{LiplRepo, LyricPost};
-use super::ListQuery;
+use futures_util::{FutureExt, TryFutureExt};
+use lipl_core::{tag::{has_tags, tagged_hits}, Etag, LiplRepo, Lyric, LyricPost, SearchHit};
+use super::{ListQuery, RenderQuery};
 
-/// Handler for getting all lyrics
+/// The hits for the search query, only on the lyrics with all the tags if tags are given
+async fn search(connection: &dyn LiplRepo, q: &str, tags: &[String]) -> lipl_core::Result<Vec<SearchHit>> {
+    let hits = connection.search_lyrics(q).await?;
+    if tags.is_empty() {
+        return Ok(hits);
+    }
+    Ok(tagged_hits(hits, &connection.get_lyrics().await?, tags))
+}
+
+/// Handler for getting all lyrics, or the lyrics matching the search query q, optionally only those with all the tags.
+/// The tags also filter the search hits.
 pub async fn list(
     State(connection): State<Arc<dyn LiplRepo>>,
+    OriginalUri(uri): OriginalUri,
     query: Query<ListQuery>,
 ) -> Response 
 {
-    if query.full == Some(true) {
+    if let Some(q) = query.q.as_deref() {
+        search(connection.as_ref(), q, &query.tags)
+            .map_ok_or_else(to_error_response, to_json_response(StatusCode::OK))
+            .await
+    }
+    else if query.full == Some(true) {
         connection
             .get_lyrics()
+            .map_ok(|lyrics| lyrics.into_iter().filter(|lyric| has_tags(&lyric.tags, &query.tags)).collect::<Vec<_>>())
             .map_ok_or_else(to_error_response, to_json_response(StatusCode::OK))
             .await
     }
     else {
         connection
-            .get_lyric_summaries()
-            .map_ok_or_else(to_error_response, to_json_response(StatusCode::OK))
+            .get_lyric_summaries_page((&*query).into())
+            .map_ok_or_else(to_error_response, to_page_response(uri.path().to_owned(), (&*query).into()))
             .await
     }
 }
 
-/// Handler for getting a specific lyric
+/// Handler for getting a specific lyric, with the chords transposed, the parts arranged or in another language if asked for,
+/// and as ChordPro text if the Accept header asks for it
 pub async fn item(
     State(connection): State<Arc<dyn LiplRepo>>,
+    headers: HeaderMap,
+    render: Query<RenderQuery>,
     key: Key,
 ) -> Response 
 {
-    connection
-        .get_lyric(key.id)
-        .map_ok_or_else(to_error_response, to_json_response(StatusCode::OK))
-        .await
+    let chordpro = headers
+        .get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .is_some_and(|accept| accept.contains(lipl_core::chordpro::CONTENT_TYPE));
+    let lyric = connection.get_lyric(key.id).map(|lyric| lyric.and_then(|lyric| render.apply(lyric)));
+    let mut response = if chordpro {
+        lyric
+            .map_ok_or_else(to_error_response, to_chordpro_response)
+            .await
+    }
+    else {
+        lyric
+            .map_ok_or_else(to_error_response, to_json_response_with_etag(StatusCode::OK))
+            .await
+    };
+    response.headers_mut().insert(header::VARY, HeaderValue::from_static("accept"));
+    response
+}
+
+/// The etag is taken from the ChordPro text, so caches do not mix it up with the JSON body
+fn to_chordpro_response(lyric: Lyric) -> Response {
+    let content_type = [(header::CONTENT_TYPE, lipl_core::chordpro::CONTENT_TYPE.to_owned())];
+    let chordpro = lyric.to_chordpro();
+    match chordpro.etag() {
+        Some(etag) => (StatusCode::OK, content_type, [(header::ETAG, etag)], chordpro).into_response(),
+        None => (StatusCode::OK, content_type, chordpro).into_response(),
+    }
 }
 
 /// Handler for posting a new lyric
@@ -51,31 +94,36 @@ pub async fn post(
 {
     connection
         .upsert_lyric((None, lyric_post).into())
-        .map_ok_or_else(to_error_response, to_json_response(StatusCode::CREATED))
+        .map_ok_or_else(to_error_response, to_json_response_with_etag(StatusCode::CREATED))
         .await
 }
 
-/// Handler for deleting a specific lyric
+/// Handler for deleting a specific lyric, conditional if the If-Match header is present
 pub async fn delete(
     State(connection): State<Arc<dyn LiplRepo>>,
     key: Key,
+    IfMatch(if_match): IfMatch,
 ) -> Response
 {
-        connection
-            .delete_lyric(key.id)
-            .map_ok_or_else(to_error_response, to_status_ok)
-            .await
+    match if_match {
+        Some(etag) => connection.delete_lyric_if_match(key.id, etag).await,
+        None => connection.delete_lyric(key.id).await,
+    }
+    .map_or_else(to_error_response, to_status_ok)
 }
 
-/// Handler for changing a specific lyric
+/// Handler for changing a specific lyric, conditional if the If-Match header is present
 pub async fn put(
     State(connection): State<Arc<dyn LiplRepo>>,
     key: Key,
+    IfMatch(if_match): IfMatch,
     Json(lyric_post): Json<LyricPost>,
 ) -> Response
 {
-    connection
-        .upsert_lyric((Some(key.id), lyric_post).into())
-        .map_ok_or_else(to_error_response, to_json_response(StatusCode::OK))
-        .await
+    let lyric = (Some(key.id), lyric_post).into();
+    match if_match {
+        Some(etag) => connection.upsert_lyric_if_match(lyric, etag).await,
+        None => connection.upsert_lyric(lyric).await,
+    }
+    .map_or_else(to_error_response, to_json_response_with_etag(StatusCode::OK))
 }
@@ -1,19 +1,79 @@
 use std::sync::Arc;
 
-use axum::{response::{IntoResponse, Json, Response}, extract::FromRequestParts};
+use async_trait::async_trait;
+use axum::{response::{IntoResponse, Json, Response}, extract::FromRequestParts, http::header};
 use futures_util::FutureExt;
 use hyper::StatusCode;
-use lipl_core::LiplRepo;
+use lipl_core::{Accidental, Etag, LiplRepo, Lyric, Page, SortField, SortOrder};
 use serde::{Deserialize, Serialize};
 
 use crate::{error::ErrorReport};
 
+pub mod batch;
+pub mod duplicate;
+pub mod history;
 pub mod lyric;
 pub mod playlist;
+pub mod schedule;
+pub mod tag;
+pub mod trash;
+pub mod watch;
+
+const X_TOTAL_COUNT: &str = "x-total-count";
 
 #[derive(Deserialize)]
 pub struct ListQuery {
     full: Option<bool>,
+    q: Option<String>,
+    offset: Option<usize>,
+    limit: Option<usize>,
+    sort: Option<SortField>,
+    order: Option<SortOrder>,
+    prefix: Option<String>,
+    #[serde(default, deserialize_with = "lipl_core::tag::comma_separated::deserialize")]
+    tags: Vec<String>,
+}
+
+impl From<&ListQuery> for lipl_core::ListQuery {
+    fn from(query: &ListQuery) -> Self {
+        Self {
+            offset: query.offset.unwrap_or_default(),
+            limit: query.limit,
+            sort: query.sort.unwrap_or_default(),
+            order: query.order.unwrap_or_default(),
+            prefix: query.prefix.clone(),
+            tags: query.tags.clone(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct RenderQuery {
+    transpose: Option<i32>,
+    #[serde(default)]
+    accidental: Accidental,
+    #[serde(default)]
+    arranged: bool,
+    language: Option<String>,
+    #[serde(default)]
+    side_by_side: bool,
+}
+
+impl RenderQuery {
+    /// The lyric with the chords transposed, the parts in the order they are sung
+    /// and in another language, or side by side with it, if the query asks for it
+    pub fn apply(&self, lyric: Lyric) -> lipl_core::Result<Lyric> {
+        let lyric = match self.transpose {
+            Some(semitones) => lyric.transpose(semitones, self.accidental),
+            None => lyric,
+        };
+        let lyric = if self.arranged { lyric.arranged() } else { lyric };
+        match self.language.as_deref() {
+            Some(language) if self.side_by_side => lyric.side_by_side(language),
+            Some(language) => lyric.translated(language),
+            None => Ok(lyric),
+        }
+    }
 }
 
 pub struct Key {
@@ -34,7 +94,7 @@ impl FromRequestParts<Arc<dyn LiplRepo>> for Key
 
     fn from_request_parts<'life0,'life1,'async_trait>(parts: &'life0 mut axum::http::request::Parts, _state: &'life1 Arc<dyn LiplRepo>) ->  core::pin::Pin<Box<dyn core::future::Future<Output = Result<Self, Self::Rejection> > + core::marker::Send+'async_trait>> where 'life0:'async_trait,'life1:'async_trait,Self:'async_trait {
         async move {
-            parts.uri.path().split('/').last().ok_or(StatusCode::NOT_FOUND)
+            parts.uri.path().split('/').next_back().ok_or(StatusCode::NOT_FOUND)
                 .and_then(|s| s.parse::<lipl_core::Uuid>().map_err(|_| StatusCode::NOT_FOUND))
                 .map(Key::new)
         }
@@ -42,15 +102,58 @@ impl FromRequestParts<Arc<dyn LiplRepo>> for Key
     }
 }
 
+/// Value of the optional If-Match request header
+pub struct IfMatch(pub Option<String>);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for IfMatch
+where
+    S: Send + Sync,
+{
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(parts: &mut axum::http::request::Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts.headers.get(header::IF_MATCH)
+            .map(|value| value.to_str().map(String::from).map_err(|_| StatusCode::BAD_REQUEST))
+            .transpose()
+            .map(IfMatch)
+    }
+}
+
 pub(crate) fn to_json_response<T>(status_code: StatusCode) -> impl Fn(T) -> Response
 where T: Serialize
 {
     move |t| (status_code, Json(t)).into_response()
 }
 
+pub(crate) fn to_json_response_with_etag<T>(status_code: StatusCode) -> impl Fn(T) -> Response
+where T: Serialize
+{
+    move |t| match t.etag() {
+        Some(etag) => (status_code, [(header::ETAG, etag)], Json(t)).into_response(),
+        None => (status_code, Json(t)).into_response(),
+    }
+}
+
+/// Responds with the items of the page, the total count in the X-Total-Count header and links to other pages in the Link header
+pub(crate) fn to_page_response<T>(path: String, query: lipl_core::ListQuery) -> impl Fn(Page<T>) -> Response
+where T: Serialize
+{
+    move |page| {
+        let mut response = (StatusCode::OK, [(X_TOTAL_COUNT, page.total.to_string())], Json(&page.items)).into_response();
+        if let Some(link) = page.link_header(&path, &query).and_then(|link| link.parse().ok()) {
+            response.headers_mut().insert(header::LINK, link);
+        }
+        response
+    }
+}
+
 pub(crate) fn to_error_response(error: lipl_core::Error) -> Response {
     match error {
-        lipl_core::Error::NoKey(_) => (StatusCode::NOT_FOUND, Json(ErrorReport::from(error))).into_response(),
+        lipl_core::Error::NoKey(_) | lipl_core::Error::NotFound(_) => (StatusCode::NOT_FOUND, Json(ErrorReport::from(error))).into_response(),
+        lipl_core::Error::EtagMismatch(_) => (StatusCode::PRECONDITION_FAILED, Json(ErrorReport::from(error))).into_response(),
+        lipl_core::Error::Occupied => (StatusCode::CONFLICT, Json(ErrorReport::from(error))).into_response(),
+        lipl_core::Error::InvalidMembers(_, ref members) => (StatusCode::UNPROCESSABLE_ENTITY, Json(ErrorReport::invalid_members(&error, members.clone()))).into_response(),
         _ => (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorReport::from(error))).into_response()
     }
     
@@ -1,7 +1,7 @@
 use std::sync::Arc;
 
-use super::{to_error_response, to_json_response, to_status_ok, Key};
-use axum::{extract::{State, Query}, http::StatusCode, Json, response::Response};
+use super::{to_error_response, to_json_response, to_json_response_with_etag, to_page_response, to_status_ok, IfMatch, Key};
+use axum::{extract::{OriginalUri, State, Query}, http::StatusCode, Json, response::Response};
 use futures_util::TryFutureExt;
 use lipl_core::{LiplRepo, PlaylistPost};
 use super::ListQuery;
@@ -9,6 +9,7 @@ use super::ListQuery;
 /// Handler for getting all playlists
 pub async fn list(
     State(connection): State<Arc<dyn LiplRepo>>,
+    OriginalUri(uri): OriginalUri,
     query: Query<ListQuery>,
 ) -> Response
 {
@@ -20,8 +21,8 @@ pub async fn list(
     }
     else {
         connection
-        .get_playlist_summaries()
-        .map_ok_or_else(to_error_response, to_json_response(StatusCode::OK))
+        .get_playlist_summaries_page((&*query).into())
+        .map_ok_or_else(to_error_response, to_page_response(uri.path().to_owned(), (&*query).into()))
         .await
     }
 }
@@ -34,7 +35,7 @@ pub async fn item(
 {
     connection
         .get_playlist(key.id)
-        .map_ok_or_else(to_error_response, to_json_response(StatusCode::OK))
+        .map_ok_or_else(to_error_response, to_json_response_with_etag(StatusCode::OK))
         .await
 }
 
@@ -46,30 +47,36 @@ pub async fn post(
 {
     connection
         .upsert_playlist((None, playlist_post).into())
-        .map_ok_or_else(to_error_response, to_json_response(StatusCode::CREATED))
+        .map_ok_or_else(to_error_response, to_json_response_with_etag(StatusCode::CREATED))
         .await
 }
 
-/// Handler for deleting a specific playlist
+/// Handler for deleting a specific playlist, conditional if the If-Match header is present
 pub async fn delete(
     State(connection): State<Arc<dyn LiplRepo>>,
     key: Key,
+    IfMatch(if_match): IfMatch,
 ) -> Response
 {
-    connection.delete_playlist(key.id)
-        .map_ok_or_else(to_error_response, to_status_ok)
-        .await
+    match if_match {
+        Some(etag) => connection.delete_playlist_if_match(key.id, etag).await,
+        None => connection.delete_playlist(key.id).await,
+    }
+    .map_or_else(to_error_response, to_status_ok)
 }
 
-/// Handler for changing a specific playlist
+/// Handler for changing a specific playlist, conditional if the If-Match header is present
 pub async fn put(
     State(connection): State<Arc<dyn LiplRepo>>,
     key: Key,
+    IfMatch(if_match): IfMatch,
     Json(playlist_post): Json<PlaylistPost>,
 ) -> Response
 {
-    connection
-        .upsert_playlist((Some(key.id), playlist_post).into())
-        .map_ok_or_else(to_error_response, to_json_response(StatusCode::OK))
-        .await
+    let playlist = (Some(key.id), playlist_post).into();
+    match if_match {
+        Some(etag) => connection.upsert_playlist_if_match(playlist, etag).await,
+        None => connection.upsert_playlist(playlist).await,
+    }
+    .map_or_else(to_error_response, to_json_response_with_etag(StatusCode::OK))
 }
@@ -0,0 +1,49 @@
+use std::sync::Arc;
+
+use super::{to_error_response, to_json_response};
+use axum::{
+    extract::{Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use futures_util::{future::try_join, TryFutureExt};
+use lipl_core::{schedule::{to_icalendar, CONTENT_TYPE}, DateRange, LiplRepo};
+
+/// Handler for getting the playlists with an event in the date range, ordered by the start of the event,
+/// and as iCalendar feed if the Accept header asks for it
+pub async fn list(
+    State(connection): State<Arc<dyn LiplRepo>>,
+    headers: HeaderMap,
+    Query(range): Query<DateRange>,
+) -> Response
+{
+    let icalendar = headers
+        .get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .is_some_and(|accept| accept.contains(CONTENT_TYPE));
+    if icalendar {
+        calendar(State(connection), Query(range)).await
+    }
+    else {
+        connection
+            .get_scheduled_playlists(range)
+            .map_ok_or_else(to_error_response, to_json_response(StatusCode::OK))
+            .await
+    }
+}
+
+/// Handler for the iCalendar feed with the playlists with an event in the date range, for calendars that cannot set an Accept header
+pub async fn calendar(
+    State(connection): State<Arc<dyn LiplRepo>>,
+    Query(range): Query<DateRange>,
+) -> Response
+{
+    match try_join(connection.get_scheduled_playlists(range), connection.get_lyric_summaries()).await {
+        Ok((playlists, summaries)) => (
+            StatusCode::OK,
+            [(header::CONTENT_TYPE, CONTENT_TYPE)],
+            to_icalendar(&playlists, &summaries),
+        ).into_response(),
+        Err(error) => to_error_response(error),
+    }
+}
@@ -0,0 +1,17 @@
+use std::sync::Arc;
+
+use super::{to_json_response, to_error_response};
+use axum::{extract::State, http::StatusCode, response::Response};
+use futures_util::TryFutureExt;
+use lipl_core::LiplRepo;
+
+/// Handler for getting every tag with the number of lyrics that have it
+pub async fn list(
+    State(connection): State<Arc<dyn LiplRepo>>,
+) -> Response
+{
+    connection
+        .get_tags()
+        .map_ok_or_else(to_error_response, to_json_response(StatusCode::OK))
+        .await
+}
@@ -0,0 +1,41 @@
+use std::sync::Arc;
+
+use super::{to_json_response, to_status_ok, to_error_response, Key};
+use axum::{extract::State, http::StatusCode, response::Response};
+use futures_util::TryFutureExt;
+use lipl_core::LiplRepo;
+
+/// Handler for getting the deleted lyrics and playlists, most recently deleted first
+pub async fn list(
+    State(connection): State<Arc<dyn LiplRepo>>,
+) -> Response
+{
+    connection
+        .list_trash()
+        .map_ok_or_else(to_error_response, to_json_response(StatusCode::OK))
+        .await
+}
+
+/// Handler for restoring a deleted lyric or playlist
+pub async fn restore(
+    State(connection): State<Arc<dyn LiplRepo>>,
+    key: Key,
+) -> Response
+{
+    connection
+        .restore(key.id)
+        .await
+        .map_or_else(to_error_response, to_status_ok)
+}
+
+/// Handler for removing a deleted lyric or playlist permanently
+pub async fn purge(
+    State(connection): State<Arc<dyn LiplRepo>>,
+    key: Key,
+) -> Response
+{
+    connection
+        .purge(key.id)
+        .await
+        .map_or_else(to_error_response, to_status_ok)
+}
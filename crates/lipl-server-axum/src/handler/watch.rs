@@ -0,0 +1,29 @@
+use std::{convert::Infallible, sync::Arc};
+
+use super::to_error_response;
+use axum::{extract::State, response::{sse::{Event, KeepAlive}, IntoResponse, Response, Sse}};
+use futures_util::StreamExt;
+use lipl_core::{Change, LiplRepo};
+
+fn to_event(change: Change) -> Result<Event, Infallible> {
+    Ok(
+        Event::default()
+        .id(change.sequence.to_string())
+        .json_data(&change)
+        .unwrap_or_default()
+    )
+}
+
+/// Handler streaming the committed changes as server-sent events
+pub async fn get(
+    State(connection): State<Arc<dyn LiplRepo>>,
+) -> Response
+{
+    connection
+        .watch()
+        .await
+        .map_or_else(
+            to_error_response,
+            |changes| Sse::new(changes.map(to_event)).keep_alive(KeepAlive::default()).into_response(),
+        )
+}
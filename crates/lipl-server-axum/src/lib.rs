@@ -1,14 +1,16 @@
-use axum::routing::{get};
+use axum::routing::{get, post};
 use axum::{Router};
 use futures_util::TryFutureExt;
 use lipl_core::{ToRepo};
+use lipl_repo_instrumented::{DURATION, DURATION_BUCKETS};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder};
 use tower::ServiceBuilder;
 use tower_http::compression::CompressionLayer;
 use tower_http::trace::TraceLayer;
 
 pub use crate::error::Error;
 pub use crate::param::app::LiplApp;
-use crate::handler::{lyric, playlist};
+use crate::handler::{batch, duplicate, history, lyric, playlist, schedule, tag, trash, watch};
 
 pub mod constant;
 mod error;
@@ -39,8 +41,21 @@ where
             Router::new().nest(constant::PREFIX, Router::new()
                 .route("/lyric", get(lyric::list).post(lyric::post))
                 .route("/lyric/:id", get(lyric::item).delete(lyric::delete).put(lyric::put))
+                .route("/lyric/:id/history", get(history::list))
+                .route("/lyric/:id/history/:rev", get(history::item))
+                .route("/lyric/:id/history/:rev/diff", get(history::diff))
+                .route("/lyric/:id/history/:rev/revert", post(history::revert))
                 .route("/playlist", get(playlist::list).post(playlist::post))
                 .route("/playlist/:id", get(playlist::item).delete(playlist::delete).put(playlist::put))
+                .route("/schedule", get(schedule::list))
+                .route("/schedule.ics", get(schedule::calendar))
+                .route("/tag", get(tag::list))
+                .route("/duplicates", get(duplicate::list))
+                .route("/merge", post(duplicate::merge))
+                .route("/batch", post(batch::post))
+                .route("/trash", get(trash::list))
+                .route("/trash/:id", post(trash::restore).delete(trash::purge))
+                .route("/watch", get(watch::get))
             )
             .layer(
                 ServiceBuilder::new()
@@ -52,3 +67,16 @@ where
         )
         .await
 }
+
+/// Installs the recorder for the metrics of the repo calls and serves them in the prometheus text format.
+/// Only one recorder can be installed per process.
+pub fn with_metrics(service: Router) -> lipl_core::Result<Router> {
+    let handle =
+        PrometheusBuilder::new()
+        .set_buckets_for_metric(Matcher::Full(DURATION.to_owned()), DURATION_BUCKETS)
+        .and_then(PrometheusBuilder::install_recorder)
+        .map_err(|error| lipl_core::Error::Axum(Box::new(error)))?;
+    Ok(
+        service.route(constant::METRICS, get(move || std::future::ready(handle.render())))
+    )
+}
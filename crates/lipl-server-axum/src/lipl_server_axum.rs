@@ -3,7 +3,7 @@ use std::{net::SocketAddr};
 use axum::Router;
 use clap::Parser;
 use futures_util::TryFutureExt;
-use lipl_server_axum::{constant, create_service, exit_on_signal_int, LiplApp};
+use lipl_server_axum::{constant, create_service, exit_on_signal_int, with_metrics, LiplApp};
 use lipl_core::{Result};
 
 async fn run(service: Router) -> Result<()> {
@@ -27,6 +27,7 @@ pub async fn main() -> Result<()> {
         .init();
 
     create_service(LiplApp::parse())
+        .and_then(|service| futures_util::future::ready(with_metrics(service)))
         .and_then(run)
         .await
 }
@@ -1,9 +1,25 @@
+use std::sync::Arc;
+use lipl_core::LiplRepo;
+use lipl_repo_cache::{CacheConfig, CachedRepo};
+use lipl_repo_instrumented::InstrumentedRepo;
+
+/// Traces and measures the calls to the backend, and keeps the results in memory if a cache is configured
+fn wrap(repo: Arc<dyn LiplRepo>, backend: &'static str, cache: Option<CacheConfig>) -> Arc<dyn LiplRepo> {
+    let repo: Arc<dyn LiplRepo> = Arc::new(InstrumentedRepo::new(repo, backend));
+    match cache {
+        Some(config) => Arc::new(CachedRepo::new(repo, config)),
+        None => repo,
+    }
+}
+
 #[cfg(feature = "postgres")]
 pub mod app {
     use std::sync::Arc;
     use async_trait::async_trait;
     use clap::{ArgGroup, Parser};
     use lipl_core::{LiplRepo, ToRepo};
+    use lipl_repo_cache::CacheConfig;
+    use super::wrap;
     use lipl_repo_memory::MemoryRepoConfig;
 
     #[derive(Parser)]
@@ -14,11 +30,13 @@ pub mod app {
         pub postgres: Option<String>,
         #[arg(long, group = "db")]
         pub memory: Option<bool>,
+        #[arg(long, num_args = 0..=1, default_missing_value = "", help = "Keep lyrics and playlists in memory, e.g. --cache ttl=30,capacity=500")]
+        pub cache: Option<CacheConfig>,
     }
 
     impl LiplApp {
         pub fn new_memory(memory: bool) -> Self {
-            Self { postgres: None, memory: Some(memory)}
+            Self { postgres: None, memory: Some(memory), cache: None }
         }
     }
 
@@ -26,17 +44,15 @@ pub mod app {
     impl ToRepo for LiplApp {
     async fn to_repo(self) -> lipl_core::Result<Arc<dyn LiplRepo>> {
         if let Some(postgres) = self.postgres {
-            let pool = lipl_axum_postgres::connection_pool(&postgres).await?;
-            Ok(
-                Arc::new(pool)
-            )    
-    
+            let pool = lipl_repo_postgres_axum::connection_pool(&postgres).await?;
+            Ok(wrap(Arc::new(pool), "postgres", self.cache))
         }
         else {
             let memory = self.memory.unwrap();
-            MemoryRepoConfig { include_sample_data: memory }
+            let repo = MemoryRepoConfig { sample_data: memory, transaction_log: None }
                 .to_repo()
-                .await
+                .await?;
+            Ok(wrap(repo, "memory", self.cache))
         }
     }
 }
@@ -50,18 +66,23 @@ pub mod app {
     use async_trait::async_trait;
     use clap::{Parser};
     use lipl_core::{LiplRepo, ToRepo};
+    use lipl_repo_cache::CacheConfig;
+    use super::wrap;
 
     #[derive(Parser)]
     #[command(author, version, about, long_about = None)]
     pub struct LiplApp {
         #[arg(long)]
         pub memory: bool,
+        #[arg(long, num_args = 0..=1, default_missing_value = "", help = "Keep lyrics and playlists in memory, e.g. --cache ttl=30,capacity=500")]
+        pub cache: Option<CacheConfig>,
     }    
 
     impl LiplApp {
         pub fn new_memory(include_sample_data: bool) -> Self {
             Self {
                 memory: include_sample_data,
+                cache: None,
             }
         }    
     }
@@ -69,9 +90,10 @@ pub mod app {
     #[async_trait]
     impl ToRepo for LiplApp {
     async fn to_repo(self) -> lipl_core::Result<Arc<dyn LiplRepo>> {
-        lipl_repo_memory::MemoryRepoConfig { sample_data: self.memory, transaction_log: None }
+        let repo = lipl_repo_memory::MemoryRepoConfig { sample_data: self.memory, transaction_log: None }
             .to_repo()
-            .await
+            .await?;
+        Ok(wrap(repo, "memory", self.cache))
         }
     }
 }
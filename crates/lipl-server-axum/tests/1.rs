@@ -1,16 +1,17 @@
 use std::vec;
 
 use lipl_server_axum::{create_service, LiplApp};
-use lipl_core::{Lyric, LyricPost, Summary, Playlist, PlaylistPost, Uuid};
+use lipl_core::{Chord, DuplicateCluster, Etag, Lyric, LyricDiff, LyricMetadata, LyricPost, Merge, Revision, SearchHit, Summary, TagCount, Playlist, PlaylistEvent, PlaylistPost, Transaction, Translation, Trashed, Uuid};
 use axum::{
     body::{Body},
-    http::{Request, StatusCode}, Router,
+    http::{header, Request, StatusCode}, Router,
 };
 use serde::{Serialize, de::DeserializeOwned};
 use tower::{ServiceExt};
 
 const LYRIC: &str = "lyric";
 const PLAYLIST: &str = "playlist";
+const TRASH: &str = "trash";
 const PREFIX: &str = "/api/v1/";
 
 fn daar_bij_die_molen() -> LyricPost {
@@ -24,6 +25,12 @@ fn daar_bij_die_molen() -> LyricPost {
                 "Daar bij die molen, die mooie molen".to_owned(),
             ]
         ],
+        chords: vec![],
+        labels: vec![],
+        arrangement: vec![],
+        translations: Default::default(),
+        tags: Default::default(),
+        metadata: Default::default(),
     }
 }
 
@@ -39,7 +46,13 @@ fn roodkapje() -> LyricPost {
                 "'k ga naar grootmoeder koekjes brengen in het bos, in het bos".to_owned(),
                 "'k ga naar grootmoeder koekjes brengen in het bos".to_owned(),
             ]
-        ]
+        ],
+        chords: vec![],
+        labels: vec![],
+        arrangement: vec![],
+        translations: Default::default(),
+        tags: Default::default(),
+        metadata: Default::default(),
     }
 }
 
@@ -68,6 +81,12 @@ async fn lyric_post() {
     let lyric_post = LyricPost {
         title: "Er is er één jarig".to_owned(),
         parts: vec![],
+        chords: vec![],
+        labels: vec![],
+        arrangement: vec![],
+        translations: Default::default(),
+        tags: Default::default(),
+        metadata: Default::default(),
     };
 
     let lyric: Lyric = post(&service, LYRIC, &lyric_post).await;
@@ -75,6 +94,25 @@ async fn lyric_post() {
     assert_eq!(lyric.parts, lyric_post.parts);
 }
 
+#[tokio::test(flavor = "current_thread")]
+async fn lyric_post_metadata() {
+    let service = create_service(LiplApp::new_memory(false)).await.unwrap();
+
+    let mut lyric_post = roodkapje();
+    lyric_post.metadata = LyricMetadata {
+        sub_title: Some("Kinderlied".to_owned()),
+        language: Some("nl".to_owned()),
+        year: Some(1900),
+        ..Default::default()
+    };
+
+    let lyric: Lyric = post(&service, LYRIC, &lyric_post).await;
+    assert_eq!(lyric.metadata, lyric_post.metadata);
+
+    let lyric: Lyric = item(&service, LYRIC, lyric.id.to_string()).await;
+    assert_eq!(lyric.metadata, lyric_post.metadata);
+}
+
 #[tokio::test(flavor = "current_thread")]
 async fn lyric_post_change() {
     let service = create_service(LiplApp::new_memory(false)).await.unwrap();
@@ -92,6 +130,81 @@ async fn lyric_post_change() {
     assert_eq!(lyric_changed.title, lyric_post.title);
 }
 
+#[tokio::test(flavor = "current_thread")]
+async fn lyric_put_if_match() {
+    let service = create_service(LiplApp::new_memory(false)).await.unwrap();
+
+    let mut lyric_post = daar_bij_die_molen();
+    let lyric: Lyric = post(&service, LYRIC, &lyric_post).await;
+    let id = lyric.id.to_string();
+    let etag = item_etag(&service, LYRIC, id.clone()).await;
+    assert_eq!(Some(etag.clone()), lyric.etag());
+
+    lyric_post.title = "Daar bij dat molengedrag".to_owned();
+    let status = put_if_match(&service, LYRIC, id.clone(), "\"stale\"", &lyric_post).await;
+    assert_eq!(status, StatusCode::PRECONDITION_FAILED);
+
+    let status = put_if_match(&service, LYRIC, id.clone(), &etag, &lyric_post).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let status = put_if_match(&service, LYRIC, id.clone(), &etag, &lyric_post).await;
+    assert_eq!(status, StatusCode::PRECONDITION_FAILED);
+
+    let lyric_changed: Lyric = item(&service, LYRIC, id).await;
+    assert_eq!(lyric_changed.title, lyric_post.title);
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn lyric_search() {
+    let service = create_service(LiplApp::new_memory(false)).await.unwrap();
+
+    let roodkapje: Lyric = post(&service, LYRIC, &roodkapje()).await;
+    let _daar_bij_die_molen: Lyric = post(&service, LYRIC, &daar_bij_die_molen()).await;
+
+    let hits: Vec<SearchHit> = list(&service, "lyric?q=GROOTMOEDER%20koekjes").await;
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].summary.id, roodkapje.id);
+    assert_eq!(hits[0].line, Some("'k ga naar grootmoeder koekjes brengen in het bos, in het bos".to_owned()));
+
+    let hits: Vec<SearchHit> = list(&service, "lyric?q=molen").await;
+    assert_eq!(hits[0].summary.title, "Daar bij die molen".to_owned());
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn lyric_list_page() {
+    let service = create_service(LiplApp::new_memory(false)).await.unwrap();
+
+    let _roodkapje: Lyric = post(&service, LYRIC, &roodkapje()).await;
+    let daar_bij_die_molen: Lyric = post(&service, LYRIC, &daar_bij_die_molen()).await;
+
+    let response = service
+        .clone()
+        .oneshot(
+            Request::get(format!("{PREFIX}lyric?limit=1&order=desc"))
+            .body(Body::empty())
+            .unwrap()
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.headers().get("x-total-count").unwrap(), "2");
+    assert_eq!(
+        response.headers().get(header::LINK).unwrap(),
+        concat!(
+            "</api/v1/lyric?offset=0&limit=1&sort=title&order=desc>; rel=\"first\", ",
+            "</api/v1/lyric?offset=1&limit=1&sort=title&order=desc>; rel=\"next\", ",
+            "</api/v1/lyric?offset=1&limit=1&sort=title&order=desc>; rel=\"last\"",
+        ),
+    );
+
+    let lyrics: Vec<Summary> = list(&service, "lyric?offset=1&limit=1&order=desc").await;
+    assert_eq!(lyrics, vec![Summary { id: daar_bij_die_molen.id, title: daar_bij_die_molen.title }]);
+
+    let lyrics: Vec<Summary> = list(&service, "lyric?prefix=ROOD").await;
+    assert_eq!(lyrics.len(), 1);
+    assert_eq!(lyrics[0].title, "Roodkapje".to_owned());
+}
+
 #[tokio::test(flavor = "current_thread")]
 async fn lyric_delete() {
     let service = create_service(LiplApp::new_memory(false)).await.unwrap();
@@ -118,6 +231,8 @@ async fn playlist_list() {
     let playlist_post = PlaylistPost {
         title: "Alle 13 goed".to_owned(),
         members: vec![],
+        entries: vec![],
+        event: None,
     };
 
     let _playlist: Playlist = post(&service, PLAYLIST, &playlist_post).await;
@@ -136,6 +251,8 @@ async fn playlist_post() {
     let playlist_post = PlaylistPost {
         title: "Alle 13 goed".to_owned(),
         members: vec![],
+        entries: vec![],
+        event: None,
     };
 
     let playlist: Playlist = post(&service, PLAYLIST, &playlist_post).await;
@@ -155,6 +272,8 @@ async fn playlist_post_lyric_delete() {
     let playlist_post = PlaylistPost {
         title: "Alle 13 goed".to_owned(),
         members: vec![roodkapje.id, daar_bij_die_molen.id],
+        entries: vec![],
+        event: None,
     };
 
     let playlist: Playlist = post(&service, PLAYLIST, &playlist_post).await;
@@ -166,6 +285,327 @@ async fn playlist_post_lyric_delete() {
     assert_eq!(playlist.members, vec![daar_bij_die_molen.id]);
 }
 
+#[tokio::test(flavor = "current_thread")]
+async fn playlist_post_invalid_members() {
+    let service = create_service(LiplApp::new_memory(false)).await.unwrap();
+
+    let roodkapje: Lyric = post(&service, LYRIC, &roodkapje()).await;
+    let unknown = Uuid::default();
+    let playlist_post = PlaylistPost {
+        title: "Alle 13 goed".to_owned(),
+        members: vec![roodkapje.id, unknown],
+        entries: vec![],
+        event: None,
+    };
+
+    let response =
+        service
+        .clone()
+        .oneshot(
+            Request::post(format!("{PREFIX}{PLAYLIST}"))
+            .header("Content-Type", "application/json")
+            .body(serde_json::to_string(&playlist_post).unwrap().into())
+            .unwrap()
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let report: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(report["invalid_members"], serde_json::json!([unknown]));
+
+    let playlists: Vec<Summary> = list(&service, PLAYLIST).await;
+    assert!(playlists.is_empty());
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn batch_post() {
+    let service = create_service(LiplApp::new_memory(false)).await.unwrap();
+
+    let roodkapje: Lyric = roodkapje().into();
+    let daar_bij_die_molen: Lyric = daar_bij_die_molen().into();
+    let playlist: Playlist = (None, PlaylistPost { title: "Kinderliedjes".to_owned(), members: vec![roodkapje.id, daar_bij_die_molen.id], entries: vec![], event: None }).into();
+
+    let status = batch(
+        &service,
+        &[
+            Transaction::LyricUpsert(roodkapje.clone()),
+            Transaction::LyricUpsert(daar_bij_die_molen.clone()),
+            Transaction::PlaylistUpsert(playlist.clone()),
+        ],
+    ).await;
+    assert_eq!(status, StatusCode::OK);
+    let retrieved: Playlist = item(&service, PLAYLIST, playlist.id.to_string()).await;
+    assert_eq!(retrieved.members, vec![roodkapje.id, daar_bij_die_molen.id]);
+
+    let status = batch(
+        &service,
+        &[
+            Transaction::LyricDelete(roodkapje.id),
+            Transaction::PlaylistDelete(Uuid::default()),
+        ],
+    ).await;
+    assert_ne!(status, StatusCode::OK);
+    let lyrics: Vec<Summary> = list(&service, LYRIC).await;
+    assert_eq!(lyrics.len(), 2);
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn watch_event() {
+    let service = create_service(LiplApp::new_memory(false)).await.unwrap();
+
+    let response = 
+        service
+        .clone()
+        .oneshot(Request::get(format!("{PREFIX}watch")).body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[header::CONTENT_TYPE], "text/event-stream");
+
+    let roodkapje: Lyric = roodkapje().into();
+    let status = batch(&service, &[Transaction::LyricUpsert(roodkapje.clone())]).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let mut body = response.into_body();
+    let chunk = hyper::body::HttpBody::data(&mut body).await.unwrap().unwrap();
+    let event = String::from_utf8(chunk.to_vec()).unwrap();
+    assert!(event.contains("id:1\n"));
+    assert!(event.contains(&roodkapje.id.to_string()));
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn trash_restore_purge() {
+    let service = create_service(LiplApp::new_memory(false)).await.unwrap();
+
+    let roodkapje: Lyric = post(&service, LYRIC, &roodkapje()).await;
+    let daar_bij_die_molen: Lyric = post(&service, LYRIC, &daar_bij_die_molen()).await;
+    let playlist_post = PlaylistPost {
+        title: "Kinderliedjes".to_owned(),
+        members: vec![roodkapje.id, daar_bij_die_molen.id],
+        entries: vec![],
+        event: None,
+    };
+    let playlist: Playlist = post(&service, PLAYLIST, &playlist_post).await;
+
+    delete(&service, LYRIC, roodkapje.id.to_string()).await;
+    let trash: Vec<Trashed> = list(&service, TRASH).await;
+    assert_eq!(trash.len(), 1);
+    assert_eq!(trash[0].id(), roodkapje.id);
+
+    let status = trash_request(&service, Request::post(format!("{PREFIX}{TRASH}/{}", roodkapje.id))).await;
+    assert_eq!(status, StatusCode::OK);
+    let restored: Playlist = item(&service, PLAYLIST, playlist.id.to_string()).await;
+    assert_eq!(restored.members, vec![roodkapje.id, daar_bij_die_molen.id]);
+    let trash: Vec<Trashed> = list(&service, TRASH).await;
+    assert!(trash.is_empty());
+
+    delete(&service, LYRIC, roodkapje.id.to_string()).await;
+    let status = trash_request(&service, Request::delete(format!("{PREFIX}{TRASH}/{}", roodkapje.id))).await;
+    assert_eq!(status, StatusCode::OK);
+    let status = trash_request(&service, Request::post(format!("{PREFIX}{TRASH}/{}", roodkapje.id))).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn lyric_history_revert() {
+    let service = create_service(LiplApp::new_memory(false)).await.unwrap();
+
+    let lyric: Lyric = post(&service, LYRIC, &roodkapje()).await;
+    let mut changed: LyricPost = lyric.clone().into();
+    changed.title = "Zeg roodkapje".to_owned();
+    let _: Lyric = put(&service, LYRIC, lyric.id.to_string(), &changed).await;
+
+    let history: Vec<Revision> = item(&service, LYRIC, format!("{}/history", lyric.id)).await;
+    assert_eq!(history.iter().map(|revision| revision.rev).collect::<Vec<_>>(), vec![1, 2]);
+
+    let diff: LyricDiff = item(&service, LYRIC, format!("{}/history/1/diff", lyric.id)).await;
+    assert_eq!(diff.title, Some(("Roodkapje".to_owned(), "Zeg roodkapje".to_owned())));
+
+    let status = trash_request(&service, Request::post(format!("{PREFIX}{LYRIC}/{}/history/1/revert", lyric.id))).await;
+    assert_eq!(status, StatusCode::OK);
+    let revision: Revision = item(&service, LYRIC, format!("{}/history/3", lyric.id)).await;
+    assert_eq!(revision.lyric.title, "Roodkapje".to_owned());
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn lyric_chordpro() {
+    let service = create_service(LiplApp::new_memory(false)).await.unwrap();
+    let lyric: Lyric = post(&service, LYRIC, &roodkapje()).await;
+
+    let response = service
+        .clone()
+        .oneshot(
+            Request::get(format!("{PREFIX}{LYRIC}/{}", lyric.id))
+            .header(header::ACCEPT, lipl_core::chordpro::CONTENT_TYPE)
+            .body(Body::empty())
+            .unwrap()
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[header::CONTENT_TYPE], lipl_core::chordpro::CONTENT_TYPE);
+    assert_eq!(response.headers()[header::ETAG], lyric.to_chordpro().etag().unwrap().as_str());
+    assert_ne!(response.headers()[header::ETAG], lyric.etag().unwrap().as_str());
+    assert_eq!(response.headers()[header::VARY], "accept");
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    assert_eq!(String::from_utf8(body.to_vec()).unwrap(), lyric.to_chordpro());
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn lyric_transpose() {
+    let service = create_service(LiplApp::new_memory(false)).await.unwrap();
+    let lyric_post = LyricPost {
+        chords: vec![vec![vec![Chord { position: 0, name: "A".to_owned() }, Chord { position: 14, name: "E7".to_owned() }], vec![]]],
+        ..roodkapje()
+    };
+    let lyric: Lyric = post(&service, LYRIC, &lyric_post).await;
+    assert_eq!(lyric.chords, lyric_post.chords);
+
+    let transposed: Lyric = item(&service, LYRIC, format!("{}?transpose=1&accidental=flat", lyric.id)).await;
+    assert_eq!(transposed.parts, lyric.parts);
+    assert_eq!(transposed.chords[0][0].iter().map(|chord| chord.name.as_str()).collect::<Vec<_>>(), vec!["Bb", "F7"]);
+    let unchanged: Lyric = item(&service, LYRIC, lyric.id.to_string()).await;
+    assert_eq!(unchanged.chords, lyric.chords);
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn lyric_arranged() {
+    let service = create_service(LiplApp::new_memory(false)).await.unwrap();
+    let lyric_post = LyricPost::from(("Roodkapje", "Zeg roodkapje\n\nRefrein:\nNaar grootmoeder\n\nWat heb je in je mandje\n\nRefrein"));
+    let lyric: Lyric = post(&service, LYRIC, &lyric_post).await;
+    assert_eq!(lyric.parts.len(), 3);
+    assert_eq!(lyric.arrangement, vec!["1", "Refrein", "3", "Refrein"]);
+
+    let arranged: Lyric = item(&service, LYRIC, format!("{}?arranged=true", lyric.id)).await;
+    assert_eq!(arranged.parts.len(), 4);
+    assert_eq!(arranged.parts[3], lyric.parts[1]);
+    assert!(arranged.arrangement.is_empty());
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn lyric_translations() {
+    let service = create_service(LiplApp::new_memory(false)).await.unwrap();
+    let mut lyric_post = LyricPost::from(("Roodkapje", "Zeg roodkapje\nwaar ga je heen"));
+    lyric_post.translations.insert(
+        "en".to_owned(),
+        Translation { title: "Red Riding Hood".to_owned(), parts: vec![vec!["Say red riding hood".to_owned(), "where are you going".to_owned()]] },
+    );
+    let lyric: Lyric = post(&service, LYRIC, &lyric_post).await;
+    assert_eq!(lyric.translations, lyric_post.translations);
+
+    let english: Lyric = item(&service, LYRIC, format!("{}?language=en", lyric.id)).await;
+    assert_eq!(english.title, "Red Riding Hood");
+    assert_eq!(english.parts, lyric_post.translations["en"].parts);
+
+    let side_by_side: Lyric = item(&service, LYRIC, format!("{}?language=en&side_by_side=true", lyric.id)).await;
+    assert_eq!(side_by_side.parts, vec![vec!["Zeg roodkapje", "Say red riding hood", "waar ga je heen", "where are you going"]]);
+
+    let status = trash_request(&service, Request::get(format!("{PREFIX}{LYRIC}/{}?language=fy", lyric.id))).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn schedule_calendar() {
+    let service = create_service(LiplApp::new_memory(false)).await.unwrap();
+    let roodkapje: Lyric = post(&service, LYRIC, &roodkapje()).await;
+    let event = |start: &str| Some(PlaylistEvent { start: start.parse().unwrap(), location: Some("Grote Kerk".to_owned()), leader: None });
+    let sunday: Playlist = post(&service, PLAYLIST, &PlaylistPost { title: "Zondag".to_owned(), members: vec![roodkapje.id], entries: vec![], event: event("2026-10-25T10:00:00") }).await;
+    let _past: Playlist = post(&service, PLAYLIST, &PlaylistPost { title: "Vorige week".to_owned(), members: vec![], entries: vec![], event: event("2026-10-11T10:00:00") }).await;
+    let _unscheduled: Playlist = post(&service, PLAYLIST, &PlaylistPost { title: "Ooit".to_owned(), members: vec![], entries: vec![], event: None }).await;
+
+    let scheduled: Vec<Playlist> = list(&service, "schedule?from=2026-10-18").await;
+    assert_eq!(scheduled.iter().map(|playlist| playlist.id).collect::<Vec<_>>(), vec![sunday.id]);
+    assert_eq!(scheduled[0].event, sunday.event);
+
+    let response = service
+        .clone()
+        .oneshot(
+            Request::get(format!("{PREFIX}schedule.ics?from=2026-10-18"))
+            .body(Body::empty())
+            .unwrap()
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[header::CONTENT_TYPE], lipl_core::schedule::CONTENT_TYPE);
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let calendar = String::from_utf8(body.to_vec()).unwrap();
+    assert_eq!(calendar.matches("BEGIN:VEVENT").count(), 1);
+    assert!(calendar.contains(&format!("UID:{}@lipl\r\n", sunday.id)));
+    assert!(calendar.contains("SUMMARY:Zondag\r\n"));
+    assert!(calendar.contains("DESCRIPTION:Roodkapje\r\n"));
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn duplicates_merge() {
+    let service = create_service(LiplApp::new_memory(false)).await.unwrap();
+    let copy: Lyric = post(&service, LYRIC, &LyricPost { title: "Roodkapje (kopie)".to_owned(), ..roodkapje() }).await;
+    let roodkapje: Lyric = post(&service, LYRIC, &roodkapje()).await;
+    let molen: Lyric = post(&service, LYRIC, &daar_bij_die_molen()).await;
+    let playlist: Playlist = post(&service, PLAYLIST, &PlaylistPost { title: "Kinderliedjes".to_owned(), members: vec![copy.id, molen.id], entries: vec![], event: None }).await;
+
+    let clusters: Vec<DuplicateCluster> = list(&service, "duplicates").await;
+    assert_eq!(clusters.len(), 1);
+    assert_eq!(clusters[0].lyrics.iter().map(|summary| summary.id).collect::<Vec<_>>(), vec![roodkapje.id, copy.id]);
+    let clusters: Vec<DuplicateCluster> = list(&service, "duplicates?threshold=1.1").await;
+    assert!(clusters.is_empty());
+
+    let body = serde_json::to_string(&Merge { keep: roodkapje.id, duplicates: vec![copy.id] }).unwrap();
+    let response = service
+        .clone()
+        .oneshot(
+            Request::post(format!("{PREFIX}merge"))
+            .header("Content-Type", "application/json")
+            .body(body.into())
+            .unwrap()
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let kept: Lyric = serde_json::from_slice(&body).unwrap();
+    assert_eq!(kept.id, roodkapje.id);
+
+    let merged: Playlist = item(&service, PLAYLIST, playlist.id.to_string()).await;
+    assert_eq!(merged.members, vec![roodkapje.id, molen.id]);
+    let trashed: Vec<Trashed> = list(&service, TRASH).await;
+    assert_eq!(trashed.len(), 1);
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn lyric_tags() {
+    let service = create_service(LiplApp::new_memory(false)).await.unwrap();
+    let tags = |list: &[&str]| list.iter().map(|tag| tag.to_string()).collect();
+    let roodkapje: Lyric = post(&service, LYRIC, &LyricPost { tags: tags(&["kinderliedjes", "sprookjes"]), ..roodkapje() }).await;
+    let molen: Lyric = post(&service, LYRIC, &LyricPost { tags: tags(&["kinderliedjes"]), ..daar_bij_die_molen() }).await;
+
+    let counts: Vec<TagCount> = list(&service, "tag").await;
+    assert_eq!(
+        counts,
+        vec![
+            TagCount { tag: "kinderliedjes".to_owned(), count: 2 },
+            TagCount { tag: "sprookjes".to_owned(), count: 1 },
+        ]
+    );
+
+    let summaries: Vec<Summary> = list(&service, "lyric?tags=kinderliedjes").await;
+    assert_eq!(summaries.iter().map(|summary| summary.id).collect::<Vec<_>>(), vec![molen.id, roodkapje.id]);
+    let summaries: Vec<Summary> = list(&service, "lyric?tags=kinderliedjes,sprookjes").await;
+    assert_eq!(summaries.iter().map(|summary| summary.id).collect::<Vec<_>>(), vec![roodkapje.id]);
+    let lyrics: Vec<Lyric> = list(&service, "lyric?full=true&tags=sprookjes").await;
+    assert_eq!(lyrics.iter().map(|lyric| lyric.id).collect::<Vec<_>>(), vec![roodkapje.id]);
+
+    let hits: Vec<SearchHit> = list(&service, "lyric?q=grootmoeder&tags=sprookjes").await;
+    assert_eq!(hits.iter().map(|hit| hit.summary.id).collect::<Vec<_>>(), vec![roodkapje.id]);
+    let hits: Vec<SearchHit> = list(&service, "lyric?q=molen&tags=sprookjes").await;
+    assert!(hits.is_empty(), "the tags should filter the search hits");
+}
+
 async fn list<R: DeserializeOwned>(service: &Router<()>, name: &'static str) -> Vec<R> {
     let response = service
         .clone()
@@ -212,13 +652,13 @@ async fn delete(service: &Router<()>, name: &'static str, id: String) {
     assert_eq!(response.status(), StatusCode::OK);
 }
 
-async fn post<'a, T: Serialize, R: DeserializeOwned>(service: &'a Router<()>, name: &str, t: &T) -> R {
+async fn post<T: Serialize, R: DeserializeOwned>(service: &Router<()>, name: &str, t: &T) -> R {
     let body = serde_json::to_string(t).unwrap();
     let response =
         service
         .clone()
         .oneshot(
-            Request::post(format!("{}{}", PREFIX, name.to_string()))
+            Request::post(format!("{PREFIX}{name}"))
             .header("Content-Type", "application/json")
             .body(body.into())
             .unwrap()
@@ -233,7 +673,7 @@ async fn post<'a, T: Serialize, R: DeserializeOwned>(service: &'a Router<()>, na
     r
 }
 
-async fn put<'a, T: Serialize, R: DeserializeOwned>(service: &'a Router<()>, name: &str, id: String, t: &T) -> R {
+async fn put<T: Serialize, R: DeserializeOwned>(service: &Router<()>, name: &str, id: String, t: &T) -> R {
     let body = serde_json::to_string(t).unwrap();
     let response =
         service
@@ -252,4 +692,63 @@ async fn put<'a, T: Serialize, R: DeserializeOwned>(service: &'a Router<()>, nam
     let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
     let r: R = serde_json::from_slice(&body).unwrap();
     r
-}
\ No newline at end of file
+}
+async fn item_etag(service: &Router<()>, name: &'static str, uuid: String) -> String {
+    let response = service
+        .clone()
+        .oneshot(
+            Request::get(format!("{PREFIX}{name}/{uuid}"))
+            .body(Body::empty())
+            .unwrap()
+        )
+        .await
+        .unwrap();
+
+    response.headers().get(header::ETAG).unwrap().to_str().unwrap().to_owned()
+}
+
+async fn put_if_match<T: Serialize>(service: &Router<()>, name: &str, id: String, etag: &str, t: &T) -> StatusCode {
+    let body = serde_json::to_string(t).unwrap();
+    let response =
+        service
+        .clone()
+        .oneshot(
+            Request::put(format!("{PREFIX}{name}/{id}"))
+            .header("Content-Type", "application/json")
+            .header(header::IF_MATCH, etag)
+            .body(body.into())
+            .unwrap()
+        )
+        .await
+        .unwrap();
+
+    response.status()
+}
+
+async fn batch(service: &Router<()>, transactions: &[Transaction]) -> StatusCode {
+    let body = serde_json::to_string(transactions).unwrap();
+    let response =
+        service
+        .clone()
+        .oneshot(
+            Request::post(format!("{PREFIX}batch"))
+            .header("Content-Type", "application/json")
+            .body(body.into())
+            .unwrap()
+        )
+        .await
+        .unwrap();
+
+    response.status()
+}
+
+async fn trash_request(service: &Router<()>, builder: axum::http::request::Builder) -> StatusCode {
+    let response =
+        service
+        .clone()
+        .oneshot(builder.body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+
+    response.status()
+}
@@ -3,6 +3,14 @@ pub const VERSION: &str = "v1";
 pub const HOST: [u8; 4] = [0, 0, 0, 0];
 pub const LYRIC: &str = "lyric";
 pub const PLAYLIST: &str = "playlist";
+pub const BATCH: &str = "batch";
+pub const TRASH: &str = "trash";
+pub const SCHEDULE: &str = "schedule";
+pub const CALENDAR: &str = "schedule.ics";
+pub const TAG: &str = "tag";
+pub const DUPLICATES: &str = "duplicates";
+pub const MERGE: &str = "merge";
+pub const METRICS: &str = "metrics";
 pub const LOG_LEVEL: &str = "info";
 pub const LOG_NAME: &str = "request";
 pub const RUST_LOG: &str = "RUST_LOG";
@@ -1,6 +1,6 @@
 use std::sync::Arc;
 
-use lipl_core::{LiplRepo, RepoDb};
+use lipl_core::{chordpro::lyrics_to_chordpro, duplicate::{find_duplicates, DEFAULT_THRESHOLD}, LiplRepo, Merge, RepoDb, Uuid};
 use tracing::{info};
 
 pub async fn list(repo: Arc<dyn LiplRepo>, yaml: bool) -> lipl_core::Result<()>
@@ -14,6 +14,42 @@ pub async fn list(repo: Arc<dyn LiplRepo>, yaml: bool) -> lipl_core::Result<()>
     Ok(())
 }
 
+pub async fn export(repo: Arc<dyn LiplRepo>, playlist: Option<Uuid>) -> lipl_core::Result<()>
+{
+    let lyrics = match playlist {
+        Some(id) => {
+            let mut lyrics = vec![];
+            for member in repo.get_playlist(id).await?.members {
+                lyrics.push(repo.get_lyric(member).await?);
+            }
+            lyrics
+        },
+        None => repo.get_lyrics().await?,
+    };
+
+    print!("{}", lyrics_to_chordpro(&lyrics));
+    Ok(())
+}
+
+pub async fn duplicates(repo: Arc<dyn LiplRepo>, threshold: Option<f32>) -> lipl_core::Result<()>
+{
+    let lyrics = repo.get_lyrics().await?;
+    for cluster in find_duplicates(&lyrics, threshold.unwrap_or(DEFAULT_THRESHOLD)) {
+        println!("Similarity {:.2}", cluster.similarity);
+        for summary in cluster.lyrics {
+            println!("  {} {}", summary.id, summary.title);
+        }
+    }
+    Ok(())
+}
+
+pub async fn merge(repo: Arc<dyn LiplRepo>, merge: Merge) -> lipl_core::Result<()>
+{
+    let lyric = repo.merge_lyrics(merge).await?;
+    info!("Merged duplicates into lyric {} with id {}", lyric.title, lyric.id);
+    repo.stop().await
+}
+
 pub async fn copy(source: Arc<dyn LiplRepo>, target: Arc<dyn LiplRepo>) -> lipl_core::Result<()>
 {
     for lyric in source.get_lyrics().await? {
@@ -26,5 +62,23 @@ pub async fn copy(source: Arc<dyn LiplRepo>, target: Arc<dyn LiplRepo>) -> lipl_
         target.upsert_playlist(playlist).await.unwrap();
     }
 
-    Ok(())
+    target.stop().await
+}
+
+#[cfg(feature = "file")]
+pub async fn compact(config: lipl_repo_fs::FileRepoConfig) -> lipl_core::Result<()>
+{
+    let repo = lipl_repo_fs::FileRepo::with_log_format(config.path, config.log_format).await?;
+    repo.compact().await?;
+    info!("Compacted transaction log");
+    repo.stop().await
+}
+
+#[cfg(feature = "file")]
+pub async fn recover(source: lipl_repo_fs::FileRepoConfig, target: Arc<dyn LiplRepo>, until: lipl_core::transaction::ReplayUntil) -> lipl_core::Result<()>
+{
+    let log = std::path::Path::new(&source.path).join(lipl_repo_fs::TRANSACTION_LOG);
+    let replayed = lipl_core::transaction::replay_until(&log, target.as_ref(), &until).await?;
+    info!("Replayed {replayed} transactions");
+    target.stop().await
 }
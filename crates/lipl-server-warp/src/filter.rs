@@ -1,10 +1,18 @@
 use std::sync::Arc;
-use warp::{body, path, Filter};
+use warp::{body, header, path, Filter};
 use warp::filters::query;
 use lipl_core::{LiplRepo};
 use crate::constant::{API, VERSION};
 use crate::handler::lyric as lyric_handler;
 use crate::handler::playlist as playlist_handler;
+use crate::handler::search as search_handler;
+use crate::handler::batch as batch_handler;
+use crate::handler::chords as chords_handler;
+use crate::handler::trash as trash_handler;
+use crate::handler::history as history_handler;
+use crate::handler::schedule as schedule_handler;
+use crate::handler::duplicate as duplicate_handler;
+use crate::handler::tag as tag_handler;
 
 macro_rules! join_paths {
     ($head:expr, $($rest:expr),*) => { warp::path($head)$(.and(warp::path($rest)))* };
@@ -26,17 +34,135 @@ macro_rules! create_fn {
             let prefix = join_paths!(API, VERSION, name);
         
             let list         = and! (warp::get()   , prefix, path::end()  , repo_filter.clone(), query::query() ) .and_then($handler::list);
-            let summaries    = and! (warp::get()   , prefix, path::end()  , repo_filter.clone()                 ) .and_then($handler::list_summary);
-            let item         = and! (warp::get()   , prefix, path::param(), repo_filter.clone()                 ) .and_then($handler::item);
+            let summaries    = and! (warp::get()   , prefix, path::end()  , path::full(), repo_filter.clone(), query::query()) .and_then($handler::list_summary);
+            let item         = and! (warp::get()   , prefix, path::param(), path::end(), repo_filter.clone()) .and_then($handler::item);
             let post         = and! (warp::post()  , prefix, path::end()  , repo_filter.clone(), body::json()   ) .and_then($handler::post);
-            let put          = and! (warp::put()   , prefix, path::param(), repo_filter.clone(), body::json()   ) .and_then($handler::put);
-            let delete       = and! (warp::delete(), prefix, path::param(), repo_filter.clone()                 ) .and_then($handler::delete);
+            let if_match     = header::optional::<String>("if-match");
+        
+            let put          = and! (warp::put()   , prefix, path::param(), path::end(), repo_filter.clone(), if_match.clone(), body::json()) .and_then($handler::put);
+            let delete       = and! (warp::delete(), prefix, path::param(), path::end(), repo_filter.clone(), if_match.clone()) .and_then($handler::delete);
         
             or!(list, summaries, item, post, put, delete)
         }
     };
 }
 
+pub fn get_lyric_search_route(repo: Arc<dyn LiplRepo>, name: &'static str) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone
+{
+    let repo_filter = warp::any().map(move || repo.clone());
+    let prefix = join_paths!(API, VERSION, name);
+
+    and! (warp::get(), prefix, path::end(), repo_filter, query::query()).and_then(search_handler::lyrics)
+}
+
+pub fn get_lyric_chords_route(repo: Arc<dyn LiplRepo>, name: &'static str) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone
+{
+    let repo_filter = warp::any().map(move || repo.clone());
+    let prefix = join_paths!(API, VERSION, name);
+
+    and! (warp::get(), prefix, path::param(), path::end(), query::query(), header::optional::<String>("accept"), repo_filter).and_then(chords_handler::lyric)
+}
+
+pub fn get_batch_route(repo: Arc<dyn LiplRepo>, name: &'static str) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone
+{
+    let repo_filter = warp::any().map(move || repo.clone());
+    let prefix = join_paths!(API, VERSION, name);
+
+    and! (warp::post(), prefix, path::end(), repo_filter, body::json()).and_then(batch_handler::post)
+}
+
+/// Every tag with the number of lyrics that have it
+pub fn get_tag_route(repo: Arc<dyn LiplRepo>, name: &'static str) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone
+{
+    let repo_filter = warp::any().map(move || repo.clone());
+    let prefix = join_paths!(API, VERSION, name);
+
+    and! (warp::get(), prefix, path::end(), repo_filter).and_then(tag_handler::list)
+}
+
+/// The clusters of probable duplicates and the merge of duplicates into the lyric to keep
+pub fn get_duplicate_routes(repo: Arc<dyn LiplRepo>, name: &'static str, merge: &'static str) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone
+{
+    let repo_filter = warp::any().map(move || repo.clone());
+
+    let list  = and! (warp::get() , join_paths!(API, VERSION, name) , path::end(), repo_filter.clone(), query::query()) .and_then(duplicate_handler::list);
+    let merge = and! (warp::post(), join_paths!(API, VERSION, merge), path::end(), repo_filter.clone(), body::json() ) .and_then(duplicate_handler::merge);
+
+    or!(list, merge)
+}
+
+pub fn get_trash_routes(repo: Arc<dyn LiplRepo>, name: &'static str) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone
+{
+    let repo_filter = warp::any().map(move || repo.clone());
+    let prefix = join_paths!(API, VERSION, name);
+
+    let list    = and! (warp::get()   , prefix, path::end()  , repo_filter.clone()) .and_then(trash_handler::list);
+    let restore = and! (warp::post()  , prefix, path::param(), repo_filter.clone()) .and_then(trash_handler::restore);
+    let purge   = and! (warp::delete(), prefix, path::param(), repo_filter.clone()) .and_then(trash_handler::purge);
+
+    or!(list, restore, purge)
+}
+
+/// The scheduled playlists as json or as iCalendar feed, and the feed on a path of its own for calendars that cannot set an Accept header
+pub fn get_schedule_routes(repo: Arc<dyn LiplRepo>, name: &'static str, calendar: &'static str) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone
+{
+    let repo_filter = warp::any().map(move || repo.clone());
+
+    let list = and! (warp::get(), join_paths!(API, VERSION, name), path::end(), query::query(), header::optional::<String>("accept"), repo_filter.clone()) .and_then(schedule_handler::list);
+    let feed = and! (warp::get(), join_paths!(API, VERSION, calendar), path::end(), query::query(), repo_filter.clone()) .and_then(schedule_handler::calendar);
+
+    or!(list, feed)
+}
+
+/// Metrics of the repo calls in the prometheus text format
+pub fn get_metrics_route(handle: metrics_exporter_prometheus::PrometheusHandle, name: &'static str) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone
+{
+    and! (warp::get(), path(name), path::end()).map(move || handle.render())
+}
+
+pub fn get_history_routes(repo: Arc<dyn LiplRepo>, name: &'static str) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone
+{
+    let repo_filter = warp::any().map(move || repo.clone());
+    let prefix = join_paths!(API, VERSION, name).and(path::param::<String>()).and(path("history"));
+
+    let list   = and! (warp::get() , prefix, path::end(), repo_filter.clone()) .and_then(history_handler::list);
+    let item   = and! (warp::get() , prefix, path::param(), path::end(), repo_filter.clone()) .and_then(history_handler::item);
+    let diff   = and! (warp::get() , prefix, path::param(), path("diff"), path::end(), repo_filter.clone(), query::query()) .and_then(history_handler::diff);
+    let revert = and! (warp::post(), prefix, path::param(), path("revert"), path::end(), repo_filter.clone()) .and_then(history_handler::revert);
+
+    or!(list, item, diff, revert)
+}
+
 create_fn!(get_lyric_routes, lyric_handler);
 create_fn!(get_playlist_routes, playlist_handler);
 
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use lipl_core::{LiplRepo, Lyric, LyricPost};
+    use lipl_repo_memory::MemoryRepo;
+    use super::get_lyric_routes;
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn full_list_with_tags() {
+        let mut roodkapje: Lyric = LyricPost::from(("Roodkapje", "Zeg Roodkapje, waar ga je heen?")).into();
+        roodkapje.tags = ["kinderliedjes".to_owned(), "sprookjes".to_owned()].into_iter().collect();
+        let mut molen: Lyric = LyricPost::from(("Daar bij die molen", "Daar bij die molen, die mooie molen")).into();
+        molen.tags = ["kinderliedjes".to_owned()].into_iter().collect();
+        let repo: Arc<dyn LiplRepo> = Arc::new(MemoryRepo::new(vec![roodkapje.clone(), molen].into_iter(), std::iter::empty()));
+        let routes = get_lyric_routes(repo, "lyric");
+
+        let titles = |body: &[u8]| {
+            let mut titles = serde_json::from_slice::<Vec<Lyric>>(body).unwrap().into_iter().map(|lyric| lyric.title).collect::<Vec<_>>();
+            titles.sort();
+            titles
+        };
+        let response = warp::test::request().path("/api/v1/lyric?full=true&tags=kinderliedjes,sprookjes").reply(&routes).await;
+        assert_eq!(titles(response.body()), vec![roodkapje.title.clone()]);
+        let response = warp::test::request().path("/api/v1/lyric?full=true&tags=kinderliedjes").reply(&routes).await;
+        assert_eq!(titles(response.body()), vec!["Daar bij die molen".to_owned(), "Roodkapje".to_owned()]);
+        let response = warp::test::request().path("/api/v1/lyric?full=true").reply(&routes).await;
+        assert_eq!(titles(response.body()).len(), 2);
+    }
+}
@@ -1,26 +1,32 @@
 
 macro_rules! create_handler {
-    ($name:ident, $list:ident, $summaries:ident, $item:ident, $delete:ident, $update:ident, $post_type:path, $posted_type:path) => {
+    ($name:ident, $list:ident, $summaries:ident, $item:ident, $delete:ident, $delete_if_match:ident, $update:ident, $update_if_match:ident, $post_type:path, $posted_type:path, $has_tags:expr) => {
         pub mod $name {
             use std::sync::Arc;
-            use lipl_core::{LiplRepo, Uuid};
+            use lipl_core::{Etag, ListQuery, LiplRepo, Uuid};
             use warp::{Reply, Rejection};
-            use warp::reply::{json, with_status};
+            use warp::path::FullPath;
+            use warp::reply::{json, with_header, with_status};
             use warp::http::status::StatusCode;
             use crate::model::{Query};
             use crate::error::{RepoError};
 
-            pub async fn list_summary(repo: Arc<dyn LiplRepo>) -> Result<impl Reply, Rejection> 
+            pub async fn list_summary(path: FullPath, repo: Arc<dyn LiplRepo>, query: ListQuery) -> Result<impl Reply, Rejection> 
             {
-                let data = repo.$summaries().await.map_err(reject)?;
-                Ok(json(&data))
+                let page = repo.$summaries(query.clone()).await.map_err(reject)?;
+                let mut response = with_header(json(&page.items), "x-total-count", page.total).into_response();
+                if let Some(link) = page.link_header(path.as_str(), &query).and_then(|link| link.parse().ok()) {
+                    response.headers_mut().insert("link", link);
+                }
+                Ok(response)
             }
 
             pub async fn list(repo: Arc<dyn LiplRepo>, query: Query) -> Result<impl Reply, Rejection>
             {
                 if query.full {
                     let data = repo.$list().await.map_err(reject)?;
-                    Ok(json(&data))
+                    let has_tags: fn(&$posted_type, &[String]) -> bool = $has_tags;
+                    Ok(json(&data.into_iter().filter(|item| has_tags(item, &query.tags)).collect::<Vec<_>>()))
                 } else {
                     Err(warp::reject::not_found())
                 }
@@ -34,7 +40,7 @@ macro_rules! create_handler {
             {
                 let uuid = id.parse::<Uuid>().map_err(reject)?;
                 let data = repo.$item(uuid).await.map_err(reject)?;
-                Ok(json(&data))
+                Ok(with_header(json(&data), "etag", data.etag().unwrap_or_default()))
             }
 
             pub async fn post(
@@ -44,26 +50,35 @@ macro_rules! create_handler {
             {
                 let o: $posted_type = (None, object).into();
                 let data = repo.$update(o).await.map_err(reject)?;
-                Ok(with_status(json(&data), StatusCode::CREATED))
+                Ok(with_status(with_header(json(&data), "etag", data.etag().unwrap_or_default()), StatusCode::CREATED))
             }
 
-            pub async fn delete(id: String, repo: Arc<dyn LiplRepo>) -> Result<impl Reply, Rejection>
+            pub async fn delete(id: String, repo: Arc<dyn LiplRepo>, if_match: Option<String>) -> Result<impl Reply, Rejection>
             {
                 let uuid = id.parse::<Uuid>().map_err(reject)?;
-                repo.$delete(uuid).await.map_err(reject)?;
+                match if_match {
+                    Some(etag) => repo.$delete_if_match(uuid, etag).await,
+                    None => repo.$delete(uuid).await,
+                }
+                .map_err(reject)?;
                 Ok(with_status(warp::reply::reply(), StatusCode::NO_CONTENT))
             }
 
             pub async fn put(
                 id: String,
                 repo: Arc<dyn LiplRepo>,
+                if_match: Option<String>,
                 object: $post_type,
             ) -> Result<impl Reply, Rejection>
             {
                 let uuid = id.parse::<Uuid>().map_err(reject)?;
                 let o: $posted_type = (Some(uuid), object).into();
-                let data = repo.$update(o).await.map_err(reject)?;
-                Ok(json(&data))
+                let data = match if_match {
+                    Some(etag) => repo.$update_if_match(o, etag).await,
+                    None => repo.$update(o).await,
+                }
+                .map_err(reject)?;
+                Ok(with_header(json(&data), "etag", data.etag().unwrap_or_default()))
             }
         }
     };
@@ -72,21 +87,263 @@ macro_rules! create_handler {
 create_handler! (
     lyric,
     get_lyrics,
-    get_lyric_summaries,
+    get_lyric_summaries_page,
     get_lyric,
     delete_lyric,
+    delete_lyric_if_match,
     upsert_lyric,
+    upsert_lyric_if_match,
     lipl_core::LyricPost,
-    lipl_core::Lyric
+    lipl_core::Lyric,
+    |lyric, tags| lipl_core::tag::has_tags(&lyric.tags, tags)
 );
 
 create_handler! (
     playlist,
     get_playlists,
-    get_playlist_summaries,
+    get_playlist_summaries_page,
     get_playlist,
     delete_playlist,
+    delete_playlist_if_match,
     upsert_playlist,
+    upsert_playlist_if_match,
     lipl_core::PlaylistPost,
-    lipl_core::Playlist
+    lipl_core::Playlist,
+    |_, _| true
 );
+
+pub mod search {
+    use std::sync::Arc;
+    use lipl_core::{tag::tagged_hits, LiplRepo};
+    use warp::{Reply, Rejection};
+    use warp::reply::json;
+    use crate::model::SearchQuery;
+    use crate::error::RepoError;
+
+    /// The hits for the search query q, only on the lyrics with all the tags if tags are given
+    pub async fn lyrics(repo: Arc<dyn LiplRepo>, query: SearchQuery) -> Result<impl Reply, Rejection>
+    {
+        let mut data = repo.search_lyrics(&query.q).await.map_err(|e| warp::reject::custom::<RepoError>(e.into()))?;
+        if !query.tags.is_empty() {
+            let lyrics = repo.get_lyrics().await.map_err(|e| warp::reject::custom::<RepoError>(e.into()))?;
+            data = tagged_hits(data, &lyrics, &query.tags);
+        }
+        Ok(json(&data))
+    }
+}
+
+pub mod chords {
+    use std::sync::Arc;
+    use lipl_core::{chordpro::CONTENT_TYPE, Etag, LiplRepo, Uuid};
+    use warp::{Reply, Rejection};
+    use warp::reply::{json, with_header, Response};
+    use warp::http::StatusCode;
+    use crate::model::RenderQuery;
+    use crate::error::RepoError;
+    use crate::recover::json_response;
+
+    /// The lyric with the chords transposed, with the parts in the order they are sung or in another language,
+    /// or in the ChordPro format if the client accepts it. Answers every request for a lyric, so each response varies on Accept.
+    pub async fn lyric(id: String, query: RenderQuery, accept: Option<String>, repo: Arc<dyn LiplRepo>) -> Result<Response, Rejection>
+    {
+        let chordpro = accept.unwrap_or_default().contains(CONTENT_TYPE);
+        let uuid = id.parse::<Uuid>().map_err(|e| warp::reject::custom::<RepoError>(e.into()))?;
+        let mut lyric = repo.get_lyric(uuid).await.map_err(|e| warp::reject::custom::<RepoError>(e.into()))?;
+        if let Some(semitones) = query.transpose {
+            lyric = lyric.transpose(semitones, query.accidental);
+        }
+        if query.arranged {
+            lyric = lyric.arranged();
+        }
+        if let Some(language) = query.language.as_deref() {
+            let rendered = if query.side_by_side { lyric.side_by_side(language) } else { lyric.translated(language) };
+            match rendered {
+                Ok(rendered) => lyric = rendered,
+                // A rejection would let the lyric routes answer with the lyric in its own language
+                Err(error) => {
+                    let Ok(reply) = json_response(StatusCode::NOT_FOUND, &error.to_string());
+                    return Ok(reply.into_response());
+                },
+            }
+        }
+        if chordpro {
+            // The etag is taken from the ChordPro text, so caches do not mix it up with the JSON body
+            let text = lyric.to_chordpro();
+            let etag = text.etag().unwrap_or_default();
+            Ok(with_header(with_header(with_header(text, "content-type", CONTENT_TYPE), "etag", etag), "vary", "accept").into_response())
+        }
+        else {
+            let etag = lyric.etag().unwrap_or_default();
+            Ok(with_header(with_header(json(&lyric), "etag", etag), "vary", "accept").into_response())
+        }
+    }
+}
+
+pub mod schedule {
+    use std::sync::Arc;
+    use lipl_core::{schedule::{to_icalendar, CONTENT_TYPE}, DateRange, LiplRepo};
+    use warp::{Reply, Rejection};
+    use warp::reply::{json, with_header, Response};
+    use crate::error::RepoError;
+
+    fn reject<E: Into<RepoError>>(e: E) -> Rejection {
+        warp::reject::custom::<RepoError>(e.into())
+    }
+
+    /// The playlists with an event in the date range, ordered by the start of the event,
+    /// or the iCalendar feed with these playlists if the client accepts it
+    pub async fn list(range: DateRange, accept: Option<String>, repo: Arc<dyn LiplRepo>) -> Result<Response, Rejection>
+    {
+        if accept.unwrap_or_default().contains(CONTENT_TYPE) {
+            return calendar(range, repo).await;
+        }
+        let data = repo.get_scheduled_playlists(range).await.map_err(reject)?;
+        Ok(json(&data).into_response())
+    }
+
+    pub async fn calendar(range: DateRange, repo: Arc<dyn LiplRepo>) -> Result<Response, Rejection>
+    {
+        let playlists = repo.get_scheduled_playlists(range).await.map_err(reject)?;
+        let summaries = repo.get_lyric_summaries().await.map_err(reject)?;
+        Ok(with_header(to_icalendar(&playlists, &summaries), "content-type", CONTENT_TYPE).into_response())
+    }
+}
+
+pub mod tag {
+    use std::sync::Arc;
+    use lipl_core::LiplRepo;
+    use warp::{Reply, Rejection};
+    use warp::reply::json;
+    use crate::error::RepoError;
+
+    fn reject<E: Into<RepoError>>(e: E) -> Rejection {
+        warp::reject::custom::<RepoError>(e.into())
+    }
+
+    /// Every tag with the number of lyrics that have it
+    pub async fn list(repo: Arc<dyn LiplRepo>) -> Result<impl Reply, Rejection>
+    {
+        let tags = repo.get_tags().await.map_err(reject)?;
+        Ok(json(&tags))
+    }
+}
+
+pub mod duplicate {
+    use std::sync::Arc;
+    use lipl_core::{duplicate::{find_duplicates, DEFAULT_THRESHOLD}, LiplRepo, Merge};
+    use warp::{Reply, Rejection};
+    use warp::reply::json;
+    use crate::error::RepoError;
+    use crate::model::DuplicateQuery;
+
+    fn reject<E: Into<RepoError>>(e: E) -> Rejection {
+        warp::reject::custom::<RepoError>(e.into())
+    }
+
+    /// The clusters of lyrics that are probably the same song
+    pub async fn list(repo: Arc<dyn LiplRepo>, query: DuplicateQuery) -> Result<impl Reply, Rejection>
+    {
+        let lyrics = repo.get_lyrics().await.map_err(reject)?;
+        Ok(json(&find_duplicates(&lyrics, query.threshold.unwrap_or(DEFAULT_THRESHOLD))))
+    }
+
+    /// Merges the duplicates into the lyric to keep and returns the kept lyric
+    pub async fn merge(repo: Arc<dyn LiplRepo>, merge: Merge) -> Result<impl Reply, Rejection>
+    {
+        let lyric = repo.merge_lyrics(merge).await.map_err(reject)?;
+        Ok(json(&lyric))
+    }
+}
+
+pub mod batch {
+    use std::sync::Arc;
+    use lipl_core::{LiplRepo, Transaction};
+    use warp::{Reply, Rejection};
+    use warp::reply::with_status;
+    use warp::http::status::StatusCode;
+    use crate::error::RepoError;
+
+    pub async fn post(repo: Arc<dyn LiplRepo>, batch: Vec<Transaction>) -> Result<impl Reply, Rejection>
+    {
+        repo.apply_batch(batch).await.map_err(|e| warp::reject::custom::<RepoError>(e.into()))?;
+        Ok(with_status(warp::reply::reply(), StatusCode::NO_CONTENT))
+    }
+}
+
+pub mod trash {
+    use std::sync::Arc;
+    use lipl_core::{LiplRepo, Uuid};
+    use warp::{Reply, Rejection};
+    use warp::reply::{json, with_status};
+    use warp::http::status::StatusCode;
+    use crate::error::RepoError;
+
+    fn reject<E: Into<RepoError>>(e: E) -> Rejection {
+        warp::reject::custom::<RepoError>(e.into())
+    }
+
+    pub async fn list(repo: Arc<dyn LiplRepo>) -> Result<impl Reply, Rejection>
+    {
+        let data = repo.list_trash().await.map_err(reject)?;
+        Ok(json(&data))
+    }
+
+    pub async fn restore(id: String, repo: Arc<dyn LiplRepo>) -> Result<impl Reply, Rejection>
+    {
+        let uuid = id.parse::<Uuid>().map_err(reject)?;
+        repo.restore(uuid).await.map_err(reject)?;
+        Ok(with_status(warp::reply::reply(), StatusCode::NO_CONTENT))
+    }
+
+    pub async fn purge(id: String, repo: Arc<dyn LiplRepo>) -> Result<impl Reply, Rejection>
+    {
+        let uuid = id.parse::<Uuid>().map_err(reject)?;
+        repo.purge(uuid).await.map_err(reject)?;
+        Ok(with_status(warp::reply::reply(), StatusCode::NO_CONTENT))
+    }
+}
+
+pub mod history {
+    use std::sync::Arc;
+    use lipl_core::{Etag, LiplRepo, Uuid};
+    use warp::{Reply, Rejection};
+    use warp::reply::{json, with_header};
+    use crate::error::RepoError;
+    use crate::model::DiffQuery;
+
+    fn reject<E: Into<RepoError>>(e: E) -> Rejection {
+        warp::reject::custom::<RepoError>(e.into())
+    }
+
+    pub async fn list(id: String, repo: Arc<dyn LiplRepo>) -> Result<impl Reply, Rejection>
+    {
+        let uuid = id.parse::<Uuid>().map_err(reject)?;
+        let data = repo.get_lyric_history(uuid).await.map_err(reject)?;
+        Ok(json(&data))
+    }
+
+    pub async fn item(id: String, rev: u64, repo: Arc<dyn LiplRepo>) -> Result<impl Reply, Rejection>
+    {
+        let uuid = id.parse::<Uuid>().map_err(reject)?;
+        let data = repo.get_lyric_revision(uuid, rev).await.map_err(reject)?;
+        Ok(json(&data))
+    }
+
+    pub async fn diff(id: String, rev: u64, repo: Arc<dyn LiplRepo>, query: DiffQuery) -> Result<impl Reply, Rejection>
+    {
+        let uuid = id.parse::<Uuid>().map_err(reject)?;
+        let from = repo.get_lyric_revision(uuid, rev).await.map_err(reject)?;
+        let to = match query.to {
+            Some(to) => repo.get_lyric_revision(uuid, to).await.map_err(reject)?,
+            None => repo.get_lyric_history(uuid).await.map_err(reject)?.pop().ok_or(lipl_core::Error::NotFound(uuid)).map_err(reject)?,
+        };
+        Ok(json(&from.diff(&to)))
+    }
+
+    pub async fn revert(id: String, rev: u64, repo: Arc<dyn LiplRepo>) -> Result<impl Reply, Rejection>
+    {
+        let uuid = id.parse::<Uuid>().map_err(reject)?;
+        let data = repo.revert_lyric(uuid, rev).await.map_err(reject)?;
+        Ok(with_header(json(&data), "etag", data.etag().unwrap_or_default()))
+    }
+}
@@ -32,6 +32,31 @@ pub async fn run() -> lipl_core::Result<()> {
             list.source.build_repo()
             .and_then(|source| crate::db::list(source, list.yaml))
             .await
+        },
+        LiplCommand::Export(export) => {
+            export.source.build_repo()
+            .and_then(|source| crate::db::export(source, export.playlist))
+            .await
+        },
+        LiplCommand::Duplicates(duplicates) => {
+            duplicates.source.build_repo()
+            .and_then(|source| crate::db::duplicates(source, duplicates.threshold))
+            .await
+        },
+        LiplCommand::Merge(merge) => {
+            merge.source.build_repo()
+            .and_then(|source| crate::db::merge(source, lipl_core::Merge { keep: merge.keep, duplicates: merge.duplicates }))
+            .await
+        },
+        #[cfg(feature = "file")]
+        LiplCommand::Compact(compact) => {
+            crate::db::compact(compact.source).await
+        },
+        #[cfg(feature = "file")]
+        LiplCommand::Recover(recover) => {
+            recover.target.build_repo()
+            .and_then(|target| crate::db::recover(recover.source, target, recover.until))
+            .await
         }
     }
 }
@@ -1,6 +1,40 @@
+use lipl_core::Accidental;
 use serde::{Serialize, Deserialize};
 
 #[derive(Deserialize, Serialize)]
 pub struct Query {
-    pub full: bool
+    pub full: bool,
+    /// Only lyrics with all of these tags, not used for playlists
+    #[serde(default, with = "lipl_core::tag::comma_separated")]
+    pub tags: Vec<String>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct SearchQuery {
+    pub q: String,
+    /// Only hits on lyrics with all of these tags
+    #[serde(default, with = "lipl_core::tag::comma_separated")]
+    pub tags: Vec<String>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct DiffQuery {
+    pub to: Option<u64>
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct RenderQuery {
+    pub transpose: Option<i32>,
+    #[serde(default)]
+    pub accidental: Accidental,
+    #[serde(default)]
+    pub arranged: bool,
+    pub language: Option<String>,
+    #[serde(default)]
+    pub side_by_side: bool,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct DuplicateQuery {
+    pub threshold: Option<f32>
 }
@@ -1,4 +1,4 @@
-use clap::{command, Subcommand, Parser};
+use clap::{Subcommand, Parser};
 use crate::repo::{RepoConfig};
 
 #[derive(Parser)]
@@ -25,6 +25,50 @@ pub struct ListCommand {
     pub yaml: bool,
 }
 
+#[derive(Parser)]
+pub struct ExportCommand {
+    #[arg(long, short)]
+    pub source: RepoConfig,
+    #[arg(long, short, help = "Only the lyrics of this playlist, in the order of the playlist")]
+    pub playlist: Option<lipl_core::Uuid>,
+}
+
+#[derive(Parser)]
+pub struct DuplicatesCommand {
+    #[arg(long, short)]
+    pub source: RepoConfig,
+    #[arg(long, short, help = "Similarity from 0 to 1 from which lyrics are reported as duplicates")]
+    pub threshold: Option<f32>,
+}
+
+#[derive(Parser)]
+pub struct MergeCommand {
+    #[arg(long, short)]
+    pub source: RepoConfig,
+    #[arg(long, short, help = "Lyric to keep")]
+    pub keep: lipl_core::Uuid,
+    #[arg(long, short, required = true, help = "Lyrics to replace with the lyric to keep and move to the trash")]
+    pub duplicates: Vec<lipl_core::Uuid>,
+}
+
+#[cfg(feature = "file")]
+#[derive(Parser)]
+pub struct CompactCommand {
+    #[arg(long, short, help = "Directory of the file repo")]
+    pub source: lipl_repo_fs::FileRepoConfig,
+}
+
+#[cfg(feature = "file")]
+#[derive(Parser)]
+pub struct RecoverCommand {
+    #[arg(long, short, help = "Directory of the file repo with the transaction logs")]
+    pub source: lipl_repo_fs::FileRepoConfig,
+    #[arg(long, short, help = "Empty repo to write the lyrics and playlists to")]
+    pub target: Box<RepoConfig>,
+    #[arg(long, short, help = "RFC3339 timestamp, or the number of transactions to replay")]
+    pub until: lipl_core::transaction::ReplayUntil,
+}
+
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 pub struct LiplApp {
@@ -37,5 +81,17 @@ pub enum LiplCommand {
     Serve(ServeCommand),
     Copy(CopyCommand),
     List(ListCommand),
+    /// Writes the lyrics of a repo or a playlist in the ChordPro format
+    Export(ExportCommand),
+    /// Lists the lyrics that are probably the same song
+    Duplicates(DuplicatesCommand),
+    /// Replaces duplicates with the lyric to keep in every playlist and moves the duplicates to the trash
+    Merge(MergeCommand),
+    /// Writes a snapshot of a file repo and starts a new transaction log
+    #[cfg(feature = "file")]
+    Compact(CompactCommand),
+    /// Writes the lyrics and playlists of a file repo as they were at a moment in the past to the target repo
+    #[cfg(feature = "file")]
+    Recover(RecoverCommand),
 }
 
@@ -3,23 +3,29 @@ use std::error::Error;
 use tracing::error;
 
 use serde::Serialize;
-use warp::{Rejection, hyper::StatusCode, Reply};
+use warp::{Rejection, hyper::StatusCode, Reply, reply::{Json, WithStatus}};
 use crate::error::RepoError;
 
 #[derive(Serialize)]
 struct ErrorMessage<'a> {
     code: u16,
     message: &'a str,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    invalid_members: &'a [lipl_core::Uuid],
 }
 
 impl<'a> ErrorMessage<'a> {
-    fn new(code: StatusCode, message: &'a str) -> ErrorMessage {
-        ErrorMessage { code: code.as_u16(), message }
+    fn new(code: StatusCode, message: &'a str) -> ErrorMessage<'a> {
+        ErrorMessage { code: code.as_u16(), message, invalid_members: &[] }
     }
 }
 
-pub fn json_response(code: StatusCode, message: &str) -> Result<impl Reply, Infallible> {
-    let json = warp::reply::json(&ErrorMessage::new(code, message));
+pub fn json_response(code: StatusCode, message: &str) -> Result<WithStatus<Json>, Infallible> {
+    invalid_members_response(code, message, &[])
+}
+
+fn invalid_members_response(code: StatusCode, message: &str, invalid_members: &[lipl_core::Uuid]) -> Result<WithStatus<Json>, Infallible> {
+    let json = warp::reply::json(&ErrorMessage { invalid_members, ..ErrorMessage::new(code, message) });
     Ok(    
         warp::reply::with_status(json, code)
     )
@@ -28,6 +34,15 @@ pub fn json_response(code: StatusCode, message: &str) -> Result<impl Reply, Infa
 pub async fn handle_rejection(err: Rejection) -> Result<impl Reply, Infallible> {
     if let Some(e) = err.find::<RepoError>() {
         match e {
+            RepoError::Model(m @ lipl_core::Error::EtagMismatch(_)) => {
+                json_response(StatusCode::PRECONDITION_FAILED, &m.to_string())
+            },
+            RepoError::Model(m @ lipl_core::Error::Occupied) => {
+                json_response(StatusCode::CONFLICT, &m.to_string())
+            },
+            RepoError::Model(m @ lipl_core::Error::InvalidMembers(_, members)) => {
+                invalid_members_response(StatusCode::UNPROCESSABLE_ENTITY, &m.to_string(), members)
+            },
             RepoError::Model(m) => {
                 json_response(StatusCode::NOT_FOUND, &m.to_string())
             },
@@ -48,14 +63,8 @@ pub async fn handle_rejection(err: Rejection) -> Result<impl Reply, Infallible>
         // This error happens if the body could not be deserialized correctly
         // We can use the cause to analyze the error and customize the error message
         let message = match e.source() {
-            Some(cause) => {
-                if cause.to_string().contains("denom") {
-                    "FIELD_ERROR: denom"
-                } else {
-                    "BAD_REQUEST"
-                }
-            }
-            None => "BAD_REQUEST",
+            Some(cause) if cause.to_string().contains("denom") => "FIELD_ERROR: denom",
+            _ => "BAD_REQUEST",
         };
         json_response(StatusCode::BAD_REQUEST, message)
     }
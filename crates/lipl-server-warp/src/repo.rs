@@ -1,6 +1,13 @@
 use lipl_core::{LiplRepo, ToRepo};
+use lipl_repo_cache::{CacheConfig, CachedRepo};
+use lipl_repo_instrumented::InstrumentedRepo;
+use lipl_repo_mirror::{MirrorConfig, MirrorRepo};
 use std::{str::FromStr, sync::Arc};
 
+const PREFIX_CACHE: &str = "cache:";
+const PREFIX_MIRROR: &str = "mirror:";
+const MIRROR_SEPARATOR: char = '|';
+
 #[cfg(feature = "file")]
 const PREFIX_FILE: &str = "file:";
 
@@ -15,6 +22,11 @@ const PREFIX_REDIS: &str = "redis:";
 
 #[derive(Clone)]
 pub enum RepoConfig {
+    Cache(Box<CacheConfig>, Box<RepoConfig>),
+
+    /// The primary followed by the secondaries
+    Mirror(Box<MirrorConfig>, Vec<RepoConfig>),
+
     #[cfg(feature = "postgres")]
     Postgres(Box<lipl_repo_postgres::PostgresRepoConfig>),
 
@@ -28,27 +40,47 @@ pub enum RepoConfig {
     Memory(Box<lipl_repo_memory::MemoryRepoConfig>),
 }
 
+/// Traces and measures the calls to the backend
+fn instrumented(backend: &'static str) -> impl FnOnce(Arc<dyn LiplRepo>) -> Arc<dyn LiplRepo> {
+    move |repo| Arc::new(InstrumentedRepo::new(repo, backend))
+}
+
 impl RepoConfig {
     pub async fn build_repo(self) -> lipl_core::Result<Arc<dyn LiplRepo>> {
         match self {
+            RepoConfig::Cache(config, repo) => {
+                let repo = Box::pin(repo.build_repo()).await?;
+                Ok(Arc::new(CachedRepo::new(repo, *config)))
+            },
+
+            RepoConfig::Mirror(config, repos) => {
+                let mut repos = repos.into_iter();
+                let primary = Box::pin(repos.next().ok_or(lipl_core::Error::Argument("mirror needs a primary"))?.build_repo()).await?;
+                let mut secondaries = vec![];
+                for repo in repos {
+                    secondaries.push(Box::pin(repo.build_repo()).await?);
+                }
+                Ok(Arc::new(MirrorRepo::new(primary, secondaries, *config)))
+            },
+
             #[cfg(feature = "file")]
             RepoConfig::File(config) => {
-                config.to_repo().await
+                config.to_repo().await.map(instrumented("file"))
             },
 
             #[cfg(feature = "postgres")]
             RepoConfig::Postgres(config) => {
-                config.to_repo().await
+                config.to_repo().await.map(instrumented("postgres"))
             },
 
             #[cfg(feature = "redis")]
             RepoConfig::Redis(config) => {
-                config.to_repo().await
+                config.to_repo().await.map(instrumented("redis"))
             }
 
             #[cfg(feature = "memory")]
             RepoConfig::Memory(config) => {
-                config.to_repo().await
+                config.to_repo().await.map(instrumented("memory"))
             }
         }
     }
@@ -65,7 +97,31 @@ impl FromStr for Box<RepoConfig> {
 impl FromStr for RepoConfig {
     type Err = lipl_core::Error;
 
+    /// Parses `cache:` followed by an optional list of cache options and the repo to cache,
+    /// e.g. `cache:ttl=30,capacity=500:file:./data` or `cache:memory:true`.
+    /// Parses `mirror:` followed by an optional list of mirror options, the primary and the secondaries separated by `|`,
+    /// e.g. `mirror:mode=async:postgres:host=localhost|file:./copy`
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(cached) = s.strip_prefix(PREFIX_CACHE) {
+            let (config, repo) = match cached.split_once(':') {
+                Some((options, repo)) if options.contains('=') => (options.parse::<CacheConfig>()?, repo),
+                _ => (CacheConfig::default(), cached),
+            };
+            return repo.parse::<RepoConfig>().map(|repo| RepoConfig::Cache(Box::new(config), Box::new(repo)));
+        }
+
+        if let Some(mirrored) = s.strip_prefix(PREFIX_MIRROR) {
+            let (config, repos) = match mirrored.split_once(':') {
+                Some((options, repos)) if options.contains('=') => (options.parse::<MirrorConfig>()?, repos),
+                _ => (MirrorConfig::default(), mirrored),
+            };
+            let repos = repos.split(MIRROR_SEPARATOR).map(str::parse::<RepoConfig>).collect::<Result<Vec<_>, _>>()?;
+            if repos.len() < 2 {
+                return Err(lipl_core::Error::Argument("mirror needs a primary and at least one secondary"));
+            }
+            return Ok(RepoConfig::Mirror(Box::new(config), repos));
+        }
+
         #[cfg(feature = "file")]
         if s.starts_with(PREFIX_FILE) {
             return 
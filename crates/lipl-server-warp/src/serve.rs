@@ -1,6 +1,8 @@
 use std::sync::Arc;
 
 use lipl_core::LiplRepo;
+use lipl_repo_instrumented::{DURATION, DURATION_BUCKETS};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder};
 use tokio::signal;
 use tracing::{info, error};
 use warp::Filter;
@@ -8,7 +10,7 @@ use warp::Filter;
 use crate::constant;
 use crate::error::RepoError;
 use crate::message;
-use crate::filter::{get_lyric_routes, get_playlist_routes};
+use crate::filter::{get_batch_route, get_duplicate_routes, get_history_routes, get_lyric_chords_route, get_lyric_routes, get_lyric_search_route, get_metrics_route, get_playlist_routes, get_schedule_routes, get_tag_route, get_trash_routes};
 
 pub async fn run(repo: Arc<dyn LiplRepo>, port: u16) -> lipl_core::Result<()> 
 {
@@ -20,15 +22,48 @@ pub async fn run(repo: Arc<dyn LiplRepo>, port: u16) -> lipl_core::Result<()>
     .with_env_filter(filter)
     .init();
 
+    let metrics =
+        PrometheusBuilder::new()
+        .set_buckets_for_metric(Matcher::Full(DURATION.to_owned()), DURATION_BUCKETS)
+        .and_then(PrometheusBuilder::install_recorder)
+        .map_err(|e| lipl_core::Error::Warp(Box::new(e)))?;
+
     // Cache warmup
     let _lyrics = repo.get_lyrics().await;
     let _playlists = repo.get_playlists().await;
 
     let routes = 
-        get_lyric_routes(repo.clone(), constant::LYRIC)
+        get_lyric_search_route(repo.clone(), constant::LYRIC)
+        .or(
+            get_history_routes(repo.clone(), constant::LYRIC)
+        )
+        .or(
+            get_lyric_chords_route(repo.clone(), constant::LYRIC)
+        )
+        .or(
+            get_lyric_routes(repo.clone(), constant::LYRIC)
+        )
         .or(
             get_playlist_routes(repo.clone(), constant::PLAYLIST)
         )
+        .or(
+            get_schedule_routes(repo.clone(), constant::SCHEDULE, constant::CALENDAR)
+        )
+        .or(
+            get_tag_route(repo.clone(), constant::TAG)
+        )
+        .or(
+            get_duplicate_routes(repo.clone(), constant::DUPLICATES, constant::MERGE)
+        )
+        .or(
+            get_batch_route(repo.clone(), constant::BATCH)
+        )
+        .or(
+            get_trash_routes(repo.clone(), constant::TRASH)
+        )
+        .or(
+            get_metrics_route(metrics, constant::METRICS)
+        )
         .with(warp::trace::request())
         .recover(crate::recover::handle_rejection);
 
@@ -10,9 +10,11 @@ pub trait Api {
     async fn lyric_summaries(&self) -> Result<Vec<Summary>>;
     async fn lyric_delete(&self, id: Uuid) -> Result<()>;
     async fn lyric_insert(&self, lyric_post: LyricPost) -> Result<Lyric>;
+    async fn lyric_upsert(&self, id: Uuid, lyric_post: LyricPost) -> Result<Lyric>;
     async fn playlist_summaries(&self) -> Result<Vec<Summary>>;
     async fn playlist_delete(&self, id: Uuid) -> Result<()>;
     async fn playlist_insert(&self, playlist_post: PlaylistPost) -> Result<Playlist>;
+    async fn playlist_upsert(&self, id: Uuid, playlist_post: PlaylistPost) -> Result<Playlist>;
 }
 
 pub struct UploadClient {
@@ -47,6 +49,12 @@ impl Api for UploadClient {
         .await
     }
 
+    async fn lyric_upsert(&self, id: Uuid, lyric_post: LyricPost) -> Result<Lyric> {
+        self.inner.put(&format!("lyric/{}", id), lyric_post)
+        .err_into()
+        .await
+    }
+
     async fn playlist_summaries(&self) -> Result<Vec<Summary>> {
         self.inner.get("playlist")
         .err_into()
@@ -64,4 +72,10 @@ impl Api for UploadClient {
         .err_into()
         .await
     }
+
+    async fn playlist_upsert(&self, id: Uuid, playlist_post: PlaylistPost) -> Result<Playlist> {
+        self.inner.put(&format!("playlist/{}", id), playlist_post)
+        .err_into()
+        .await
+    }
 }
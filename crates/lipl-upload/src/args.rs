@@ -7,8 +7,10 @@ pub struct Args {
     pub source_path: String,
     #[arg(short, long, required = true, help = "API Prefix")]
     pub prefix: String,
-    #[arg(short, long, required = true, help = "File with this extension is to be uploaded")]
+    #[arg(short, long, required = true, help = "File with this extension is to be uploaded, e.g. txt, or cho for ChordPro files")]
     pub filter: String,
+    #[arg(short, long, help = "Derive the ids from the titles, so uploading the same files again gives the same ids")]
+    pub title_ids: bool,
     #[arg(required = true, help = "Sets the name of the playlist where uploaded lyrics are to be made member of")]
     pub playlist_name: String,
 }
@@ -1,12 +1,13 @@
 use std::path::{Path, PathBuf};
 use std::ffi::OsStr;
 use std::io::Error as IOError;
-use futures::{future::{ready, Ready}, Future, Stream, TryFutureExt, TryStream, TryStreamExt};
+use futures::{future::{ready, Ready}, Future, FutureExt, Stream, TryFutureExt, TryStream, TryStreamExt};
 use tokio_stream::wrappers::ReadDirStream;
 use tokio::fs::{read_dir, read_to_string, DirEntry};
 use crate::Result;
 use crate::api::{UploadClient, Api};
-use lipl_core::{Uuid, LyricPost};
+use crate::model::try_iter;
+use lipl_core::{Uuid};
 use crate::error::Error;
 
 pub struct Entry {
@@ -46,7 +47,7 @@ async fn get_files_stream<P: AsRef<Path>>(path: P) -> Result<impl Stream<Item=st
     .await
 }
 
-pub async fn post_lyrics<'a, P, F, Fut>(path: P, filter: F, client: &'a UploadClient) -> Result<impl TryStream<Ok=Uuid, Error=Error> + 'a>
+pub async fn post_lyrics<'a, P, F, Fut>(path: P, filter: F, client: &'a UploadClient, title_ids: bool) -> Result<impl TryStream<Ok=Uuid, Error=Error> + 'a>
 where 
     P: AsRef<Path> + 'a,
     F: Fn(&PathBuf) -> Fut + 'a,
@@ -58,8 +59,12 @@ where
         .err_into()
         .try_filter(filter)
         .and_then(entry_from_file)
-        .map_ok(LyricPost::from)
-        .and_then(|lp| client.lyric_insert(lp))
+        .map_ok(|entry| try_iter(entry.lyric_posts()))
+        .try_flatten()
+        .and_then(move |lp|
+            if title_ids { client.lyric_upsert(lp.title_id(), lp).boxed() }
+            else { client.lyric_insert(lp).boxed() }
+        )
         .map_ok(|lyric| lyric.id)
     )
     .await
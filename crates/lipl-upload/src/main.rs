@@ -53,8 +53,9 @@ async fn main() -> anyhow::Result<()> {
     let ids = 
         fs::post_lyrics(
             args.source_path,
-            fs::extension_filter("txt"),
+            fs::extension_filter(&args.filter),
             &client,
+            args.title_ids,
         )
         .await?
         .try_collect::<Vec<Uuid>>()
@@ -67,8 +68,12 @@ async fn main() -> anyhow::Result<()> {
     let playlist_post = PlaylistPost {
         title: args.playlist_name,
         members: ids,
+        entries: vec![],
+        event: None,
     };
-    let playlist = client.playlist_insert(playlist_post).await?;
+    let playlist =
+        if args.title_ids { client.playlist_upsert(playlist_post.title_id(), playlist_post).await? }
+        else { client.playlist_insert(playlist_post).await? };
     println!("Playlist posted with id {}, title {}", playlist.id, playlist.title);
 
     println!("Elapsed: {} milliseconds", now.elapsed().as_millis());
@@ -1,14 +1,47 @@
-use lipl_core::LyricPost;
+use lipl_core::{chordpro::lyrics_from_chordpro, LyricPost};
 use futures::TryStream;
 use futures::stream::iter;
 use crate::{fs, Result, error::Error};
-use parts::{to_parts};
+use parts::{to_chord_parts, without_chords};
+
+const CHORDPRO_EXTENSIONS: &[&str] = &["cho", "chopro", "chordpro", "crd"];
 
 impl From<fs::Entry> for LyricPost {
+    /// Chords on the line above the text and labels like `Refrein:` are kept
     fn from(entry: fs::Entry) -> Self {
+        let title = entry.title();
+        let labeled = to_chord_parts(entry.contents);
+        let (parts, chords) = without_chords(labeled.parts);
         Self {
-            title: entry.title(),
-            parts: to_parts(entry.contents),
+            title,
+            parts,
+            chords,
+            labels: labeled.labels,
+            arrangement: labeled.arrangement,
+            translations: Default::default(),
+            tags: Default::default(),
+            metadata: Default::default(),
+        }
+    }
+}
+
+impl fs::Entry {
+    /// A ChordPro file can have more than one song. A song without a title gets the title of the file.
+    pub fn lyric_posts(self) -> Vec<LyricPost> {
+        let is_chordpro = 
+            self.path
+            .extension()
+            .map(|extension| CHORDPRO_EXTENSIONS.contains(&extension.to_string_lossy().to_lowercase().as_str()))
+            .unwrap_or_default();
+        if is_chordpro {
+            let title = self.title();
+            lyrics_from_chordpro(&self.contents)
+                .into_iter()
+                .map(|lyric_post| if lyric_post.title.is_empty() { LyricPost { title: title.clone(), ..lyric_post } } else { lyric_post })
+                .collect()
+        }
+        else {
+            vec![self.into()]
         }
     }
 }
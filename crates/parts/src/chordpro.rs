@@ -0,0 +1,244 @@
+use core::fmt::{Display, Formatter, Result as FmtResult};
+use crate::{arrange, label_parts, ChordLine};
+
+/// A song in the ChordPro format: the metadata directives in the order they were found and the parts of the lyric,
+/// with the labels of the sections and the order in which they are sung
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ChordPro {
+    pub directives: Vec<(String, String)>,
+    pub parts: Vec<Vec<ChordLine>>,
+    pub labels: Vec<Option<String>>,
+    pub arrangement: Vec<String>,
+}
+
+/// Directives that start a part, with the kind of section they start
+const START_OF_SECTION: &[(&str, &str)] = &[
+    ("start_of_chorus", "chorus"), ("soc", "chorus"),
+    ("start_of_verse", "verse"), ("sov", "verse"),
+    ("start_of_bridge", "bridge"), ("sob", "bridge"),
+];
+
+/// Directives that end the current part
+const END_OF_SECTION: &[&str] = &["end_of_chorus", "eoc", "end_of_verse", "eov", "end_of_bridge", "eob"];
+
+/// Directive that repeats a chorus
+const CHORUS: &str = "chorus";
+
+/// Directives of which the contents are not part of the lyric
+const IGNORED: &[&str] = &["comment", "c", "comment_italic", "ci", "comment_box", "cb", "new_song", "ns"];
+
+const START_OF_TAB: &[&str] = &["start_of_tab", "sot"];
+const END_OF_TAB: &[&str] = &["end_of_tab", "eot"];
+
+/// Full name of a directive that has an abbreviation
+fn full_name(name: &str) -> &str {
+    match name {
+        "t" => "title",
+        "st" => "subtitle",
+        _ => name,
+    }
+}
+
+/// Name and value of a line like `{title: Roodkapje}` or `{soc}`
+fn directive(line: &str) -> Option<(String, String)> {
+    let inner = line.trim().strip_prefix('{')?.strip_suffix('}')?;
+    let (name, value) = inner.split_once(':').unwrap_or((inner, ""));
+    Some((full_name(&name.trim().to_lowercase()).to_owned(), value.trim().to_owned()))
+}
+
+/// Line that labels the part it starts
+fn heading(label: &str) -> ChordLine {
+    ChordLine { text: format!("[{label}]"), chords: vec![] }
+}
+
+/// Kind of section for a part with the label
+fn section_kind(label: &str) -> &'static str {
+    let label = label.to_lowercase();
+    if ["chorus", "refrein", "refrain"].iter().any(|kind| label.contains(kind)) {
+        "chorus"
+    }
+    else if ["bridge", "brug"].iter().any(|kind| label.contains(kind)) {
+        "bridge"
+    }
+    else {
+        "verse"
+    }
+}
+
+fn end_part(parts: &mut Vec<Vec<ChordLine>>, part: &mut Vec<ChordLine>) {
+    if !part.is_empty() {
+        parts.push(std::mem::take(part));
+    }
+}
+
+impl ChordPro {
+    /// Value of the first directive with the name. Abbreviated names are stored with their full name.
+    pub fn directive(&self, name: &str) -> Option<&str> {
+        self.directives
+            .iter()
+            .find(|(directive, _)| directive == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Values of the `{meta: name value}` directive with the name
+    pub fn meta(&self, name: &str) -> Option<&str> {
+        self.directive(name)
+            .or_else(||
+                self.directives
+                .iter()
+                .filter(|(directive, _)| directive == "meta")
+                .find_map(|(_, value)| value.strip_prefix(name).filter(|value| value.starts_with(' ')).map(str::trim))
+            )
+    }
+
+    /// The parts with only the text of the lines
+    pub fn text_parts(&self) -> Vec<Vec<String>> {
+        self.parts
+            .iter()
+            .map(|part| part.iter().map(|line| line.text.trim_end().to_owned()).collect())
+            .collect()
+    }
+}
+
+/// Parses a ChordPro song. Blank lines and the start or end of a chorus, verse or bridge separate the parts.
+/// The label of a section is the value of the directive that starts it, a chorus without one is labeled `Chorus`.
+/// `{chorus}` repeats the last chorus, or the part with the label in its value. Comments, comment directives and tabs are skipped.
+pub fn parse_chordpro(s: &str) -> ChordPro {
+    let mut chordpro = ChordPro::default();
+    let mut parts = vec![];
+    let mut part = vec![];
+    let mut in_tab = false;
+    let mut last_chorus: Option<String> = None;
+    for line in s.lines().map(str::trim_end) {
+        if line.trim_start().starts_with('#') {
+            continue;
+        }
+        match directive(line) {
+            Some((name, _)) if START_OF_TAB.contains(&name.as_str()) => { in_tab = true; },
+            Some((name, _)) if END_OF_TAB.contains(&name.as_str()) => { in_tab = false; },
+            _ if in_tab => {},
+            Some((name, value)) if START_OF_SECTION.iter().any(|(start, _)| *start == name) => {
+                end_part(&mut parts, &mut part);
+                let is_chorus = START_OF_SECTION.contains(&(name.as_str(), "chorus"));
+                let label = Some(value).filter(|value| !value.is_empty()).or_else(|| is_chorus.then(|| "Chorus".to_owned()));
+                if let Some(label) = label {
+                    if is_chorus {
+                        last_chorus = Some(label.clone());
+                    }
+                    part.push(heading(&label));
+                }
+            },
+            Some((name, _)) if END_OF_SECTION.contains(&name.as_str()) => end_part(&mut parts, &mut part),
+            Some((name, value)) if name == CHORUS => {
+                end_part(&mut parts, &mut part);
+                if let Some(label) = Some(value).filter(|value| !value.is_empty()).or_else(|| last_chorus.clone()) {
+                    parts.push(vec![heading(&label)]);
+                }
+            },
+            Some((name, _)) if IGNORED.contains(&name.as_str()) => {},
+            Some(directive) => chordpro.directives.push(directive),
+            None if line.trim().is_empty() => end_part(&mut parts, &mut part),
+            None => part.push(ChordLine::from(line)),
+        }
+    }
+    end_part(&mut parts, &mut part);
+    let labeled = label_parts(parts);
+    chordpro.parts = labeled.parts;
+    chordpro.labels = labeled.labels;
+    chordpro.arrangement = labeled.arrangement;
+    chordpro
+}
+
+/// Splits a file with more than one song on the `{new_song}` directive
+pub fn split_songs(s: &str) -> Vec<String> {
+    let mut songs = vec![String::new()];
+    for line in s.lines() {
+        match directive(line) {
+            Some((name, _)) if name == "new_song" || name == "ns" => songs.push(String::new()),
+            _ => {
+                let song = songs.last_mut().unwrap();
+                song.push_str(line);
+                song.push('\n');
+            },
+        }
+    }
+    songs.into_iter().filter(|song| !song.trim().is_empty()).collect()
+}
+
+impl Display for ChordPro {
+    /// Writes the parts in the order they are sung, a part that is sung again as `{chorus: label}`
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        for (name, value) in self.directives.iter() {
+            writeln!(f, "{{{name}: {value}}}")?;
+        }
+        let order = if self.arrangement.is_empty() { (0..self.parts.len()).collect() } else { arrange(&self.labels, &self.arrangement) };
+        let mut written = vec![];
+        for index in order {
+            writeln!(f)?;
+            let label = self.labels.get(index).and_then(Option::as_deref);
+            match label {
+                Some(label) if written.contains(&index) => writeln!(f, "{{{CHORUS}: {label}}}")?,
+                Some(label) => {
+                    let kind = section_kind(label);
+                    writeln!(f, "{{start_of_{kind}: {label}}}")?;
+                    self.parts[index].iter().try_for_each(|line| writeln!(f, "{line}"))?;
+                    writeln!(f, "{{end_of_{kind}}}")?;
+                },
+                None => self.parts[index].iter().try_for_each(|line| writeln!(f, "{line}"))?,
+            }
+            written.push(index);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse_chordpro, split_songs};
+
+    const ROODKAPJE: &str = "\
+{title: Roodkapje}
+{st: Kinderlied}
+{meta: language nl}
+# traditional
+
+[C]Zeg roodkapje waar ga je [G]heen
+Zo alleen, zo [C]alleen
+{soc}
+{c: twice}
+Ik ga naar grootmoeder
+{eoc}
+";
+
+    #[test]
+    fn parse() {
+        let chordpro = parse_chordpro(ROODKAPJE);
+        assert_eq!(chordpro.directive("title"), Some("Roodkapje"));
+        assert_eq!(chordpro.directive("subtitle"), Some("Kinderlied"));
+        assert_eq!(chordpro.meta("language"), Some("nl"));
+        assert_eq!(
+            chordpro.text_parts(),
+            vec![
+                vec!["Zeg roodkapje waar ga je heen", "Zo alleen, zo alleen"],
+                vec!["Ik ga naar grootmoeder"],
+            ],
+        );
+        assert_eq!(parse_chordpro(&chordpro.to_string()), chordpro);
+    }
+
+    #[test]
+    fn repeated_chorus() {
+        let chordpro = parse_chordpro("{sov: Couplet 1}\nZeg roodkapje\n{eov}\n{soc: Refrein}\nNaar grootmoeder\n{eoc}\n\nWat heb je in je mandje\n{chorus}\n");
+        assert_eq!(chordpro.text_parts(), vec![vec!["Zeg roodkapje"], vec!["Naar grootmoeder"], vec!["Wat heb je in je mandje"]]);
+        assert_eq!(chordpro.labels, vec![Some("Couplet 1".to_owned()), Some("Refrein".to_owned()), Some("3".to_owned())]);
+        assert_eq!(chordpro.arrangement, vec!["Couplet 1", "Refrein", "3", "Refrein"]);
+        assert_eq!(parse_chordpro(&chordpro.to_string()), chordpro);
+    }
+
+    #[test]
+    fn songs() {
+        let songs = split_songs(&format!("{ROODKAPJE}{{new_song}}\n{{title: Daar bij die molen}}\n"));
+        assert_eq!(songs.len(), 2);
+        assert_eq!(parse_chordpro(&songs[1]).directive("title"), Some("Daar bij die molen"));
+    }
+}
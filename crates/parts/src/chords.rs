@@ -0,0 +1,313 @@
+use core::fmt::{Display, Formatter, Result as FmtResult};
+use core::str::FromStr;
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use crate::{label_parts, split_parts, to_text, LabeledParts};
+
+const CHORD: &str = r"^(N\.C\.|[A-G][#b]?(maj|min|dim|aug|sus|add|m|M|[0-9]|[#b+\-°ø()])*(/[A-G][#b]?)?)$";
+const NO_CHORD: &str = "N.C.";
+
+const SHARPS: [&str; 12] = ["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"];
+const FLATS: [&str; 12] = ["C", "Db", "D", "Eb", "E", "F", "Gb", "G", "Ab", "A", "Bb", "B"];
+
+lazy_static! {
+    static ref CHORD_REGEX: Regex = CHORD.parse().unwrap();
+}
+
+/// A chord that is played at a position in a line, counted in characters of the text without chords
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Chord {
+    pub position: usize,
+    pub name: String,
+}
+
+/// Line of text with the chords played on it
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ChordLine {
+    pub text: String,
+    pub chords: Vec<Chord>,
+}
+
+/// Whether the notes between the natural ones are written with sharps or with flats
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Accidental {
+    #[default]
+    Sharp,
+    Flat,
+}
+
+impl FromStr for Accidental {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sharp" | "#" => Ok(Accidental::Sharp),
+            "flat" | "b" => Ok(Accidental::Flat),
+            _ => Err(format!("{s} is not sharp or flat")),
+        }
+    }
+}
+
+/// Chord names like `C`, `F#m7`, `Bbsus4`, `D/F#` and `N.C.`
+pub fn is_chord(name: &str) -> bool {
+    CHORD_REGEX.is_match(name)
+}
+
+/// Transposes the note at the start of the chord name, and returns it with the rest of the name
+fn transpose_note(name: &str, semitones: i32, accidental: Accidental) -> (&'static str, &str) {
+    let mut chars = name.chars();
+    let natural = match chars.next() {
+        Some('C') => 0,
+        Some('D') => 2,
+        Some('E') => 4,
+        Some('F') => 5,
+        Some('G') => 7,
+        Some('A') => 9,
+        _ => 11,
+    };
+    let (semitone, rest) = match chars.next() {
+        Some('#') => (natural + 1, &name[2..]),
+        Some('b') => (natural - 1, &name[2..]),
+        _ => (natural, &name[1..]),
+    };
+    let index = (semitone + semitones).rem_euclid(12) as usize;
+    match accidental {
+        Accidental::Sharp => (SHARPS[index], rest),
+        Accidental::Flat => (FLATS[index], rest),
+    }
+}
+
+/// Transposes the chord by a number of semitones, up if positive. Names that are not a chord are returned as they are.
+pub fn transpose_chord(name: &str, semitones: i32, accidental: Accidental) -> String {
+    if name == NO_CHORD || !is_chord(name) {
+        return name.to_owned();
+    }
+    let (root, rest) = transpose_note(name, semitones, accidental);
+    match rest.split_once('/') {
+        Some((suffix, bass)) => {
+            let (bass, _) = transpose_note(bass, semitones, accidental);
+            format!("{root}{suffix}/{bass}")
+        },
+        None => format!("{root}{rest}"),
+    }
+}
+
+impl ChordLine {
+    pub fn transpose(&self, semitones: i32, accidental: Accidental) -> Self {
+        Self {
+            text: self.text.clone(),
+            chords:
+                self.chords
+                .iter()
+                .map(|chord| Chord { position: chord.position, name: transpose_chord(&chord.name, semitones, accidental) })
+                .collect(),
+        }
+    }
+}
+
+impl From<&str> for ChordLine {
+    /// Splits a line like `[C]Zeg roodkapje [G]waar ga je heen` in the text and the chords.
+    /// Brackets around something that is not a chord are kept in the text.
+    fn from(line: &str) -> Self {
+        let mut chord_line = ChordLine::default();
+        let mut rest = line;
+        while let Some(start) = rest.find('[') {
+            match rest[start..].find(']') {
+                Some(end) => {
+                    let name = &rest[start + 1..start + end];
+                    if is_chord(name) {
+                        chord_line.text.push_str(&rest[..start]);
+                        chord_line.chords.push(Chord { position: chord_line.text.chars().count(), name: name.to_owned() });
+                    }
+                    else {
+                        chord_line.text.push_str(&rest[..=start + end]);
+                    }
+                    rest = &rest[start + end + 1..];
+                },
+                None => break,
+            }
+        }
+        chord_line.text.push_str(rest);
+        chord_line.text.truncate(chord_line.text.trim_end().len());
+        chord_line
+    }
+}
+
+impl Display for ChordLine {
+    /// Writes the chords inline, in brackets before the character they are played on.
+    /// Chords after the end of the text are padded with spaces to their position.
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let mut chords = self.chords.iter().peekable();
+        for (position, c) in self.text.chars().enumerate() {
+            while let Some(chord) = chords.next_if(|chord| chord.position <= position) {
+                write!(f, "[{}]", chord.name)?;
+            }
+            write!(f, "{c}")?;
+        }
+        let mut length = self.text.chars().count();
+        chords.try_for_each(|chord| {
+            if chord.position > length {
+                write!(f, "{:1$}", "", chord.position - length)?;
+                length = chord.position;
+            }
+            write!(f, "[{}]", chord.name)
+        })
+    }
+}
+
+/// The chords of a line with nothing but chords, like `C       G7`, at the position they are written
+fn chords_of_line(line: &str) -> Option<Vec<Chord>> {
+    let mut chords = vec![];
+    let mut name = String::new();
+    for (position, c) in line.chars().chain(std::iter::once(' ')).enumerate() {
+        if !c.is_whitespace() {
+            name.push(c);
+        }
+        else if !name.is_empty() {
+            if !is_chord(&name) {
+                return None;
+            }
+            chords.push(Chord { position: position - name.chars().count(), name: std::mem::take(&mut name) });
+        }
+    }
+    Some(chords).filter(|chords| !chords.is_empty())
+}
+
+/// Labeled parts of a text with chords written on the line above the text they are played on.
+/// A line of chords without text below it becomes a line without text.
+pub fn to_chord_parts(s: String) -> LabeledParts<ChordLine> {
+    let parts =
+        split_parts(s)
+        .into_iter()
+        .map(|part| {
+            let mut lines = vec![];
+            let mut pending: Option<Vec<Chord>> = None;
+            for line in part {
+                match chords_of_line(&line) {
+                    Some(chords) => if let Some(chords) = pending.replace(chords) {
+                        lines.push(ChordLine { text: String::new(), chords });
+                    },
+                    None => lines.push(ChordLine { text: line, chords: pending.take().unwrap_or_default() }),
+                }
+            }
+            lines.extend(pending.map(|chords| ChordLine { text: String::new(), chords }));
+            lines
+        })
+        .collect();
+    label_parts(parts)
+}
+
+/// Text with the chords inline, like `[C]Zeg roodkapje`, that can be read back with `from_chord_text`
+pub fn to_chord_text(parts: &[Vec<ChordLine>]) -> String {
+    to_text(
+        &parts
+        .iter()
+        .map(|part| part.iter().map(ChordLine::to_string).collect())
+        .collect::<Vec<_>>()
+    )
+}
+
+/// Parts of a text with the chords inline
+pub fn from_chord_text(s: String) -> Vec<Vec<ChordLine>> {
+    split_parts(s)
+        .iter()
+        .map(|part| part.iter().map(|line| ChordLine::from(line.as_str())).collect())
+        .collect()
+}
+
+/// Combines the lines of the parts with the chords per line, which can be missing for lines without chords
+pub fn with_chords(parts: &[Vec<String>], chords: &[Vec<Vec<Chord>>]) -> Vec<Vec<ChordLine>> {
+    parts
+        .iter()
+        .enumerate()
+        .map(|(i, part)|
+            part
+            .iter()
+            .enumerate()
+            .map(|(j, line)| ChordLine {
+                text: line.clone(),
+                chords: chords.get(i).and_then(|part| part.get(j)).cloned().unwrap_or_default(),
+            })
+            .collect()
+        )
+        .collect()
+}
+
+/// Splits the lines of the parts in the text and the chords per line. There are no chords if none of the lines has them.
+pub fn without_chords(parts: Vec<Vec<ChordLine>>) -> (Vec<Vec<String>>, Vec<Vec<Vec<Chord>>>) {
+    let has_chords = parts.iter().flatten().any(|line| !line.chords.is_empty());
+    let chords =
+        if has_chords {
+            parts.iter().map(|part| part.iter().map(|line| line.chords.clone()).collect()).collect()
+        }
+        else {
+            vec![]
+        };
+    let parts =
+        parts
+        .into_iter()
+        .map(|part| part.into_iter().map(|line| line.text.trim_end().to_owned()).collect())
+        .collect();
+    (parts, chords)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{from_chord_text, is_chord, to_chord_parts, to_chord_text, transpose_chord, Accidental, Chord, ChordLine};
+
+    fn chord(position: usize, name: &str) -> Chord {
+        Chord { position, name: name.to_owned() }
+    }
+
+    #[test]
+    fn chord_line() {
+        let line = ChordLine::from("[C]Zeg roodkapje [G7]waar");
+        assert_eq!(line.text, "Zeg roodkapje waar");
+        assert_eq!(line.chords, vec![chord(0, "C"), chord(14, "G7")]);
+        assert_eq!(line.to_string(), "[C]Zeg roodkapje [G7]waar");
+        assert_eq!(ChordLine::from("end[Am]").to_string(), "end[Am]");
+        assert_eq!(ChordLine::from("[Refrein] twee keer").text, "[Refrein] twee keer");
+    }
+
+    #[test]
+    fn chords() {
+        for name in ["C", "F#m7", "Bbsus4", "Cmaj7", "D/F#", "Ebadd9", "N.C."] {
+            assert!(is_chord(name), "{name} should be a chord");
+        }
+        for name in ["Zeg", "H", "Refrein", "c"] {
+            assert!(!is_chord(name), "{name} should not be a chord");
+        }
+    }
+
+    #[test]
+    fn transpose() {
+        assert_eq!(transpose_chord("C", 2, Accidental::Sharp), "D");
+        assert_eq!(transpose_chord("F#m7", 1, Accidental::Sharp), "Gm7");
+        assert_eq!(transpose_chord("A", 1, Accidental::Flat), "Bb");
+        assert_eq!(transpose_chord("A", 1, Accidental::Sharp), "A#");
+        assert_eq!(transpose_chord("Bb", -3, Accidental::Sharp), "G");
+        assert_eq!(transpose_chord("D/F#", -2, Accidental::Flat), "C/E");
+        assert_eq!(transpose_chord("B", 13, Accidental::Sharp), "C");
+        assert_eq!(transpose_chord("N.C.", 2, Accidental::Sharp), "N.C.");
+    }
+
+    #[test]
+    fn chords_over_lyrics() {
+        let text = "C             G\nZeg roodkapje waar ga je heen\nZo alleen\n\nAm  F\n";
+        let parts = to_chord_parts(text.to_owned()).parts;
+        assert_eq!(
+            parts,
+            vec![
+                vec![
+                    ChordLine { text: "Zeg roodkapje waar ga je heen".to_owned(), chords: vec![chord(0, "C"), chord(14, "G")] },
+                    ChordLine { text: "Zo alleen".to_owned(), chords: vec![] },
+                ],
+                vec![
+                    ChordLine { text: String::new(), chords: vec![chord(0, "Am"), chord(4, "F")] },
+                ],
+            ],
+        );
+        assert_eq!(from_chord_text(to_chord_text(&parts)), parts);
+    }
+}
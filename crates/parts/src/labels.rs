@@ -0,0 +1,141 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+use crate::{is_chord, ChordLine};
+
+const LABEL: &str = r"(?i)^((pre-?)?(verse|couplet|chorus|refrein|refrain|bridge|brug|intro|outro|interlude|solo|coda|tag))(\s+\d+)?:?$";
+const BRACKETED: &str = r"^\[([^\[\]]+)\]:?$";
+
+lazy_static! {
+    static ref LABEL_REGEX: Regex = LABEL.parse().unwrap();
+    static ref BRACKETED_REGEX: Regex = BRACKETED.parse().unwrap();
+}
+
+/// Parts with their labels, and the order in which they are sung
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LabeledParts<T = String> {
+    pub parts: Vec<Vec<T>>,
+    /// Label per part, empty if none of the parts has a label
+    pub labels: Vec<Option<String>>,
+    /// Labels of the parts in the order they are sung, empty if they are sung in the order of the parts
+    pub arrangement: Vec<String>,
+}
+
+impl<T> Default for LabeledParts<T> {
+    fn default() -> Self {
+        Self { parts: vec![], labels: vec![], arrangement: vec![] }
+    }
+}
+
+impl AsRef<str> for ChordLine {
+    fn as_ref(&self) -> &str {
+        &self.text
+    }
+}
+
+/// The label of a heading like `Refrein:`, `Chorus`, `Verse 2` or `[Intro]`, without the brackets and the colon
+pub fn label_of(line: &str) -> Option<String> {
+    let line = line.trim();
+    if let Some(captures) = BRACKETED_REGEX.captures(line) {
+        let label = captures[1].trim();
+        return Some(label.to_owned()).filter(|label| !label.is_empty() && !is_chord(label));
+    }
+    LABEL_REGEX.is_match(line).then(|| line.trim_end_matches(':').to_owned())
+}
+
+/// Takes the labels from the headings on the first line of the parts.
+/// A heading without lines repeats the part with that label, or else labels the part after it.
+/// If parts are repeated, the parts without a label get their number as label, so that they can be in the arrangement.
+pub fn label_parts<T: AsRef<str>>(parts: Vec<Vec<T>>) -> LabeledParts<T> {
+    let mut labeled = LabeledParts::default();
+    let mut sung = vec![];
+    let mut repeated = false;
+    let mut next_label = None;
+    for mut part in parts {
+        let label = part.first().and_then(|line| label_of(line.as_ref()));
+        if label.is_some() {
+            part.remove(0);
+        }
+        match label {
+            Some(label) if part.is_empty() => {
+                match labeled.labels.iter().position(|existing: &Option<String>| existing.as_deref() == Some(label.as_str())) {
+                    Some(index) => {
+                        sung.push(index);
+                        repeated = true;
+                    },
+                    None => {
+                        next_label = Some(label);
+                    },
+                }
+            },
+            _ if part.is_empty() => {},
+            label => {
+                sung.push(labeled.parts.len());
+                labeled.parts.push(part);
+                labeled.labels.push(label.or_else(|| next_label.take()));
+            },
+        }
+    }
+    if repeated {
+        for index in sung.iter().copied() {
+            labeled.labels[index].get_or_insert_with(|| (index + 1).to_string());
+        }
+        labeled.arrangement = sung.into_iter().filter_map(|index| labeled.labels[index].clone()).collect();
+    }
+    if labeled.labels.iter().all(Option::is_none) {
+        labeled.labels.clear();
+    }
+    labeled
+}
+
+/// The indexes of the parts in the order they are sung. Labels in the arrangement without a part are skipped.
+pub fn arrange(labels: &[Option<String>], arrangement: &[String]) -> Vec<usize> {
+    arrangement
+        .iter()
+        .filter_map(|label| labels.iter().position(|existing| existing.as_deref() == Some(label.as_str())))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{arrange, label_of, label_parts};
+
+    fn parts(parts: &[&[&str]]) -> Vec<Vec<String>> {
+        parts.iter().map(|part| part.iter().map(|line| line.to_string()).collect()).collect()
+    }
+
+    #[test]
+    fn labels() {
+        assert_eq!(label_of("Refrein:"), Some("Refrein".to_owned()));
+        assert_eq!(label_of("chorus"), Some("chorus".to_owned()));
+        assert_eq!(label_of("Verse 2"), Some("Verse 2".to_owned()));
+        assert_eq!(label_of("[Tussenspel]"), Some("Tussenspel".to_owned()));
+        assert_eq!(label_of("[Am]"), None);
+        assert_eq!(label_of("Hij zei:"), None);
+        assert_eq!(label_of("Brug naar de overkant"), None);
+    }
+
+    #[test]
+    fn repeated_chorus() {
+        let labeled = label_parts(parts(&[
+            &["Zeg roodkapje waar ga je heen"],
+            &["Refrein:", "Naar grootmoeder"],
+            &["Wat heb je in je mandje"],
+            &["Refrein"],
+        ]));
+        assert_eq!(labeled.parts, parts(&[&["Zeg roodkapje waar ga je heen"], &["Naar grootmoeder"], &["Wat heb je in je mandje"]]));
+        assert_eq!(labeled.labels, vec![Some("1".to_owned()), Some("Refrein".to_owned()), Some("3".to_owned())]);
+        assert_eq!(labeled.arrangement, vec!["1", "Refrein", "3", "Refrein"]);
+        assert_eq!(arrange(&labeled.labels, &labeled.arrangement), vec![0, 1, 2, 1]);
+    }
+
+    #[test]
+    fn heading_on_own_line() {
+        let labeled = label_parts(parts(&[&["[Intro]"], &["La la la"], &["Zeg roodkapje"]]));
+        assert_eq!(labeled.parts, parts(&[&["La la la"], &["Zeg roodkapje"]]));
+        assert_eq!(labeled.labels, vec![Some("Intro".to_owned()), None]);
+        assert!(labeled.arrangement.is_empty());
+
+        let unlabeled = label_parts(parts(&[&["Zeg roodkapje"]]));
+        assert!(unlabeled.labels.is_empty());
+    }
+}
@@ -1,12 +1,22 @@
 use lazy_static::lazy_static;
 use regex::Regex;
 
+mod chordpro;
+mod chords;
 mod from_async_reader;
 mod from_reader;
+mod labels;
+mod normalize;
 mod st;
 pub use st::to_parts_async;
+pub use chordpro::{parse_chordpro, split_songs, ChordPro};
+pub use chords::{
+    from_chord_text, is_chord, to_chord_parts, to_chord_text, transpose_chord, with_chords, without_chords, Accidental, Chord, ChordLine,
+};
 pub use from_async_reader::from_async_reader;
 pub use from_reader::parts_from_reader;
+pub use labels::{arrange, label_of, label_parts, LabeledParts};
+pub use normalize::{normalize, to_words};
 
 const DOUBLE_LINE: &str = r"\n\s*\n";
 
@@ -64,7 +74,8 @@ fn to_lines(s: &str) -> Vec<String> {
         .collect()
 }
 
-pub fn to_parts(s: String) -> Vec<Vec<String>> {
+/// Splits the text in parts on blank lines, without looking for labels
+pub(crate) fn split_parts(s: String) -> Vec<Vec<String>> {
     DOUBLE_LINE_REGEX
     .split(&s)
     .map(to_lines)
@@ -72,6 +83,15 @@ pub fn to_parts(s: String) -> Vec<Vec<String>> {
     .collect()
 }
 
+/// The parts of the text, without the headings that label them
+pub fn to_parts(s: String) -> Vec<Vec<String>> {
+    to_labeled_parts(s).parts
+}
+
+pub fn to_labeled_parts(s: String) -> LabeledParts {
+    label_parts(split_parts(s))
+}
+
 pub fn to_text(parts: &[Vec<String>]) -> String {
     parts
     .iter()
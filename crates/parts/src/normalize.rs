@@ -0,0 +1,32 @@
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+
+/// Lowercases the text and strips diacritics, so that "Één" and "een" compare equal
+pub fn normalize(s: &str) -> String {
+    s.nfd()
+        .filter(|c| !is_combining_mark(*c))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Splits the normalized text into words, dropping punctuation like the apostrophe in "'t"
+pub fn to_words(s: &str) -> Vec<String> {
+    normalize(s)
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(super::normalize("Één Café"), "een cafe");
+    }
+
+    #[test]
+    fn test_to_words() {
+        assert_eq!(super::to_words("'t Knaapje, ’t kíndje!"), vec!["t", "knaapje", "t", "kindje"]);
+    }
+}
@@ -91,6 +91,22 @@ impl ApiRequest for ApiClient {
         .and_then(identity)
     }
 
+    async fn put<T, U>(&self, uri: &str, object: T) -> Result<U>
+    where 
+        T: Serialize + Send + Sync,
+        U: DeserializeOwned
+    {
+        api_request(
+            self.uri(uri),
+            Method::PUT,
+            Some(object.to_json()?.into())
+        )
+        .and_then(|r| self.send(r))
+        .map_ok(to_object)
+        .await
+        .and_then(identity)
+    }
+
     async fn delete(&self, uri: &str) -> Result<()> {
         api_request(
             self.uri(uri),
@@ -13,5 +13,6 @@ pub type Result<T> = std::result::Result<T, Error>;
 pub trait ApiRequest {
     async fn get<T: DeserializeOwned>(&self, uri: &str) -> Result<T>;
     async fn post<T: Serialize + Send + Sync, U: DeserializeOwned>(&self, uri: &str, object: T) -> Result<U>;
+    async fn put<T: Serialize + Send + Sync, U: DeserializeOwned>(&self, uri: &str, object: T) -> Result<U>;
     async fn delete(&self, uri: &str) -> Result<()>;
 }